@@ -1,6 +1,6 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
-use markflow_core::{get_event_iterator, MarkdownStream};
+use criterion::{Criterion, Throughput, black_box, criterion_group, criterion_main};
 use markdown::to_html;
+use markflow_core::{MarkdownStream, get_event_iterator};
 use std::io::{self, Write};
 
 // A dummy writer that discards data, similar to /dev/null
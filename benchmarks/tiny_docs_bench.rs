@@ -0,0 +1,54 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use markflow_core::{AnchorOptions, RenderOptions, render};
+
+const DOCUMENTS: usize = 100_000;
+
+/// Comment-sized inputs: a sentence or two, the odd emphasis or link, no headings.
+fn generate_comments() -> Vec<String> {
+    (0..DOCUMENTS)
+        .map(|i| match i % 3 {
+            0 => format!("Thanks, merged in #{i}!"),
+            1 => format!("LGTM, but see *this* [note](https://example.com/{i})."),
+            _ => format!("Could you rebase?\nThe build for {i} is red."),
+        })
+        .collect()
+}
+
+fn benchmark_tiny_documents(c: &mut Criterion) {
+    let comments = generate_comments();
+    let options = RenderOptions {
+        extract_title: true,
+        anchors: Some(AnchorOptions {
+            base_url: "https://example.com".into(),
+            path: "comments".into(),
+        }),
+        ..RenderOptions::default()
+    };
+
+    let mut group = c.benchmark_group("tiny_documents");
+    group.throughput(Throughput::Elements(DOCUMENTS as u64));
+    group.sample_size(10);
+
+    group.bench_function("default_options", |b| {
+        b.iter(|| {
+            for comment in &comments {
+                black_box(render(black_box(comment), &RenderOptions::default()).unwrap());
+            }
+        })
+    });
+
+    group.bench_function("heading_metadata", |b| {
+        b.iter(|| {
+            for comment in &comments {
+                black_box(render(black_box(comment), &options).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_tiny_documents);
+criterion_main!(benches);
@@ -1,6 +1,10 @@
 use crate::event::Event;
-use crate::html_renderer::HtmlRenderer;
+use crate::html_renderer::{HtmlOptions, HtmlRenderer};
+use crate::text_renderer::TextRenderer;
+use smallvec::SmallVec;
 use std::io::{self, Write};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 /// Extension trait to pipe Markdown events directly to a Writer.
 ///
@@ -14,8 +18,35 @@ pub trait MarkdownStream: Sized {
     ///
     /// Returns the writer back to the caller upon success.
     fn stream_to_writer<W: Write>(self, writer: W) -> io::Result<W>;
+
+    /// Same as [`MarkdownStream::stream_to_writer`], rendering with the supplied [`HtmlOptions`].
+    fn stream_to_writer_with_options<W: Write>(
+        self,
+        writer: W,
+        options: &HtmlOptions,
+    ) -> io::Result<W>;
+
+    /// Drives the iterator events into the writer as plain text, dropping all markup.
+    ///
+    /// Useful for search indexing and notification previews.
+    fn stream_to_text<W: Write>(self, writer: W) -> io::Result<W>;
+
+    /// Same as [`MarkdownStream::stream_to_writer_with_options`] for a tokio `AsyncWrite`, such
+    /// as a hyper/axum response body. Output is handed over in chunks of about
+    /// [`ASYNC_CHUNK_BYTES`], and rendering waits while the writer applies backpressure. The
+    /// writer is flushed but not shut down.
+    #[cfg(feature = "tokio")]
+    fn stream_to_async_writer<W: AsyncWrite + Unpin>(
+        self,
+        writer: W,
+        options: &HtmlOptions,
+    ) -> impl Future<Output = io::Result<W>>;
 }
 
+/// Rendered bytes [`MarkdownStream::stream_to_async_writer`] collects before each write.
+#[cfg(feature = "tokio")]
+pub const ASYNC_CHUNK_BYTES: usize = 8 * 1024;
+
 impl<'a, I> MarkdownStream for I
 where
     I: Iterator<Item = Event<'a>>,
@@ -23,12 +54,106 @@ where
     fn stream_to_writer<W: Write>(self, writer: W) -> io::Result<W> {
         HtmlRenderer::new(writer).render(self)
     }
+
+    fn stream_to_writer_with_options<W: Write>(
+        self,
+        writer: W,
+        options: &HtmlOptions,
+    ) -> io::Result<W> {
+        HtmlRenderer::with_options(writer, options.clone()).render(self)
+    }
+
+    fn stream_to_text<W: Write>(self, writer: W) -> io::Result<W> {
+        TextRenderer::new(writer).render(self)
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn stream_to_async_writer<W: AsyncWrite + Unpin>(
+        self,
+        mut writer: W,
+        options: &HtmlOptions,
+    ) -> io::Result<W> {
+        let mut renderer = HtmlRenderer::with_options(Vec::new(), options.clone());
+        for event in self {
+            renderer.render_events([event])?;
+            if renderer.writer_mut().len() >= ASYNC_CHUNK_BYTES {
+                writer
+                    .write_all(&std::mem::take(renderer.writer_mut()))
+                    .await?;
+            }
+        }
+        renderer.close_sections(0)?;
+        writer.write_all(renderer.writer_mut()).await?;
+        writer.flush().await?;
+        Ok(writer)
+    }
+}
+
+/// Events produced by one [`EventFilter::map`] call; most filters emit zero, one, or two.
+pub type FilterOutput<'a> = SmallVec<[Event<'a>; 2]>;
+
+/// Per-event middleware between a parser and [`MarkdownStream::stream_to_writer`].
+///
+/// Each input event maps to any number of output events, so a filter can rewrite, drop, or
+/// surround events. Closures of the same shape implement the trait.
+pub trait EventFilter<'a> {
+    /// Maps one event to its replacements.
+    fn map(&mut self, event: Event<'a>) -> FilterOutput<'a>;
+}
+
+impl<'a, F> EventFilter<'a> for F
+where
+    F: FnMut(Event<'a>) -> FilterOutput<'a>,
+{
+    fn map(&mut self, event: Event<'a>) -> FilterOutput<'a> {
+        self(event)
+    }
+}
+
+/// Iterator that runs every event of `inner` through an [`EventFilter`].
+pub struct FilteredStream<'a, I, F> {
+    inner: I,
+    filter: F,
+    pending: smallvec::IntoIter<[Event<'a>; 2]>,
+}
+
+impl<'a, I, F> FilteredStream<'a, I, F>
+where
+    I: Iterator<Item = Event<'a>>,
+    F: EventFilter<'a>,
+{
+    /// Wraps `inner` so its events pass through `filter`.
+    pub fn new(inner: I, filter: F) -> Self {
+        Self {
+            inner,
+            filter,
+            pending: FilterOutput::new().into_iter(),
+        }
+    }
+}
+
+impl<'a, I, F> Iterator for FilteredStream<'a, I, F>
+where
+    I: Iterator<Item = Event<'a>>,
+    F: EventFilter<'a>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.next() {
+                return Some(event);
+            }
+            self.pending = self.filter.map(self.inner.next()?).into_iter();
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::event::{Event as MfEvent, HeadingLevel, Tag};
+    use crate::event::{Event as MfEvent, HeadingLevel, Tag, TagEnd};
+    use smallvec::smallvec;
     use std::borrow::Cow;
 
     #[test]
@@ -56,4 +181,54 @@ mod tests {
 
         assert!(output_str.contains("<h1>Hello Stream</h1>"));
     }
+
+    #[test]
+    fn test_serialized_events_replay_identically() {
+        let input = "# Title\n\n| a | b |\n|:-|-:|\n| `x` | [y](/y \"t\") |\n\n- [x] done\n\n```rust run\nfn f() {}\n```";
+        let events: Vec<MfEvent<'static>> = crate::get_event_iterator(input).unwrap().collect();
+
+        let json = serde_json::to_string(&events).expect("events serialize");
+        let replayed: Vec<MfEvent<'static>> =
+            serde_json::from_str(&json).expect("events deserialize");
+        assert_eq!(replayed, events);
+
+        let original = events.into_iter().stream_to_writer(Vec::new()).unwrap();
+        let from_cache = replayed.into_iter().stream_to_writer(Vec::new()).unwrap();
+        assert_eq!(original, from_cache);
+    }
+
+    #[test]
+    fn test_filtered_stream_rewrites_drops_and_inserts() {
+        let input = "See [docs](/docs) and <b>bold</b>.\n\n---";
+        let filter = |event: MfEvent<'static>| -> FilterOutput<'static> {
+            match event {
+                MfEvent::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }) => smallvec![MfEvent::Start(Tag::Link {
+                    link_type,
+                    dest_url: format!("https://example.com{dest_url}").into(),
+                    title,
+                    id,
+                })],
+                MfEvent::Html(_) | MfEvent::InlineHtml(_) => smallvec![],
+                MfEvent::Rule => smallvec![
+                    MfEvent::Start(Tag::Paragraph),
+                    MfEvent::Text("* * *".into()),
+                    MfEvent::End(TagEnd::Paragraph),
+                ],
+                other => smallvec![other],
+            }
+        };
+
+        let events = FilteredStream::new(crate::get_event_iterator(input).unwrap(), filter);
+        let html = String::from_utf8(events.stream_to_writer(Vec::new()).unwrap()).unwrap();
+
+        assert_eq!(
+            html,
+            "<p>See <a href=\"https://example.com/docs\">docs</a> and bold.</p>\n<p>* * *</p>\n"
+        );
+    }
 }
@@ -0,0 +1,103 @@
+//! Fully-qualified heading anchor URLs for sitemaps and structured data.
+
+use serde::{Deserialize, Serialize};
+
+use crate::slug::Heading;
+
+/// Page location that heading slugs are appended to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnchorOptions {
+    /// Site origin, e.g. `https://example.com` or `https://example.com/docs/`.
+    pub base_url: String,
+    /// Page path relative to `base_url`, e.g. `guide/install`.
+    pub path: String,
+}
+
+impl AnchorOptions {
+    /// Joins `base_url` and `path` with exactly one `/` between them.
+    pub fn page_url(&self) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        let path = self.path.trim_start_matches('/');
+        if path.is_empty() {
+            base.to_string()
+        } else {
+            format!("{base}/{path}")
+        }
+    }
+}
+
+/// A heading together with its absolute anchor URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadingAnchor {
+    /// Heading level (1-6).
+    pub level: u8,
+    /// Plain text of the heading.
+    pub text: String,
+    /// Slug used as the heading's `id`.
+    pub id: String,
+    /// `page_url#id`, with the fragment percent-encoded.
+    pub url: String,
+}
+
+/// Anchors for every heading with an id; the page URL is only joined when there is one.
+pub(crate) fn anchors_for(headings: &[Heading], options: &AnchorOptions) -> Vec<HeadingAnchor> {
+    let mut page_url = None;
+    headings
+        .iter()
+        .filter_map(|heading| {
+            let id = heading.id.as_ref()?;
+            let page_url: &String = page_url.get_or_insert_with(|| options.page_url());
+            Some(HeadingAnchor {
+                level: heading.level,
+                text: heading.text.clone(),
+                id: id.clone(),
+                url: format!("{page_url}#{}", encode_fragment(id)),
+            })
+        })
+        .collect()
+}
+
+/// Percent-encodes everything outside the RFC 3986 unreserved set.
+pub(crate) fn encode_fragment(fragment: &str) -> String {
+    let mut out = String::with_capacity(fragment.len());
+    for byte in fragment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_page_url_segments() {
+        let cases = [
+            ("https://example.com", "guide", "https://example.com/guide"),
+            (
+                "https://example.com/",
+                "/guide/",
+                "https://example.com/guide/",
+            ),
+            ("https://example.com/docs", "", "https://example.com/docs"),
+        ];
+
+        for (base_url, path, expected) in cases {
+            let options = AnchorOptions {
+                base_url: base_url.into(),
+                path: path.into(),
+            };
+            assert_eq!(options.page_url(), expected);
+        }
+    }
+
+    #[test]
+    fn percent_encodes_non_ascii_slugs() {
+        assert_eq!(encode_fragment("install-guide"), "install-guide");
+        assert_eq!(encode_fragment("見出し"), "%E8%A6%8B%E5%87%BA%E3%81%97");
+    }
+}
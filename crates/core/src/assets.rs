@@ -0,0 +1,207 @@
+//! Local asset URLs referenced by a document, for bundlers that copy or fingerprint them.
+
+use crate::event::{Event, Tag};
+use crate::urls;
+
+/// Extensions of links that lead to pages rather than downloadable files.
+const PAGE_EXTENSIONS: [&str; 7] = ["htm", "html", "md", "markdown", "mdx", "php", "xhtml"];
+
+/// Raw HTML elements and the attributes holding their media sources.
+const MEDIA_ATTRIBUTES: [(&str, &[&str]); 5] = [
+    ("audio", &["src"]),
+    ("img", &["src"]),
+    ("source", &["src", "srcset"]),
+    ("track", &["src"]),
+    ("video", &["src", "poster"]),
+];
+
+/// How a document references an [`Asset`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetKind {
+    /// Markdown image source.
+    Image,
+    /// Link to a file other than a page, e.g. a PDF or an archive.
+    File,
+    /// Source of an `<img>`, `<video>`, `<audio>`, `<source>`, or `<track>` in raw HTML.
+    Media,
+}
+
+/// A relative or root-relative URL the document references, as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Asset {
+    /// URL as it appears in the document, fragment and query included.
+    pub url: String,
+    /// Where the URL came from.
+    pub kind: AssetKind,
+}
+
+/// Collects the local assets of a document from its event stream, each URL once in order of
+/// first reference.
+#[derive(Debug, Default)]
+pub struct AssetCollector {
+    assets: Vec<Asset>,
+}
+
+impl AssetCollector {
+    /// Creates an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one event; call for every event of the document in order.
+    pub fn observe(&mut self, event: &Event<'_>) {
+        match event {
+            Event::Start(Tag::Image { dest_url, .. }) => self.push(dest_url, AssetKind::Image),
+            Event::Start(Tag::Link { dest_url, .. }) if is_file(dest_url) => {
+                self.push(dest_url, AssetKind::File);
+            }
+            Event::Html(html) | Event::InlineHtml(html) => {
+                for url in media_urls(html) {
+                    self.push(&url, AssetKind::Media);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Consumes the collector, returning the assets in document order.
+    pub fn finish(self) -> Vec<Asset> {
+        self.assets
+    }
+
+    fn push(&mut self, url: &str, kind: AssetKind) {
+        let url = url.trim();
+        if urls::is_local(url) && !self.assets.iter().any(|asset| asset.url == url) {
+            self.assets.push(Asset {
+                url: url.to_string(),
+                kind,
+            });
+        }
+    }
+}
+
+/// Whether a link to `url` downloads a file: its last path segment has an extension that is
+/// not a page's.
+fn is_file(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.is_empty() => {
+            !PAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        }
+        _ => false,
+    }
+}
+
+/// Media source URLs in a chunk of raw HTML; `srcset` candidates are split into their URLs.
+fn media_urls(html: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let name_len = rest
+            .find(|ch: char| !ch.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let name = rest[..name_len].to_ascii_lowercase();
+        let Some((_, attributes)) = MEDIA_ATTRIBUTES.iter().find(|(tag, _)| *tag == name) else {
+            continue;
+        };
+        let end = rest.find('>').unwrap_or(rest.len());
+        for (key, value) in parse_attributes(&rest[name_len..end]) {
+            if !attributes.contains(&key.as_str()) {
+                continue;
+            }
+            if key == "srcset" {
+                urls.extend(
+                    value
+                        .split(',')
+                        .filter_map(|candidate| candidate.split_whitespace().next())
+                        .map(str::to_string),
+                );
+            } else {
+                urls.push(value.to_string());
+            }
+        }
+        rest = &rest[end..];
+    }
+    urls
+}
+
+/// `name=value` pairs of a tag's attribute list, names lowercased. Attributes without a value
+/// are skipped.
+fn parse_attributes(mut text: &str) -> Vec<(String, &str)> {
+    let mut attributes = Vec::new();
+    loop {
+        text = text.trim_start_matches(|ch: char| ch.is_ascii_whitespace() || ch == '/');
+        let name_len = text
+            .find(|ch: char| ch.is_ascii_whitespace() || matches!(ch, '=' | '/' | '>'))
+            .unwrap_or(text.len());
+        if name_len == 0 {
+            return attributes;
+        }
+        let name = text[..name_len].to_ascii_lowercase();
+        text = text[name_len..].trim_start();
+        let Some(value) = text.strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let (value, rest) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let inner = &value[1..];
+                let close = inner.find(quote).unwrap_or(inner.len());
+                (&inner[..close], inner.get(close + 1..).unwrap_or_default())
+            }
+            _ => {
+                let end = value
+                    .find(|ch: char| ch.is_ascii_whitespace() || ch == '>')
+                    .unwrap_or(value.len());
+                value.split_at(end)
+            }
+        };
+        attributes.push((name, value));
+        text = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Asset, AssetKind, RenderOptions, render};
+
+    #[test]
+    fn collects_local_assets() {
+        let input = "![Cat](img/cat.png) ![Remote](https://cdn.test/x.png) ![Again](img/cat.png)\n\n\
+                     [Guide](guide.md) [Slides](/files/talk.pdf#page=2) [Home](/) [Top](#top) \
+                     [Archive](v1.2/release.tar.gz) [Mail](mailto:a@b.test)\n\n\
+                     <video src=\"clips/intro.mp4\" poster='clips/poster.jpg' controls>\n\
+                     <source srcset=\"a.webp 1x, a@2x.webp 2x\"><track src=//cdn.test/captions.vtt>\n\
+                     </video>";
+        let options = RenderOptions {
+            assets: true,
+            ..RenderOptions::default()
+        };
+        let assets = render(input, &options).unwrap().assets;
+
+        let asset = |url: &str, kind| Asset {
+            url: url.to_string(),
+            kind,
+        };
+        assert_eq!(
+            assets,
+            [
+                asset("img/cat.png", AssetKind::Image),
+                asset("/files/talk.pdf#page=2", AssetKind::File),
+                asset("v1.2/release.tar.gz", AssetKind::File),
+                asset("clips/intro.mp4", AssetKind::Media),
+                asset("clips/poster.jpg", AssetKind::Media),
+                asset("a.webp", AssetKind::Media),
+                asset("a@2x.webp", AssetKind::Media),
+            ]
+        );
+        assert!(
+            render(input, &RenderOptions::default())
+                .unwrap()
+                .assets
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Typed document tree built from the event stream, serializable to JSON for JS tooling.
+
+use std::borrow::Cow;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::event::{Alignment, Event, LinkType, MdxJsxAttribute, Tag};
+
+/// Node kinds; serialized as camelCase strings in the `type` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AstKind {
+    /// Root of the tree.
+    Document,
+    /// Paragraph block.
+    Paragraph,
+    /// Heading with `level` and optional `id` attributes.
+    Heading,
+    /// Block quote.
+    BlockQuote,
+    /// Code block with optional `lang` and `meta` attributes and the code as `value`. A `title`
+    /// and `highlight` line ranges (`[[1, 1], [3, 5]]`) parsed from the meta are added when
+    /// present.
+    CodeBlock,
+    /// List with a `start` attribute when ordered.
+    List,
+    /// List item.
+    Item,
+    /// Footnote definition with a `label` attribute. Renumbered notes are labeled with their
+    /// number.
+    FootnoteDefinition,
+    /// `footnoteDefinition`s gathered at the end of the document, as endnotes or as a
+    /// GitHub-style list of renumbered notes.
+    FootnoteList,
+    /// `↩` link from a renumbered note back to one of its references, with `number` and `nth`
+    /// attributes.
+    FootnoteBackref,
+    /// Margin note with `label` and `nth` attributes, holding a footnote's content inline.
+    Sidenote,
+    /// Table with an `align` attribute listing column alignments.
+    Table,
+    /// Table header section.
+    TableHead,
+    /// Table row.
+    TableRow,
+    /// Table cell.
+    TableCell,
+    /// Emphasis.
+    Emphasis,
+    /// Strong emphasis.
+    Strong,
+    /// Strikethrough.
+    Strikethrough,
+    /// Highlighted (`==marked==`) text.
+    Highlight,
+    /// `^superscript^` text.
+    Superscript,
+    /// `~subscript~` text.
+    Subscript,
+    /// `[[Ctrl]]` keyboard key.
+    Keyboard,
+    /// Ruby annotation: base text followed by `rubyText` readings.
+    Ruby,
+    /// Reading inside a `ruby` node.
+    RubyText,
+    /// Collapsible block whose first child is its `summary`.
+    Details,
+    /// Title of a `details` node.
+    Summary,
+    /// Alert box with a `kind` attribute (`note`, `tip`, ...).
+    Callout,
+    /// Heading number in a table of contents entry.
+    HeadingNumber,
+    /// Numbered figure with an optional `id`: an image followed by its `figureCaption`.
+    Figure,
+    /// Caption of a `figure` node.
+    FigureCaption,
+    /// Numbered image inside a paragraph, with an optional `id`.
+    InlineFigure,
+    /// Link with `url` and optional `title` attributes, plus `reference` (`full`, `collapsed`, or
+    /// `shortcut`) and `label` when written against a link reference definition.
+    Link,
+    /// Image with `url`, `alt`, and optional `title` attributes, plus `reference` and `label` like
+    /// [`AstKind::Link`].
+    Image,
+    /// MDX expression source `value`, without braces.
+    MdxExpression,
+    /// MDX JSX element with a `name` (absent for fragments), `attributes`, and `selfClosing`.
+    MdxJsxElement,
+    /// Plain text `value`.
+    Text,
+    /// Inline code `value`.
+    Code,
+    /// Block-level raw HTML `value`.
+    Html,
+    /// Inline raw HTML `value`.
+    InlineHtml,
+    /// Inline math `value`.
+    InlineMath,
+    /// Display math `value`.
+    DisplayMath,
+    /// Footnote reference with a `label` attribute.
+    FootnoteReference,
+    /// Task list checkbox with a `checked` attribute.
+    TaskListMarker,
+    /// Thematic break.
+    Rule,
+    /// Hard line break.
+    HardBreak,
+    /// Soft line break.
+    SoftBreak,
+}
+
+/// A node in the document tree.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AstNode {
+    /// Node kind.
+    #[serde(rename = "type")]
+    pub kind: AstKind,
+    /// Kind-specific attributes (heading level, link url, ...).
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub attrs: Map<String, Value>,
+    /// Literal content of text-like nodes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Child nodes of containers.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<AstNode>,
+}
+
+impl AstNode {
+    fn new(kind: AstKind) -> Self {
+        Self {
+            kind,
+            attrs: Map::new(),
+            value: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn leaf(kind: AstKind, value: &str) -> Self {
+        let mut node = Self::new(kind);
+        node.value = Some(value.to_string());
+        node
+    }
+
+    fn attr(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.attrs.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Serializes the tree into a JSON value.
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+
+    /// Concatenated text of this node and its descendants.
+    pub fn text(&self) -> String {
+        let mut buf = String::new();
+        self.collect_text(&mut buf);
+        buf
+    }
+
+    fn collect_text(&self, buf: &mut String) {
+        match self.kind {
+            AstKind::Text | AstKind::Code => buf.push_str(self.value.as_deref().unwrap_or("")),
+            AstKind::Image => {
+                if let Some(Value::String(alt)) = self.attrs.get("alt") {
+                    buf.push_str(alt);
+                }
+            }
+            _ => self
+                .children
+                .iter()
+                .for_each(|child| child.collect_text(buf)),
+        }
+    }
+}
+
+/// Folds a balanced event stream into an [`AstNode`] tree rooted at [`AstKind::Document`].
+pub(crate) fn build<'a, I>(events: I) -> AstNode
+where
+    I: IntoIterator<Item = Event<'a>>,
+{
+    let mut stack = vec![AstNode::new(AstKind::Document)];
+    // Text inside code blocks and images becomes an attribute of the container.
+    let mut literal: Option<String> = None;
+
+    for event in events {
+        match event {
+            Event::Start(tag) => {
+                if matches!(tag, Tag::CodeBlock(_) | Tag::Image { .. }) {
+                    literal = Some(String::new());
+                }
+                stack.push(start_node(tag));
+            }
+            Event::End(_) => {
+                let Some(mut node) = stack.pop() else {
+                    continue;
+                };
+                if let Some(text) = literal.take() {
+                    match node.kind {
+                        AstKind::CodeBlock => node.value = Some(text),
+                        _ => node = node.attr("alt", text),
+                    }
+                }
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    // Unbalanced end tag: keep the popped node as the new root.
+                    None => stack.push(node),
+                }
+            }
+            Event::Text(text) | Event::Code(text) if literal.is_some() => {
+                if let Some(buf) = literal.as_mut() {
+                    buf.push_str(&text);
+                }
+            }
+            leaf => {
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(leaf_node(leaf));
+                }
+            }
+        }
+    }
+
+    while stack.len() > 1 {
+        if let Some(node) = stack.pop()
+            && let Some(parent) = stack.last_mut()
+        {
+            parent.children.push(node);
+        }
+    }
+    stack
+        .pop()
+        .unwrap_or_else(|| AstNode::new(AstKind::Document))
+}
+
+fn start_node(tag: Tag<'_>) -> AstNode {
+    match tag {
+        Tag::Paragraph => AstNode::new(AstKind::Paragraph),
+        Tag::Heading { level, id, .. } => {
+            let node = AstNode::new(AstKind::Heading).attr("level", level as u8);
+            match id {
+                Some(id) => node.attr("id", id.into_owned()),
+                None => node,
+            }
+        }
+        Tag::BlockQuote => AstNode::new(AstKind::BlockQuote),
+        Tag::CodeBlock(kind) => {
+            let mut node = AstNode::new(AstKind::CodeBlock);
+            if let Some(lang) = kind.lang() {
+                node = node.attr("lang", lang);
+            }
+            if let Some(meta) = kind.meta() {
+                node = node.attr("meta", meta);
+            }
+            if let Some(meta) = kind.code_meta() {
+                if let Some(title) = &meta.title {
+                    node = node.attr("title", title.as_str());
+                }
+                if !meta.highlight.is_empty() {
+                    let ranges: Vec<Value> = meta
+                        .highlight
+                        .iter()
+                        .map(|range| Value::from(vec![*range.start(), *range.end()]))
+                        .collect();
+                    node = node.attr("highlight", ranges);
+                }
+            }
+            node
+        }
+        Tag::List(start) => match start {
+            Some(start) => AstNode::new(AstKind::List).attr("start", start),
+            None => AstNode::new(AstKind::List),
+        },
+        Tag::Item => AstNode::new(AstKind::Item),
+        Tag::FootnoteDefinition(label) => {
+            AstNode::new(AstKind::FootnoteDefinition).attr("label", label.into_owned())
+        }
+        Tag::Endnotes | Tag::FootnoteList => AstNode::new(AstKind::FootnoteList),
+        Tag::FootnoteItem(number) => {
+            AstNode::new(AstKind::FootnoteDefinition).attr("label", number.to_string())
+        }
+        Tag::NumberedFootnoteReference { number, .. } => {
+            AstNode::new(AstKind::FootnoteReference).attr("label", number.to_string())
+        }
+        Tag::FootnoteBackref { number, nth } => AstNode::new(AstKind::FootnoteBackref)
+            .attr("number", number)
+            .attr("nth", nth),
+        Tag::Sidenote { label, nth } => AstNode::new(AstKind::Sidenote)
+            .attr("label", label.into_owned())
+            .attr("nth", nth),
+        Tag::Table(alignments) => {
+            let align: Vec<Value> = alignments
+                .iter()
+                .map(|alignment| match alignment {
+                    Alignment::None => Value::Null,
+                    Alignment::Left => "left".into(),
+                    Alignment::Center => "center".into(),
+                    Alignment::Right => "right".into(),
+                })
+                .collect();
+            AstNode::new(AstKind::Table).attr("align", align)
+        }
+        Tag::TableHead => AstNode::new(AstKind::TableHead),
+        Tag::TableRow => AstNode::new(AstKind::TableRow),
+        Tag::TableCell => AstNode::new(AstKind::TableCell),
+        Tag::Emphasis => AstNode::new(AstKind::Emphasis),
+        Tag::Strong => AstNode::new(AstKind::Strong),
+        Tag::Strikethrough => AstNode::new(AstKind::Strikethrough),
+        Tag::Highlight => AstNode::new(AstKind::Highlight),
+        Tag::Superscript => AstNode::new(AstKind::Superscript),
+        Tag::Keyboard => AstNode::new(AstKind::Keyboard),
+        Tag::Ruby => AstNode::new(AstKind::Ruby),
+        Tag::RubyText => AstNode::new(AstKind::RubyText),
+        Tag::Details => AstNode::new(AstKind::Details),
+        Tag::Summary => AstNode::new(AstKind::Summary),
+        Tag::HeadingNumber => AstNode::new(AstKind::HeadingNumber),
+        Tag::Figure(id) => figure(AstKind::Figure, id),
+        Tag::FigureCaption => AstNode::new(AstKind::FigureCaption),
+        Tag::InlineFigure(id) => figure(AstKind::InlineFigure, id),
+        Tag::Callout(kind) => AstNode::new(AstKind::Callout).attr("kind", kind.name()),
+        Tag::Subscript => AstNode::new(AstKind::Subscript),
+        Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        } => reference(link_like(AstKind::Link, &dest_url, &title), link_type, &id),
+        Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+            width,
+            height,
+            ..
+        } => {
+            let mut node = reference(link_like(AstKind::Image, &dest_url, &title), link_type, &id);
+            if let Some(width) = width {
+                node = node.attr("width", width);
+            }
+            if let Some(height) = height {
+                node = node.attr("height", height);
+            }
+            node
+        }
+        Tag::MdxJsxElement {
+            name,
+            attributes,
+            self_closing,
+        } => {
+            let attributes: Vec<Value> = attributes.iter().map(jsx_attribute).collect();
+            let node = AstNode::new(AstKind::MdxJsxElement)
+                .attr("attributes", attributes)
+                .attr("selfClosing", self_closing);
+            match name {
+                Some(name) => node.attr("name", name.into_owned()),
+                None => node,
+            }
+        }
+    }
+}
+
+fn link_like(kind: AstKind, url: &str, title: &str) -> AstNode {
+    let node = AstNode::new(kind).attr("url", url);
+    if title.is_empty() {
+        node
+    } else {
+        node.attr("title", title)
+    }
+}
+
+fn figure(kind: AstKind, id: Option<Cow<'_, str>>) -> AstNode {
+    match id {
+        Some(id) => AstNode::new(kind).attr("id", id.as_ref()),
+        None => AstNode::new(kind),
+    }
+}
+
+/// Records how a link or image refers to its definition; inline ones are left as they are.
+fn reference(node: AstNode, link_type: LinkType, label: &str) -> AstNode {
+    let reference = match link_type {
+        LinkType::Reference => "full",
+        LinkType::Collapsed => "collapsed",
+        LinkType::Shortcut => "shortcut",
+        _ => return node,
+    };
+    node.attr("reference", reference).attr("label", label)
+}
+
+/// `{ "type": "property" | "expression" | "spread", "name"?, "value"? }`.
+fn jsx_attribute(attribute: &MdxJsxAttribute<'_>) -> Value {
+    let mut object = Map::new();
+    let (kind, name, value) = match attribute {
+        MdxJsxAttribute::Property { name, value } => ("property", Some(name), value.as_ref()),
+        MdxJsxAttribute::Expression { name, value } => ("expression", Some(name), Some(value)),
+        MdxJsxAttribute::Spread(value) => ("spread", None, Some(value)),
+    };
+    object.insert("type".into(), kind.into());
+    if let Some(name) = name {
+        object.insert("name".into(), name.to_string().into());
+    }
+    if let Some(value) = value {
+        object.insert("value".into(), value.to_string().into());
+    }
+    Value::Object(object)
+}
+
+fn leaf_node(event: Event<'_>) -> AstNode {
+    match event {
+        Event::Text(text) => AstNode::leaf(AstKind::Text, &text),
+        Event::Code(code) => AstNode::leaf(AstKind::Code, &code),
+        Event::Html(html) => AstNode::leaf(AstKind::Html, &html),
+        Event::InlineHtml(html) => AstNode::leaf(AstKind::InlineHtml, &html),
+        Event::InlineMath(math) => AstNode::leaf(AstKind::InlineMath, &math),
+        Event::DisplayMath(math) => AstNode::leaf(AstKind::DisplayMath, &math),
+        Event::FootnoteReference(label) => {
+            AstNode::new(AstKind::FootnoteReference).attr("label", label.into_owned())
+        }
+        Event::TaskListMarker(checked) => {
+            AstNode::new(AstKind::TaskListMarker).attr("checked", checked)
+        }
+        Event::Rule => AstNode::new(AstKind::Rule),
+        Event::HardBreak => AstNode::new(AstKind::HardBreak),
+        Event::SoftBreak => AstNode::new(AstKind::SoftBreak),
+        Event::MdxExpression(expression) => AstNode::leaf(AstKind::MdxExpression, &expression),
+        Event::Start(_) | Event::End(_) => unreachable!("container events handled by build"),
+    }
+}
@@ -0,0 +1,137 @@
+//! [`StreamingRewriter`] over a tokio [`AsyncWrite`], for streaming rewritten HTML into
+//! hyper/axum response bodies.
+//!
+//! lol_html rewrites synchronously into a buffer; each write first hands the previous output to
+//! the underlying writer, so a slow client holds back whoever feeds the rewriter. Like
+//! [`StreamingRewriter`], the rewriter is not `Send`: drive it from a `LocalSet`, or render in
+//! `spawn_blocking` and forward the chunks over a channel.
+
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use tokio::io::AsyncWrite;
+
+use crate::rewrite_options::RewriteOptions;
+use crate::streaming_rewriter::StreamingRewriter;
+
+/// Implements [`AsyncWrite`] by rewriting through a [`StreamingRewriter`] into `W`.
+///
+/// [`poll_flush`](AsyncWrite::poll_flush) only hands over the output produced so far;
+/// [`poll_shutdown`](AsyncWrite::poll_shutdown) ends the document, writes the rest, and shuts
+/// `W` down.
+pub struct AsyncStreamingRewriter<W> {
+    rewriter: StreamingRewriter<Vec<u8>>,
+    writer: W,
+    /// Rewritten output `writer` has not accepted yet, from `written` on.
+    pending: Vec<u8>,
+    written: usize,
+    ended: bool,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncStreamingRewriter<W> {
+    /// Rewrites with `options` into `writer`.
+    pub fn new(writer: W, options: RewriteOptions) -> Self {
+        Self::with_rewriter(StreamingRewriter::new(Vec::new(), options), writer)
+    }
+
+    /// Forwards the output of `rewriter`, e.g. one from a
+    /// [`RewriterFactory`](crate::RewriterFactory) or a builder with custom handlers, into
+    /// `writer`. Output already in `rewriter`'s buffer is written first.
+    pub fn with_rewriter(rewriter: StreamingRewriter<Vec<u8>>, writer: W) -> Self {
+        Self {
+            rewriter,
+            writer,
+            pending: Vec::new(),
+            written: 0,
+            ended: false,
+        }
+    }
+
+    /// Returns the underlying writer, dropping output it has not accepted yet; shut the
+    /// rewriter down first to end the document.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes pending output until `writer` has taken all of it.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if self.written == self.pending.len() {
+                self.pending = self.rewriter.take_output();
+                self.written = 0;
+                if self.pending.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+            }
+            let written =
+                ready!(Pin::new(&mut self.writer).poll_write(cx, &self.pending[self.written..]))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += written;
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncStreamingRewriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        this.rewriter.write_all(buf)?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.ended {
+            // `flush` ends the lol_html document, writing what it held back.
+            this.rewriter.flush()?;
+            this.ended = true;
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MarkdownStream;
+    use crate::html_renderer::HtmlOptions;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn streams_rewritten_html_into_async_writers() {
+        let input = "# Title\n\n![cat](/cat.png)\n\n".repeat(500);
+        let rewriter = StreamingRewriter::new(Vec::new(), RewriteOptions::default());
+        let expected = crate::get_event_iterator(&input)
+            .unwrap()
+            .stream_to_writer(rewriter)
+            .unwrap()
+            .into_inner()
+            .unwrap();
+
+        let rewriter = AsyncStreamingRewriter::new(Vec::new(), RewriteOptions::default());
+        let mut rewriter = crate::get_event_iterator(&input)
+            .unwrap()
+            .stream_to_async_writer(rewriter, &HtmlOptions::default())
+            .await
+            .unwrap();
+        rewriter.shutdown().await.unwrap();
+
+        let output = rewriter.into_inner();
+        assert!(output.len() > crate::adapter::ASYNC_CHUNK_BYTES);
+        assert_eq!(output, expected);
+    }
+}
@@ -0,0 +1,306 @@
+//! Parser backend selection.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::MarkflowError;
+use crate::diagnostics::Diagnostics;
+use crate::event::EventStream;
+use crate::inline_footnotes::InlineFootnotes;
+use crate::markdown_adapter::MarkdownRsEventIter;
+use crate::marks::{Mark, Marks};
+use crate::pulldown_adapter::PulldownEventIter;
+use crate::slug::Slugger;
+
+/// Markdown parser that feeds the event pipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParserBackend {
+    /// markdown-rs: builds an mdast tree first; supports MDX constructs and source positions.
+    #[default]
+    MarkdownRs,
+    /// pulldown-cmark: a pull parser without an intermediate tree, generally faster and lighter
+    /// on memory, but without MDX support.
+    PulldownCmark,
+}
+
+/// Optional Markdown constructs; everything except heading attributes, highlights, superscript
+/// and subscript, and MDX is enabled by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntaxOptions {
+    /// Turn bare `www.` and `https://` URLs into links (GFM autolink literals). pulldown-cmark
+    /// does not support them and ignores this flag.
+    pub autolinks: bool,
+    /// `$inline$` and `$$display$$` math. Always off without the `math` cargo feature.
+    pub math: bool,
+    /// `[^label]` footnote references and definitions, and `^[inline]` footnotes. Always off
+    /// without the `footnotes` cargo feature.
+    pub footnotes: bool,
+    /// YAML (`---`) and TOML (`+++`) frontmatter blocks. Always off without the `frontmatter`
+    /// cargo feature.
+    pub frontmatter: bool,
+    /// Trailing `{#id .class key=value}` blocks on headings set the heading's id, classes, and
+    /// attributes, and the same blocks in a fenced code block's info string set them on its
+    /// `<pre>`. Keys that are not plain attribute names are left out. Off by default.
+    pub heading_attributes: bool,
+    /// `==marked==` text, rendered as `<mark>`. Off by default.
+    pub highlight: bool,
+    /// `^superscript^` and `~subscript~` text, rendered as `<sup>` and `<sub>`. While set, only
+    /// `~~double~~` tildes strike text through. Off by default.
+    pub superscript_subscript: bool,
+    /// Render every line break inside a paragraph as `<br />`, as chat and comment boxes do,
+    /// instead of only those ending in two spaces or a backslash. Off by default.
+    pub breaks: bool,
+    /// Parse MDX: JSX elements, `{expressions}`, and `import`/`export` statements. As in MDX, raw
+    /// HTML, indented code, and `<url>` autolinks are off while this is set. Off by default;
+    /// pulldown-cmark does not support MDX and ignores this flag.
+    pub mdx: bool,
+    /// Read image size hints from a trailing `=640x480` in the title or from `w`/`h` query
+    /// parameters, rendered as `width` and `height` attributes so pages don't shift as images
+    /// load. Off by default.
+    pub image_sizes: bool,
+}
+
+impl Default for SyntaxOptions {
+    fn default() -> Self {
+        Self {
+            autolinks: true,
+            math: true,
+            footnotes: true,
+            frontmatter: true,
+            heading_attributes: false,
+            highlight: false,
+            superscript_subscript: false,
+            breaks: false,
+            mdx: false,
+            image_sizes: false,
+        }
+    }
+}
+
+impl ParserBackend {
+    /// Parses `input` into Markflow events with this backend.
+    pub fn events<'a>(
+        self,
+        input: &'a str,
+        syntax: &SyntaxOptions,
+    ) -> Result<EventStream<'a>, MarkflowError> {
+        self.events_with_slugger(input, syntax, Slugger::new())
+    }
+
+    /// Same as [`ParserBackend::events`], with heading ids handed out by `slugger`.
+    pub fn events_with_slugger<'a>(
+        self,
+        input: &'a str,
+        syntax: &SyntaxOptions,
+        slugger: Slugger,
+    ) -> Result<EventStream<'a>, MarkflowError> {
+        self.events_with_diagnostics(input, syntax, slugger, None)
+    }
+
+    /// Same as [`ParserBackend::events_with_slugger`], with the adapter recording what it finds
+    /// into `diagnostics` as it produces the events.
+    pub(crate) fn events_with_diagnostics<'a>(
+        self,
+        input: &'a str,
+        syntax: &SyntaxOptions,
+        slugger: Slugger,
+        diagnostics: Option<Rc<RefCell<Diagnostics>>>,
+    ) -> Result<EventStream<'a>, MarkflowError> {
+        let events: EventStream<'a> = match self {
+            ParserBackend::MarkdownRs => Box::new(
+                MarkdownRsEventIter::with_syntax(input, syntax)
+                    .map_err(MarkflowError::parse)?
+                    .with_slugger(slugger)
+                    .with_diagnostics(diagnostics),
+            ),
+            ParserBackend::PulldownCmark => Box::new(
+                PulldownEventIter::with_syntax(input, syntax, diagnostics).with_slugger(slugger),
+            ),
+        };
+        let events: EventStream<'a> = if syntax.footnotes && cfg!(feature = "footnotes") {
+            Box::new(InlineFootnotes::new(events, input))
+        } else {
+            events
+        };
+        let mut marks = Vec::new();
+        if syntax.highlight {
+            marks.push(Mark::Highlight);
+        }
+        // pulldown-cmark parses superscript and subscript only where `_` emphasis could open,
+        // so intraword marks such as `x^2^` still reach the transform as text.
+        if syntax.superscript_subscript {
+            marks.extend([Mark::Superscript, Mark::Subscript]);
+        }
+        Ok(if marks.is_empty() {
+            events
+        } else {
+            Box::new(Marks::new(events, marks))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RenderOptions, render};
+
+    #[test]
+    fn backends_render_identical_html() {
+        let cases = [
+            "# Hello *world*\n\nSome `code` and [a link](/x \"T\").",
+            "- one\n- two\n  - nested\n\n1. first\n2. second",
+            "- loose\n\n- list",
+            "> quote\n>\n> more",
+            "```rust run\nfn main() {}\n```\n\n    indented",
+            "| a | b |\n|:-|-:|\n| 1 | 2 |",
+            "- [x] done\n- [ ] todo",
+            "[ref][R] and ![img]\n\n[r]: /r \"T\"\n[img]: /i.png",
+            "~~gone~~ $x^2$\n\n$$\ny\n$$",
+            "---\ntitle: x\n---\n\nbody",
+            "line\nsoft\\\nhard\n\n---\n\n![alt *t*](i.png)",
+        ];
+
+        for input in cases {
+            let html = |backend| {
+                let options = RenderOptions {
+                    backend,
+                    ..RenderOptions::default()
+                };
+                render(input, &options).unwrap().html
+            };
+            assert_eq!(
+                html(ParserBackend::PulldownCmark),
+                html(ParserBackend::MarkdownRs),
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn backends_dedupe_heading_ids() {
+        let input = "# Intro\n\n## Intro\n\n> ### Intro 1\n\n# *Intro*";
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                headings: true,
+                ..RenderOptions::default()
+            };
+            let ids: Vec<_> = render(input, &options)
+                .unwrap()
+                .headings
+                .into_iter()
+                .filter_map(|heading| heading.id)
+                .collect();
+            assert_eq!(
+                ids,
+                ["intro", "intro-1", "intro-1-1", "intro-2"],
+                "{backend:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn backends_parse_heading_attributes() {
+        let cases = [
+            (
+                "# Title {#intro .wide .dark data-x=1 hidden}",
+                "<h1 id=\"intro\" class=\"wide dark\" data-x=\"1\" hidden>Title</h1>",
+            ),
+            ("## *Em* {.x}", "<h2 id=\"em\" class=\"x\"><em>Em</em></h2>"),
+            ("## Set {x} up", "<h2 id=\"set-x-up\">Set {x} up</h2>"),
+            ("# Bad {@x=1 1y=2 ok=1}", "<h1 id=\"bad\" ok=\"1\">Bad</h1>"),
+            (
+                "```rust {#main .wide data-x=1 x\"y=2} {2}\nfn main() {}\n```",
+                "<pre id=\"main\" class=\"wide\" data-x=\"1\"><code class=\"language-rust\">fn main() {}</code></pre>",
+            ),
+        ];
+        let syntax = SyntaxOptions {
+            heading_attributes: true,
+            ..SyntaxOptions::default()
+        };
+
+        for (input, expected) in cases {
+            for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+                let options = RenderOptions {
+                    backend,
+                    syntax,
+                    ..RenderOptions::default()
+                };
+                let html = render(input, &options).unwrap().html;
+                assert_eq!(html.trim_end(), expected, "{backend:?}: {input:?}");
+            }
+        }
+
+        let options = RenderOptions {
+            syntax,
+            ..RenderOptions::default()
+        };
+        let html = render("# Bad {a\"><b=1}", &options).unwrap().html;
+        assert_eq!(html, "<h1 id=\"bad\">Bad</h1>\n");
+
+        let html = render("# Title {#intro}", &RenderOptions::default())
+            .unwrap()
+            .html;
+        assert_eq!(html, "<h1 id=\"title-intro\">Title {#intro}</h1>\n");
+        let html = render("```rust {#main}\nx\n```", &RenderOptions::default())
+            .unwrap()
+            .html;
+        assert_eq!(html, "<pre><code class=\"language-rust\">x</code></pre>\n");
+    }
+
+    #[test]
+    fn backends_render_breaks() {
+        let cases = [
+            (
+                "one\ntwo *em\nphasis*",
+                "<p>one<br />\ntwo <em>em<br />\nphasis</em></p>",
+            ),
+            ("a  \nb\nc", "<p>a<br />\nb<br />\nc</p>"),
+            ("```\nx\ny\n```", "<pre><code>x\ny</code></pre>"),
+        ];
+        let syntax = SyntaxOptions {
+            breaks: true,
+            ..SyntaxOptions::default()
+        };
+
+        for (input, expected) in cases {
+            for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+                let options = RenderOptions {
+                    backend,
+                    syntax,
+                    ..RenderOptions::default()
+                };
+                let html = render(input, &options).unwrap().html;
+                assert_eq!(html.trim_end(), expected, "{backend:?}: {input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn backends_respect_disabled_syntax() {
+        let input = "---\na: 1\n---\n\n$x$ and a note[^n]\n\n[^n]: two words";
+        let syntax = SyntaxOptions {
+            math: false,
+            footnotes: false,
+            frontmatter: false,
+            ..SyntaxOptions::default()
+        };
+
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                syntax,
+                ..RenderOptions::default()
+            };
+            let html = render(input, &options).unwrap().html;
+            assert!(html.starts_with("<hr />"), "{backend:?}: {html}");
+            assert!(
+                html.contains("<p>$x$ and a note[^n]</p>"),
+                "{backend:?}: {html}"
+            );
+            assert!(!html.contains("footnote"), "{backend:?}: {html}");
+        }
+    }
+}
@@ -0,0 +1,543 @@
+//! Rendering a set of documents together, e.g. the pages of a documentation site.
+//!
+//! [`BatchRenderer`] renders every document with the same [`RenderOptions`] (in parallel with
+//! the `rayon` feature) and then builds what no single render can see: which heading ids each
+//! document defines, and which documents link to which.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use markdown::mdast;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::markdown_adapter::parse_tree_with;
+use crate::render::{RenderOptions, RenderResult, render};
+use crate::span::SourceSpan;
+use crate::{MarkflowError, urls};
+
+/// Extensions of link targets [`BatchResult::check_links`] expects to be documents of the batch.
+const DOCUMENT_EXTENSIONS: [&str; 3] = ["md", "markdown", "mdx"];
+
+/// A Markdown document of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    /// Path of the document relative to the batch root, with `/` separators (`guide/setup.md`).
+    pub path: String,
+    /// Markdown source.
+    pub source: String,
+}
+
+impl Document {
+    /// Creates a document at `path`.
+    pub fn new(path: impl Into<String>, source: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+}
+
+/// Renders many documents with shared options; see the module docs.
+#[derive(Debug, Clone, Default)]
+pub struct BatchRenderer {
+    options: RenderOptions,
+}
+
+/// A link from one document of a batch to a local target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentLink {
+    /// Link destination as written (`../api.md#errors`, `#usage`).
+    pub href: String,
+    /// Batch path the destination points to, resolved against the linking document; the linking
+    /// document itself for `#anchor` links.
+    pub path: String,
+    /// Fragment of the destination, without the `#`.
+    pub fragment: Option<String>,
+    /// Location of the link in the linking document.
+    pub span: Option<SourceSpan>,
+}
+
+/// Output of one document of a batch.
+#[derive(Debug)]
+pub struct BatchDocument {
+    /// [`Document::path`].
+    pub path: String,
+    /// The document rendered with the batch options.
+    pub result: Result<RenderResult, MarkflowError>,
+    /// Local links of the document, in source order.
+    pub links: Vec<DocumentLink>,
+}
+
+/// Element ids of every document in a batch: heading ids, and the ids the renderer generates for
+/// footnotes, figures, and sidenotes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SlugRegistry {
+    ids: HashMap<String, HashSet<String>>,
+}
+
+impl SlugRegistry {
+    /// Whether the document at `path` is part of the batch.
+    pub fn has_document(&self, path: &str) -> bool {
+        self.ids.contains_key(path)
+    }
+
+    /// Whether the document at `path` has an element with the id `id`.
+    pub fn contains(&self, path: &str, id: &str) -> bool {
+        self.ids.get(path).is_some_and(|ids| ids.contains(id))
+    }
+
+    /// Element ids of the document at `path`.
+    pub fn ids(&self, path: &str) -> Option<&HashSet<String>> {
+        self.ids.get(path)
+    }
+}
+
+/// A problem found while rendering a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchDiagnostic {
+    /// Path of the document the problem is in.
+    pub path: String,
+    /// Location in that document, when known.
+    pub span: Option<SourceSpan>,
+    /// What went wrong.
+    pub issue: BatchIssue,
+}
+
+/// Kinds of [`BatchDiagnostic`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchIssue {
+    /// The document failed to render; its [`BatchDocument::result`] holds the error.
+    RenderFailed {
+        /// [`MarkflowError::code`] of the error.
+        code: &'static str,
+        /// The error message.
+        message: String,
+    },
+    /// A link points to a Markdown document that is not part of the batch.
+    MissingDocument {
+        /// Link destination as written.
+        target: String,
+    },
+    /// A link points to a fragment that no heading of the target document has as its id.
+    MissingAnchor {
+        /// Link destination as written.
+        target: String,
+    },
+}
+
+impl fmt::Display for BatchDiagnostic {
+    /// `path:line: message`, the shape CI annotations and editors pick up.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{}:{}: ", self.path, span.start_line)?,
+            None => write!(f, "{}: ", self.path)?,
+        }
+        match &self.issue {
+            BatchIssue::RenderFailed { code, message } => write!(f, "{code}: {message}"),
+            BatchIssue::MissingDocument { target } => {
+                write!(f, "link to `{target}`: no such document")
+            }
+            BatchIssue::MissingAnchor { target } => {
+                write!(f, "link to `{target}`: no heading with that id")
+            }
+        }
+    }
+}
+
+/// Output of [`BatchRenderer::render`].
+#[derive(Debug)]
+pub struct BatchResult {
+    /// One entry per input document, in input order.
+    pub documents: Vec<BatchDocument>,
+    /// Heading ids of every document that rendered.
+    pub slugs: SlugRegistry,
+    /// Problems across all documents, in document order.
+    pub diagnostics: Vec<BatchDiagnostic>,
+}
+
+impl BatchResult {
+    /// Links from any document of the batch to the document at `path`, with the linking path.
+    pub fn links_to<'r>(
+        &'r self,
+        path: &'r str,
+    ) -> impl Iterator<Item = (&'r str, &'r DocumentLink)> + 'r {
+        self.documents.iter().flat_map(move |document| {
+            document
+                .links
+                .iter()
+                .filter(move |link| link.path == path)
+                .map(move |link| (document.path.as_str(), link))
+        })
+    }
+
+    /// Links to Markdown documents missing from the batch, and links whose fragment matches no
+    /// heading id of the target document, in document order. Links to other files are not
+    /// checked, and neither are links into documents that failed to render.
+    pub fn check_links(&self) -> Vec<BatchDiagnostic> {
+        let paths: HashSet<&str> = self
+            .documents
+            .iter()
+            .map(|document| document.path.as_str())
+            .collect();
+        let mut diagnostics = Vec::new();
+        for document in &self.documents {
+            for link in &document.links {
+                let issue = if !paths.contains(link.path.as_str()) {
+                    let extension = link.path.rsplit_once('.').map(|(_, ext)| ext);
+                    extension
+                        .filter(|ext| {
+                            DOCUMENT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())
+                        })
+                        .map(|_| BatchIssue::MissingDocument {
+                            target: link.href.clone(),
+                        })
+                } else {
+                    link.fragment
+                        .as_deref()
+                        .filter(|fragment| {
+                            self.slugs.has_document(&link.path)
+                                && !self.slugs.contains(&link.path, fragment)
+                        })
+                        .map(|_| BatchIssue::MissingAnchor {
+                            target: link.href.clone(),
+                        })
+                };
+                if let Some(issue) = issue {
+                    diagnostics.push(BatchDiagnostic {
+                        path: document.path.clone(),
+                        span: link.span,
+                        issue,
+                    });
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+impl BatchRenderer {
+    /// Creates a renderer sharing `options` between documents. Headings are collected even when
+    /// [`RenderOptions::headings`] is off, because the slug registry needs them.
+    pub fn new(options: RenderOptions) -> Self {
+        Self { options }
+    }
+
+    /// The options every document is rendered with.
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// Renders `documents`, collecting their heading ids and local links.
+    pub fn render(&self, documents: &[Document]) -> BatchResult {
+        let options = RenderOptions {
+            headings: true,
+            ..self.options.clone()
+        };
+        let render_one = |document: &Document| {
+            let result = render(&document.source, &options);
+            let links = match result {
+                Ok(_) => document_links(document, &options),
+                Err(_) => Vec::new(),
+            };
+            BatchDocument {
+                path: document.path.clone(),
+                result,
+                links,
+            }
+        };
+        #[cfg(feature = "rayon")]
+        let mut rendered: Vec<BatchDocument> = documents.par_iter().map(render_one).collect();
+        #[cfg(not(feature = "rayon"))]
+        let mut rendered: Vec<BatchDocument> = documents.iter().map(render_one).collect();
+
+        let mut slugs = SlugRegistry::default();
+        let mut diagnostics = Vec::new();
+        for document in &mut rendered {
+            match &mut document.result {
+                Ok(result) => {
+                    let ids = result
+                        .headings
+                        .iter()
+                        .filter_map(|heading| heading.id.clone())
+                        .chain(element_ids(&result.html));
+                    slugs.ids.insert(document.path.clone(), ids.collect());
+                    if !self.options.headings {
+                        result.headings.clear();
+                    }
+                }
+                Err(err) => diagnostics.push(BatchDiagnostic {
+                    path: document.path.clone(),
+                    span: err.span(),
+                    issue: BatchIssue::RenderFailed {
+                        code: err.code(),
+                        message: err.to_string(),
+                    },
+                }),
+            }
+        }
+        BatchResult {
+            documents: rendered,
+            slugs,
+            diagnostics,
+        }
+    }
+}
+
+/// Every `id` attribute the renderer wrote into `html`, including generated ones such as `fn-1`.
+fn element_ids(html: &str) -> impl Iterator<Item = String> + '_ {
+    html.split(" id=\"").skip(1).filter_map(|rest| {
+        let (id, _) = rest.split_once('"')?;
+        Some(html_escape::decode_html_entities(id).into_owned())
+    })
+}
+
+/// Local links of `document`, with reference links resolved through their definitions.
+fn document_links(document: &Document, options: &RenderOptions) -> Vec<DocumentLink> {
+    let Ok(tree) = parse_tree_with(&document.source, &options.syntax) else {
+        return Vec::new();
+    };
+    let mut definitions = HashMap::new();
+    collect_definitions(&tree, &mut definitions);
+    let mut links = Vec::new();
+    collect_links(&tree, &definitions, &document.path, &mut links);
+    links
+}
+
+fn collect_definitions<'t>(node: &'t mdast::Node, definitions: &mut HashMap<&'t str, &'t str>) {
+    if let mdast::Node::Definition(definition) = node {
+        definitions
+            .entry(definition.identifier.as_str())
+            .or_insert(definition.url.as_str());
+    }
+    for child in node.children().into_iter().flatten() {
+        collect_definitions(child, definitions);
+    }
+}
+
+fn collect_links(
+    node: &mdast::Node,
+    definitions: &HashMap<&str, &str>,
+    from: &str,
+    links: &mut Vec<DocumentLink>,
+) {
+    let target = match node {
+        mdast::Node::Link(link) => Some((link.url.as_str(), &link.position)),
+        mdast::Node::LinkReference(link) => definitions
+            .get(link.identifier.as_str())
+            .map(|url| (*url, &link.position)),
+        _ => None,
+    };
+    if let Some((href, position)) = target
+        && let Some((path, fragment)) = resolve(from, href)
+    {
+        links.push(DocumentLink {
+            href: href.to_string(),
+            path,
+            fragment,
+            span: position.as_ref().map(SourceSpan::from),
+        });
+    }
+    for child in node.children().into_iter().flatten() {
+        collect_links(child, definitions, from, links);
+    }
+}
+
+/// Batch path and percent-decoded fragment `href` points to from the document at `from`, or
+/// `None` for links that leave the batch (other sites, other schemes, or above its root).
+fn resolve(from: &str, href: &str) -> Option<(String, Option<String>)> {
+    let href = href.trim();
+    let (rest, fragment) = match href.split_once('#') {
+        Some((rest, fragment)) => (
+            rest,
+            (!fragment.is_empty()).then(|| percent_decode(fragment)),
+        ),
+        None => (href, None),
+    };
+    let rest = rest.split('?').next().unwrap_or_default();
+    if rest.is_empty() {
+        return fragment.map(|fragment| (from.to_string(), Some(fragment)));
+    }
+    if !urls::is_local(rest) {
+        return None;
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+    let base = match rest.strip_prefix('/') {
+        Some(_) => "",
+        None => from.rsplit_once('/').map_or("", |(dir, _)| dir),
+    };
+    for segment in base.split('/').chain(rest.split('/')) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some((segments.join("/"), fragment))
+}
+
+/// `text` with `%XX` escapes decoded as UTF-8; malformed escapes stay as written.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escaped = (bytes[idx] == b'%')
+            .then(|| text.get(idx + 1..idx + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            None => {
+                decoded.push(bytes[idx]);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Limits;
+
+    #[test]
+    fn renders_batches_with_shared_registries() {
+        let documents = [
+            Document::new(
+                "index.md",
+                "# Home\n\nSee [setup](guide/setup.md#install) and [usage][u].\n\n[u]: #usage\n\n## Usage",
+            ),
+            Document::new(
+                "guide/setup.md",
+                "# Install\n\nBack [home](../index.md), [away](https://example.com), [root](/index.md?x=1#home).",
+            ),
+            Document::new("huge.md", "x".repeat(300)),
+        ];
+        let options = RenderOptions {
+            limits: Limits {
+                max_input_bytes: Some(200),
+                ..Limits::default()
+            },
+            ..RenderOptions::default()
+        };
+        let batch = BatchRenderer::new(options).render(&documents);
+
+        let paths: Vec<&str> = batch
+            .documents
+            .iter()
+            .map(|doc| doc.path.as_str())
+            .collect();
+        assert_eq!(paths, ["index.md", "guide/setup.md", "huge.md"]);
+        let index = batch.documents[0].result.as_ref().unwrap();
+        assert!(index.html.contains("<h2 id=\"usage\">Usage</h2>"));
+        assert!(index.headings.is_empty());
+
+        assert!(batch.slugs.contains("index.md", "usage"));
+        assert!(batch.slugs.contains("guide/setup.md", "install"));
+        assert!(!batch.slugs.has_document("huge.md"));
+
+        let links: Vec<(&str, Option<&str>)> = batch.documents[0]
+            .links
+            .iter()
+            .map(|link| (link.path.as_str(), link.fragment.as_deref()))
+            .collect();
+        assert_eq!(
+            links,
+            [
+                ("guide/setup.md", Some("install")),
+                ("index.md", Some("usage"))
+            ]
+        );
+        let backlinks: Vec<(&str, &str)> = batch
+            .links_to("index.md")
+            .map(|(from, link)| (from, link.href.as_str()))
+            .collect();
+        assert_eq!(
+            backlinks,
+            [
+                ("index.md", "#usage"),
+                ("guide/setup.md", "../index.md"),
+                ("guide/setup.md", "/index.md?x=1#home"),
+            ]
+        );
+        assert_eq!(
+            batch.documents[1].links[0].span.map(|span| span.start_line),
+            Some(3)
+        );
+
+        assert_eq!(batch.diagnostics.len(), 1);
+        assert_eq!(batch.diagnostics[0].path, "huge.md");
+        assert!(matches!(
+            batch.diagnostics[0].issue,
+            BatchIssue::RenderFailed {
+                code: "ERR_MARKFLOW_LIMIT",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn reports_broken_cross_document_links() {
+        let documents = [
+            Document::new(
+                "docs/a.md",
+                "# A\n\n## Setup\n\n[ok](b.md#usage) [self](#setup) [file](diagram.png)\n\n[gone](missing.md) [typo](b.md#usgae) [bad](#nope)",
+            ),
+            Document::new("docs/b.md", "# B\n\n## Usage\n\n[up](../README.MD)"),
+        ];
+        let batch = BatchRenderer::default().render(&documents);
+        let broken: Vec<String> = batch
+            .check_links()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            broken,
+            [
+                "docs/a.md:7: link to `missing.md`: no such document",
+                "docs/a.md:7: link to `b.md#usgae`: no heading with that id",
+                "docs/a.md:7: link to `#nope`: no heading with that id",
+                "docs/b.md:5: link to `../README.MD`: no such document",
+            ]
+        );
+        let span = batch.check_links()[0].span.unwrap();
+        assert_eq!(span.slice(&documents[0].source), Some("[gone](missing.md)"));
+    }
+
+    #[test]
+    #[cfg(feature = "footnotes")]
+    fn checks_generated_and_encoded_fragments() {
+        let documents = [
+            Document::new(
+                "a.md",
+                "# Café\n\nText.[^1]\n\n![Cat](cat.png){#fig:cat}\n\n[^1]: Note.\n\n\
+                 [n](#fn-1) [r](#fnref-1) [c](#caf%C3%A9) [f](#fig:cat) [x](b.md#caf%C3%A9) [bad](#caf%C3%A8)",
+            ),
+            Document::new("b.md", "# Café"),
+        ];
+        let options = RenderOptions {
+            figures: Some(crate::FigureOptions::default()),
+            ..RenderOptions::default()
+        };
+        let batch = BatchRenderer::new(options).render(&documents);
+        assert!(batch.slugs.contains("a.md", "fn-1"));
+
+        let broken: Vec<String> = batch
+            .check_links()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            broken,
+            ["a.md:9: link to `#caf%C3%A8`: no heading with that id"]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+//! Block-level HTML caching hooks.
+//!
+//! Top-level blocks are hashed from their events, so a host can serve HTML for unchanged blocks
+//! from an external store (Redis, memcached, ...) and receive HTML for new ones. Hashes are
+//! FNV-1a over the serialized events, the HTML options, and the crate version, so they are stable
+//! across processes and invalidate on upgrades.
+//!
+//! [`IncrementalRenderer`] caches one step earlier, by block source, so unchanged blocks of an
+//! edited document are not even parsed again.
+//!
+//! Besides a block's own events, its HTML depends on two pieces of document-wide renderer
+//! state, and both caches account for them. The table count numbers table header ids: it is
+//! part of the key of blocks holding tables, and a cache hit advances it past the cached
+//! tables. The open sections of [`HtmlOptions::sections`] change at every heading: blocks
+//! holding headings are always rendered while `sections` is on. Everything else the renderer
+//! tracks (open footnotes, pending paragraphs and figures, JSX elements) opens and closes within
+//! a top-level block. Metadata such as headings and assets is collected from the event stream
+//! before the cache, so it is complete on hits too.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::MarkflowError;
+use crate::backend::{ParserBackend, SyntaxOptions};
+use crate::event::{Event, Tag};
+use crate::html_renderer::{HtmlOptions, HtmlRenderer};
+use crate::incremental;
+use crate::slug::Slugger;
+
+/// Host-provided storage for rendered block HTML, keyed by block hash.
+pub trait BlockCache {
+    /// Returns previously rendered HTML for `hash`, if the host has it.
+    fn get(&mut self, hash: u64) -> Option<String>;
+    /// Receives freshly rendered HTML for a block that missed the cache.
+    fn put(&mut self, hash: u64, html: &str);
+}
+
+impl BlockCache for HashMap<u64, String> {
+    fn get(&mut self, hash: u64) -> Option<String> {
+        HashMap::get(self, &hash).cloned()
+    }
+
+    fn put(&mut self, hash: u64, html: &str) {
+        self.insert(hash, html.to_string());
+    }
+}
+
+/// 64-bit FNV-1a, chosen over `DefaultHasher` because its output is specified and therefore
+/// safe to share between processes and releases.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET)
+    }
+}
+
+impl Write for Fnv1a {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hash of a top-level block as rendered with `options`. `table_offset` is mixed in for blocks
+/// containing tables, because their header ids are numbered document-wide.
+fn block_hash(block: &[Event<'_>], options: &HtmlOptions, table_offset: Option<usize>) -> u64 {
+    let mut hasher = Fnv1a::new();
+    let _ = write!(
+        hasher,
+        "{}\0{options:?}\0{table_offset:?}\0",
+        env!("CARGO_PKG_VERSION")
+    );
+    // Writing into the hasher cannot fail, and events always serialize.
+    let _ = serde_json::to_writer(&mut hasher, block);
+    hasher.0
+}
+
+/// Renders `events` into `writer` block by block, consulting `cache` before rendering each one.
+pub(crate) fn render_blocks<'a, I, W>(
+    events: I,
+    writer: W,
+    options: &HtmlOptions,
+    cache: &mut dyn BlockCache,
+) -> io::Result<W>
+where
+    I: Iterator<Item = Event<'a>>,
+    W: Write,
+{
+    let mut renderer = HtmlRenderer::with_options(Vec::new(), options.clone());
+    let mut writer = writer;
+    let mut block = Vec::new();
+    let mut depth = 0usize;
+
+    for event in events {
+        match &event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        block.push(event);
+        if depth > 0 {
+            continue;
+        }
+        // A top-level heading opens and closes sections depending on the ones already open, so
+        // its HTML is not a function of its events alone.
+        if options.sections && matches!(block.first(), Some(Event::Start(Tag::Heading { .. }))) {
+            renderer.render_events(block.drain(..))?;
+            writer.write_all(&std::mem::take(renderer.writer_mut()))?;
+            continue;
+        }
+
+        let tables = block
+            .iter()
+            .filter(|event| matches!(event, Event::Start(Tag::Table(_))))
+            .count();
+        let hash = block_hash(
+            &block,
+            options,
+            (tables > 0).then(|| renderer.table_count()),
+        );
+
+        match cache.get(hash) {
+            Some(html) => {
+                renderer.skip_tables(tables);
+                writer.write_all(html.as_bytes())?;
+            }
+            None => {
+                renderer.render_events(block.drain(..))?;
+                let html = std::mem::take(renderer.writer_mut());
+                writer.write_all(&html)?;
+                cache.put(hash, &String::from_utf8_lossy(&html));
+            }
+        }
+        block.clear();
+    }
+
+    // A truncated stream (e.g. after a nesting-limit violation) leaves an unfinished block.
+    renderer.render_events(block)?;
+    renderer.close_sections(0)?;
+    writer.write_all(renderer.writer_mut())?;
+    Ok(writer)
+}
+
+/// Rendered HTML of one source block, with the document state it was rendered in and what the
+/// blocks after it depend on.
+#[derive(Debug, Clone)]
+struct RenderedBlock {
+    html: Vec<u8>,
+    /// Hash of the heading ids handed out before the block, if it holds a heading and its ids
+    /// therefore depend on them.
+    ids_before: Option<u64>,
+    /// Tables before the block, if it holds tables and their header ids therefore depend on it.
+    tables_before: Option<usize>,
+    /// Heading ids the block handed out, in order.
+    heading_ids: Vec<String>,
+    /// Tables in the block, which advance the document-wide table numbering.
+    tables: usize,
+}
+
+impl RenderedBlock {
+    /// Whether the block renders the same after `ids` heading ids and `tables` tables.
+    fn fits(&self, ids: u64, tables: usize) -> bool {
+        self.ids_before.is_none_or(|before| before == ids)
+            && self.tables_before.is_none_or(|before| before == tables)
+    }
+}
+
+/// Re-renders only the top-level blocks whose source changed since the previous call, for
+/// editor live previews of large documents.
+///
+/// Blocks are split with the rules of [`IncrementalParser`](crate::IncrementalParser) and keyed by
+/// their text and the HTML options. A cached block that holds headings or tables is only reused
+/// after the same heading ids and table count it was rendered after, so ids and numbering stay
+/// document-wide; with `sections`, blocks holding headings are always rendered again. As there,
+/// link reference and footnote definitions only resolve within their own block. Cached blocks
+/// the latest document no longer contains are dropped.
+#[derive(Debug, Default)]
+pub struct IncrementalRenderer {
+    backend: ParserBackend,
+    syntax: SyntaxOptions,
+    options: HtmlOptions,
+    /// Rendered blocks by source key; equal sources rendered after different headings or tables
+    /// each keep an entry.
+    blocks: HashMap<u64, Vec<RenderedBlock>>,
+    /// Blocks the latest render had to parse.
+    rendered: usize,
+}
+
+impl IncrementalRenderer {
+    /// Creates a renderer with an empty cache.
+    pub fn new(backend: ParserBackend, syntax: SyntaxOptions, options: HtmlOptions) -> Self {
+        Self {
+            backend,
+            syntax,
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Renders `input` to HTML, reusing the HTML of blocks that have not changed.
+    pub fn render(&mut self, input: &str) -> Result<String, MarkflowError> {
+        let mut renderer = HtmlRenderer::with_options(Vec::new(), self.options.clone());
+        let mut slugger = Slugger::new();
+        // Running hash of the heading ids handed out so far.
+        let mut ids = Fnv1a::new();
+        let mut used: HashMap<u64, Vec<RenderedBlock>> = HashMap::with_capacity(self.blocks.len());
+        let mut html = Vec::with_capacity(input.len() * 2);
+        self.rendered = 0;
+
+        for text in incremental::split_blocks(input, &self.syntax) {
+            let mut hasher = Fnv1a::new();
+            let _ = write!(
+                hasher,
+                "{}\0{:?}\0{text}\0",
+                env!("CARGO_PKG_VERSION"),
+                self.options
+            );
+            let key = hasher.0;
+            let tables_before = renderer.table_count();
+
+            // A heading opens and closes sections depending on the ones already open, so its
+            // HTML is not a function of its source alone.
+            let reusable = |block: &RenderedBlock| {
+                block.fits(ids.0, tables_before)
+                    && !(self.options.sections && block.ids_before.is_some())
+            };
+            let cached = match self.blocks.get_mut(&key) {
+                Some(blocks) => blocks
+                    .iter()
+                    .position(reusable)
+                    .map(|idx| blocks.swap_remove(idx)),
+                None => None,
+            }
+            .or_else(|| {
+                used.get(&key)?
+                    .iter()
+                    .find(|block| reusable(block))
+                    .cloned()
+            });
+            let block = match cached {
+                Some(block) => {
+                    renderer.skip_tables(block.tables);
+                    block
+                }
+                None => {
+                    self.rendered += 1;
+                    let events: Vec<Event<'static>> = self
+                        .backend
+                        .events_with_slugger(text, &self.syntax, slugger.clone())?
+                        .collect();
+                    let mut block = RenderedBlock {
+                        html: Vec::new(),
+                        ids_before: None,
+                        tables_before: None,
+                        heading_ids: Vec::new(),
+                        tables: 0,
+                    };
+                    for event in &events {
+                        match event {
+                            Event::Start(Tag::Heading { id, .. }) => {
+                                block.ids_before = Some(ids.0);
+                                block.heading_ids.extend(id.as_deref().map(str::to_string));
+                            }
+                            Event::Start(Tag::Table(_)) => {
+                                block.tables_before = Some(tables_before);
+                                block.tables += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                    renderer.render_events(events)?;
+                    block.html = std::mem::take(renderer.writer_mut());
+                    block
+                }
+            };
+
+            html.extend_from_slice(&block.html);
+            for id in &block.heading_ids {
+                slugger.unique(id.clone());
+                let _ = write!(ids, "{id}\0");
+            }
+            used.entry(key).or_default().push(block);
+        }
+
+        renderer.close_sections(0)?;
+        html.extend_from_slice(renderer.writer_mut());
+        self.blocks = used;
+        Ok(String::from_utf8(html)?)
+    }
+
+    /// Number of blocks the latest [`IncrementalRenderer::render`] parsed and rendered instead of
+    /// taking them from the cache.
+    pub fn rendered_blocks(&self) -> usize {
+        self.rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records every lookup so tests can tell hits from misses.
+    #[derive(Default)]
+    struct Recording {
+        store: HashMap<u64, String>,
+        misses: usize,
+    }
+
+    impl BlockCache for Recording {
+        fn get(&mut self, hash: u64) -> Option<String> {
+            let hit = self.store.get(&hash).cloned();
+            self.misses += usize::from(hit.is_none());
+            hit
+        }
+
+        fn put(&mut self, hash: u64, html: &str) {
+            self.store.put(hash, html);
+        }
+    }
+
+    fn render(input: &str, options: &HtmlOptions, cache: &mut dyn BlockCache) -> String {
+        let events = crate::get_event_iterator(input).unwrap();
+        String::from_utf8(render_blocks(events, Vec::new(), options, cache).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reuses_unchanged_blocks() {
+        let options = HtmlOptions::default();
+        let mut cache = Recording::default();
+
+        let first = render("# A\n\npara *one*\n\n- x", &options, &mut cache);
+        assert_eq!(cache.misses, 3);
+        assert_eq!(first, crate::parse("# A\n\npara *one*\n\n- x").unwrap());
+
+        let edited = render("# A\n\npara *two*\n\n- x", &options, &mut cache);
+        assert_eq!(cache.misses, 4);
+        assert!(edited.contains("<em>two</em>"));
+    }
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn keys_depend_on_options_and_table_position() {
+        let table = "| a | b |\n|---|---|\n| 1 | 2 |";
+        let options = HtmlOptions {
+            table_header_ids: Some(1),
+            ..HtmlOptions::default()
+        };
+        let mut cache = Recording::default();
+
+        let html = render(&format!("{table}\n\n{table}"), &options, &mut cache);
+        assert_eq!(cache.misses, 2);
+        assert!(html.contains("id=\"table-1-col-1\""));
+        assert!(html.contains("id=\"table-2-col-1\""));
+        assert_eq!(
+            render(&format!("{table}\n\n{table}"), &options, &mut cache),
+            html
+        );
+        assert_eq!(cache.misses, 2);
+
+        render(table, &HtmlOptions::default(), &mut cache);
+        assert_eq!(cache.misses, 3);
+    }
+
+    #[test]
+    fn rerenders_changed_blocks_only() {
+        let doc = "# Intro\n\nFirst *para*.\n\n| a |\n|---|\n| 1 |\n\n# Intro\n\n```\ncode\n\nmore\n```\n\n- x\n\n- y\n";
+        let options = HtmlOptions {
+            table_header_ids: Some(1),
+            ..HtmlOptions::default()
+        };
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let full = |input: &str| {
+                let events = backend.events(input, &SyntaxOptions::default()).unwrap();
+                let html = HtmlRenderer::with_options(Vec::new(), options.clone())
+                    .render(events)
+                    .unwrap();
+                String::from_utf8(html).unwrap()
+            };
+            let mut renderer =
+                IncrementalRenderer::new(backend, SyntaxOptions::default(), options.clone());
+
+            assert_eq!(renderer.render(doc).unwrap(), full(doc), "{backend:?}");
+            assert_eq!(renderer.rendered_blocks(), 6, "{backend:?}");
+            assert_eq!(renderer.render(doc).unwrap(), full(doc), "{backend:?}");
+            assert_eq!(renderer.rendered_blocks(), 0, "{backend:?}");
+
+            let edited = doc.replace("First *para*", "First *edit*");
+            assert_eq!(
+                renderer.render(&edited).unwrap(),
+                full(&edited),
+                "{backend:?}"
+            );
+            assert_eq!(renderer.rendered_blocks(), 1, "{backend:?}");
+
+            // Renaming the first heading frees its id for the second one.
+            let renamed = edited.replacen("# Intro", "# Start", 1);
+            let html = renderer.render(&renamed).unwrap();
+            assert_eq!(html, full(&renamed), "{backend:?}");
+            assert!(html.contains("<h1 id=\"intro\">"), "{backend:?}: {html}");
+            assert_eq!(renderer.rendered_blocks(), 2, "{backend:?}");
+        }
+    }
+}
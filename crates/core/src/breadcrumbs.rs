@@ -0,0 +1,102 @@
+//! The chain of headings around a source position, for editor breadcrumbs and deep-link
+//! previews.
+
+use markdown::mdast;
+
+use crate::MarkflowError;
+use crate::markdown_adapter::{collect_text, parse_tree};
+use crate::slug::Slugger;
+use crate::span::SourceSpan;
+
+/// A heading that encloses a source position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breadcrumb {
+    /// Heading level (1-6).
+    pub level: u8,
+    /// Plain text of the heading.
+    pub text: String,
+    /// The `id` [`render`](crate::render) gives the heading with default options.
+    pub id: Option<String>,
+    /// Location of the heading line.
+    pub span: SourceSpan,
+}
+
+/// Returns the headings whose sections contain `byte_offset`, outermost first: the last H1
+/// before the offset, then the last H2 after that H1, and so on. A skipped level is simply
+/// absent, so `# A` / `### C` yields `[A, C]`. Offsets before the first heading yield an empty
+/// chain.
+pub fn breadcrumbs_for_offset(
+    source: &str,
+    byte_offset: usize,
+) -> Result<Vec<Breadcrumb>, MarkflowError> {
+    let tree = parse_tree(source).map_err(MarkflowError::parse)?;
+    let mut headings = Vec::new();
+    collect(&tree, &mut headings);
+
+    let mut slugger = Slugger::new();
+    let mut trail: Vec<Breadcrumb> = Vec::new();
+    for (heading, span) in headings {
+        let mut text = String::new();
+        collect_text(&heading.children, &mut text);
+        let id = slugger.slug(&text);
+        if span.start > byte_offset {
+            break;
+        }
+        while trail.last().is_some_and(|last| last.level >= heading.depth) {
+            trail.pop();
+        }
+        trail.push(Breadcrumb {
+            level: heading.depth,
+            text,
+            id,
+            span,
+        });
+    }
+    Ok(trail)
+}
+
+fn collect<'t>(node: &'t mdast::Node, headings: &mut Vec<(&'t mdast::Heading, SourceSpan)>) {
+    if let mdast::Node::Heading(heading) = node {
+        if let Some(span) = heading.position.as_ref().map(SourceSpan::from) {
+            headings.push((heading, span));
+        }
+        return;
+    }
+    if let Some(children) = node.children() {
+        for child in children {
+            collect(child, headings);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trail(source: &str, offset: usize) -> Vec<String> {
+        breadcrumbs_for_offset(source, offset)
+            .unwrap()
+            .into_iter()
+            .map(|crumb| format!("{}:{}", crumb.level, crumb.id.unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn follows_enclosing_headings() {
+        let source = "Intro\n\n# Guide\n\n## Install\n\ntext\n\n### Linux\n\nmore\n\n## Usage\n\n#### Deep\n\nend\n\n# Guide\n";
+        let at = |needle: &str| source.find(needle).unwrap();
+
+        assert!(trail(source, 0).is_empty());
+        assert_eq!(trail(source, at("text")), ["1:guide", "2:install"]);
+        assert_eq!(
+            trail(source, at("more")),
+            ["1:guide", "2:install", "3:linux"]
+        );
+        assert_eq!(trail(source, at("end")), ["1:guide", "2:usage", "4:deep"]);
+        assert_eq!(trail(source, source.len()), ["1:guide-1"]);
+
+        let crumbs = breadcrumbs_for_offset(source, at("text")).unwrap();
+        assert_eq!(crumbs[1].text, "Install");
+        assert_eq!(crumbs[1].span.slice(source), Some("## Install"));
+    }
+}
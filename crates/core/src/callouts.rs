@@ -0,0 +1,192 @@
+//! GitHub-style callouts (`> [!NOTE]`) rendered as alert boxes.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::event::{CalloutKind, Event, Tag, TagEnd};
+
+/// Rewrites block quotes whose first line is exactly `[!KIND]` into [`Tag::Callout`], which the
+/// renderer writes as `<div class="markdown-alert markdown-alert-kind">` with a title paragraph,
+/// as GitHub does. Other block quotes pass through unchanged.
+pub(crate) struct Callouts<I> {
+    inner: I,
+    /// Events read ahead that still need to go through [`Callouts::next`].
+    replay: VecDeque<Event<'static>>,
+    /// Events ready to be returned as-is.
+    pending: VecDeque<Event<'static>>,
+    /// Whether each open block quote became a callout, innermost last.
+    open: Vec<bool>,
+}
+
+impl<I> Callouts<I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    pub(crate) fn new(inner: I) -> Self {
+        Self {
+            inner,
+            replay: VecDeque::new(),
+            pending: VecDeque::new(),
+            open: Vec::new(),
+        }
+    }
+
+    fn pull(&mut self) -> Option<Event<'static>> {
+        self.replay.pop_front().or_else(|| self.inner.next())
+    }
+
+    /// Decides whether the block quote that just started is a callout.
+    fn start_block_quote(&mut self) {
+        let mut consumed = Vec::new();
+        let kind = self.read_marker(&mut consumed);
+        let Some((kind, rest)) = kind else {
+            self.open.push(false);
+            self.pending.push_back(Event::Start(Tag::BlockQuote));
+            for event in consumed.into_iter().rev() {
+                self.replay.push_front(event);
+            }
+            return;
+        };
+
+        self.open.push(true);
+        self.pending.push_back(Event::Start(Tag::Callout(kind)));
+        if let Some(rest) = rest {
+            self.pending.push_back(Event::Start(Tag::Paragraph));
+            if !rest.is_empty() {
+                self.replay.push_front(Event::Text(Cow::Owned(rest)));
+            }
+        }
+    }
+
+    /// Reads the first line of the block quote's first paragraph. On a match returns the kind
+    /// and — when the paragraph continues past the marker line — the remaining text
+    /// of the line's text event. Every event read is recorded in `consumed` for replay.
+    fn read_marker(
+        &mut self,
+        consumed: &mut Vec<Event<'static>>,
+    ) -> Option<(CalloutKind, Option<String>)> {
+        let first = self.pull()?;
+        let is_paragraph = matches!(first, Event::Start(Tag::Paragraph));
+        consumed.push(first);
+        if !is_paragraph {
+            return None;
+        }
+
+        let mut line = String::new();
+        let (rest, continues) = loop {
+            let event = self.pull()?;
+            match &event {
+                // markdown-rs keeps soft breaks inside text events.
+                Event::Text(text) if text.contains('\n') => {
+                    let (head, tail) = text.split_once('\n').unwrap_or_default();
+                    line.push_str(head);
+                    let tail = tail.to_string();
+                    consumed.push(event);
+                    break (tail, true);
+                }
+                Event::Text(text) if line.len() <= "[!IMPORTANT]".len() => line.push_str(text),
+                Event::SoftBreak | Event::HardBreak => {
+                    consumed.push(event);
+                    break (String::new(), true);
+                }
+                Event::End(TagEnd::Paragraph) => {
+                    consumed.push(event);
+                    break (String::new(), false);
+                }
+                _ => {
+                    consumed.push(event);
+                    return None;
+                }
+            }
+            consumed.push(event);
+        };
+
+        let name = line.trim().strip_prefix("[!")?.strip_suffix(']')?;
+        let kind = CalloutKind::ALL
+            .into_iter()
+            .find(|kind| name.eq_ignore_ascii_case(kind.name()))?;
+        Some((kind, continues.then_some(rest)))
+    }
+}
+
+impl<I> Iterator for Callouts<I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    type Item = Event<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        match self.pull()? {
+            Event::Start(Tag::BlockQuote) => {
+                self.start_block_quote();
+                self.next()
+            }
+            Event::End(TagEnd::BlockQuote) => match self.open.pop() {
+                Some(true) => Some(Event::End(TagEnd::Callout)),
+                _ => Some(Event::End(TagEnd::BlockQuote)),
+            },
+            event => Some(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{HtmlOptions, RenderOptions, render};
+
+    #[test]
+    fn renders_github_callouts() {
+        let cases = [
+            (
+                "> [!NOTE]\n> Useful *info*.",
+                "<div class=\"markdown-alert markdown-alert-note\"><p class=\"markdown-alert-title\">Note</p>\n<p>Useful <em>info</em>.</p>\n</div>\n",
+            ),
+            (
+                "> [!warning]\n>\n> > nested\n\n> plain",
+                "<div class=\"markdown-alert markdown-alert-warning\"><p class=\"markdown-alert-title\">Warning</p>\n<blockquote><p>nested</p>\n</blockquote>\n</div>\n<blockquote><p>plain</p>\n</blockquote>\n",
+            ),
+            (
+                "> [!NOTE] inline\n\n> [!BOGUS]",
+                "<blockquote><p>[!NOTE] inline</p>\n</blockquote>\n<blockquote><p>[!BOGUS]</p>\n</blockquote>\n",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            for backend in [
+                crate::ParserBackend::MarkdownRs,
+                crate::ParserBackend::PulldownCmark,
+            ] {
+                let options = RenderOptions {
+                    backend,
+                    callouts: true,
+                    ..RenderOptions::default()
+                };
+                assert_eq!(
+                    render(input, &options).unwrap().html,
+                    expected,
+                    "{backend:?}: {input:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn callouts_survive_escaped_raw_html() {
+        let options = RenderOptions {
+            callouts: true,
+            html: HtmlOptions {
+                escape_raw_html: true,
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            render("> [!TIP]\n> Use <kbd>.", &options).unwrap().html,
+            "<div class=\"markdown-alert markdown-alert-tip\"><p class=\"markdown-alert-title\">Tip</p>\n\
+             <p>Use &lt;kbd&gt;.</p>\n</div>\n"
+        );
+    }
+}
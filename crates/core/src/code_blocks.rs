@@ -0,0 +1,262 @@
+//! Structured access to fenced code blocks, for documentation test runners.
+
+use std::borrow::Cow;
+use std::ops::RangeInclusive;
+
+use markdown::mdast;
+use serde::{Deserialize, Serialize};
+
+use crate::MarkflowError;
+use crate::markdown_adapter::{parse_attribute_block, parse_tree};
+use crate::span::SourceSpan;
+
+/// A fenced code block exactly as the renderer sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// Language from the info string (`bash` in ```` ```bash no-test ````).
+    pub lang: Option<String>,
+    /// Remainder of the info string after the language (`no-test`).
+    pub meta: Option<String>,
+    /// Code content without the fences.
+    pub code: String,
+    /// Location of the whole block, fences included.
+    pub span: SourceSpan,
+}
+
+impl CodeBlock {
+    /// Whether the meta string contains `flag` as a whitespace-separated word.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.meta
+            .as_deref()
+            .is_some_and(|meta| meta.split_whitespace().any(|word| word == flag))
+    }
+
+    /// The meta string parsed into title, highlighted lines, attributes, and flags.
+    pub fn parsed_meta(&self) -> CodeMeta<'_> {
+        CodeMeta::parse(self.meta.as_deref().unwrap_or_default())
+    }
+}
+
+/// The info string of a fenced code block after the language, split into the conventions
+/// documentation tools share: ```` ```rust title="main.rs" {1,3-5} {#main .wide} no-test ````.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeMeta<'a> {
+    /// Meta string as written (`title="main.rs" {1,3-5} no-test`).
+    pub raw: Cow<'a, str>,
+    /// Label from `title="…"`, or else from `filename="…"` or `file="…"`.
+    pub title: Option<String>,
+    /// Lines to highlight from `{1,3-5}`, as 1-based inclusive ranges.
+    pub highlight: Vec<RangeInclusive<u32>>,
+    /// Remaining `key=value` and `key="quoted value"` attributes, in order.
+    pub attrs: Vec<(String, String)>,
+    /// Bare words such as `no-test`.
+    pub flags: Vec<String>,
+    /// Attributes from a `{#id .class key=value}` block as they are written onto `<pre>` while
+    /// [`SyntaxOptions::heading_attributes`](crate::SyntaxOptions::heading_attributes) is set:
+    /// `id`, then the classes joined into `class`, then the rest in order; bare keys have no value.
+    pub block_attrs: Vec<(String, Option<String>)>,
+}
+
+impl<'a> CodeMeta<'a> {
+    /// Parses a meta string. A `{…}` block without line ranges is an attribute block. Malformed
+    /// line ranges are skipped; an unclosed quote runs to the end of the string.
+    pub fn parse(raw: impl Into<Cow<'a, str>>) -> Self {
+        let raw = raw.into();
+        let mut title = None;
+        let mut highlight = Vec::new();
+        let mut attrs = Vec::new();
+        let mut flags = Vec::new();
+        let mut block_attrs = Vec::new();
+
+        let mut rest = raw.trim_start();
+        while !rest.is_empty() {
+            if let Some(block) = rest.strip_prefix('{')
+                && let Some(end) = block.find('}')
+            {
+                let ranges: Vec<_> = block[..end].split(',').filter_map(line_range).collect();
+                if ranges.is_empty() {
+                    let block = parse_attribute_block(&block[..end]);
+                    if let Some(id) = block.id {
+                        block_attrs.push(("id".to_string(), Some(id)));
+                    }
+                    if !block.classes.is_empty() {
+                        block_attrs.push(("class".to_string(), Some(block.classes.join(" "))));
+                    }
+                    block_attrs.extend(block.attrs);
+                } else {
+                    highlight.extend(ranges);
+                }
+                rest = block[end + 1..].trim_start();
+                continue;
+            }
+            let key_end = rest
+                .find(|ch: char| ch.is_whitespace() || ch == '=')
+                .unwrap_or(rest.len());
+            let key = &rest[..key_end];
+            rest = &rest[key_end..];
+            match rest.strip_prefix('=') {
+                Some(value) => {
+                    let (value, after) = attr_value(value);
+                    rest = after;
+                    match key {
+                        "title" => title = Some(value.to_string()),
+                        "filename" | "file" => {
+                            title.get_or_insert_with(|| value.to_string());
+                        }
+                        _ => attrs.push((key.to_string(), value.to_string())),
+                    }
+                }
+                None => flags.push(key.to_string()),
+            }
+            rest = rest.trim_start();
+        }
+
+        Self {
+            raw,
+            title,
+            highlight,
+            attrs,
+            flags,
+            block_attrs,
+        }
+    }
+
+    /// Copies borrowed text so the meta outlives the input.
+    pub fn into_owned(self) -> CodeMeta<'static> {
+        CodeMeta {
+            raw: Cow::Owned(self.raw.into_owned()),
+            title: self.title,
+            highlight: self.highlight,
+            attrs: self.attrs,
+            flags: self.flags,
+            block_attrs: self.block_attrs,
+        }
+    }
+
+    /// Forgets the attribute block, for parsers that leave attribute blocks off.
+    pub(crate) fn clear_attribute_block(&mut self) {
+        self.block_attrs.clear();
+    }
+}
+
+/// `3` or `3-5`; `None` for zero, reversed, or non-numeric ranges.
+fn line_range(range: &str) -> Option<RangeInclusive<u32>> {
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+        None => {
+            let line = range.trim().parse().ok()?;
+            (line, line)
+        }
+    };
+    (start >= 1 && start <= end).then_some(start..=end)
+}
+
+/// Splits an attribute value, quoted or bare, from the text after it.
+fn attr_value(input: &str) -> (&str, &str) {
+    if let Some(quote) = input.chars().next().filter(|ch| matches!(ch, '"' | '\'')) {
+        let value = &input[1..];
+        return match value.find(quote) {
+            Some(end) => (&value[..end], &value[end + 1..]),
+            None => (value, ""),
+        };
+    }
+    let end = input.find(char::is_whitespace).unwrap_or(input.len());
+    input.split_at(end)
+}
+
+/// Returns every fenced code block in `input`, in document order, including blocks nested in
+/// lists and block quotes. Indented code blocks are skipped because they carry no info string.
+pub fn extract_code_blocks(input: &str) -> Result<Vec<CodeBlock>, MarkflowError> {
+    let tree = parse_tree(input).map_err(MarkflowError::parse)?;
+    let mut blocks = Vec::new();
+    collect(&tree, input, &mut blocks);
+    Ok(blocks)
+}
+
+fn collect(node: &mdast::Node, input: &str, blocks: &mut Vec<CodeBlock>) {
+    if let mdast::Node::Code(code) = node {
+        let Some(span) = code.position.as_ref().map(SourceSpan::from) else {
+            return;
+        };
+        if is_fenced(input, &span) {
+            blocks.push(CodeBlock {
+                lang: code.lang.clone(),
+                meta: code.meta.clone(),
+                code: code.value.clone(),
+                span,
+            });
+        }
+        return;
+    }
+
+    if let Some(children) = node.children() {
+        for child in children {
+            collect(child, input, blocks);
+        }
+    }
+}
+
+fn is_fenced(input: &str, span: &SourceSpan) -> bool {
+    span.slice(input).is_some_and(|source| {
+        let source = source.trim_start();
+        source.starts_with("```") || source.starts_with("~~~")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_fenced_blocks_with_meta_and_spans() {
+        let input = "# 手順\n\n```bash no-test\necho hi\n```\n\n    indented\n\n- item\n\n  ~~~\n  plain\n  ~~~\n";
+        let blocks = extract_code_blocks(input).unwrap();
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].lang.as_deref(), Some("bash"));
+        assert_eq!(blocks[0].meta.as_deref(), Some("no-test"));
+        assert!(blocks[0].has_flag("no-test"));
+        assert_eq!(blocks[0].code, "echo hi");
+        assert_eq!(
+            blocks[0].span.slice(input),
+            Some("```bash no-test\necho hi\n```")
+        );
+        assert_eq!((blocks[0].span.start_line, blocks[0].span.end_line), (3, 5));
+
+        assert_eq!(blocks[0].parsed_meta().flags, ["no-test"]);
+
+        assert_eq!(blocks[1].lang, None);
+        assert_eq!(blocks[1].code, "plain");
+        assert!(!blocks[1].has_flag("no-test"));
+    }
+
+    #[test]
+    fn parses_code_meta() {
+        let meta = CodeMeta::parse(r#"title="src/main.rs" {1,3-5, 9-7,x} mode='a b' run"#);
+        assert_eq!(meta.title.as_deref(), Some("src/main.rs"));
+        assert_eq!(meta.highlight, [1..=1, 3..=5]);
+        assert_eq!(meta.attrs, [("mode".to_string(), "a b".to_string())]);
+        assert_eq!(meta.flags, ["run"]);
+
+        let meta = CodeMeta::parse("filename=lib.rs title=\"Library\" {2");
+        assert_eq!(meta.title.as_deref(), Some("Library"));
+        assert!(meta.highlight.is_empty());
+        assert_eq!(meta.flags, ["{2"]);
+
+        let meta = CodeMeta::parse("{2} {#main .wide data-x=1 open} run");
+        assert_eq!(meta.highlight, [2..=2]);
+        assert_eq!(
+            meta.block_attrs,
+            [
+                ("id".to_string(), Some("main".to_string())),
+                ("class".to_string(), Some("wide".to_string())),
+                ("data-x".to_string(), Some("1".to_string())),
+                ("open".to_string(), None)
+            ]
+        );
+        assert_eq!(meta.flags, ["run"]);
+
+        assert_eq!(CodeMeta::parse("file=a.rs").title.as_deref(), Some("a.rs"));
+        assert_eq!(CodeMeta::parse(""), CodeMeta::default());
+    }
+}
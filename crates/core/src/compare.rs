@@ -0,0 +1,411 @@
+//! Differential rendering through both parser backends.
+//!
+//! Both backends are meant to render the same HTML. [`compare_backends`] renders one input with
+//! each and lines the results up tag by tag, so a disagreement is reported at the element where
+//! it happens instead of as a line diff of the whole document. It is a debugging aid: the diff is
+//! quadratic in the size of the region that differs.
+
+use std::fmt;
+
+use crate::backend::ParserBackend;
+use crate::error::MarkflowError;
+use crate::render::{RenderOptions, render};
+
+/// Differing regions larger than this many token pairs are reported as one difference.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Elements without a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// The HTML of both backends for one input and where they differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendComparison {
+    /// HTML rendered with [`ParserBackend::MarkdownRs`].
+    pub markdown_rs: String,
+    /// HTML rendered with [`ParserBackend::PulldownCmark`].
+    pub pulldown_cmark: String,
+    /// Differing regions in document order; empty when the HTML matches.
+    pub differences: Vec<HtmlDifference>,
+}
+
+impl BackendComparison {
+    /// Whether both backends produced the same HTML, ignoring whitespace between tags and
+    /// self-closing slashes.
+    pub fn is_identical(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// One region where the backends disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtmlDifference {
+    /// CSS selector of the element that contains the region in the markdown-rs HTML, such as
+    /// `ul:nth-child(2) > li:nth-child(3)`; empty at the top level.
+    pub path: String,
+    /// The region as markdown-rs renders it; empty when only pulldown-cmark has it.
+    pub markdown_rs: String,
+    /// The region as pulldown-cmark renders it; empty when only markdown-rs has it.
+    pub pulldown_cmark: String,
+}
+
+impl fmt::Display for HtmlDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "(top level)"
+        } else {
+            &self.path
+        };
+        write!(
+            f,
+            "{path}: markdown-rs {:?}, pulldown-cmark {:?}",
+            self.markdown_rs, self.pulldown_cmark
+        )
+    }
+}
+
+/// Renders `source` with [`RenderOptions::default`] through both backends and compares the HTML.
+pub fn compare_backends(source: &str) -> Result<BackendComparison, MarkflowError> {
+    compare_backends_with(source, &RenderOptions::default())
+}
+
+/// Same as [`compare_backends`] with `options`; [`RenderOptions::backend`] is ignored.
+pub fn compare_backends_with(
+    source: &str,
+    options: &RenderOptions,
+) -> Result<BackendComparison, MarkflowError> {
+    let render_with = |backend| {
+        let options = RenderOptions {
+            backend,
+            ..options.clone()
+        };
+        render(source, &options).map(|result| result.html)
+    };
+    let markdown_rs = render_with(ParserBackend::MarkdownRs)?;
+    let pulldown_cmark = render_with(ParserBackend::PulldownCmark)?;
+    let differences = diff_html(&markdown_rs, &pulldown_cmark);
+    Ok(BackendComparison {
+        markdown_rs,
+        pulldown_cmark,
+        differences,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Open,
+    Close,
+    /// Text, void elements, comments, and anything else that neither opens nor closes.
+    Leaf,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Token<'a> {
+    kind: TokenKind,
+    /// Lowercase tag name of `Open` and `Close` tokens.
+    name: String,
+    /// Normalized HTML of the token; what tokens are compared by.
+    html: std::borrow::Cow<'a, str>,
+}
+
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            push_text(&mut tokens, rest);
+            break;
+        };
+        push_text(&mut tokens, &rest[..start]);
+        rest = &rest[start..];
+        let end = if rest.starts_with("<!--") {
+            rest.find("-->").map(|end| end + 3)
+        } else {
+            rest.find('>').map(|end| end + 1)
+        }
+        .unwrap_or(rest.len());
+        tokens.push(tag_token(&rest[..end]));
+        rest = &rest[end..];
+    }
+    tokens
+}
+
+/// Text as is, except that whitespace between tags is dropped.
+fn push_text<'a>(tokens: &mut Vec<Token<'a>>, text: &'a str) {
+    if text.trim().is_empty() {
+        return;
+    }
+    tokens.push(Token {
+        kind: TokenKind::Leaf,
+        name: String::new(),
+        html: text.into(),
+    });
+}
+
+fn tag_token(tag: &str) -> Token<'_> {
+    let (closing, body) = match tag.strip_prefix("</") {
+        Some(body) => (true, body),
+        None => (false, tag.strip_prefix('<').unwrap_or(tag)),
+    };
+    let name: String = body
+        .chars()
+        .take_while(|ch| ch.is_ascii_alphanumeric() || *ch == '-')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if name.is_empty() {
+        return Token {
+            kind: TokenKind::Leaf,
+            name,
+            html: tag.into(),
+        };
+    }
+    if closing {
+        return Token {
+            kind: TokenKind::Close,
+            name,
+            html: tag.into(),
+        };
+    }
+    match tag.strip_suffix("/>") {
+        Some(inner) => Token {
+            kind: TokenKind::Leaf,
+            name,
+            html: format!("{}>", inner.trim_end()).into(),
+        },
+        None => Token {
+            kind: if VOID_ELEMENTS.contains(&name.as_str()) {
+                TokenKind::Leaf
+            } else {
+                TokenKind::Open
+            },
+            name,
+            html: tag.into(),
+        },
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// Both sides have the token.
+    Same,
+    /// Only the left side has the token.
+    Left,
+    /// Only the right side has the token.
+    Right,
+}
+
+/// Edit script from `left` to `right`, after trimming their common prefix and suffix.
+fn edit_script(left: &[Token<'_>], right: &[Token<'_>]) -> Vec<Op> {
+    let prefix = left
+        .iter()
+        .zip(right)
+        .take_while(|(left, right)| left == right)
+        .count();
+    let suffix = left[prefix..]
+        .iter()
+        .rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(left, right)| left == right)
+        .count();
+    let left_mid = &left[prefix..left.len() - suffix];
+    let right_mid = &right[prefix..right.len() - suffix];
+
+    let mut ops = vec![Op::Same; prefix];
+    let (n, m) = (left_mid.len(), right_mid.len());
+    if n.saturating_mul(m) > MAX_DIFF_CELLS {
+        ops.extend(std::iter::repeat_n(Op::Left, n));
+        ops.extend(std::iter::repeat_n(Op::Right, m));
+    } else {
+        // Longest common subsequence lengths of the suffixes `left_mid[i..]` and `right_mid[j..]`.
+        let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if left_mid[i] == right_mid[j] {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && left_mid[i] == right_mid[j] {
+                ops.push(Op::Same);
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1]) {
+                ops.push(Op::Left);
+                i += 1;
+            } else {
+                ops.push(Op::Right);
+                j += 1;
+            }
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Same, suffix));
+    ops
+}
+
+/// Open elements of the left side, with the number of element children seen in each.
+struct Path {
+    /// `(selector, children)` of each open element, outermost first.
+    open: Vec<(String, usize)>,
+    top_level_children: usize,
+}
+
+impl Path {
+    fn visit(&mut self, token: &Token<'_>) {
+        match token.kind {
+            TokenKind::Open => {
+                let index = self.child() + 1;
+                *self.child_mut() = index;
+                self.open
+                    .push((format!("{}:nth-child({index})", token.name), 0));
+            }
+            TokenKind::Close => {
+                if let Some(at) = self
+                    .open
+                    .iter()
+                    .rposition(|(selector, _)| selector.split(':').next() == Some(&token.name))
+                {
+                    self.open.truncate(at);
+                }
+            }
+            TokenKind::Leaf if !token.name.is_empty() => *self.child_mut() += 1,
+            TokenKind::Leaf => {}
+        }
+    }
+
+    fn child(&self) -> usize {
+        self.open
+            .last()
+            .map_or(self.top_level_children, |(_, children)| *children)
+    }
+
+    fn child_mut(&mut self) -> &mut usize {
+        match self.open.last_mut() {
+            Some((_, children)) => children,
+            None => &mut self.top_level_children,
+        }
+    }
+
+    fn selector(&self) -> String {
+        self.open
+            .iter()
+            .map(|(selector, _)| selector.as_str())
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
+
+/// Regions where `left` (markdown-rs) and `right` (pulldown-cmark) HTML differ.
+fn diff_html(left: &str, right: &str) -> Vec<HtmlDifference> {
+    let left = tokenize(left);
+    let right = tokenize(right);
+    let mut differences: Vec<HtmlDifference> = Vec::new();
+    let mut path = Path {
+        open: Vec::new(),
+        top_level_children: 0,
+    };
+    let (mut i, mut j) = (0, 0);
+    let mut in_region = false;
+    for op in edit_script(&left, &right) {
+        if op == Op::Same {
+            in_region = false;
+            path.visit(&left[i]);
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if !in_region {
+            in_region = true;
+            differences.push(HtmlDifference {
+                path: path.selector(),
+                markdown_rs: String::new(),
+                pulldown_cmark: String::new(),
+            });
+        }
+        let difference = differences.last_mut().expect("region was just opened");
+        if op == Op::Left {
+            difference.markdown_rs.push_str(&left[i].html);
+            path.visit(&left[i]);
+            i += 1;
+        } else {
+            difference.pulldown_cmark.push_str(&right[j].html);
+            j += 1;
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locates_differences_by_element() {
+        let cases = [
+            ("<p>a</p>\n<p>b</p>", "<p>a</p><p>b</p>\n", vec![]),
+            ("<p>a<br /></p>", "<p>a<br></p>", vec![]),
+            (
+                "<p>x</p><ul><li>a</li><li>b</li></ul>",
+                "<p>x</p><ul><li>a</li><li>c</li></ul>",
+                vec![("ul:nth-child(2) > li:nth-child(2)", "b", "c")],
+            ),
+            (
+                "<p>a <em>b</em></p>",
+                "<p>a *b*</p>",
+                vec![("p:nth-child(1)", "a <em>b</em>", "a *b*")],
+            ),
+            ("<h1>T</h1>", "<h1>T</h1><hr />", vec![("", "", "<hr>")]),
+        ];
+        for (left, right, expected) in cases {
+            let found: Vec<_> = diff_html(left, right)
+                .into_iter()
+                .map(|difference| {
+                    (
+                        difference.path,
+                        difference.markdown_rs,
+                        difference.pulldown_cmark,
+                    )
+                })
+                .collect();
+            let expected: Vec<_> = expected
+                .into_iter()
+                .map(|(path, left, right)| (path.to_string(), left.to_string(), right.to_string()))
+                .collect();
+            assert_eq!(found, expected, "{left:?} vs {right:?}");
+        }
+    }
+
+    #[test]
+    fn compares_both_backends() {
+        let same = compare_backends("# Title\n\n- a\n- b\n\n> quote\n").unwrap();
+        assert!(same.is_identical(), "{:?}", same.differences);
+        assert_eq!(same.markdown_rs, same.pulldown_cmark);
+
+        let options = RenderOptions {
+            syntax: crate::SyntaxOptions {
+                mdx: true,
+                ..crate::SyntaxOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let formatting =
+            compare_backends_with("<Note>\n\nhi *there*\n\n</Note>\n", &options).unwrap();
+        assert_ne!(formatting.markdown_rs, formatting.pulldown_cmark);
+        assert!(formatting.is_identical(), "{:?}", formatting.differences);
+
+        let email = compare_backends("# Mail\n\nWrite to <me@example.com>.\n").unwrap();
+        assert_eq!(
+            email.differences,
+            [HtmlDifference {
+                path: "p:nth-child(2)".into(),
+                markdown_rs: "<a href=\"mailto:me@example.com\">".into(),
+                pulldown_cmark: "<a href=\"me@example.com\">".into(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Data tables from fenced ` ```csv ` and ` ```tsv ` blocks.
+//!
+//! The first record is the header row. The table is as wide as its longest record, and shorter
+//! records, the header included, are padded with empty cells. A block without records stays a
+//! code block.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::event::{Alignment, Event, Tag, TagEnd};
+
+/// Replaces CSV and TSV code blocks with table events.
+pub(crate) struct CsvTables<'a, I> {
+    inner: I,
+    pending: VecDeque<Event<'a>>,
+}
+
+impl<'a, I> CsvTables<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    pub(crate) fn new(inner: I) -> Self {
+        Self {
+            inner,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Reads the code block that `start` opened up to its end and queues the table built from
+    /// it, or the code block itself if it holds no records.
+    fn table(&mut self, start: Event<'a>, separator: Separator) {
+        let mut block = vec![start];
+        let mut source = String::new();
+        for event in self.inner.by_ref() {
+            let end = matches!(event, Event::End(TagEnd::CodeBlock));
+            if let Event::Text(text) = &event {
+                source.push_str(text);
+            }
+            block.push(event);
+            if end {
+                break;
+            }
+        }
+        let records = separator.records(&source);
+        let Some((header, rows)) = records.split_first() else {
+            self.pending.extend(block);
+            return;
+        };
+
+        let columns = records.iter().map(Vec::len).max().unwrap_or_default();
+        self.pending
+            .push_back(Event::Start(Tag::Table(vec![Alignment::None; columns])));
+        self.pending.push_back(Event::Start(Tag::TableHead));
+        self.push_row(header, columns);
+        self.pending.push_back(Event::End(TagEnd::TableHead));
+        for row in rows {
+            self.push_row(row, columns);
+        }
+        self.pending.push_back(Event::End(TagEnd::Table));
+    }
+
+    fn push_row(&mut self, fields: &[String], columns: usize) {
+        self.pending.push_back(Event::Start(Tag::TableRow));
+        for column in 0..columns {
+            self.pending.push_back(Event::Start(Tag::TableCell));
+            if let Some(field) = fields.get(column).filter(|field| !field.is_empty()) {
+                self.pending
+                    .push_back(Event::Text(Cow::Owned(field.clone())));
+            }
+            self.pending.push_back(Event::End(TagEnd::TableCell));
+        }
+        self.pending.push_back(Event::End(TagEnd::TableRow));
+    }
+}
+
+impl<'a, I> Iterator for CsvTables<'a, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            let event = self.inner.next()?;
+            let separator = match &event {
+                Event::Start(Tag::CodeBlock(kind)) => match kind.lang() {
+                    Some("csv") => Separator::Comma,
+                    Some("tsv") => Separator::Tab,
+                    _ => return Some(event),
+                },
+                _ => return Some(event),
+            };
+            self.table(event, separator);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Separator {
+    /// RFC 4180: fields may be `"quoted"`, with `""` for a literal quote and separators or
+    /// line breaks inside the quotes.
+    Comma,
+    /// Tab-separated, without quoting.
+    Tab,
+}
+
+impl Separator {
+    /// Records of `source`, skipping blank lines.
+    fn records(self, source: &str) -> Vec<Vec<String>> {
+        match self {
+            Separator::Tab => source
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| {
+                    line.split('\t')
+                        .map(|field| field.trim().to_string())
+                        .collect()
+                })
+                .collect(),
+            Separator::Comma => csv_records(source),
+        }
+    }
+}
+
+fn csv_records(source: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = source.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => record.push(std::mem::take(&mut field).trim().to_string()),
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field).trim().to_string());
+                end_record(&mut records, std::mem::take(&mut record));
+            }
+            '\r' if !quoted => {}
+            _ => field.push(ch),
+        }
+    }
+    record.push(field.trim().to_string());
+    end_record(&mut records, record);
+    records
+}
+
+/// Keeps `record` unless it came from a blank line.
+fn end_record(records: &mut Vec<Vec<String>>, record: Vec<String>) {
+    if !(record.len() == 1 && record[0].is_empty()) {
+        records.push(record);
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use crate::{RenderOptions, render};
+
+    #[test]
+    fn renders_csv_and_tsv_blocks_as_tables() {
+        let cases = [
+            (
+                "```csv\nName, Score\n\"Doe, Jane\",\"say \"\"hi\"\"\"\nBob\n```",
+                "<table><thead><tr><th>Name</th><th>Score</th></tr>\n</thead>\n<tbody>\n\
+                 <tr><td>Doe, Jane</td><td>say &quot;hi&quot;</td></tr>\n\
+                 <tr><td>Bob</td><td></td></tr>\n</tbody>\n</table>\n",
+            ),
+            (
+                "```tsv\na\tb\n\n1\t2\t3\n```",
+                "<table><thead><tr><th>a</th><th>b</th><th></th></tr>\n</thead>\n<tbody>\n\
+                 <tr><td>1</td><td>2</td><td>3</td></tr>\n</tbody>\n</table>\n",
+            ),
+            (
+                "```csv\n\"multi\nline\"\n```",
+                "<table><thead><tr><th>multi\nline</th></tr>\n</thead>\n</table>\n",
+            ),
+            (
+                "```csv\n```\n\n```tsv\n\n\n```",
+                "<pre><code class=\"language-csv\"></code></pre>\n\
+                 <pre><code class=\"language-tsv\">\n</code></pre>\n",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let options = RenderOptions {
+                csv_tables: true,
+                ..RenderOptions::default()
+            };
+            assert_eq!(render(input, &options).unwrap().html, expected, "{input:?}");
+        }
+
+        let html = render("```csv\na,b\n```", &RenderOptions::default())
+            .unwrap()
+            .html;
+        assert!(html.starts_with("<pre><code class=\"language-csv\">a,b"));
+    }
+}
@@ -0,0 +1,246 @@
+//! Collapsible `:::details Title` … `:::` blocks rendered as `<details>`.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::event::{Event, Tag, TagEnd};
+
+/// Opening marker; the rest of its line is the summary.
+const OPENER: &str = ":::details";
+/// Closing marker, alone on the last line of a paragraph.
+const CLOSER: &str = ":::";
+
+/// Rewrites paragraphs that open with a `:::details Title` line into [`Tag::Details`] with a
+/// [`Tag::Summary`] of the title, and closes the innermost open block at a paragraph whose
+/// last line is `:::`. The markers may share a paragraph with the content or stand alone, and an
+/// empty title reads `Details`. Blocks still open at the end of the container they started in,
+/// or of the document, are closed there, so the events stay balanced.
+pub(crate) struct Details<I> {
+    inner: I,
+    pending: VecDeque<Event<'static>>,
+    /// Container nesting at which each open details block started, innermost last.
+    open: Vec<usize>,
+    /// Containers open around the current event.
+    nesting: usize,
+}
+
+impl<I> Details<I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    pub(crate) fn new(inner: I) -> Self {
+        Self {
+            inner,
+            pending: VecDeque::new(),
+            open: Vec::new(),
+            nesting: 0,
+        }
+    }
+
+    /// Reads the paragraph that just started and queues it, with any markers replaced.
+    fn paragraph(&mut self) {
+        let mut original = Vec::new();
+        for event in self.inner.by_ref() {
+            if matches!(event, Event::End(TagEnd::Paragraph)) {
+                break;
+            }
+            original.push(event);
+        }
+
+        let mut body = split_lines(&original);
+        let opened = self.open(&mut body);
+        let closed = self.open.last() == Some(&self.nesting) && close(&mut body);
+        if !opened && !closed {
+            body = original;
+        }
+        if !body.is_empty() {
+            self.pending.push_back(Event::Start(Tag::Paragraph));
+            self.pending.extend(body);
+            self.pending.push_back(Event::End(TagEnd::Paragraph));
+        }
+        if closed {
+            self.open.pop();
+            self.pending.push_back(Event::End(TagEnd::Details));
+        }
+    }
+
+    /// Takes an opener line off the front of `body` and queues the summary.
+    fn open(&mut self, body: &mut Vec<Event<'static>>) -> bool {
+        let Some(Event::Text(first)) = body.first() else {
+            return false;
+        };
+        let Some(title) = first.trim_start().strip_prefix(OPENER) else {
+            return false;
+        };
+        if title.starts_with(|ch: char| !ch.is_whitespace()) {
+            return false;
+        }
+
+        let title = title.trim_start().to_string();
+        let line_end = body.iter().position(is_break).unwrap_or(body.len());
+        let mut summary: Vec<_> = body.drain(..line_end).skip(1).collect();
+        if !body.is_empty() {
+            body.remove(0);
+        }
+        if !title.is_empty() {
+            summary.insert(0, Event::Text(Cow::Owned(title)));
+        }
+        if summary.is_empty() {
+            summary.push(Event::Text(Cow::Borrowed("Details")));
+        }
+
+        self.open.push(self.nesting);
+        self.pending.push_back(Event::Start(Tag::Details));
+        self.pending.push_back(Event::Start(Tag::Summary));
+        self.pending.extend(summary);
+        self.pending.push_back(Event::End(TagEnd::Summary));
+        true
+    }
+}
+
+/// Takes a closer line off the end of `body`.
+fn close(body: &mut Vec<Event<'static>>) -> bool {
+    let line_start = body.iter().rposition(is_break).map_or(0, |idx| idx + 1);
+    match &body[line_start..] {
+        [Event::Text(text)] if text.trim() == CLOSER => {
+            body.truncate(line_start.saturating_sub(1));
+            true
+        }
+        _ => false,
+    }
+}
+
+fn is_break(event: &Event<'_>) -> bool {
+    matches!(event, Event::SoftBreak | Event::HardBreak)
+}
+
+/// `events` with line breaks inside text (as markdown-rs leaves them) turned into soft breaks.
+fn split_lines(events: &[Event<'static>]) -> Vec<Event<'static>> {
+    let mut lines = Vec::with_capacity(events.len());
+    for event in events {
+        match event {
+            Event::Text(text) if text.contains('\n') => {
+                for (idx, line) in text.split('\n').enumerate() {
+                    if idx > 0 {
+                        lines.push(Event::SoftBreak);
+                    }
+                    if !line.is_empty() {
+                        lines.push(Event::Text(Cow::Owned(line.to_string())));
+                    }
+                }
+            }
+            event => lines.push(event.clone()),
+        }
+    }
+    lines
+}
+
+impl<I> Iterator for Details<I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    type Item = Event<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            match self.inner.next() {
+                Some(Event::Start(Tag::Paragraph)) => self.paragraph(),
+                Some(event @ Event::Start(_)) => {
+                    self.nesting += 1;
+                    return Some(event);
+                }
+                Some(event @ Event::End(_)) => {
+                    while self.open.last() == Some(&self.nesting) {
+                        self.open.pop();
+                        self.pending.push_back(Event::End(TagEnd::Details));
+                    }
+                    self.nesting = self.nesting.saturating_sub(1);
+                    self.pending.push_back(event);
+                }
+                Some(event) => return Some(event),
+                None => {
+                    self.open.pop()?;
+                    return Some(Event::End(TagEnd::Details));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{HtmlOptions, ParserBackend, RenderOptions, render};
+
+    #[test]
+    fn renders_details_blocks() {
+        let cases = [
+            (
+                ":::details Why *not*?\nBecause.\n:::",
+                "<details><summary>Why <em>not</em>?</summary>\n<p>Because.</p>\n</details>\n",
+            ),
+            (
+                ":::details\n\n- one\n\n:::details Inner\n\ntext\n\n:::\n\n:::\n\nafter",
+                "<details><summary>Details</summary>\n<ul><li>one</li></ul>\n\
+                 <details><summary>Inner</summary>\n<p>text</p>\n</details>\n</details>\n\
+                 <p>after</p>\n",
+            ),
+            (
+                ":::details Open\n\nnever closed",
+                "<details><summary>Open</summary>\n<p>never closed</p>\n</details>\n",
+            ),
+            (":::detailsx\n\n:::", "<p>:::detailsx</p>\n<p>:::</p>\n"),
+            (
+                "> :::details Quoted\n>\n> body\n\n:::",
+                "<blockquote><details><summary>Quoted</summary>\n<p>body</p>\n</details>\n\
+                 </blockquote>\n<p>:::</p>\n",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+                let options = RenderOptions {
+                    backend,
+                    details: true,
+                    ..RenderOptions::default()
+                };
+                assert_eq!(
+                    render(input, &options).unwrap().html,
+                    expected,
+                    "{backend:?}: {input:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn details_survive_escaped_raw_html() {
+        let options = RenderOptions {
+            details: true,
+            html: HtmlOptions {
+                escape_raw_html: true,
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render(":::details T\nbody <b>\n:::", &options)
+            .unwrap()
+            .html;
+        assert_eq!(
+            html,
+            "<details><summary>T</summary>\n<p>body &lt;b&gt;</p>\n</details>\n"
+        );
+
+        let events =
+            super::Details::new(crate::get_event_iterator(":::details T\nbody\n:::").unwrap());
+        let markdown = crate::MarkdownRenderer::new(Vec::new(), crate::MarkdownOptions::default())
+            .render(events)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(markdown).unwrap(),
+            ":::details T\n\nbody\n\n:::\n"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Non-fatal problems found while rendering, with their source positions.
+//!
+//! Rendering never fails on these: the document still renders as it would without
+//! [`RenderOptions::diagnostics`], and [`RenderResult::diagnostics`](crate::RenderResult) tells
+//! authors and linters what may not look the way they meant.
+
+use std::fmt;
+
+use crate::span::SourceSpan;
+
+/// Kinds of [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticKind {
+    /// `[text][label]` or `[label][]` without a `[label]: url` definition, rendered as text.
+    UnresolvedReference,
+    /// An explicit `{#id}` an earlier heading already has; the renderer gives it a suffix.
+    DuplicateId,
+    /// A node the selected backend cannot render, such as MDX with pulldown-cmark.
+    UnsupportedNode,
+    /// An image with empty alt text.
+    MissingAltText,
+}
+
+/// A non-fatal problem in a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// What kind of problem this is.
+    pub kind: DiagnosticKind,
+    /// Human-readable description.
+    pub message: String,
+    /// Location in the Markdown source, when known.
+    pub span: Option<SourceSpan>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{}: {}", span.start_line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Collects the [`Diagnostic`]s of a document in source order.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    /// Creates an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `kind` at `span`.
+    pub fn push(
+        &mut self,
+        kind: DiagnosticKind,
+        message: impl Into<String>,
+        span: Option<SourceSpan>,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            kind,
+            message: message.into(),
+            span,
+        });
+    }
+
+    /// Diagnostics recorded so far.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Consumes the collector, returning the diagnostics in the order they were recorded.
+    pub fn finish(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+
+    /// Records that an explicit heading `id` was taken and the heading got `unique` instead.
+    pub(crate) fn duplicate_id(&mut self, id: &str, unique: &str, span: Option<SourceSpan>) {
+        if unique != id {
+            self.push(
+                DiagnosticKind::DuplicateId,
+                format!("id `{id}` is already used; this heading gets `{unique}`"),
+                span,
+            );
+        }
+    }
+
+    /// Records an image at `span` if its `alt` text is blank.
+    pub(crate) fn image(&mut self, alt: &str, span: Option<SourceSpan>) {
+        if alt.trim().is_empty() {
+            self.push(
+                DiagnosticKind::MissingAltText,
+                "image has no alt text",
+                span,
+            );
+        }
+    }
+
+    /// Records a reference to `label` that no definition resolves.
+    pub(crate) fn unresolved_reference(&mut self, label: &str, span: Option<SourceSpan>) {
+        self.push(
+            DiagnosticKind::UnresolvedReference,
+            format!("no definition for reference `[{label}]`"),
+            span,
+        );
+    }
+
+    /// Replaces every recorded span with `map(span)`, e.g. to move spans from rewritten source
+    /// back onto the input.
+    pub(crate) fn map_spans(&mut self, map: impl Fn(SourceSpan) -> SourceSpan) {
+        for diagnostic in &mut self.diagnostics {
+            diagnostic.span = diagnostic.span.map(&map);
+        }
+    }
+}
+
+/// Labels of the full (`[text][label]`) and collapsed (`[label][]`) references left in `text`.
+pub(crate) fn reference_labels(text: &str) -> Vec<&str> {
+    let mut labels = Vec::new();
+    let mut rest = text;
+    while let Some(at) = rest.find("][") {
+        let before = &rest[..at];
+        let after = &rest[at + 2..];
+        let Some(end) = after.find(']') else {
+            break;
+        };
+        let label = match &after[..end] {
+            "" => before.rfind('[').map(|open| &before[open + 1..]),
+            label => Some(label),
+        };
+        if let Some(label) = label.filter(|label| !label.trim().is_empty() && !label.contains('['))
+        {
+            labels.push(label);
+        }
+        rest = &after[end + 1..];
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DiagnosticKind, ParserBackend, RenderOptions, SyntaxOptions, render};
+
+    #[test]
+    fn reports_diagnostics_with_positions() {
+        let input = "# Intro {#start}\n\n![](a.png) ![chart](b.png)\n\nSee [docs][missing] and [Known][].\n\n[known]: /known\n\n## Later {#start}\n";
+        let options = RenderOptions {
+            syntax: SyntaxOptions {
+                heading_attributes: true,
+                ..SyntaxOptions::default()
+            },
+            diagnostics: true,
+            ..RenderOptions::default()
+        };
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                ..options.clone()
+            };
+            let result = render(input, &options).unwrap();
+            let found: Vec<(DiagnosticKind, usize)> = result
+                .diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.kind, diagnostic.span.unwrap().start_line))
+                .collect();
+            assert_eq!(
+                found,
+                [
+                    (DiagnosticKind::MissingAltText, 3),
+                    (DiagnosticKind::UnresolvedReference, 5),
+                    (DiagnosticKind::DuplicateId, 9),
+                ],
+                "{backend:?}"
+            );
+            assert_eq!(
+                result.diagnostics[1].to_string(),
+                "5: no definition for reference `[missing]`"
+            );
+            assert!(result.html.contains("<h2 id=\"start-1\">Later</h2>"));
+        }
+
+        let quiet = RenderOptions {
+            diagnostics: false,
+            ..options
+        };
+        assert!(render(input, &quiet).unwrap().diagnostics.is_empty());
+    }
+
+    #[test]
+    fn checks_the_source_after_shortcode_protection() {
+        let input = "{{< note\n  \"[a][b]\" >}} ![]({{< img >}})\n\nSee [docs][missing].\n";
+        let options = RenderOptions {
+            shortcodes: true,
+            diagnostics: true,
+            ..RenderOptions::default()
+        };
+        let diagnostics = render(input, &options).unwrap().diagnostics;
+        let found: Vec<(DiagnosticKind, usize, &str)> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let span = diagnostic.span.unwrap();
+                (diagnostic.kind, span.start_line, span.slice(input).unwrap())
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (DiagnosticKind::MissingAltText, 2, "![]({{< img >}})"),
+                (
+                    DiagnosticKind::UnresolvedReference,
+                    4,
+                    "See [docs][missing]."
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_mdx_the_backend_cannot_render() {
+        let input = "Text\n\n<Note>hi</Note>\n";
+        for (backend, expected) in [
+            (ParserBackend::MarkdownRs, 0),
+            (ParserBackend::PulldownCmark, 1),
+        ] {
+            let options = RenderOptions {
+                backend,
+                syntax: SyntaxOptions {
+                    mdx: true,
+                    ..SyntaxOptions::default()
+                },
+                diagnostics: true,
+                ..RenderOptions::default()
+            };
+            let diagnostics = render(input, &options).unwrap().diagnostics;
+            assert_eq!(diagnostics.len(), expected, "{backend:?}: {diagnostics:?}");
+            assert!(
+                diagnostics
+                    .iter()
+                    .all(|diagnostic| diagnostic.kind == DiagnosticKind::UnsupportedNode)
+            );
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! The error type returned by every fallible Markflow entry point.
+
+use std::io;
+
+use markdown::message::{Message, Place};
+use thiserror::Error;
+
+use crate::limits::{self, LimitExceeded};
+use crate::span::SourceSpan;
+
+/// Errors that can occur during Markdown processing.
+///
+/// Each variant names the pipeline stage that failed and may point at the offending source
+/// region. [`MarkflowError::code`] is stable across releases, so bindings and callers should
+/// branch on it rather than on the message text.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MarkflowError {
+    /// The Markdown parser rejected the input.
+    #[error("parse error: {message}")]
+    Parse {
+        /// Parser diagnostic.
+        message: String,
+        /// Where the parser gave up.
+        span: Option<SourceSpan>,
+    },
+    /// The Markdown could not be read: the reader failed, or the input is not valid UTF-8.
+    #[error("input error: {source}")]
+    Input {
+        /// The reader failure, or [`io::ErrorKind::InvalidData`] for malformed UTF-8.
+        #[source]
+        source: io::Error,
+        /// Where in the input reading stopped.
+        span: Option<SourceSpan>,
+    },
+    /// An event transform could not process the document.
+    #[error("transform error: {message}")]
+    Transform {
+        /// What went wrong.
+        message: String,
+        /// The construct being transformed.
+        span: Option<SourceSpan>,
+    },
+    /// Writing or rewriting the HTML output failed.
+    #[error("rewrite error: {source}")]
+    Rewrite {
+        /// The underlying writer or lol_html failure.
+        #[source]
+        source: io::Error,
+        /// The construct being written.
+        span: Option<SourceSpan>,
+    },
+    /// A configured resource limit was exceeded.
+    #[error("limit exceeded: {source}")]
+    Limit {
+        /// Which limit tripped.
+        #[source]
+        source: LimitExceeded,
+        /// The construct that tripped it.
+        span: Option<SourceSpan>,
+    },
+    /// A user-supplied callback (plugin, chunk sink, URL hook) reported a failure.
+    #[error("callback error: {message}")]
+    Callback {
+        /// The callback's failure message.
+        message: String,
+        /// The construct the callback was handling.
+        span: Option<SourceSpan>,
+    },
+}
+
+impl MarkflowError {
+    /// Creates a [`MarkflowError::Transform`] without a span.
+    pub fn transform(message: impl Into<String>) -> Self {
+        MarkflowError::Transform {
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    /// Creates a [`MarkflowError::Callback`] without a span.
+    pub fn callback(message: impl Into<String>) -> Self {
+        MarkflowError::Callback {
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    /// Machine-readable identifier of the failure class, e.g. `ERR_MARKFLOW_LIMIT`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            MarkflowError::Parse { .. } => "ERR_MARKFLOW_PARSE",
+            MarkflowError::Input { .. } => "ERR_MARKFLOW_INPUT",
+            MarkflowError::Transform { .. } => "ERR_MARKFLOW_TRANSFORM",
+            MarkflowError::Rewrite { .. } => "ERR_MARKFLOW_REWRITE",
+            MarkflowError::Limit { .. } => "ERR_MARKFLOW_LIMIT",
+            MarkflowError::Callback { .. } => "ERR_MARKFLOW_CALLBACK",
+        }
+    }
+
+    /// The source region the failure relates to, when known.
+    pub fn span(&self) -> Option<SourceSpan> {
+        match self {
+            MarkflowError::Parse { span, .. }
+            | MarkflowError::Input { span, .. }
+            | MarkflowError::Transform { span, .. }
+            | MarkflowError::Rewrite { span, .. }
+            | MarkflowError::Limit { span, .. }
+            | MarkflowError::Callback { span, .. } => *span,
+        }
+    }
+
+    /// Attaches `span`, replacing any span already recorded.
+    pub fn with_span(mut self, new_span: SourceSpan) -> Self {
+        match &mut self {
+            MarkflowError::Parse { span, .. }
+            | MarkflowError::Input { span, .. }
+            | MarkflowError::Transform { span, .. }
+            | MarkflowError::Rewrite { span, .. }
+            | MarkflowError::Limit { span, .. }
+            | MarkflowError::Callback { span, .. } => *span = Some(new_span),
+        }
+        self
+    }
+
+    /// Converts a markdown-rs diagnostic, keeping its position.
+    pub(crate) fn parse(message: Message) -> Self {
+        let span = message.place.as_deref().map(|place| match place {
+            Place::Position(position) => SourceSpan::from(position),
+            Place::Point(point) => SourceSpan {
+                start: point.offset,
+                end: point.offset,
+                start_line: point.line,
+                end_line: point.line,
+            },
+        });
+        MarkflowError::Parse {
+            message: message.reason,
+            span,
+        }
+    }
+}
+
+impl From<io::Error> for MarkflowError {
+    fn from(err: io::Error) -> Self {
+        // Limits and callback failures surface from deep inside the writer chain as io errors.
+        if let Some(limit) = limits::limit_from_io(&err) {
+            return limit.into();
+        }
+        if err
+            .get_ref()
+            .is_some_and(|inner| inner.is::<MarkflowError>())
+        {
+            let inner = err.into_inner().expect("checked above");
+            return *inner.downcast::<MarkflowError>().expect("checked above");
+        }
+        MarkflowError::Rewrite {
+            source: err,
+            span: None,
+        }
+    }
+}
+
+impl From<std::string::FromUtf8Error> for MarkflowError {
+    fn from(err: std::string::FromUtf8Error) -> Self {
+        MarkflowError::Rewrite {
+            source: io::Error::new(io::ErrorKind::InvalidData, err),
+            span: None,
+        }
+    }
+}
+
+impl From<LimitExceeded> for MarkflowError {
+    fn from(source: LimitExceeded) -> Self {
+        MarkflowError::Limit { source, span: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LimitKind;
+
+    #[test]
+    fn classifies_errors_by_code() {
+        let limit = LimitExceeded {
+            kind: LimitKind::OutputBytes,
+            limit: 8,
+        };
+        let cases = [
+            (MarkflowError::from(limit), "ERR_MARKFLOW_LIMIT"),
+            (io::Error::other(limit).into(), "ERR_MARKFLOW_LIMIT"),
+            (
+                io::Error::other(MarkflowError::callback("sink threw")).into(),
+                "ERR_MARKFLOW_CALLBACK",
+            ),
+            (
+                io::Error::other("broken pipe").into(),
+                "ERR_MARKFLOW_REWRITE",
+            ),
+            (
+                String::from_utf8(vec![0xFF]).unwrap_err().into(),
+                "ERR_MARKFLOW_REWRITE",
+            ),
+            (MarkflowError::transform("bad"), "ERR_MARKFLOW_TRANSFORM"),
+        ];
+        for (err, code) in cases {
+            assert_eq!(err.code(), code, "{err}");
+        }
+    }
+
+    #[test]
+    fn keeps_parse_positions() {
+        let options = markdown::ParseOptions::mdx();
+        let err = markdown::to_mdast("a\n\n{b", &options).unwrap_err();
+        let err = MarkflowError::parse(err);
+        assert_eq!(err.code(), "ERR_MARKFLOW_PARSE");
+        assert_eq!(err.span().map(|span| span.start_line), Some(3));
+
+        let span = SourceSpan {
+            start: 1,
+            end: 2,
+            start_line: 1,
+            end_line: 1,
+        };
+        assert_eq!(
+            MarkflowError::callback("x").with_span(span).span(),
+            Some(span)
+        );
+    }
+}
@@ -1,8 +1,15 @@
 use std::borrow::Cow;
 use std::convert::TryFrom;
 
+use serde::{Deserialize, Serialize};
+
+use crate::code_blocks::CodeMeta;
+
 /// A Markdown event emitted by the Markflow pipeline.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Events (de)serialize with serde, so streams can be dumped for debugging, cached, and replayed
+/// through [`crate::MarkdownStream`]. Deserialized text is always owned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Event<'a> {
     /// Start of a tagged element.
     Start(Tag<'a>),
@@ -30,10 +37,15 @@ pub enum Event<'a> {
     HardBreak,
     /// Soft line break.
     SoftBreak,
+    /// MDX expression (`{props.title}`), holding the source between the braces.
+    MdxExpression(Cow<'a, str>),
 }
 
+/// Boxed, owned event stream passed between parser backends, transforms, and renderers.
+pub type EventStream<'a> = Box<dyn Iterator<Item = Event<'static>> + 'a>;
+
 /// Tags for container elements.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Tag<'a> {
     Paragraph,
     Heading {
@@ -47,6 +59,30 @@ pub enum Tag<'a> {
     List(Option<u64>),
     Item,
     FootnoteDefinition(Cow<'a, str>),
+    /// `<section class="footnotes">` holding the definitions hoisted by
+    /// [`FootnoteStyle::Endnotes`](crate::FootnoteStyle::Endnotes).
+    Endnotes,
+    /// GitHub-style list of renumbered notes at the end of the document, holding
+    /// [`Tag::FootnoteItem`]s.
+    FootnoteList,
+    /// Note `N` of a [`Tag::FootnoteList`].
+    FootnoteItem(usize),
+    /// The `nth` reference to renumbered note `number`, holding the number as text.
+    NumberedFootnoteReference {
+        number: usize,
+        nth: usize,
+    },
+    /// `↩` link from note `number` back to its `nth` reference; it holds no events.
+    FootnoteBackref {
+        number: usize,
+        nth: usize,
+    },
+    /// Margin note standing in for the `nth` reference to footnote `label`, holding the
+    /// definition flattened to inline content.
+    Sidenote {
+        label: Cow<'a, str>,
+        nth: usize,
+    },
     Table(Vec<Alignment>),
     TableHead,
     TableRow,
@@ -54,6 +90,33 @@ pub enum Tag<'a> {
     Emphasis,
     Strong,
     Strikethrough,
+    /// `==marked==` text.
+    Highlight,
+    /// `^superscript^` text.
+    Superscript,
+    /// `~subscript~` text.
+    Subscript,
+    /// `[[Ctrl]]` keyboard key.
+    Keyboard,
+    /// `{漢字|かんじ}` ruby: base text with the readings in [`Tag::RubyText`] after it.
+    Ruby,
+    /// Reading of the base text before it inside [`Tag::Ruby`].
+    RubyText,
+    /// Collapsible `:::details` block; its first child is a [`Tag::Summary`].
+    Details,
+    /// Title of a [`Tag::Details`] block.
+    Summary,
+    /// GitHub-style alert box (`> [!NOTE]`) that replaces a block quote.
+    Callout(CalloutKind),
+    /// Hierarchical heading number (`1.2`) in front of a table of contents entry.
+    HeadingNumber,
+    /// Numbered figure: an image followed by its [`Tag::FigureCaption`], with the `id` its
+    /// `{#fig:label}` resolves to (`None` for a repeated label).
+    Figure(Option<Cow<'a, str>>),
+    /// Caption of a [`Tag::Figure`].
+    FigureCaption,
+    /// Numbered image inside a paragraph, with the same `id` a [`Tag::Figure`] would carry.
+    InlineFigure(Option<Cow<'a, str>>),
     Link {
         link_type: LinkType,
         dest_url: Cow<'a, str>,
@@ -65,11 +128,43 @@ pub enum Tag<'a> {
         dest_url: Cow<'a, str>,
         title: Cow<'a, str>,
         id: Cow<'a, str>,
+        /// Width hint in pixels, from [`SyntaxOptions::image_sizes`](crate::SyntaxOptions).
+        width: Option<u32>,
+        /// Height hint in pixels, from [`SyntaxOptions::image_sizes`](crate::SyntaxOptions).
+        height: Option<u32>,
+        /// Further `<img>` attributes, such as the `srcset` an
+        /// [`ImageTransformer`](crate::ImageTransformer) adds.
+        attrs: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+    },
+    /// MDX JSX element (`<Note kind="tip">…</Note>`); children are ordinary events.
+    MdxJsxElement {
+        /// Element name; `None` for fragments (`<>…</>`).
+        name: Option<Cow<'a, str>>,
+        attributes: Vec<MdxJsxAttribute<'a>>,
+        /// Written without children (`<Chart />`).
+        self_closing: bool,
     },
 }
 
+/// Attribute of an MDX JSX element.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MdxJsxAttribute<'a> {
+    /// `name="value"`, or a bare `name` when `value` is `None`.
+    Property {
+        name: Cow<'a, str>,
+        value: Option<Cow<'a, str>>,
+    },
+    /// `name={expression}`; `value` is the expression source without braces.
+    Expression {
+        name: Cow<'a, str>,
+        value: Cow<'a, str>,
+    },
+    /// `{...spread}`; holds the source between the braces.
+    Spread(Cow<'a, str>),
+}
+
 /// Tag terminators.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TagEnd {
     Paragraph,
     Heading(HeadingLevel),
@@ -78,6 +173,12 @@ pub enum TagEnd {
     List(bool),
     Item,
     FootnoteDefinition,
+    Endnotes,
+    FootnoteList,
+    FootnoteItem,
+    NumberedFootnoteReference,
+    FootnoteBackref,
+    Sidenote,
     Table,
     TableHead,
     TableRow,
@@ -85,12 +186,69 @@ pub enum TagEnd {
     Emphasis,
     Strong,
     Strikethrough,
+    Highlight,
+    Superscript,
+    Subscript,
+    Keyboard,
+    Ruby,
+    RubyText,
+    Details,
+    Summary,
+    Callout,
+    HeadingNumber,
+    Figure,
+    FigureCaption,
+    InlineFigure,
     Link,
     Image,
+    MdxJsxElement,
+}
+
+/// Kind of a [`Tag::Callout`], one of the alerts GitHub recognizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CalloutKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl CalloutKind {
+    /// Every kind, in the order GitHub documents them.
+    pub const ALL: [CalloutKind; 5] = [
+        CalloutKind::Note,
+        CalloutKind::Tip,
+        CalloutKind::Important,
+        CalloutKind::Warning,
+        CalloutKind::Caution,
+    ];
+
+    /// Lowercase name, as in the `markdown-alert-note` class.
+    pub fn name(self) -> &'static str {
+        match self {
+            CalloutKind::Note => "note",
+            CalloutKind::Tip => "tip",
+            CalloutKind::Important => "important",
+            CalloutKind::Warning => "warning",
+            CalloutKind::Caution => "caution",
+        }
+    }
+
+    /// Title shown at the top of the alert.
+    pub fn title(self) -> &'static str {
+        match self {
+            CalloutKind::Note => "Note",
+            CalloutKind::Tip => "Tip",
+            CalloutKind::Important => "Important",
+            CalloutKind::Warning => "Warning",
+            CalloutKind::Caution => "Caution",
+        }
+    }
 }
 
 /// Heading depth.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum HeadingLevel {
     H1 = 1,
     H2,
@@ -101,14 +259,19 @@ pub enum HeadingLevel {
 }
 
 /// Code block metadata.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum CodeBlockKind<'a> {
     Indented,
-    Fenced(Cow<'a, str>),
+    /// Fenced block with its info string split into the language (`rust` in
+    /// ```` ```rust title="main.rs" {1,3-5} ````) and the parsed rest.
+    Fenced {
+        lang: Option<Cow<'a, str>>,
+        meta: CodeMeta<'a>,
+    },
 }
 
 /// Table alignment metadata.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Alignment {
     None,
     Left,
@@ -117,7 +280,7 @@ pub enum Alignment {
 }
 
 /// Link kinds used throughout the pipeline.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum LinkType {
     Inline,
     Reference,
@@ -141,6 +304,12 @@ impl<'a> Tag<'a> {
             Tag::List(start) => TagEnd::List(start.is_some()),
             Tag::Item => TagEnd::Item,
             Tag::FootnoteDefinition(_) => TagEnd::FootnoteDefinition,
+            Tag::Endnotes => TagEnd::Endnotes,
+            Tag::FootnoteList => TagEnd::FootnoteList,
+            Tag::FootnoteItem(_) => TagEnd::FootnoteItem,
+            Tag::NumberedFootnoteReference { .. } => TagEnd::NumberedFootnoteReference,
+            Tag::FootnoteBackref { .. } => TagEnd::FootnoteBackref,
+            Tag::Sidenote { .. } => TagEnd::Sidenote,
             Tag::Table(_) => TagEnd::Table,
             Tag::TableHead => TagEnd::TableHead,
             Tag::TableRow => TagEnd::TableRow,
@@ -148,10 +317,124 @@ impl<'a> Tag<'a> {
             Tag::Emphasis => TagEnd::Emphasis,
             Tag::Strong => TagEnd::Strong,
             Tag::Strikethrough => TagEnd::Strikethrough,
+            Tag::Highlight => TagEnd::Highlight,
+            Tag::Superscript => TagEnd::Superscript,
+            Tag::Keyboard => TagEnd::Keyboard,
+            Tag::Ruby => TagEnd::Ruby,
+            Tag::RubyText => TagEnd::RubyText,
+            Tag::Details => TagEnd::Details,
+            Tag::Summary => TagEnd::Summary,
+            Tag::Callout(_) => TagEnd::Callout,
+            Tag::HeadingNumber => TagEnd::HeadingNumber,
+            Tag::Figure(_) => TagEnd::Figure,
+            Tag::FigureCaption => TagEnd::FigureCaption,
+            Tag::InlineFigure(_) => TagEnd::InlineFigure,
+            Tag::Subscript => TagEnd::Subscript,
             Tag::Link { .. } => TagEnd::Link,
             Tag::Image { .. } => TagEnd::Image,
+            Tag::MdxJsxElement { .. } => TagEnd::MdxJsxElement,
+        }
+    }
+}
+
+impl MdxJsxAttribute<'_> {
+    /// JSX source of the attribute, as written in the element's opening tag.
+    pub fn to_jsx(&self) -> String {
+        match self {
+            MdxJsxAttribute::Property { name, value: None } => name.to_string(),
+            MdxJsxAttribute::Property {
+                name,
+                value: Some(value),
+            } => format!("{name}=\"{}\"", value.replace('"', "&quot;")),
+            MdxJsxAttribute::Expression { name, value } => format!("{name}={{{value}}}"),
+            MdxJsxAttribute::Spread(value) => format!("{{{value}}}"),
+        }
+    }
+}
+
+/// JSX source of an element's opening tag (`<Note kind="tip">`, `<Chart />`, `<>`).
+pub(crate) fn jsx_open_tag(
+    name: Option<&str>,
+    attributes: &[MdxJsxAttribute<'_>],
+    self_closing: bool,
+) -> String {
+    let mut tag = String::from("<");
+    tag.push_str(name.unwrap_or_default());
+    for attribute in attributes {
+        tag.push(' ');
+        tag.push_str(&attribute.to_jsx());
+    }
+    tag.push_str(if self_closing { " />" } else { ">" });
+    tag
+}
+
+impl<'a> CodeBlockKind<'a> {
+    /// Fenced block kind for a full info string: the first word is the language, the rest is
+    /// parsed into [`CodeMeta`].
+    pub fn fenced(info: Cow<'a, str>) -> Self {
+        let (lang, meta) = match info {
+            Cow::Borrowed(info) => {
+                let (lang, meta) = split_info(info);
+                (lang.map(Cow::Borrowed), Cow::Borrowed(meta))
+            }
+            Cow::Owned(info) => {
+                let (lang, meta) = split_info(&info);
+                (
+                    lang.map(|lang| Cow::Owned(lang.to_string())),
+                    Cow::Owned(meta.to_string()),
+                )
+            }
+        };
+        CodeBlockKind::Fenced {
+            lang,
+            meta: CodeMeta::parse(meta),
         }
     }
+
+    /// Language: the first word of a fenced block's info string.
+    pub fn lang(&self) -> Option<&str> {
+        match self {
+            CodeBlockKind::Fenced { lang, .. } => lang.as_deref(),
+            CodeBlockKind::Indented => None,
+        }
+    }
+
+    /// Everything in the info string after the language, trimmed.
+    pub fn meta(&self) -> Option<&str> {
+        self.code_meta()
+            .map(|meta| meta.raw.as_ref())
+            .filter(|raw| !raw.is_empty())
+    }
+
+    /// Title, highlighted lines, attributes, and flags from a fenced block's info string.
+    pub fn code_meta(&self) -> Option<&CodeMeta<'a>> {
+        match self {
+            CodeBlockKind::Fenced { meta, .. } => Some(meta),
+            CodeBlockKind::Indented => None,
+        }
+    }
+
+    /// Whether the meta string contains `flag` as a bare word.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.code_meta()
+            .is_some_and(|meta| meta.flags.iter().any(|word| word == flag))
+    }
+
+    /// Forgets the attribute block of a fenced block's meta.
+    pub(crate) fn clear_attribute_block(&mut self) {
+        if let CodeBlockKind::Fenced { meta, .. } = self {
+            meta.clear_attribute_block();
+        }
+    }
+}
+
+/// Splits an info string into its first word and the trimmed remainder.
+fn split_info(info: &str) -> (Option<&str>, &str) {
+    let info = info.trim();
+    match info.split_once(char::is_whitespace) {
+        Some((lang, meta)) => (Some(lang), meta.trim_start()),
+        None => ((!info.is_empty()).then_some(info), ""),
+    }
 }
 
 impl TryFrom<usize> for HeadingLevel {
@@ -0,0 +1,445 @@
+//! Numbered figures in the style of pandoc-crossref.
+//!
+//! `![Alt](src){#fig:label}` numbers the image in document order, and every `@fig:label` in the
+//! text becomes a link reading "Figure N". An image that fills its paragraph is wrapped in
+//! `<figure>` with a `<figcaption>` carrying the number and the alt text.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::{Event, LinkType, Tag, TagEnd};
+
+const LABEL_PREFIX: &str = "fig:";
+
+/// How numbered figures are captioned and referenced.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FigureOptions {
+    /// Word placed before the number in captions and references. Defaults to `Figure`.
+    pub prefix: String,
+}
+
+impl Default for FigureOptions {
+    fn default() -> Self {
+        Self {
+            prefix: "Figure".to_string(),
+        }
+    }
+}
+
+/// Two figures declared the same label. References resolve to the first one, and the duplicate
+/// is numbered but gets no `id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FigureLabelCollision {
+    /// The repeated label, including its `fig:` prefix.
+    pub label: String,
+    /// Number of the figure that kept the label.
+    pub first: usize,
+    /// Number of the figure that repeated it.
+    pub duplicate: usize,
+}
+
+/// A labeled image in the buffered stream.
+struct Figure {
+    /// Index of the image's `End` event.
+    end: usize,
+    label: String,
+    number: usize,
+    /// Whether this figure owns the label; `false` for duplicates.
+    owns_label: bool,
+}
+
+/// Numbers labeled images and resolves `@fig:` references, appending duplicate labels to
+/// `collisions`.
+///
+/// This buffers the whole stream because a reference may precede its figure.
+pub(crate) fn number_figures<I>(
+    events: I,
+    options: &FigureOptions,
+    id_prefix: &str,
+    collisions: &mut Vec<FigureLabelCollision>,
+) -> std::vec::IntoIter<Event<'static>>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    let Collector {
+        output: events,
+        figures,
+        numbers,
+        ..
+    } = collect_figures(events, collisions);
+    if figures.is_empty() {
+        return events.into_iter();
+    }
+    let numbering = Numbering {
+        prefix: &options.prefix,
+        id_prefix,
+        numbers: &numbers,
+    };
+
+    let mut output = Vec::with_capacity(events.len());
+    // Open links and images, whose text cannot hold another link.
+    let mut literal_depth = 0usize;
+    let mut idx = 0;
+    while idx < events.len() {
+        if let Event::Start(Tag::Paragraph) = &events[idx]
+            && let Some(figure) = figures.get(&(idx + 1))
+            && matches!(
+                events.get(figure.end + 1),
+                Some(Event::End(TagEnd::Paragraph))
+            )
+        {
+            let image = &events[idx + 1..=figure.end];
+            let mut caption = format!("{} {}", options.prefix, figure.number);
+            let alt: String = image[1..image.len() - 1]
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Text(text) | Event::Code(text) => Some(numbering.plain(text)),
+                    _ => None,
+                })
+                .collect();
+            if !alt.trim().is_empty() {
+                caption.push_str(": ");
+                caption.push_str(alt.trim());
+            }
+            output.push(Event::Start(Tag::Figure(figure.id(id_prefix))));
+            numbering.push_plain(image, &mut output);
+            output.extend([
+                Event::Start(Tag::FigureCaption),
+                Event::Text(Cow::Owned(caption)),
+                Event::End(TagEnd::FigureCaption),
+                Event::End(TagEnd::Figure),
+            ]);
+            idx = figure.end + 2;
+            continue;
+        }
+        if let Some(figure) = figures.get(&idx) {
+            output.push(Event::Start(Tag::InlineFigure(figure.id(id_prefix))));
+            numbering.push_plain(&events[idx..=figure.end], &mut output);
+            output.push(Event::End(TagEnd::InlineFigure));
+            idx = figure.end + 1;
+            continue;
+        }
+
+        match &events[idx] {
+            Event::Text(text) if literal_depth == 0 => numbering.push_linked(text, &mut output),
+            Event::Text(text) => output.push(Event::Text(Cow::Owned(numbering.plain(text)))),
+            event => {
+                match event {
+                    Event::Start(Tag::Link { .. } | Tag::Image { .. }) => literal_depth += 1,
+                    Event::End(TagEnd::Link | TagEnd::Image) => {
+                        literal_depth = literal_depth.saturating_sub(1)
+                    }
+                    _ => {}
+                }
+                output.push(event.clone());
+            }
+        }
+        idx += 1;
+    }
+    output.into_iter()
+}
+
+impl Figure {
+    /// The `id` of the figure, which duplicates of a label go without.
+    fn id(&self, id_prefix: &str) -> Option<Cow<'static, str>> {
+        self.owns_label
+            .then(|| Cow::Owned(format!("{id_prefix}{}", self.label)))
+    }
+}
+
+/// Buffers `events`, strips `{#fig:label}` blocks after images, and numbers the images they
+/// label.
+fn collect_figures<'c, I>(events: I, collisions: &'c mut Vec<FigureLabelCollision>) -> Collector<'c>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    let mut collector = Collector {
+        output: Vec::new(),
+        figures: HashMap::new(),
+        numbers: HashMap::new(),
+        collisions,
+    };
+    let mut open_images = Vec::new();
+    // Start and end of the image whose following text may hold an attribute block.
+    let mut last_image: Option<(usize, usize)> = None;
+
+    for event in events {
+        // Join text split by the parser so the attribute block is seen whole.
+        if let (Some(Event::Text(last)), Event::Text(text)) = (collector.output.last_mut(), &event)
+        {
+            last.to_mut().push_str(text);
+            continue;
+        }
+        if !matches!(event, Event::Text(_))
+            && let Some((start, end)) = last_image.take()
+        {
+            collector.label(start, end);
+        }
+        match &event {
+            Event::Start(Tag::Image { .. }) => open_images.push(collector.output.len()),
+            Event::End(TagEnd::Image) => {
+                last_image = open_images
+                    .pop()
+                    .map(|start| (start, collector.output.len()));
+            }
+            _ => {}
+        }
+        collector.output.push(event);
+    }
+    if let Some((start, end)) = last_image {
+        collector.label(start, end);
+    }
+    collector
+}
+
+struct Collector<'c> {
+    output: Vec<Event<'static>>,
+    /// Labeled images keyed by the index of their `Start` event.
+    figures: HashMap<usize, Figure>,
+    /// The number each label resolves to.
+    numbers: HashMap<String, usize>,
+    collisions: &'c mut Vec<FigureLabelCollision>,
+}
+
+impl Collector<'_> {
+    /// Numbers the image spanning `start..=end` when the text right after it opens with an
+    /// attribute block, and strips the block.
+    fn label(&mut self, start: usize, end: usize) {
+        let Some(Event::Text(text)) = self.output.get(end + 1) else {
+            return;
+        };
+        let Some((label, rest)) = attribute_block(text) else {
+            return;
+        };
+        let (label, rest) = (label.to_string(), rest.to_string());
+        let number = self.figures.len() + 1;
+        let owns_label = match self.numbers.get(&label) {
+            Some(&first) => {
+                self.collisions.push(FigureLabelCollision {
+                    label: label.clone(),
+                    first,
+                    duplicate: number,
+                });
+                false
+            }
+            None => {
+                self.numbers.insert(label.clone(), number);
+                true
+            }
+        };
+        self.figures.insert(
+            start,
+            Figure {
+                end,
+                label,
+                number,
+                owns_label,
+            },
+        );
+        if rest.trim().is_empty() {
+            self.output.remove(end + 1);
+        } else {
+            self.output[end + 1] = Event::Text(Cow::Owned(rest));
+        }
+    }
+}
+
+/// Splits a leading `{#fig:label}` off `text`, returning the label and the rest.
+fn attribute_block(text: &str) -> Option<(&str, &str)> {
+    let inner = text.strip_prefix("{#")?;
+    let end = inner.find('}')?;
+    let label = &inner[..end];
+    let name = label.strip_prefix(LABEL_PREFIX)?;
+    (!name.is_empty() && name.chars().all(is_label_char)).then(|| (label, &inner[end + 1..]))
+}
+
+fn is_label_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '-' | '_' | ':' | '.')
+}
+
+/// Figure numbers by label, for rewriting `@fig:label` references.
+struct Numbering<'n> {
+    prefix: &'n str,
+    id_prefix: &'n str,
+    numbers: &'n HashMap<String, usize>,
+}
+
+impl Numbering<'_> {
+    /// Splits `text` into literal runs and resolved references.
+    fn references<'t>(&self, text: &'t str) -> Vec<(&'t str, Option<(&'t str, usize)>)> {
+        let mut parts = Vec::new();
+        let mut literal_start = 0;
+        let mut pos = 0;
+        while let Some(offset) = text[pos..].find('@') {
+            let at = pos + offset;
+            let candidate = &text[at + 1..];
+            let len = candidate
+                .find(|ch: char| !is_label_char(ch))
+                .unwrap_or(candidate.len());
+            // Sentence punctuation after a reference is not part of the label.
+            let label = candidate[..len].trim_end_matches(['.', ':']);
+            pos = at + 1;
+            let Some(&number) = self.numbers.get(label) else {
+                continue;
+            };
+            parts.push((&text[literal_start..at], Some((label, number))));
+            literal_start = at + 1 + label.len();
+            pos = literal_start;
+        }
+        parts.push((&text[literal_start..], None));
+        parts
+    }
+
+    /// `text` with references replaced by "Figure N".
+    fn plain(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for (literal, reference) in self.references(text) {
+            out.push_str(literal);
+            if let Some((_, number)) = reference {
+                out.push_str(&format!("{} {number}", self.prefix));
+            }
+        }
+        out
+    }
+
+    /// Pushes `events` with references in their text replaced by "Figure N".
+    fn push_plain(&self, events: &[Event<'static>], output: &mut Vec<Event<'static>>) {
+        for event in events {
+            output.push(match event {
+                Event::Text(text) => Event::Text(Cow::Owned(self.plain(text))),
+                event => event.clone(),
+            });
+        }
+    }
+
+    /// Pushes `text` with references turned into links to their figures.
+    fn push_linked(&self, text: &str, output: &mut Vec<Event<'static>>) {
+        for (literal, reference) in self.references(text) {
+            if !literal.is_empty() {
+                output.push(Event::Text(Cow::Owned(literal.to_string())));
+            }
+            if let Some((label, number)) = reference {
+                output.push(Event::Start(Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: Cow::Owned(format!("#{}{label}", self.id_prefix)),
+                    title: Cow::Borrowed(""),
+                    id: Cow::Borrowed(""),
+                }));
+                output.push(Event::Text(Cow::Owned(format!("{} {number}", self.prefix))));
+                output.push(Event::End(TagEnd::Link));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParserBackend, RenderOptions, render, render_ast, render_text};
+
+    #[test]
+    fn numbers_figures_and_references() {
+        let input = "As @fig:arch shows, see also @fig:flow.\n\n\
+                     ![System *architecture*](arch.png){#fig:arch}\n\n\
+                     Inline ![flow](flow.png){#fig:flow} and [@fig:arch](#x), `@fig:arch`, @fig:none.";
+        let expected = [
+            "<p>As <a href=\"#fig:arch\">Figure 1</a> shows, see also <a href=\"#fig:flow\">Figure 2</a>.</p>",
+            "<figure id=\"fig:arch\">\n<img src=\"arch.png\" alt=\"System architecture\" loading=\"lazy\" />\n<figcaption>Figure 1: System architecture</figcaption>\n</figure>",
+            "<p>Inline <span id=\"fig:flow\"><img src=\"flow.png\" alt=\"flow\" loading=\"lazy\" /></span> and <a href=\"#x\">Figure 1</a>, <code>@fig:arch</code>, @fig:none.</p>",
+        ];
+
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                figures: Some(FigureOptions::default()),
+                ..RenderOptions::default()
+            };
+            let result = render(input, &options).unwrap();
+            for fragment in expected {
+                assert!(
+                    result.html.contains(fragment),
+                    "{backend:?}: missing {fragment:?} in {}",
+                    result.html
+                );
+            }
+            assert!(result.figure_collisions.is_empty());
+        }
+    }
+
+    #[test]
+    fn reports_duplicate_labels() {
+        let input = "![a](a.png){#fig:x}\n\n![b](b.png){#fig:x}\n\nSee @fig:x.";
+        let options = RenderOptions {
+            figures: Some(FigureOptions {
+                prefix: "Fig.".into(),
+            }),
+            ..RenderOptions::default()
+        };
+        let result = render(input, &options).unwrap();
+
+        assert_eq!(
+            result.figure_collisions,
+            [FigureLabelCollision {
+                label: "fig:x".into(),
+                first: 1,
+                duplicate: 2,
+            }]
+        );
+        assert_eq!(result.html.matches("id=\"fig:x\"").count(), 1);
+        assert!(result.html.contains("<figcaption>Fig. 2: b</figcaption>"));
+        assert!(result.html.contains("<a href=\"#fig:x\">Fig. 1</a>."));
+
+        let html = render(input, &RenderOptions::default()).unwrap().html;
+        assert!(html.contains("{#fig:x}"));
+    }
+    #[test]
+    fn numbers_figures_in_text_and_ast() {
+        let input = "![Chart](c.png){#fig:chart}\n\nAs @fig:chart shows.";
+        let options = RenderOptions {
+            figures: Some(FigureOptions::default()),
+            ..RenderOptions::default()
+        };
+
+        let text = render_text(input, &options).unwrap();
+        assert!(text.contains("As Figure 1 shows."), "{text:?}");
+        assert!(!text.contains("{#fig:chart}"), "{text:?}");
+
+        let tree = render_ast(input, &options).unwrap();
+        assert_eq!(tree.children[0].kind, crate::AstKind::Figure);
+        assert_eq!(tree.children[0].children[1].text(), "Figure 1: Chart");
+        assert_eq!(tree.children[1].children[1].text(), "Figure 1");
+        let mdast = crate::mdast::to_mdast(&tree);
+        assert_eq!(mdast["children"][0]["type"], "containerDirective");
+        assert_eq!(mdast["children"][0]["attributes"]["id"], "fig:chart");
+        assert_eq!(
+            mdast["children"][0]["children"][0]["children"][0]["value"],
+            "Figure 1: Chart"
+        );
+    }
+
+    #[test]
+    fn figures_survive_escaped_raw_html() {
+        let options = RenderOptions {
+            figures: Some(FigureOptions::default()),
+            html: crate::HtmlOptions {
+                escape_raw_html: true,
+                ..crate::HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render(
+            "![a <b>](a.png){#fig:a}\n\nInline ![b](b.png){#fig:b}",
+            &options,
+        )
+        .unwrap()
+        .html;
+        assert_eq!(
+            html,
+            "<figure id=\"fig:a\">\n<img src=\"a.png\" alt=\"a &lt;b&gt;\" loading=\"lazy\" />\n\
+             <figcaption>Figure 1: a &lt;b&gt;</figcaption>\n</figure>\n\
+             <p>Inline <span id=\"fig:b\"><img src=\"b.png\" alt=\"b\" loading=\"lazy\" /></span></p>\n"
+        );
+    }
+}
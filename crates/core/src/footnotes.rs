@@ -0,0 +1,410 @@
+//! Footnote layout transforms applied to the event stream before HTML rendering.
+
+#[cfg(feature = "footnotes")]
+use std::borrow::Cow;
+#[cfg(feature = "footnotes")]
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "footnotes")]
+use crate::event::{Event, Tag, TagEnd};
+
+/// Where footnote content ends up in the rendered HTML.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FootnoteStyle {
+    /// Definitions render as `<section class="footnote">` wherever they appear in the source.
+    #[default]
+    Definitions,
+    /// Each reference is followed by a Tufte-style `<span class="sidenote">` holding the
+    /// definition flattened to inline content, and the definitions themselves are removed from
+    /// the flow.
+    Sidenotes,
+    /// Referenced definitions are moved, in order of first reference, into a single
+    /// `<section class="footnotes">` at the end of the document; unreferenced ones are dropped.
+    Endnotes,
+    /// Like [`FootnoteStyle::Endnotes`], but laid out as cmark-gfm and GitHub do: notes are
+    /// renumbered from 1 in order of first reference, listed in
+    /// `<section class="footnotes" data-footnotes><ol>`, and end with `↩` links back to each
+    /// reference.
+    Gfm,
+}
+
+#[cfg(feature = "footnotes")]
+/// Splits `FootnoteDefinition` subtrees out of the stream, keyed by label, and returns the
+/// remaining events in order.
+fn take_definitions<'a, I>(events: I) -> (Vec<Event<'a>>, HashMap<String, Vec<Event<'a>>>)
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let mut events = events;
+    let mut body = Vec::new();
+    let mut definitions = HashMap::new();
+
+    while let Some(event) = events.next() {
+        let Event::Start(Tag::FootnoteDefinition(label)) = event else {
+            body.push(event);
+            continue;
+        };
+
+        let mut depth = 1usize;
+        let mut content = Vec::new();
+        for event in events.by_ref() {
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            content.push(event);
+        }
+        definitions.entry(label.into_owned()).or_insert(content);
+    }
+
+    (body, definitions)
+}
+
+#[cfg(feature = "footnotes")]
+/// Rewrites footnotes into sidenotes placed directly after each reference.
+///
+/// This buffers the whole stream because a definition may follow its reference.
+pub(crate) fn sidenotes<'a, I>(events: I) -> std::vec::IntoIter<Event<'a>>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let (body, definitions) = take_definitions(events);
+    let mut output = Vec::with_capacity(body.len());
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+
+    for event in body {
+        let Event::FootnoteReference(label) = &event else {
+            output.push(event);
+            continue;
+        };
+        let Some(content) = definitions.get(label.as_ref()) else {
+            output.push(event);
+            continue;
+        };
+
+        let seen = occurrences.entry(label.to_string()).or_default();
+        *seen += 1;
+        let tag = Tag::Sidenote {
+            label: label.clone(),
+            nth: *seen,
+        };
+        let end = tag.to_end();
+        output.push(Event::Start(tag));
+        push_inline_content(&mut output, content);
+        output.push(Event::End(end));
+    }
+
+    output.into_iter()
+}
+
+#[cfg(feature = "footnotes")]
+/// Moves footnote definitions to the end of the document, ordered by first reference.
+///
+/// This buffers the whole stream because every definition must be known before the body ends.
+pub(crate) fn endnotes<'a, I>(events: I) -> std::vec::IntoIter<Event<'a>>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let (mut output, mut definitions) = take_definitions(events);
+    let mut order = Vec::new();
+    for event in &output {
+        if let Event::FootnoteReference(label) = event
+            && let Some(content) = definitions.remove(label.as_ref())
+        {
+            order.push((label.to_string(), content));
+        }
+    }
+
+    if !order.is_empty() {
+        output.push(Event::Start(Tag::Endnotes));
+        for (label, content) in order {
+            let tag = Tag::FootnoteDefinition(Cow::Owned(label));
+            let end = tag.to_end();
+            output.push(Event::Start(tag));
+            output.extend(content);
+            output.push(Event::End(end));
+        }
+        output.push(Event::End(TagEnd::Endnotes));
+    }
+    output.into_iter()
+}
+
+#[cfg(feature = "footnotes")]
+/// Hoists footnotes into a numbered list with backlinks, as cmark-gfm renders them.
+///
+/// This buffers the whole stream because every definition must be known before the body ends.
+pub(crate) fn gfm<'a, I>(events: I) -> std::vec::IntoIter<Event<'a>>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    let (body, mut definitions) = take_definitions(events);
+    let mut output = Vec::with_capacity(body.len());
+    // Referenced notes in order of first reference, with how often each was referenced.
+    let mut notes: Vec<(Vec<Event<'a>>, usize)> = Vec::new();
+    let mut numbers: HashMap<String, usize> = HashMap::new();
+
+    for event in body {
+        let Event::FootnoteReference(label) = &event else {
+            output.push(event);
+            continue;
+        };
+        let number = match numbers.get(label.as_ref()) {
+            Some(&number) => number,
+            None => {
+                let Some(content) = definitions.remove(label.as_ref()) else {
+                    output.push(event);
+                    continue;
+                };
+                notes.push((content, 0));
+                numbers.insert(label.to_string(), notes.len());
+                notes.len()
+            }
+        };
+        let references = &mut notes[number - 1].1;
+        *references += 1;
+        let tag = Tag::NumberedFootnoteReference {
+            number,
+            nth: *references,
+        };
+        let end = tag.to_end();
+        output.extend([
+            Event::Start(tag),
+            Event::Text(Cow::Owned(number.to_string())),
+            Event::End(end),
+        ]);
+    }
+
+    if notes.is_empty() {
+        return output.into_iter();
+    }
+    output.push(Event::Start(Tag::FootnoteList));
+    for (idx, (mut content, references)) in notes.into_iter().enumerate() {
+        let number = idx + 1;
+        let backrefs = (1..=references).flat_map(|nth| {
+            let tag = Tag::FootnoteBackref { number, nth };
+            let end = tag.to_end();
+            [
+                Event::Text(Cow::Borrowed(" ")),
+                Event::Start(tag),
+                Event::End(end),
+            ]
+        });
+        output.push(Event::Start(Tag::FootnoteItem(number)));
+        if content.last() == Some(&Event::End(TagEnd::Paragraph)) {
+            let end = content.pop();
+            output.extend(content);
+            output.extend(backrefs);
+            output.extend(end);
+        } else {
+            output.extend(content);
+            output.push(Event::Start(Tag::Paragraph));
+            output.extend(backrefs.skip(1));
+            output.push(Event::End(TagEnd::Paragraph));
+        }
+        output.push(Event::End(TagEnd::FootnoteItem));
+    }
+    output.push(Event::End(TagEnd::FootnoteList));
+    output.into_iter()
+}
+
+#[cfg(feature = "footnotes")]
+/// Copies definition content flattened so the result is valid inside a `<span>`: block tags are
+/// dropped and the blocks they held are separated by line breaks, code blocks become inline code,
+/// display math becomes inline math, and raw HTML blocks are kept as inline HTML.
+fn push_inline_content<'a>(output: &mut Vec<Event<'a>>, content: &[Event<'a>]) {
+    // Whether each open tag was copied, so its end is copied or dropped with it.
+    let mut open = Vec::new();
+    let mut in_code_block = false;
+    let mut written = false;
+    let mut separate = false;
+    for event in content {
+        let event = match event {
+            Event::Start(tag) => {
+                let block = is_block(tag);
+                open.push(!block);
+                if block {
+                    in_code_block = matches!(tag, Tag::CodeBlock(_));
+                    separate = true;
+                    continue;
+                }
+                event.clone()
+            }
+            Event::End(_) => {
+                if open.pop().unwrap_or(false) {
+                    event.clone()
+                } else {
+                    in_code_block = false;
+                    separate = true;
+                    continue;
+                }
+            }
+            Event::Text(code) if in_code_block => {
+                Event::Code(Cow::Owned(code.trim_end_matches('\n').to_string()))
+            }
+            Event::Html(html) => Event::InlineHtml(html.clone()),
+            Event::DisplayMath(math) => Event::InlineMath(math.clone()),
+            Event::Rule => {
+                separate = true;
+                continue;
+            }
+            other => other.clone(),
+        };
+        if separate && written {
+            output.push(Event::HardBreak);
+        }
+        separate = false;
+        written = true;
+        output.push(event);
+    }
+}
+
+#[cfg(feature = "footnotes")]
+/// Tags that render as block elements, which a `<span>` must not contain.
+fn is_block(tag: &Tag<'_>) -> bool {
+    matches!(
+        tag,
+        Tag::Paragraph
+            | Tag::Heading { .. }
+            | Tag::BlockQuote
+            | Tag::CodeBlock(_)
+            | Tag::List(_)
+            | Tag::Item
+            | Tag::FootnoteDefinition(_)
+            | Tag::Endnotes
+            | Tag::FootnoteList
+            | Tag::FootnoteItem(_)
+            | Tag::Table(_)
+            | Tag::TableHead
+            | Tag::TableRow
+            | Tag::TableCell
+            | Tag::Details
+            | Tag::Summary
+            | Tag::Callout(_)
+            | Tag::Figure(_)
+            | Tag::FigureCaption
+    )
+}
+
+#[cfg(all(test, feature = "footnotes"))]
+mod tests {
+    use super::*;
+
+    fn text(value: &'static str) -> Event<'static> {
+        Event::Text(Cow::Borrowed(value))
+    }
+
+    #[test]
+    fn moves_definitions_next_to_references() {
+        let events = vec![
+            Event::Start(Tag::Paragraph),
+            text("Claim"),
+            Event::FootnoteReference(Cow::Borrowed("a")),
+            Event::FootnoteReference(Cow::Borrowed("missing")),
+            Event::End(TagEnd::Paragraph),
+            Event::Start(Tag::FootnoteDefinition(Cow::Borrowed("a"))),
+            Event::Start(Tag::Paragraph),
+            text("Source"),
+            Event::End(TagEnd::Paragraph),
+            Event::End(TagEnd::FootnoteDefinition),
+        ];
+
+        let output: Vec<_> = sidenotes(events.into_iter()).collect();
+
+        assert!(!output.iter().any(|event| matches!(
+            event,
+            Event::Start(Tag::FootnoteDefinition(_)) | Event::End(TagEnd::FootnoteDefinition)
+        )));
+        assert_eq!(
+            output[2],
+            Event::Start(Tag::Sidenote {
+                label: Cow::Borrowed("a"),
+                nth: 1,
+            })
+        );
+        assert_eq!(output[3], text("Source"));
+        assert_eq!(output[4], Event::End(TagEnd::Sidenote));
+        assert_eq!(
+            output[5],
+            Event::FootnoteReference(Cow::Borrowed("missing"))
+        );
+    }
+
+    #[test]
+    fn hoists_referenced_definitions_in_reference_order() {
+        let definition = |label: &'static str, body: &'static str| {
+            [
+                Event::Start(Tag::FootnoteDefinition(Cow::Borrowed(label))),
+                text(body),
+                Event::End(TagEnd::FootnoteDefinition),
+            ]
+        };
+        let mut events = definition("b", "B").to_vec();
+        events.extend(definition("unused", "U"));
+        events.extend([
+            Event::FootnoteReference(Cow::Borrowed("a")),
+            Event::FootnoteReference(Cow::Borrowed("b")),
+            Event::FootnoteReference(Cow::Borrowed("a")),
+        ]);
+        events.extend(definition("a", "A"));
+
+        let output: Vec<_> = endnotes(events.into_iter()).collect();
+
+        assert_eq!(output.len(), 3 + 1 + 3 + 3 + 1);
+        assert_eq!(output[3], Event::Start(Tag::Endnotes));
+        assert_eq!(output[4..7], definition("a", "A"));
+        assert_eq!(output[7..10], definition("b", "B"));
+        assert_eq!(output[10], Event::End(TagEnd::Endnotes));
+        assert!(!output.contains(&text("U")));
+    }
+
+    #[test]
+    fn renumbers_notes_with_backlinks() {
+        let input = "A[^z] b[^a] c[^z] d[^missing]\n\n[^a]: Second.\n\n[^z]: First.\n\n        code\n\n[^unused]: Gone.";
+        let options = crate::RenderOptions {
+            footnote_style: FootnoteStyle::Gfm,
+            ..crate::RenderOptions::default()
+        };
+        let html = crate::render(input, &options).unwrap().html;
+
+        let expected = "<p>A<sup class=\"footnote-ref\"><a href=\"#fn-1\" id=\"fnref-1\" data-footnote-ref>1</a></sup> \
+             b<sup class=\"footnote-ref\"><a href=\"#fn-2\" id=\"fnref-2\" data-footnote-ref>2</a></sup> \
+             c<sup class=\"footnote-ref\"><a href=\"#fn-1\" id=\"fnref-1-2\" data-footnote-ref>1</a></sup> \
+             d[^missing]</p>\n\
+             <section class=\"footnotes\" data-footnotes>\n<ol>\n<li id=\"fn-1\">\n<p>First.</p>\n\
+             <pre><code>code</code></pre>\n\
+             <p><a href=\"#fnref-1\" class=\"footnote-backref\" data-footnote-backref data-footnote-backref-idx=\"1\" aria-label=\"Back to reference 1\">↩</a> \
+             <a href=\"#fnref-1-2\" class=\"footnote-backref\" data-footnote-backref data-footnote-backref-idx=\"1-2\" aria-label=\"Back to reference 1-2\">↩<sup class=\"footnote-ref\">2</sup></a></p>\n\
+             </li>\n<li id=\"fn-2\">\n<p>Second. <a href=\"#fnref-2\" class=\"footnote-backref\" data-footnote-backref data-footnote-backref-idx=\"2\" aria-label=\"Back to reference 2\">↩</a></p>\n\
+             </li>\n</ol>\n</section>\n";
+        assert_eq!(html, expected);
+    }
+
+    #[test]
+    fn gfm_notes_survive_escaped_raw_html() {
+        let options = crate::RenderOptions {
+            footnote_style: FootnoteStyle::Gfm,
+            html: crate::HtmlOptions {
+                escape_raw_html: true,
+                ..crate::HtmlOptions::default()
+            },
+            ..crate::RenderOptions::default()
+        };
+        let html = crate::render("A[^a]\n\n[^a]: <b>B</b>", &options)
+            .unwrap()
+            .html;
+
+        let expected = "<p>A<sup class=\"footnote-ref\"><a href=\"#fn-1\" id=\"fnref-1\" data-footnote-ref>1</a></sup></p>\n\
+             <section class=\"footnotes\" data-footnotes>\n<ol>\n<li id=\"fn-1\">\n\
+             <p>&lt;b&gt;B&lt;/b&gt; <a href=\"#fnref-1\" class=\"footnote-backref\" data-footnote-backref data-footnote-backref-idx=\"1\" aria-label=\"Back to reference 1\">↩</a></p>\n\
+             </li>\n</ol>\n</section>\n";
+        assert_eq!(html, expected);
+    }
+}
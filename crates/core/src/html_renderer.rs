@@ -1,41 +1,345 @@
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::io::{self, Write};
+use std::ops::RangeInclusive;
 
-use crate::event::{Alignment, CodeBlockKind, Event, LinkType, Tag, TagEnd};
+use html_escape::encode_double_quoted_attribute;
+use serde::{Deserialize, Serialize};
+
+use crate::event::{Alignment, Event, Tag, TagEnd, jsx_open_tag};
+use crate::slug;
+use crate::urls::{self, DataUriPolicy};
+
+/// Rendering switches for [`HtmlRenderer`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HtmlOptions {
+    /// Tables with at least this many columns get `id`s on header cells and matching `headers`
+    /// attributes on data cells so screen readers can announce the column for each cell.
+    pub table_header_ids: Option<usize>,
+    /// Wrap each `<table>` in a `<div>` with this class (e.g. `table-wrapper`), which a
+    /// stylesheet can give `overflow-x: auto` so wide tables scroll on narrow screens.
+    pub table_wrapper_class: Option<String>,
+    /// Start each table with a `<colgroup>` holding one `<col>` per column, classed
+    /// `align-left`, `align-center`, or `align-right` from the delimiter row, and leave the
+    /// inline `text-align` style off the cells so stylesheets decide column alignment.
+    pub table_colgroup: bool,
+    /// Wrap code blocks whose meta contains `run` or `interactive` in
+    /// `<div class="runnable" data-runnable="…">` and append the raw source in a
+    /// `<script type="text/plain">` sibling for playground widgets, with `</` and `<!--` written
+    /// as `<\/` and `<\!--` so the source cannot end the script element.
+    pub runnable_snippets: bool,
+    /// Place task list checkboxes inside the item's first paragraph (`<li><p><input …/> …`), as
+    /// GitHub does, instead of before it. Only loose items are affected.
+    pub task_marker_in_paragraph: bool,
+    /// Render raw HTML blocks and inline tags as escaped text instead of passing them through.
+    pub escape_raw_html: bool,
+    /// Leave `<!-- comments -->` in raw HTML out of the output.
+    pub strip_comments: bool,
+    /// Replace link and image URLs with unsafe schemes (`javascript:`, `data:text/html`, ...)
+    /// by an empty string; see [`crate::urls`].
+    pub sanitize_urls: bool,
+    /// Which inline `data:` image sources are written; rejected ones become an empty string.
+    pub data_uri_images: DataUriPolicy,
+    /// Start each heading that has an id with a self-link,
+    /// `<a class="heading-anchor" href="#id" aria-hidden="true">#</a>`.
+    pub heading_anchor_links: bool,
+    /// Prepended to heading `id` attributes but not to self-link `href`s, the way GitHub writes
+    /// `user-content-` ids that its page script resolves plain `#fragment`s against.
+    pub heading_id_prefix: String,
+    /// Prepended to the footnote (`fn-`, `fnref-`) and table header ids the renderer generates,
+    /// and to the links pointing at them. [`render`](crate::render) puts
+    /// [`RenderOptions::id_prefix`](crate::RenderOptions::id_prefix) in front of it.
+    pub id_prefix: String,
+    /// Expose the info string after the language as `data-meta` on `<pre>` so client-side
+    /// highlighters can read titles and line ranges.
+    pub code_meta: bool,
+    /// Wrap each line of a code block in `<span class="line">`, adding `highlighted` to the
+    /// lines in the meta's `{1,3-5}` ranges, so stylesheets can number and highlight lines
+    /// without client-side script.
+    pub code_lines: bool,
+    /// Wrap code blocks in `<figure class="code-block" data-lang="…">` and repeat the raw code
+    /// in a trailing `<template class="code-source">`, so sites can attach copy buttons without
+    /// reading the code back out of the rendered markup.
+    pub code_figures: bool,
+    /// Fence languages (e.g. `mermaid`, `plantuml`, `graphviz`) whose blocks are written as
+    /// `<pre class="mermaid">` with the escaped source and no `<code>`, for client-side diagram
+    /// renderers to pick up. Other code block options don't apply to them.
+    pub diagram_languages: Vec<String>,
+    /// Canonical names for fence languages (`js` → `javascript`), applied before the language
+    /// reaches the `language-…` class and the other code block options, so highlighters see one
+    /// name per language. [`HtmlOptions::common_language_aliases`] is a starting point.
+    pub language_aliases: BTreeMap<String, String>,
+    /// Wrap each heading and the content up to the next heading of the same or a shallower level
+    /// in a `<section>`, nested by depth, and move the heading's id onto it. Headings inside
+    /// block quotes, lists, footnotes, and JSX elements don't open sections.
+    pub sections: bool,
+    /// Which images that fill their paragraph are written as `<figure>`, with the title (if any)
+    /// in a `<figcaption>` instead of a `title` attribute. Images sharing a paragraph with other
+    /// content stay bare, since a figure can't sit inside `<p>`.
+    pub image_figures: ImageFigures,
+    /// How MDX JSX element tags are written around their rendered children.
+    pub mdx_jsx: MdxJsxOutput,
+    /// What becomes of MDX `{expressions}`.
+    pub mdx_expressions: MdxExpressionOutput,
+    /// Link text for a `<a href="#fnref-N" class="footnote-backref">` back to the reference,
+    /// written at the end of each footnote definition (inside its last paragraph when it ends
+    /// with one). `None` writes no backlinks.
+    pub footnote_backref: Option<String>,
+}
+
+impl HtmlOptions {
+    /// Widespread short names mapped to the names highlighters such as Prism and Shiki use.
+    /// `jsx` and `tsx` are grammars of their own there, so they are not aliased.
+    pub fn common_language_aliases() -> BTreeMap<String, String> {
+        [
+            ("js", "javascript"),
+            ("ts", "typescript"),
+            ("py", "python"),
+            ("rb", "ruby"),
+            ("rs", "rust"),
+            ("sh", "bash"),
+            ("shell", "bash"),
+            ("zsh", "bash"),
+            ("yml", "yaml"),
+            ("md", "markdown"),
+            ("golang", "go"),
+        ]
+        .into_iter()
+        .map(|(alias, lang)| (alias.to_string(), lang.to_string()))
+        .collect()
+    }
+}
+
+/// Images rendered as `<figure>` by [`HtmlOptions::image_figures`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageFigures {
+    /// Write every image as a bare `<img>`.
+    #[default]
+    Off,
+    /// Images with a title, captioned with it.
+    Titled,
+    /// Every image alone in its paragraph, captioned with its title when it has one.
+    Standalone,
+}
+
+/// Output of MDX JSX element tags (`<Note kind="tip">`) in HTML.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MdxJsxOutput {
+    /// Write the tags as their JSX source, for a later JSX compile step.
+    #[default]
+    Verbatim,
+    /// Write the tags as escaped text so they show up literally in the page.
+    Escape,
+}
+
+/// Output of MDX expressions (`{props.title}`) in HTML.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MdxExpressionOutput {
+    /// Write the expression with its braces, for a later JSX compile step.
+    #[default]
+    Passthrough,
+    /// Keep the expression in an HTML comment, invisible but inspectable.
+    Comment,
+    /// Leave the expression out.
+    Drop,
+}
+
+/// Whether `name` is a tag or attribute name made of ASCII letters, digits, `-`, and `_`,
+/// starting with a letter.
+pub(crate) fn is_name(name: &str) -> bool {
+    name.starts_with(|ch: char| ch.is_ascii_alphabetic())
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+/// `<colgroup>` with a `<col>` per column, classed by alignment.
+fn colgroup(alignments: &[Alignment]) -> String {
+    let mut html = String::from("<colgroup>");
+    for alignment in alignments {
+        html.push_str(match alignment {
+            Alignment::None => "<col />",
+            Alignment::Left => "<col class=\"align-left\" />",
+            Alignment::Center => "<col class=\"align-center\" />",
+            Alignment::Right => "<col class=\"align-right\" />",
+        });
+    }
+    html.push_str("</colgroup>");
+    html
+}
+
+/// `1` for the first reference to note 1, `1-2` for the second, as cmark-gfm numbers them.
+fn reference_id(number: usize, nth: usize) -> String {
+    if nth == 1 {
+        number.to_string()
+    } else {
+        format!("{number}-{nth}")
+    }
+}
+
+/// `diff-add` or `diff-del` for a changed line of a unified diff; `+++`/`---` file headers are
+/// left unclassed.
+fn diff_class(line: &str) -> Option<&'static str> {
+    if line.starts_with("+++") || line.starts_with("---") {
+        return None;
+    }
+    match line.as_bytes().first() {
+        Some(b'+') => Some("diff-add"),
+        Some(b'-') => Some("diff-del"),
+        _ => None,
+    }
+}
+
+/// Meta flags that mark a fenced block as executable.
+const RUNNABLE_FLAGS: [&str; 2] = ["run", "interactive"];
 
 pub struct HtmlRenderer<W: Write> {
     writer: W,
+    options: HtmlOptions,
     table_head_depth: usize,
+    table_count: usize,
     table_stack: Vec<TableState>,
     image_stack: Vec<ImageContext>,
+    runnable: Option<RunnableSnippet>,
+    /// Code block whose text is held back to be written line by line at its end.
+    code_lines: Option<CodeLines>,
+    /// Raw source of the code block being rendered, for [`HtmlOptions::code_figures`].
+    code_figure: Option<String>,
+    /// Inside a block of one of the [`HtmlOptions::diagram_languages`].
+    in_diagram: bool,
+    /// Checkbox held back by `task_marker_in_paragraph` until the next event is known.
+    pending_task_marker: Option<bool>,
+    /// A `<p>` held back by [`HtmlOptions::image_figures`] until we know whether an image opens
+    /// the paragraph.
+    pending_paragraph: bool,
+    /// The image being rendered opened its paragraph and may become a figure.
+    figure_candidate: bool,
+    /// Finished image that opened its paragraph, held back until we know whether it also ends it.
+    pending_figure: Option<ImageContext>,
+    /// Closing tag of each open MDX JSX element; `None` for self-closing ones.
+    jsx_stack: Vec<Option<String>>,
+    /// Labels of the open footnote definitions.
+    footnote_stack: Vec<String>,
+    /// A footnote paragraph's `</p>` held back until we know whether the backlink goes in it.
+    pending_footnote_paragraph: bool,
+    /// Open block quotes and lists, inside which headings don't open sections.
+    container_depth: usize,
+    /// Heading level of each open [`HtmlOptions::sections`] section, outermost first.
+    sections: Vec<u8>,
+}
+
+/// Source of a code block written line by line, for [`HtmlOptions::code_lines`] or a `diff`
+/// block.
+struct CodeLines {
+    /// Wrap every line in `<span class="line">`.
+    wrap: bool,
+    /// Class `+` and `-` lines as `diff-add` and `diff-del`.
+    diff: bool,
+    highlight: Vec<RangeInclusive<u32>>,
+    source: String,
+}
+
+/// Raw source of the executable code block being rendered.
+struct RunnableSnippet {
+    lang: Option<String>,
+    source: String,
 }
 
 struct TableState {
     alignments: Vec<Alignment>,
     column_index: usize,
+    /// Document-wide table number used to namespace header ids, when header association is on.
+    header_ids: Option<usize>,
+    /// Whether `<tbody>` has been opened for the body rows.
+    in_body: bool,
 }
 
 struct ImageContext {
     dest_url: String,
     title: String,
     alt: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    attrs: Vec<(String, String)>,
 }
 
 impl<W: Write> HtmlRenderer<W> {
     pub fn new(writer: W) -> Self {
+        Self::with_options(writer, HtmlOptions::default())
+    }
+
+    pub fn with_options(writer: W, options: HtmlOptions) -> Self {
         Self {
             writer,
+            options,
             table_head_depth: 0,
+            table_count: 0,
             table_stack: Vec::new(),
             image_stack: Vec::new(),
+            runnable: None,
+            code_lines: None,
+            code_figure: None,
+            in_diagram: false,
+            pending_task_marker: None,
+            pending_paragraph: false,
+            figure_candidate: false,
+            pending_figure: None,
+            jsx_stack: Vec::new(),
+            footnote_stack: Vec::new(),
+            pending_footnote_paragraph: false,
+            container_depth: 0,
+            sections: Vec::new(),
         }
     }
 
     pub fn render<'a, I>(mut self, iter: I) -> io::Result<W>
+    where
+        I: IntoIterator<Item = Event<'a>>,
+    {
+        self.render_events(iter)?;
+        self.close_sections(0)?;
+        Ok(self.writer)
+    }
+
+    /// Renders `iter` while keeping the renderer, so a document can be fed in pieces.
+    pub(crate) fn render_events<'a, I>(&mut self, iter: I) -> io::Result<()>
     where
         I: IntoIterator<Item = Event<'a>>,
     {
         for event in iter.into_iter() {
+            if let Some(done) = self.pending_task_marker.take() {
+                // Loose items open a paragraph right after the marker; put the checkbox inside.
+                if matches!(event, Event::Start(Tag::Paragraph)) {
+                    self.writer.write_all(b"<p>")?;
+                    self.write_task_marker(done)?;
+                    continue;
+                }
+                self.write_task_marker(done)?;
+            }
+            if std::mem::take(&mut self.pending_footnote_paragraph) {
+                if matches!(event, Event::End(TagEnd::FootnoteDefinition)) {
+                    self.write_footnote_backref()?;
+                    self.writer.write_all(b"</p>\n</section>\n")?;
+                    self.footnote_stack.pop();
+                    continue;
+                }
+                self.writer.write_all(b"</p>\n")?;
+            }
+            if std::mem::take(&mut self.pending_paragraph) {
+                if matches!(event, Event::Start(Tag::Image { .. })) {
+                    self.figure_candidate = true;
+                } else {
+                    self.writer.write_all(b"<p>")?;
+                }
+            }
+            if let Some(image) = self.pending_figure.take() {
+                if matches!(event, Event::End(TagEnd::Paragraph)) {
+                    self.write_figure(&image)?;
+                    continue;
+                }
+                self.writer.write_all(b"<p>")?;
+                self.write_img(&image, true)?;
+            }
             if self.handle_image_text(&event) {
                 continue;
             }
@@ -43,13 +347,25 @@ impl<W: Write> HtmlRenderer<W> {
             match event {
                 Event::Start(tag) => {
                     if let Tag::Image {
-                        link_type,
                         dest_url,
                         title,
-                        id,
+                        width,
+                        height,
+                        attrs,
+                        ..
                     } = tag
                     {
-                        self.start_image(link_type, dest_url, title, id);
+                        self.image_stack.push(ImageContext {
+                            dest_url: dest_url.into_owned(),
+                            title: title.into_owned(),
+                            alt: String::new(),
+                            width,
+                            height,
+                            attrs: attrs
+                                .into_iter()
+                                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                                .collect(),
+                        });
                     } else {
                         self.write_start_tag(tag)?;
                     }
@@ -62,7 +378,17 @@ impl<W: Write> HtmlRenderer<W> {
                     }
                 }
                 Event::Text(text) => {
-                    self.write_text(text.as_ref())?;
+                    if let Some(snippet) = self.runnable.as_mut() {
+                        snippet.source.push_str(text.as_ref());
+                    }
+                    if let Some(source) = self.code_figure.as_mut() {
+                        source.push_str(text.as_ref());
+                    }
+                    if let Some(lines) = self.code_lines.as_mut() {
+                        lines.source.push_str(text.as_ref());
+                    } else {
+                        self.write_text(text.as_ref())?;
+                    }
                 }
                 Event::Code(text) => {
                     self.writer.write_all(b"<code>")?;
@@ -70,7 +396,16 @@ impl<W: Write> HtmlRenderer<W> {
                     self.writer.write_all(b"</code>")?;
                 }
                 Event::Html(html) | Event::InlineHtml(html) => {
-                    self.writer.write_all(html.as_ref().as_bytes())?;
+                    let html = if self.options.strip_comments {
+                        Cow::Owned(self.strip_comments(&html))
+                    } else {
+                        html
+                    };
+                    if self.options.escape_raw_html {
+                        self.escape_html(html.as_ref())?;
+                    } else {
+                        self.writer.write_all(html.as_ref().as_bytes())?;
+                    }
                 }
                 Event::InlineMath(math) => {
                     self.writer.write_all(b"<span class=\"math-inline\">")?;
@@ -83,19 +418,20 @@ impl<W: Write> HtmlRenderer<W> {
                     self.writer.write_all(b"</div>")?;
                 }
                 Event::FootnoteReference(label) => {
+                    let prefix = encode_double_quoted_attribute(&self.options.id_prefix);
+                    let id = encode_double_quoted_attribute(label.as_ref());
                     write!(
                         self.writer,
-                        "<sup class=\"footnote-ref\"><a href=\"#fn-{0}\" id=\"fnref-{0}\">{0}</a></sup>",
-                        label.as_ref()
+                        "<sup class=\"footnote-ref\"><a href=\"#{prefix}fn-{id}\" id=\"{prefix}fnref-{id}\">"
                     )?;
+                    self.escape_html(label.as_ref())?;
+                    self.writer.write_all(b"</a></sup>")?;
                 }
                 Event::TaskListMarker(done) => {
-                    if done {
-                        self.writer
-                            .write_all(b"<input type=\"checkbox\" disabled=\"\" checked=\"\" />")?;
+                    if self.options.task_marker_in_paragraph {
+                        self.pending_task_marker = Some(done);
                     } else {
-                        self.writer
-                            .write_all(b"<input type=\"checkbox\" disabled=\"\" />")?;
+                        self.write_task_marker(done)?;
                     }
                 }
                 Event::Rule => {
@@ -107,14 +443,151 @@ impl<W: Write> HtmlRenderer<W> {
                 Event::SoftBreak => {
                     self.writer.write_all(b"\n")?;
                 }
+                Event::MdxExpression(expression) => self.write_mdx_expression(&expression)?,
             }
         }
 
-        Ok(self.writer)
+        Ok(())
+    }
+
+    /// `html` without its comments.
+    fn strip_comments(&self, html: &str) -> String {
+        let mut kept = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(start) = rest.find("<!--") {
+            kept.push_str(&rest[..start]);
+            rest = &rest[start + 4..];
+            // `<!-->` and `<!--->` are empty comments, closed as browsers close them. Both
+            // adapters report each HTML block and inline tag as one event, so an unclosed comment
+            // ends with its event instead of swallowing the blocks after it.
+            let end = if rest.starts_with('>') {
+                1
+            } else if rest.starts_with("->") {
+                2
+            } else {
+                rest.find("-->").map_or(rest.len(), |end| end + 3)
+            };
+            rest = &rest[end..];
+        }
+        kept.push_str(rest);
+        kept
+    }
+
+    /// Writes the backlink for the innermost open footnote definition, if backlinks are on.
+    fn write_footnote_backref(&mut self) -> io::Result<()> {
+        let (Some(text), Some(label)) = (
+            self.options.footnote_backref.clone(),
+            self.footnote_stack.last().cloned(),
+        ) else {
+            return Ok(());
+        };
+        let prefix = encode_double_quoted_attribute(&self.options.id_prefix);
+        let label = encode_double_quoted_attribute(&label);
+        write!(
+            self.writer,
+            " <a href=\"#{prefix}fnref-{label}\" class=\"footnote-backref\">"
+        )?;
+        self.escape_html(&text)?;
+        self.writer.write_all(b"</a>")
+    }
+
+    /// Writes each line of `lines` in a `<span>` carrying its line, diff, and highlight
+    /// classes; unclassed lines are written bare.
+    fn write_code_lines(&mut self, lines: &CodeLines) -> io::Result<()> {
+        let source = lines.source.strip_suffix('\n').unwrap_or(&lines.source);
+        for (idx, line) in source.split('\n').enumerate() {
+            if idx > 0 {
+                self.writer.write_all(b"\n")?;
+            }
+            let mut classes = Vec::new();
+            if lines.wrap {
+                classes.push("line");
+            }
+            if lines.diff {
+                classes.extend(diff_class(line));
+            }
+            let number = idx as u32 + 1;
+            if lines.highlight.iter().any(|range| range.contains(&number)) {
+                classes.push("highlighted");
+            }
+            if classes.is_empty() {
+                self.escape_html(line)?;
+                continue;
+            }
+            write!(self.writer, "<span class=\"{}\">", classes.join(" "))?;
+            self.escape_html(line)?;
+            self.writer.write_all(b"</span>")?;
+        }
+        if source.len() < lines.source.len() {
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn write_task_marker(&mut self, done: bool) -> io::Result<()> {
+        if done {
+            self.writer
+                .write_all(b"<input type=\"checkbox\" disabled=\"\" checked=\"\" />")
+        } else {
+            self.writer
+                .write_all(b"<input type=\"checkbox\" disabled=\"\" />")
+        }
+    }
+
+    pub(crate) fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Number of tables rendered so far, which namespaces table header ids.
+    pub(crate) fn table_count(&self) -> usize {
+        self.table_count
+    }
+
+    /// Accounts for tables whose HTML was produced elsewhere (e.g. served from a cache).
+    pub(crate) fn skip_tables(&mut self, count: usize) {
+        self.table_count += count;
+    }
+
+    /// Closes the open sections at `level` or deeper; `0` closes them all at the end of the
+    /// document.
+    pub(crate) fn close_sections(&mut self, level: u8) -> io::Result<()> {
+        while self.sections.last().is_some_and(|&open| open >= level) {
+            self.sections.pop();
+            self.writer.write_all(b"</section>\n")?;
+        }
+        Ok(())
+    }
+
+    /// Writes a heading's `id` attribute, with [`HtmlOptions::heading_id_prefix`].
+    ///
+    /// Both parser backends get their ids here, from the slugs already on the events. HTML that
+    /// did not come from this renderer gets the same ids from a pre-collected list through
+    /// [`RewriteOptions::heading_ids`](crate::RewriteOptions::heading_ids).
+    fn write_heading_id(&mut self, id: Option<&str>) -> io::Result<()> {
+        match id {
+            Some(id) if !self.options.heading_id_prefix.is_empty() => {
+                let prefixed = format!("{}{id}", self.options.heading_id_prefix);
+                self.write_attr("id", &prefixed)
+            }
+            Some(id) => self.write_attr("id", id),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether a heading starting now opens a section.
+    fn opens_section(&self) -> bool {
+        self.options.sections
+            && self.container_depth == 0
+            && self.footnote_stack.is_empty()
+            && self.jsx_stack.is_empty()
     }
 
     fn write_start_tag(&mut self, tag: Tag<'_>) -> io::Result<()> {
         match tag {
+            Tag::Paragraph if self.options.image_figures != ImageFigures::Off => {
+                self.pending_paragraph = true;
+                Ok(())
+            }
             Tag::Paragraph => self.writer.write_all(b"<p>"),
             Tag::Heading {
                 level,
@@ -122,9 +595,17 @@ impl<W: Write> HtmlRenderer<W> {
                 classes,
                 attrs,
             } => {
+                let section = self.opens_section();
+                if section {
+                    self.close_sections(level as u8)?;
+                    self.sections.push(level as u8);
+                    self.writer.write_all(b"<section")?;
+                    self.write_heading_id(id.as_deref())?;
+                    self.writer.write_all(b">\n")?;
+                }
                 write!(self.writer, "<h{}", level as u8)?;
-                if let Some(id) = id {
-                    self.write_attr("id", id.as_ref())?;
+                if !section {
+                    self.write_heading_id(id.as_deref())?;
                 }
                 if !classes.is_empty() {
                     self.writer.write_all(b" class=\"")?;
@@ -136,25 +617,141 @@ impl<W: Write> HtmlRenderer<W> {
                     }
                     self.writer.write_all(b"\"")?;
                 }
+                let mut number = None;
                 for (key, value) in attrs {
-                    if let Some(value) = value {
-                        self.write_attr(key.as_ref(), value.as_ref())?;
-                    } else {
-                        write!(self.writer, " {}", key.as_ref())?;
+                    self.write_author_attr(key.as_ref(), value.as_deref())?;
+                    if key == slug::NUMBER_ATTR {
+                        number = value;
                     }
                 }
+                self.writer.write_all(b">")?;
+                if self.options.heading_anchor_links
+                    && let Some(id) = id
+                {
+                    self.writer
+                        .write_all(b"<a class=\"heading-anchor\" href=\"#")?;
+                    self.escape_attr(id.as_ref())?;
+                    self.writer.write_all(b"\" aria-hidden=\"true\">#</a>")?;
+                }
+                if let Some(number) = number {
+                    self.writer.write_all(b"<span class=\"heading-number\">")?;
+                    self.escape_html(number.as_ref())?;
+                    self.writer.write_all(b"</span> ")?;
+                }
+                Ok(())
+            }
+            Tag::BlockQuote => {
+                self.container_depth += 1;
+                self.writer.write_all(b"<blockquote>")
+            }
+            Tag::Details => {
+                self.container_depth += 1;
+                self.writer.write_all(b"<details>")
+            }
+            Tag::Summary => self.writer.write_all(b"<summary>"),
+            Tag::Figure(id) => {
+                self.writer.write_all(b"<figure")?;
+                if let Some(id) = id {
+                    self.write_attr("id", &id)?;
+                }
+                self.writer.write_all(b">\n")
+            }
+            Tag::FigureCaption => self.writer.write_all(b"\n<figcaption>"),
+            Tag::InlineFigure(id) => {
+                self.writer.write_all(b"<span")?;
+                if let Some(id) = id {
+                    self.write_attr("id", &id)?;
+                }
                 self.writer.write_all(b">")
             }
-            Tag::BlockQuote => self.writer.write_all(b"<blockquote>"),
-            Tag::CodeBlock(kind) => match kind {
-                CodeBlockKind::Indented => self.writer.write_all(b"<pre><code>"),
-                CodeBlockKind::Fenced(lang) => {
-                    self.writer.write_all(b"<pre><code class=\"language-")?;
-                    self.escape_html(lang.as_ref())?;
-                    self.writer.write_all(b"\">")
+            Tag::Callout(kind) => {
+                self.container_depth += 1;
+                writeln!(
+                    self.writer,
+                    "<div class=\"markdown-alert markdown-alert-{}\">\
+                     <p class=\"markdown-alert-title\">{}</p>",
+                    kind.name(),
+                    kind.title()
+                )
+            }
+            Tag::CodeBlock(kind) => {
+                let lang = kind.lang().map(|lang| {
+                    self.options
+                        .language_aliases
+                        .get(lang)
+                        .map_or(lang, String::as_str)
+                        .to_string()
+                });
+                if let Some(lang) = &lang
+                    && self
+                        .options
+                        .diagram_languages
+                        .iter()
+                        .any(|diagram| diagram == lang)
+                {
+                    self.in_diagram = true;
+                    self.writer.write_all(b"<pre")?;
+                    self.write_attr("class", lang)?;
+                    return self.writer.write_all(b">");
                 }
-            },
+                if self.options.code_figures {
+                    self.writer.write_all(b"<figure class=\"code-block\"")?;
+                    if let Some(lang) = &lang {
+                        self.write_attr("data-lang", lang)?;
+                    }
+                    self.writer.write_all(b">")?;
+                    self.code_figure = Some(String::new());
+                }
+                if self.options.runnable_snippets
+                    && let Some(flag) = RUNNABLE_FLAGS.iter().find(|flag| kind.has_flag(flag))
+                {
+                    write!(
+                        self.writer,
+                        "<div class=\"runnable\" data-runnable=\"{flag}\">"
+                    )?;
+                    self.runnable = Some(RunnableSnippet {
+                        lang: lang.clone(),
+                        source: String::new(),
+                    });
+                }
+                let diff = lang.as_deref() == Some("diff");
+                if self.options.code_lines || diff {
+                    self.code_lines = Some(CodeLines {
+                        wrap: self.options.code_lines,
+                        diff,
+                        highlight: kind
+                            .code_meta()
+                            .filter(|_| self.options.code_lines)
+                            .map(|meta| meta.highlight.clone())
+                            .unwrap_or_default(),
+                        source: String::new(),
+                    });
+                }
+                self.writer.write_all(b"<pre")?;
+                for (key, value) in kind
+                    .code_meta()
+                    .into_iter()
+                    .flat_map(|meta| &meta.block_attrs)
+                {
+                    self.write_author_attr(key, value.as_deref())?;
+                }
+                if self.options.code_meta
+                    && let Some(meta) = kind.meta()
+                {
+                    self.write_attr("data-meta", meta)?;
+                }
+                self.writer.write_all(b">")?;
+                match &lang {
+                    Some(lang) => {
+                        self.writer.write_all(b"<code class=\"language-")?;
+                        self.escape_html(lang)?;
+                        self.writer.write_all(b"\">")
+                    }
+                    None => self.writer.write_all(b"<code>"),
+                }
+            }
             Tag::List(start) => {
+                self.container_depth += 1;
                 if let Some(idx) = start {
                     write!(self.writer, "<ol start=\"{}\">", idx)
                 } else {
@@ -163,17 +760,83 @@ impl<W: Write> HtmlRenderer<W> {
             }
             Tag::Item => self.writer.write_all(b"<li>"),
             Tag::FootnoteDefinition(label) => {
+                self.footnote_stack.push(label.to_string());
+                let prefix = encode_double_quoted_attribute(&self.options.id_prefix);
+                let label = encode_double_quoted_attribute(&label);
+                write!(
+                    self.writer,
+                    "<section class=\"footnote\" id=\"{prefix}fn-{label}\">"
+                )
+            }
+            Tag::Endnotes => self.writer.write_all(b"<section class=\"footnotes\">\n"),
+            Tag::FootnoteList => self
+                .writer
+                .write_all(b"<section class=\"footnotes\" data-footnotes>\n<ol>\n"),
+            Tag::FootnoteItem(number) => {
+                let prefix = encode_double_quoted_attribute(&self.options.id_prefix);
+                writeln!(self.writer, "<li id=\"{prefix}fn-{number}\">")
+            }
+            Tag::NumberedFootnoteReference { number, nth } => {
+                let prefix = encode_double_quoted_attribute(&self.options.id_prefix);
+                let id = reference_id(number, nth);
+                write!(
+                    self.writer,
+                    "<sup class=\"footnote-ref\"><a href=\"#{prefix}fn-{number}\" id=\"{prefix}fnref-{id}\" data-footnote-ref>"
+                )
+            }
+            Tag::FootnoteBackref { number, nth } => {
+                let prefix = encode_double_quoted_attribute(&self.options.id_prefix);
+                let id = reference_id(number, nth);
+                let marker = if nth > 1 {
+                    format!("<sup class=\"footnote-ref\">{nth}</sup>")
+                } else {
+                    String::new()
+                };
                 write!(
                     self.writer,
-                    "<section class=\"footnote\" id=\"fn-{label}\">"
+                    "<a href=\"#{prefix}fnref-{id}\" class=\"footnote-backref\" data-footnote-backref \
+                     data-footnote-backref-idx=\"{id}\" aria-label=\"Back to reference {id}\">↩{marker}</a>"
+                )
+            }
+            Tag::Sidenote { label, nth } => {
+                let prefix = encode_double_quoted_attribute(&self.options.id_prefix);
+                let mut id = format!("{prefix}sn-{}", encode_double_quoted_attribute(&label));
+                if nth > 1 {
+                    id.push_str(&format!("-{nth}"));
+                }
+                write!(
+                    self.writer,
+                    "<label for=\"{id}\" class=\"margin-toggle sidenote-number\"></label>\
+                     <input type=\"checkbox\" id=\"{id}\" class=\"margin-toggle\" />\
+                     <span class=\"sidenote\">"
                 )
             }
             Tag::Table(alignments) => {
+                self.table_count += 1;
+                let header_ids = self
+                    .options
+                    .table_header_ids
+                    .filter(|min_columns| alignments.len() >= *min_columns)
+                    .map(|_| self.table_count);
+                let colgroup = self.options.table_colgroup.then(|| colgroup(&alignments));
                 self.table_stack.push(TableState {
                     alignments,
                     column_index: 0,
+                    header_ids,
+                    in_body: false,
                 });
-                self.writer.write_all(b"<table>")
+                if let Some(class) = &self.options.table_wrapper_class {
+                    write!(
+                        self.writer,
+                        "<div class=\"{}\">",
+                        encode_double_quoted_attribute(class)
+                    )?;
+                }
+                self.writer.write_all(b"<table>")?;
+                match colgroup {
+                    Some(colgroup) => self.writer.write_all(colgroup.as_bytes()),
+                    None => Ok(()),
+                }
             }
             Tag::TableHead => {
                 self.table_head_depth += 1;
@@ -182,6 +845,10 @@ impl<W: Write> HtmlRenderer<W> {
             Tag::TableRow => {
                 if let Some(state) = self.table_stack.last_mut() {
                     state.column_index = 0;
+                    if self.table_head_depth == 0 && !state.in_body {
+                        state.in_body = true;
+                        self.writer.write_all(b"<tbody>\n")?;
+                    }
                 }
                 self.writer.write_all(b"<tr>")
             }
@@ -193,10 +860,28 @@ impl<W: Write> HtmlRenderer<W> {
                 };
                 self.writer.write_all(b"<")?;
                 self.writer.write_all(tag)?;
-                if let Some(state) = self.table_stack.last_mut()
-                    && let Some(alignment) = state.alignments.get(state.column_index)
-                {
-                    if !matches!(alignment, Alignment::None) {
+                if let Some(state) = self.table_stack.last_mut() {
+                    let column = state.column_index;
+                    state.column_index += 1;
+                    if let Some(table) = state.header_ids
+                        && column < state.alignments.len()
+                    {
+                        let attr = if self.table_head_depth > 0 {
+                            "id"
+                        } else {
+                            "headers"
+                        };
+                        let prefix = encode_double_quoted_attribute(&self.options.id_prefix);
+                        write!(
+                            self.writer,
+                            " {attr}=\"{prefix}table-{table}-col-{}\"",
+                            column + 1
+                        )?;
+                    }
+                    if !self.options.table_colgroup
+                        && let Some(alignment) = state.alignments.get(column)
+                        && !matches!(alignment, Alignment::None)
+                    {
                         self.writer.write_all(b" style=\"text-align:")?;
                         self.writer.write_all(match alignment {
                             Alignment::Left => b"left",
@@ -206,18 +891,26 @@ impl<W: Write> HtmlRenderer<W> {
                         })?;
                         self.writer.write_all(b"\"")?;
                     }
-                    state.column_index += 1;
                 }
                 self.writer.write_all(b">")
             }
             Tag::Emphasis => self.writer.write_all(b"<em>"),
             Tag::Strong => self.writer.write_all(b"<strong>"),
             Tag::Strikethrough => self.writer.write_all(b"<del>"),
+            Tag::Highlight => self.writer.write_all(b"<mark>"),
+            Tag::Superscript => self.writer.write_all(b"<sup>"),
+            Tag::Keyboard => self.writer.write_all(b"<kbd>"),
+            Tag::HeadingNumber => self.writer.write_all(b"<span class=\"heading-number\">"),
+            Tag::Ruby => self.writer.write_all(b"<ruby>"),
+            Tag::RubyText => self.writer.write_all(b"<rt>"),
+            Tag::Subscript => self.writer.write_all(b"<sub>"),
             Tag::Link {
                 dest_url, title, ..
             } => {
                 self.writer.write_all(b"<a href=\"")?;
-                self.escape_attr(dest_url.as_ref())?;
+                if !self.options.sanitize_urls || urls::is_safe_link(&dest_url) {
+                    self.escape_attr(dest_url.as_ref())?;
+                }
                 self.writer.write_all(b"\"")?;
                 if !title.is_empty() {
                     self.writer.write_all(b" title=\"")?;
@@ -227,16 +920,78 @@ impl<W: Write> HtmlRenderer<W> {
                 self.writer.write_all(b">")
             }
             Tag::Image { .. } => unreachable!("image handled separately"),
+            Tag::MdxJsxElement {
+                name,
+                attributes,
+                self_closing,
+            } => {
+                let open = jsx_open_tag(name.as_deref(), &attributes, self_closing);
+                self.jsx_stack.push(
+                    (!self_closing).then(|| format!("</{}>", name.as_deref().unwrap_or_default())),
+                );
+                self.write_jsx(&open)
+            }
         }
     }
 
     fn write_end_tag(&mut self, end: TagEnd) -> io::Result<()> {
         match end {
+            TagEnd::Paragraph
+                if self.options.footnote_backref.is_some() && !self.footnote_stack.is_empty() =>
+            {
+                self.pending_footnote_paragraph = true;
+                Ok(())
+            }
             TagEnd::Paragraph => self.writer.write_all(b"</p>\n"),
             TagEnd::Heading(level) => writeln!(self.writer, "</h{}>", level as u8),
-            TagEnd::BlockQuote => self.writer.write_all(b"</blockquote>\n"),
-            TagEnd::CodeBlock => self.writer.write_all(b"</code></pre>\n"),
+            TagEnd::BlockQuote => {
+                self.container_depth = self.container_depth.saturating_sub(1);
+                self.writer.write_all(b"</blockquote>\n")
+            }
+            TagEnd::Details => {
+                self.container_depth = self.container_depth.saturating_sub(1);
+                self.writer.write_all(b"</details>\n")
+            }
+            TagEnd::Summary => self.writer.write_all(b"</summary>\n"),
+            TagEnd::Figure => self.writer.write_all(b"</figure>\n"),
+            TagEnd::FigureCaption => self.writer.write_all(b"</figcaption>\n"),
+            TagEnd::InlineFigure => self.writer.write_all(b"</span>"),
+            TagEnd::Callout => {
+                self.container_depth = self.container_depth.saturating_sub(1);
+                self.writer.write_all(b"</div>\n")
+            }
+            TagEnd::CodeBlock => {
+                if std::mem::take(&mut self.in_diagram) {
+                    return self.writer.write_all(b"</pre>\n");
+                }
+                if let Some(lines) = self.code_lines.take() {
+                    self.write_code_lines(&lines)?;
+                }
+                self.writer.write_all(b"</code></pre>")?;
+                if let Some(snippet) = self.runnable.take() {
+                    self.writer.write_all(b"<script type=\"text/plain\"")?;
+                    if let Some(lang) = snippet.lang {
+                        self.write_attr("data-lang", &lang)?;
+                    }
+                    self.writer.write_all(b">")?;
+                    // Script content is raw text: any `</` could close the element in whatever
+                    // case or spacing, and `<!--` changes how the closing tag is found.
+                    let source = snippet
+                        .source
+                        .replace("</", "<\\/")
+                        .replace("<!--", "<\\!--");
+                    self.writer.write_all(source.as_bytes())?;
+                    self.writer.write_all(b"</script></div>")?;
+                }
+                if let Some(source) = self.code_figure.take() {
+                    self.writer.write_all(b"<template class=\"code-source\">")?;
+                    self.escape_html(&source)?;
+                    self.writer.write_all(b"</template></figure>")?;
+                }
+                self.writer.write_all(b"\n")
+            }
             TagEnd::List(ordered) => {
+                self.container_depth = self.container_depth.saturating_sub(1);
                 if ordered {
                     self.writer.write_all(b"</ol>\n")
                 } else {
@@ -244,10 +999,26 @@ impl<W: Write> HtmlRenderer<W> {
                 }
             }
             TagEnd::Item => self.writer.write_all(b"</li>"),
-            TagEnd::FootnoteDefinition => self.writer.write_all(b"</section>\n"),
+            TagEnd::FootnoteDefinition => {
+                self.write_footnote_backref()?;
+                self.footnote_stack.pop();
+                self.writer.write_all(b"</section>\n")
+            }
+            TagEnd::Endnotes => self.writer.write_all(b"</section>\n"),
+            TagEnd::FootnoteList => self.writer.write_all(b"</ol>\n</section>\n"),
+            TagEnd::FootnoteItem => self.writer.write_all(b"</li>\n"),
+            TagEnd::NumberedFootnoteReference => self.writer.write_all(b"</a></sup>"),
+            TagEnd::FootnoteBackref => Ok(()),
+            TagEnd::Sidenote => self.writer.write_all(b"</span>"),
             TagEnd::Table => {
-                self.table_stack.pop();
-                self.writer.write_all(b"</table>\n")
+                if self.table_stack.pop().is_some_and(|state| state.in_body) {
+                    self.writer.write_all(b"</tbody>\n")?;
+                }
+                self.writer.write_all(b"</table>\n")?;
+                if self.options.table_wrapper_class.is_some() {
+                    self.writer.write_all(b"</div>\n")?;
+                }
+                Ok(())
             }
             TagEnd::TableHead => {
                 self.table_head_depth = self.table_head_depth.saturating_sub(1);
@@ -267,8 +1038,46 @@ impl<W: Write> HtmlRenderer<W> {
             TagEnd::Emphasis => self.writer.write_all(b"</em>"),
             TagEnd::Strong => self.writer.write_all(b"</strong>"),
             TagEnd::Strikethrough => self.writer.write_all(b"</del>"),
+            TagEnd::Highlight => self.writer.write_all(b"</mark>"),
+            TagEnd::Superscript => self.writer.write_all(b"</sup>"),
+            TagEnd::Keyboard => self.writer.write_all(b"</kbd>"),
+            TagEnd::HeadingNumber => self.writer.write_all(b"</span> "),
+            TagEnd::Ruby => self.writer.write_all(b"</ruby>"),
+            TagEnd::RubyText => self.writer.write_all(b"</rt>"),
+            TagEnd::Subscript => self.writer.write_all(b"</sub>"),
             TagEnd::Link => self.writer.write_all(b"</a>"),
             TagEnd::Image => unreachable!("image handled separately"),
+            TagEnd::MdxJsxElement => match self.jsx_stack.pop().flatten() {
+                Some(close) => self.write_jsx(&close),
+                None => Ok(()),
+            },
+        }
+    }
+
+    fn write_mdx_expression(&mut self, expression: &str) -> io::Result<()> {
+        match self.options.mdx_expressions {
+            MdxExpressionOutput::Passthrough => write!(self.writer, "{{{expression}}}"),
+            MdxExpressionOutput::Comment => {
+                // `--` may not appear inside a comment; a space keeps `-->` from ending it early.
+                let mut body = String::with_capacity(expression.len());
+                let mut previous = None;
+                for ch in expression.chars() {
+                    if ch == '-' && previous == Some('-') {
+                        body.push(' ');
+                    }
+                    body.push(ch);
+                    previous = Some(ch);
+                }
+                write!(self.writer, "<!-- {{{body}}} -->")
+            }
+            MdxExpressionOutput::Drop => Ok(()),
+        }
+    }
+
+    fn write_jsx(&mut self, tag: &str) -> io::Result<()> {
+        match self.options.mdx_jsx {
+            MdxJsxOutput::Verbatim => self.writer.write_all(tag.as_bytes()),
+            MdxJsxOutput::Escape => self.escape_html(tag),
         }
     }
 
@@ -302,36 +1111,82 @@ impl<W: Write> HtmlRenderer<W> {
         self.writer.write_all(b"\"")
     }
 
-    fn start_image(
-        &mut self,
-        _: LinkType,
-        dest_url: Cow<'_, str>,
-        title: Cow<'_, str>,
-        _: Cow<'_, str>,
-    ) {
-        self.image_stack.push(ImageContext {
-            dest_url: dest_url.into_owned(),
-            title: title.into_owned(),
-            alt: String::new(),
-        });
+    /// Writes an attribute from an `{key=value}` block, leaving out keys that are not plain
+    /// attribute names so the source cannot write markup into the tag.
+    fn write_author_attr(&mut self, key: &str, value: Option<&str>) -> io::Result<()> {
+        if !is_name(key) {
+            return Ok(());
+        }
+        match value {
+            Some(value) => self.write_attr(key, value),
+            None => write!(self.writer, " {key}"),
+        }
     }
 
     fn finish_image(&mut self) -> io::Result<()> {
-        if let Some(image) = self.image_stack.pop() {
-            self.writer.write_all(b"<img src=\"")?;
+        let Some(image) = self.image_stack.pop() else {
+            return Ok(());
+        };
+        if self.image_stack.is_empty() && std::mem::take(&mut self.figure_candidate) {
+            self.pending_figure = Some(image);
+            return Ok(());
+        }
+        self.write_img(&image, true)
+    }
+
+    /// Writes an image that filled its paragraph, as a figure if [`HtmlOptions::image_figures`]
+    /// selects it and as a paragraph otherwise.
+    fn write_figure(&mut self, image: &ImageContext) -> io::Result<()> {
+        let figure = match self.options.image_figures {
+            ImageFigures::Off => false,
+            ImageFigures::Titled => !image.title.is_empty(),
+            ImageFigures::Standalone => true,
+        };
+        if !figure {
+            self.writer.write_all(b"<p>")?;
+            self.write_img(image, true)?;
+            return self.writer.write_all(b"</p>\n");
+        }
+
+        self.writer.write_all(b"<figure>\n")?;
+        self.write_img(image, false)?;
+        if !image.title.is_empty() {
+            self.writer.write_all(b"\n<figcaption>")?;
+            self.escape_html(&image.title)?;
+            self.writer.write_all(b"</figcaption>")?;
+        }
+        self.writer.write_all(b"\n</figure>\n")
+    }
+
+    fn write_img(&mut self, image: &ImageContext, with_title: bool) -> io::Result<()> {
+        self.writer.write_all(b"<img src=\"")?;
+        if (!self.options.sanitize_urls || urls::is_safe_image(&image.dest_url))
+            && self
+                .options
+                .data_uri_images
+                .violation(&image.dest_url)
+                .is_none()
+        {
             self.escape_attr(&image.dest_url)?;
-            self.writer.write_all(b"\" alt=\"")?;
-            self.escape_attr(&image.alt)?;
+        }
+        self.writer.write_all(b"\" alt=\"")?;
+        self.escape_attr(&image.alt)?;
+        self.writer.write_all(b"\"")?;
+        if with_title && !image.title.is_empty() {
+            self.writer.write_all(b" title=\"")?;
+            self.escape_attr(&image.title)?;
             self.writer.write_all(b"\"")?;
-            if !image.title.is_empty() {
-                self.writer.write_all(b" title=\"")?;
-                self.escape_attr(&image.title)?;
-                self.writer.write_all(b"\"")?;
-            }
-            self.writer.write_all(b" loading=\"lazy\" />")
-        } else {
-            Ok(())
         }
+        if let Some(width) = image.width {
+            write!(self.writer, " width=\"{width}\"")?;
+        }
+        if let Some(height) = image.height {
+            write!(self.writer, " height=\"{height}\"")?;
+        }
+        for (key, value) in &image.attrs {
+            self.write_attr(key, value)?;
+        }
+        self.writer.write_all(b" loading=\"lazy\" />")
     }
 
     fn handle_image_text<'a>(&mut self, event: &Event<'a>) -> bool {
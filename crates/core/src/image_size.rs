@@ -0,0 +1,115 @@
+//! Image size hints, read from a trailing `=640x480` in the title or from `w`/`h` query
+//! parameters on the source URL.
+//!
+//! CommonMark has no room for a bare `![alt](img.png =640x480)` destination, so the hint goes
+//! where the parser keeps free text: `![alt](img.png "=640x480")` or
+//! `![alt](img.png "Caption =640x480")`. Either side may be left out (`=640x`, `=x480`).
+
+use std::borrow::Cow;
+
+use crate::event::Tag;
+
+/// Width and height hints of an image, in pixels.
+pub(crate) type SizeHint = (Option<u32>, Option<u32>);
+
+/// Fills in the size of an image `tag` from its title or URL, dropping the hint from the title.
+/// Other tags are returned unchanged.
+pub(crate) fn apply(tag: Tag<'static>) -> Tag<'static> {
+    let Tag::Image {
+        link_type,
+        dest_url,
+        title,
+        id,
+        attrs,
+        ..
+    } = tag
+    else {
+        return tag;
+    };
+    let (title, (width, height)) = match split_title(&title) {
+        Some((rest, hint)) => (Cow::Owned(rest.to_string()), hint),
+        None => (title, query_hint(&dest_url)),
+    };
+    Tag::Image {
+        link_type,
+        dest_url,
+        title,
+        id,
+        width,
+        height,
+        attrs,
+    }
+}
+
+/// `title` without its trailing `=WxH` hint, and the hint; `None` when it has none.
+pub(crate) fn split_title(title: &str) -> Option<(&str, SizeHint)> {
+    let (rest, token) = match title.trim_end().rsplit_once(char::is_whitespace) {
+        Some((rest, token)) => (rest.trim_end(), token),
+        None => ("", title.trim()),
+    };
+    let (width, height) = token.strip_prefix('=')?.split_once('x')?;
+    let hint = (dimension(width)?, dimension(height)?);
+    (hint != (None, None)).then_some((rest, hint))
+}
+
+/// Hint from the `w`/`width` and `h`/`height` query parameters of `dest_url`.
+pub(crate) fn query_hint(dest_url: &str) -> SizeHint {
+    let query = dest_url
+        .split('#')
+        .next()
+        .and_then(|url| url.split_once('?'))
+        .map_or("", |(_, query)| query);
+    let mut hint = (None, None);
+    for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+        let value = value.parse().ok().filter(|&value| value > 0);
+        match key {
+            "w" | "width" => hint.0 = hint.0.or(value),
+            "h" | "height" => hint.1 = hint.1.or(value),
+            _ => {}
+        }
+    }
+    hint
+}
+
+/// One side of a `WxH` hint: `Some(None)` when left empty, `None` when not a positive number.
+fn dimension(text: &str) -> Option<Option<u32>> {
+    if text.is_empty() {
+        return Some(None);
+    }
+    if !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok().filter(|&value| value > 0).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_hints() {
+        let titles = [
+            ("=640x480", Some(("", (Some(640), Some(480))))),
+            ("A chart  =640x", Some(("A chart", (Some(640), None)))),
+            ("=x480", Some(("", (None, Some(480))))),
+            ("=x", None),
+            ("=0x10", None),
+            ("=640", None),
+            ("Size =ax1", None),
+            ("plain title", None),
+        ];
+        for (title, expected) in titles {
+            assert_eq!(split_title(title), expected, "{title:?}");
+        }
+
+        let urls = [
+            ("/a.png?w=640&h=480", (Some(640), Some(480))),
+            ("/a.png?width=320&fmt=webp#h=9", (Some(320), None)),
+            ("/a.png?w=big&h=0", (None, None)),
+            ("/a.png", (None, None)),
+        ];
+        for (url, expected) in urls {
+            assert_eq!(query_hint(url), expected, "{url:?}");
+        }
+    }
+}
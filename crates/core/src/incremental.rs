@@ -0,0 +1,650 @@
+//! Append-only Markdown input, parsed one finished block at a time.
+//!
+//! Chat and LLM interfaces receive Markdown a few tokens at a time. [`IncrementalParser`] keeps
+//! the unfinished tail of the input and hands out the events of each top-level block once no
+//! later input can change it: after a blank line that is followed by a new, unindented block.
+//! Fenced code, math, frontmatter, and raw HTML blocks that may contain blank lines are held
+//! back until their closing line arrives, so a half-streamed code block is never emitted as a
+//! paragraph.
+//!
+//! Each block is parsed on its own, so link reference and footnote definitions only resolve
+//! within the block they appear in. Heading ids stay unique across blocks.
+
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+
+use crate::backend::{ParserBackend, SyntaxOptions};
+use crate::event::{Event, Tag};
+use crate::html_renderer::HtmlRenderer;
+use crate::markdown_adapter::normalize_label;
+use crate::slug::Slugger;
+use crate::span::SourceSpan;
+#[cfg(feature = "rewriter")]
+use crate::streaming_rewriter::StreamingRewriter;
+use crate::{MarkflowError, RenderOptions};
+
+/// Incremental parser façade over a [`ParserBackend`]; see the module docs.
+#[derive(Debug, Clone, Default)]
+pub struct IncrementalParser {
+    backend: ParserBackend,
+    syntax: SyntaxOptions,
+    /// Input not handed out as events yet.
+    pending: String,
+    scan: Scan,
+    /// Heading ids already handed out.
+    slugger: Slugger,
+}
+
+/// Line-scanning state at the end of the scanned part of a text.
+#[derive(Debug, Clone, Default)]
+struct Scan {
+    /// Bytes of the text made up of complete, scanned lines.
+    offset: usize,
+    /// A line of the document has been scanned, so `---` no longer opens frontmatter.
+    started: bool,
+    previous_blank: bool,
+    /// The current top-level block is a list, which later list items may continue.
+    list: bool,
+    /// Closing line of the open block that may contain blank lines.
+    open: Option<Closer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Closer {
+    /// A line of at least `len` `marker`s and nothing else, as fenced code and math end with.
+    Fence { marker: u8, len: usize },
+    /// A line containing this text, as raw HTML blocks end with.
+    Contains(&'static str),
+}
+
+/// Raw HTML block openers whose content may contain blank lines, with their closers.
+const RAW_HTML_BLOCKS: [(&str, &str); 5] = [
+    ("<script", "</script>"),
+    ("<pre", "</pre>"),
+    ("<style", "</style>"),
+    ("<textarea", "</textarea>"),
+    ("<!--", "-->"),
+];
+
+impl IncrementalParser {
+    /// Creates a parser using the default backend and syntax.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a parser using `backend` with the constructs enabled in `syntax`.
+    pub fn with_syntax(backend: ParserBackend, syntax: SyntaxOptions) -> Self {
+        Self {
+            backend,
+            syntax,
+            ..Self::default()
+        }
+    }
+
+    /// Appends `chunk` to the input and returns the events of the blocks it finished, if any.
+    pub fn push_str(&mut self, chunk: &str) -> Result<Vec<Event<'static>>, MarkflowError> {
+        match self.push_text(chunk) {
+            Some(finished) => self.parse(&finished),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Appends `chunk` to the input and returns the source of the blocks it finished, if any.
+    fn push_text(&mut self, chunk: &str) -> Option<String> {
+        self.pending.push_str(chunk);
+        let mut boundary = 0;
+        self.scan
+            .advance(&self.pending, &self.syntax, |start| boundary = start);
+        if boundary == 0 {
+            return None;
+        }
+        self.scan.offset -= boundary;
+        Some(self.pending.drain(..boundary).collect())
+    }
+
+    /// Input received but not yet handed out as events, e.g. to show as a plain-text preview.
+    pub fn pending(&self) -> &str {
+        &self.pending
+    }
+
+    /// Ends the input and returns the events of the remaining blocks. An unterminated fence
+    /// runs to the end of the input, as in a complete document.
+    pub fn finish(mut self) -> Result<Vec<Event<'static>>, MarkflowError> {
+        let rest = std::mem::take(&mut self.pending);
+        if rest.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        self.parse(&rest)
+    }
+
+    fn parse(&mut self, input: &str) -> Result<Vec<Event<'static>>, MarkflowError> {
+        let events: Vec<Event<'static>> = self
+            .backend
+            .events_with_slugger(input, &self.syntax, self.slugger.clone())?
+            .collect();
+        for event in &events {
+            if let Event::Start(Tag::Heading { id: Some(id), .. }) = event {
+                self.slugger.unique(id.to_string());
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl Scan {
+    /// Scans the complete lines of `text` past `offset`, passing the start of every block known
+    /// to follow a finished one to `boundary`.
+    fn advance(&mut self, text: &str, syntax: &SyntaxOptions, mut boundary: impl FnMut(usize)) {
+        while let Some(len) = text[self.offset..].find('\n') {
+            let start = self.offset;
+            let line = &text[start..start + len];
+            self.offset += len + 1;
+            let first_line = !std::mem::replace(&mut self.started, true);
+
+            if let Some(closer) = &self.open {
+                if closes(closer, line) {
+                    self.open = None;
+                }
+                self.previous_blank = false;
+                continue;
+            }
+            if line.trim().is_empty() {
+                self.previous_blank = true;
+                continue;
+            }
+            let item = list_item(line);
+            if std::mem::take(&mut self.previous_blank)
+                && !line.starts_with([' ', '\t'])
+                && !(item && self.list)
+            {
+                boundary(start);
+                self.list = false;
+            }
+            self.list |= item;
+            self.open = opener(line, first_line, syntax);
+        }
+    }
+}
+
+/// Splits a complete document into its top-level blocks, each with its trailing blank lines,
+/// using the same rules as [`IncrementalParser`].
+pub(crate) fn split_blocks<'t>(text: &'t str, syntax: &SyntaxOptions) -> Vec<&'t str> {
+    let mut blocks = Vec::new();
+    let mut last = 0;
+    Scan::default().advance(text, syntax, |start| {
+        blocks.push(&text[last..start]);
+        last = start;
+    });
+    if last < text.len() {
+        blocks.push(&text[last..]);
+    }
+    blocks
+}
+
+/// Whether `line` starts with a list item marker. Indented lines after a blank line may
+/// continue a list item, and unindented items may continue a loose list.
+fn list_item(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    let list_marker = match bytes[0] {
+        b'-' | b'*' | b'+' => 1,
+        b'0'..=b'9' => {
+            let digits = bytes
+                .iter()
+                .take_while(|byte| byte.is_ascii_digit())
+                .count();
+            if matches!(bytes.get(digits), Some(b'.' | b')')) {
+                digits + 1
+            } else {
+                0
+            }
+        }
+        _ => 0,
+    };
+    list_marker > 0 && matches!(bytes.get(list_marker), None | Some(b' ' | b'\t'))
+}
+
+/// The closer of the block `line` opens, if that block may contain blank lines.
+fn opener(line: &str, first_line: bool, syntax: &SyntaxOptions) -> Option<Closer> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    if first_line && syntax.frontmatter && matches!(line.trim_end(), "---" | "+++") {
+        return Some(Closer::Fence {
+            marker: line.as_bytes()[0],
+            len: 3,
+        });
+    }
+    for (marker, min) in [(b'`', 3), (b'~', 3), (b'$', 2)] {
+        if marker == b'$' && !syntax.math {
+            continue;
+        }
+        let len = trimmed.bytes().take_while(|&byte| byte == marker).count();
+        if len >= min && !(marker == b'`' && trimmed[len..].contains('`')) {
+            // `$$x$$` on one line is complete display math.
+            if marker == b'$' && trimmed[len..].trim_end().ends_with("$$") {
+                return None;
+            }
+            return Some(Closer::Fence { marker, len });
+        }
+    }
+    let lower = trimmed.to_ascii_lowercase();
+    RAW_HTML_BLOCKS
+        .iter()
+        .find(|(open, close)| lower.starts_with(open) && !lower.contains(close))
+        .map(|&(_, close)| Closer::Contains(close))
+}
+
+fn closes(closer: &Closer, line: &str) -> bool {
+    match *closer {
+        Closer::Fence { marker, len } => {
+            let trimmed = line.trim();
+            trimmed.len() >= len && trimmed.bytes().all(|byte| byte == marker)
+        }
+        Closer::Contains(close) => line.to_ascii_lowercase().contains(close),
+    }
+}
+
+/// Bytes [`render_reader`] asks its reader for per call.
+const READ_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Source [`HeldBlocks`] holds at most before handing the blocks out unresolved.
+const MAX_HELD_BYTES: usize = 1024 * 1024;
+
+/// Renders Markdown read from `reader` to `writer` with the default options, one finished block
+/// at a time, so only the unfinished block and the blocks [`HeldBlocks`] waits on are held in
+/// memory.
+pub(crate) fn render_reader<R, W>(mut reader: R, writer: W) -> Result<W, MarkflowError>
+where
+    R: Read,
+    W: Write,
+{
+    let options = RenderOptions::default();
+    #[cfg(feature = "rewriter")]
+    let writer = StreamingRewriter::new(writer, options.rewrite.clone());
+    let mut renderer = HtmlRenderer::with_options(writer, options.html_options().into_owned());
+    let mut parser = IncrementalParser::new();
+    let mut held = HeldBlocks::default();
+    let mut buf = vec![0; READ_CHUNK_BYTES];
+    // Bytes of a character split across reads.
+    let mut carry = Vec::new();
+    // Offset and 1-based line of the start of `carry` in the input.
+    let (mut offset, mut line) = (0, 1);
+
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(input_error(err, offset + carry.len(), 0, line)),
+        };
+        carry.extend_from_slice(&buf[..read]);
+        let (valid, invalid) = match std::str::from_utf8(&carry) {
+            Ok(text) => (text.len(), None),
+            Err(err) => (err.valid_up_to(), err.error_len().map(|len| (err, len))),
+        };
+        let text = std::str::from_utf8(&carry[..valid]).expect("checked above");
+        let lines = text.matches('\n').count();
+        if let Some((err, len)) = invalid {
+            let err = io::Error::new(io::ErrorKind::InvalidData, err);
+            return Err(input_error(err, offset + valid, len, line + lines));
+        }
+        if let Some(finished) = parser.push_text(text)
+            && let Some(ready) = held.push(&finished)
+        {
+            renderer.render_events(parser.parse(&ready)?)?;
+        }
+        carry.drain(..valid);
+        offset += valid;
+        line += lines;
+    }
+    if !carry.is_empty() {
+        let err = io::Error::new(
+            io::ErrorKind::InvalidData,
+            "input ended inside a UTF-8 character",
+        );
+        return Err(input_error(err, offset, carry.len(), line));
+    }
+
+    let rest = held.finish(&std::mem::take(&mut parser.pending));
+    let events = if rest.trim().is_empty() {
+        Vec::new()
+    } else {
+        parser.parse(&rest)?
+    };
+    let writer = renderer.render(events)?;
+    #[cfg(feature = "rewriter")]
+    let writer = writer.into_inner()?;
+    Ok(writer)
+}
+
+/// A [`MarkflowError::Input`] for the `len` bytes at `offset`, on `line`.
+fn input_error(source: io::Error, offset: usize, len: usize, line: usize) -> MarkflowError {
+    MarkflowError::Input {
+        source,
+        span: Some(SourceSpan {
+            start: offset,
+            end: offset + len,
+            start_line: line,
+            end_line: line,
+        }),
+    }
+}
+
+/// Finished blocks [`render_reader`] cannot render yet because they reference definitions that
+/// have not arrived, so references resolve as they would in the whole document.
+///
+/// A block that uses a link label with no definition so far is held, together with every block
+/// after it, until all labels used in the held blocks are defined; link reference definitions
+/// already rendered are put in front of each later parse. Footnotes are resolved against the
+/// rest of the input: from the first `[^` on, everything is held until the input ends. Labels
+/// are found by scanning the source text, so brackets that are not references (in code, or
+/// literal text such as `[sic]`) also hold blocks back. Past [`MAX_HELD_BYTES`] the blocks are
+/// handed out anyway, and references in them whose definitions come later stay literal text.
+#[derive(Debug, Default)]
+struct HeldBlocks {
+    /// Source of the held blocks.
+    held: String,
+    /// Link reference definition lines of the blocks handed out so far.
+    definitions: String,
+    /// Normalized labels of every link reference definition seen.
+    defined: HashSet<String>,
+    /// Labels the held blocks use that no definition has matched yet.
+    undefined: HashSet<String>,
+    /// Whether the held blocks contain a `[^`.
+    footnotes: bool,
+}
+
+impl HeldBlocks {
+    /// Adds the source of finished blocks and returns the text to parse now, if the held blocks
+    /// no longer wait on a definition or have grown past [`MAX_HELD_BYTES`].
+    fn push(&mut self, finished: &str) -> Option<String> {
+        self.held.push_str(finished);
+        for (label, _) in definition_lines(finished) {
+            self.undefined.remove(&label);
+            self.defined.insert(label);
+        }
+        self.footnotes |= finished.contains("[^");
+        for label in reference_labels(finished) {
+            if !self.defined.contains(&label) {
+                self.undefined.insert(label);
+            }
+        }
+        let waiting = self.footnotes || !self.undefined.is_empty();
+        if waiting && self.held.len() < MAX_HELD_BYTES {
+            return None;
+        }
+        Some(self.take())
+    }
+
+    /// Returns the text to parse for the held blocks followed by the unfinished `rest`.
+    fn finish(mut self, rest: &str) -> String {
+        self.held.push_str(rest);
+        if self.held.trim().is_empty() {
+            return String::new();
+        }
+        self.take()
+    }
+
+    fn take(&mut self) -> String {
+        self.undefined.clear();
+        self.footnotes = false;
+        let held = std::mem::take(&mut self.held);
+        let ready = if self.definitions.is_empty() {
+            held.clone()
+        } else {
+            format!("{}\n{held}", self.definitions)
+        };
+        for (_, lines) in definition_lines(&held) {
+            self.definitions.push_str(lines);
+            self.definitions.push('\n');
+        }
+        ready
+    }
+}
+
+/// Link reference definitions starting a line of `text`, as normalized label and source lines
+/// (the label line, and the destination and title when they follow on their own lines).
+fn definition_lines(text: &str) -> impl Iterator<Item = (String, &str)> {
+    let mut offset = 0;
+    let lines: Vec<(usize, &str)> = text
+        .split_inclusive('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len();
+            (start, line)
+        })
+        .collect();
+    (0..lines.len()).filter_map(move |idx| {
+        let (start, line) = lines[idx];
+        let trimmed = line.trim_start_matches(' ');
+        if line.len() - trimmed.len() > 3 || trimmed.starts_with("[^") {
+            return None;
+        }
+        let (label, mut destination) = trimmed.strip_prefix('[')?.split_once("]:")?;
+        let mut next = idx + 1;
+        if destination.trim().is_empty() {
+            destination = lines.get(next)?.1;
+            next += 1;
+        }
+        if !destination.trim().contains(char::is_whitespace)
+            && lines
+                .get(next)
+                .is_some_and(|(_, line)| line.trim_start().starts_with(['"', '\'', '(']))
+        {
+            next += 1;
+        }
+        let end = lines.get(next).map_or(text.len(), |&(end, _)| end);
+        Some((normalize_label(label), text[start..end].trim_end()))
+    })
+}
+
+/// Labels `text` may use as link references: the second bracket of `[text][label]`, and the
+/// only one of `[label][]` and `[label]`. Inline links, definitions, and task markers are
+/// skipped.
+fn reference_labels(text: &str) -> impl Iterator<Item = String> {
+    let mut labels = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('[') {
+        let after = &rest[open + 1..];
+        let Some(close) = after.find(']') else {
+            break;
+        };
+        let label = &after[..close];
+        if label.contains('[') {
+            rest = after;
+            continue;
+        }
+        rest = &after[close + 1..];
+        match rest.as_bytes().first() {
+            Some(b'(' | b':') => {}
+            Some(b'[') => {
+                if let Some(end) = rest[1..].find(']') {
+                    let second = &rest[1..1 + end];
+                    labels.push(if second.trim().is_empty() {
+                        label
+                    } else {
+                        second
+                    });
+                    rest = &rest[2 + end..];
+                }
+            }
+            _ => labels.push(label),
+        }
+    }
+    labels
+        .into_iter()
+        .filter(|label| !matches!(label.trim(), "" | "x" | "X"))
+        .map(normalize_label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MarkdownStream;
+
+    fn html(events: Vec<Event<'static>>) -> String {
+        String::from_utf8(events.into_iter().stream_to_writer(Vec::new()).unwrap()).unwrap()
+    }
+
+    /// Feeds `input` in `size`-byte pieces, returning the HTML handed out after each push and
+    /// by `finish`.
+    fn stream(parser: &mut IncrementalParser, input: &str, size: usize) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut rest = input;
+        while !rest.is_empty() {
+            let mut end = size.min(rest.len());
+            while !rest.is_char_boundary(end) {
+                end += 1;
+            }
+            out.push(html(parser.push_str(&rest[..end]).unwrap()));
+            rest = &rest[end..];
+        }
+        out.push(html(std::mem::take(parser).finish().unwrap()));
+        out
+    }
+
+    #[test]
+    fn emits_blocks_once_finished() {
+        let mut parser = IncrementalParser::new();
+        assert_eq!(html(parser.push_str("# Title\n\nSome *te").unwrap()), "");
+        assert_eq!(
+            html(parser.push_str("xt*\n").unwrap()),
+            "<h1 id=\"title\">Title</h1>\n"
+        );
+        assert_eq!(
+            html(parser.push_str("\n- a\n\n- b\n\n").unwrap()),
+            "<p>Some <em>text</em></p>\n"
+        );
+        assert_eq!(parser.pending(), "- a\n\n- b\n\n");
+        assert_eq!(
+            html(parser.push_str("  more\n\n# Title\n").unwrap()),
+            "<ul><li><p>a</p>\n</li><li><p>b</p>\n<p>more</p>\n</li></ul>\n"
+        );
+        assert_eq!(
+            html(parser.finish().unwrap()),
+            "<h1 id=\"title-1\">Title</h1>\n"
+        );
+    }
+
+    #[test]
+    fn holds_unterminated_fences() {
+        let input = "Intro\n\n```rust\nfn a() {}\n\n\nfn b() {}\n```\n\n$$\nx\n\ny\n$$\n\n<pre>\n\n</pre>\n\nEnd\n";
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let whole = html(
+                backend
+                    .events(input, &SyntaxOptions::default())
+                    .unwrap()
+                    .collect(),
+            );
+            for size in [1, 3, 7, input.len()] {
+                let mut parser = IncrementalParser::with_syntax(backend, SyntaxOptions::default());
+                let chunks = stream(&mut parser, input, size);
+                assert_eq!(chunks.concat(), whole, "{backend:?}, {size}");
+                assert!(
+                    chunks.iter().all(|chunk| !chunk.contains("<p>fn")),
+                    "{backend:?}, {size}: {chunks:?}"
+                );
+            }
+        }
+
+        let mut parser = IncrementalParser::new();
+        assert_eq!(html(parser.push_str("```\ncode\n\nmore").unwrap()), "");
+        assert_eq!(
+            html(parser.finish().unwrap()),
+            "<pre><code>code\n\nmore</code></pre>\n"
+        );
+    }
+
+    /// Hands out at most three bytes per read, splitting multibyte characters.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn holds_blocks_until_their_definitions_arrive() {
+        let mut held = HeldBlocks::default();
+        assert_eq!(held.push("# Title\n\n").as_deref(), Some("# Title\n\n"));
+        assert_eq!(held.push("See [Foo  Bar].\n\n"), None);
+        assert_eq!(held.push("More.\n\n"), None);
+        assert_eq!(
+            held.push("[foo bar]: /url\n\n").as_deref(),
+            Some("See [Foo  Bar].\n\nMore.\n\n[foo bar]: /url\n\n")
+        );
+        assert_eq!(
+            held.push("Again [foo bar](x) [FOO BAR].\n\n").as_deref(),
+            Some("[foo bar]: /url\n\nAgain [foo bar](x) [FOO BAR].\n\n")
+        );
+        assert_eq!(held.push("A note[^1].\n\n"), None);
+        assert_eq!(held.push("[^1]: Text\n\n"), None);
+        assert_eq!(
+            held.finish("End"),
+            "[foo bar]: /url\n\nA note[^1].\n\n[^1]: Text\n\nEnd"
+        );
+    }
+
+    #[test]
+    fn hands_out_held_blocks_past_the_cap() {
+        let mut held = HeldBlocks::default();
+        assert_eq!(held.push("A [sic] remark.\n\n"), None);
+        let filler = format!("{}\n\n", "x".repeat(MAX_HELD_BYTES));
+        let ready = held.push(&filler).unwrap();
+        assert!(ready.starts_with("A [sic] remark.\n\nxxx"));
+        assert_eq!(held.push("Next.\n\n").as_deref(), Some("Next.\n\n"));
+    }
+
+    #[test]
+    fn renders_from_readers() {
+        let input = "# Überschrift\n\nText mit *Betonung* und `code`.\n\n```\nfn main() {}\n\n```\n\n| a | b |\n|---|---|\n| 日本 | [x](https://example.com) |\n";
+        let expected = crate::parse(input).unwrap();
+        assert_eq!(crate::parse_reader(input.as_bytes()).unwrap(), expected);
+        assert_eq!(
+            crate::parse_reader(Trickle(input.as_bytes())).unwrap(),
+            expected
+        );
+        let written = crate::parse_reader_to_writer(Trickle(input.as_bytes()), Vec::new()).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), expected);
+
+        for input in [
+            "See [foo] and [the docs][Docs].\n\n# Next\n\n[foo]: /foo\n\n[docs]:\n  /docs\n  \"Title\"\n\nAgain [foo][].\n",
+            "[bar]: /bar 'Bar'\n\nText\n\n- [x] done\n\nA [bar] link and [no ref].\n",
+            "A note[^1] and `[code]`.\n\n[^1]: Text\n\nAfter [^1].\n",
+        ] {
+            assert_eq!(
+                crate::parse_reader(Trickle(input.as_bytes())).unwrap(),
+                crate::parse(input).unwrap(),
+                "{input:?}"
+            );
+        }
+
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk gone"))
+            }
+        }
+        let err = crate::parse_reader(Failing).unwrap_err();
+        assert_eq!(err.code(), "ERR_MARKFLOW_INPUT");
+
+        for (invalid, start, end, line) in [
+            (&b"ok\n\n\xff"[..], 4, 5, 3),
+            (&b"cut \xe6\x97"[..], 4, 6, 1),
+        ] {
+            let err = crate::parse_reader(invalid).unwrap_err();
+            assert_eq!(err.code(), "ERR_MARKFLOW_INPUT", "{invalid:?}");
+            let span = err.span().unwrap();
+            assert_eq!(
+                (span.start, span.end, span.start_line),
+                (start, end, line),
+                "{invalid:?}"
+            );
+        }
+    }
+}
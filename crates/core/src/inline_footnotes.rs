@@ -0,0 +1,277 @@
+//! Pandoc-style inline footnotes, `text^[The note itself.]`.
+//!
+//! Neither parser knows the syntax, so the note arrives as text around whatever inline events
+//! its content produced. [`InlineFootnotes`] replaces each note with a numbered
+//! [`Event::FootnoteReference`] and appends a matching footnote definition at the end of the
+//! document, where the footnote layout transforms pick it up like any other definition.
+
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+
+use crate::event::{Event, Tag, TagEnd};
+use crate::marks::{is_inline, merge_text};
+
+const OPENER: &str = "^[";
+
+/// Turns `^[…]` in paragraph text into footnote references with synthesized definitions.
+///
+/// Notes are labeled `1`, `2`, … in document order, skipping labels the source already uses
+/// for `[^label]` footnotes. Notes without a closing bracket in the same block, or whose
+/// brackets cut through emphasis or a link, stay literal text.
+pub(crate) struct InlineFootnotes<I> {
+    inner: I,
+    pending: VecDeque<Event<'static>>,
+    /// Labels of `[^label]` footnotes written in the source.
+    taken: HashSet<String>,
+    next_number: usize,
+    /// Synthesized definitions, emitted once the document ends.
+    definitions: Vec<Event<'static>>,
+    code_depth: usize,
+}
+
+impl<I> InlineFootnotes<I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    /// `source` is the Markdown `inner` was parsed from; it is scanned for footnote labels so
+    /// synthesized ones never collide with them.
+    pub(crate) fn new(inner: I, source: &str) -> Self {
+        Self {
+            inner,
+            pending: VecDeque::new(),
+            taken: footnote_labels(source),
+            next_number: 1,
+            definitions: Vec::new(),
+            code_depth: 0,
+        }
+    }
+
+    /// Buffers the inline events starting at `first` and queues them with notes expanded.
+    fn run(&mut self, first: Event<'static>) {
+        let mut run = vec![first];
+        let mut after = None;
+        for event in self.inner.by_ref() {
+            if is_inline(&event) {
+                run.push(event);
+            } else {
+                after = Some(event);
+                break;
+            }
+        }
+
+        let mut queue: VecDeque<_> = merge_text(run).into();
+        let mut output = Vec::with_capacity(queue.len());
+        while let Some(event) = queue.pop_front() {
+            let Event::Text(text) = &event else {
+                output.push(event);
+                continue;
+            };
+            let Some(start) = text.find(OPENER) else {
+                output.push(event);
+                continue;
+            };
+            let (before, after) = (&text[..start], &text[start + OPENER.len()..]);
+            match close_note(after, &queue) {
+                Some(note) => {
+                    output.push(Event::Text(Cow::Owned(before.to_string())));
+                    let label = self.next_label();
+                    output.push(Event::FootnoteReference(Cow::Owned(label.clone())));
+                    self.define(label, note.body);
+                    queue.drain(..note.consumed);
+                    if !note.rest.is_empty() {
+                        queue.push_front(Event::Text(Cow::Owned(note.rest)));
+                    }
+                }
+                None => {
+                    output.push(Event::Text(Cow::Owned(
+                        text[..start + OPENER.len()].to_string(),
+                    )));
+                    if !after.is_empty() {
+                        queue.push_front(Event::Text(Cow::Owned(after.to_string())));
+                    }
+                }
+            }
+        }
+
+        self.pending.extend(
+            merge_text(output)
+                .into_iter()
+                .filter(|event| !matches!(event, Event::Text(text) if text.is_empty())),
+        );
+        if let Some(event) = after {
+            self.track(&event);
+            self.pending.push_back(event);
+        }
+    }
+
+    fn next_label(&mut self) -> String {
+        loop {
+            let label = self.next_number.to_string();
+            self.next_number += 1;
+            if !self.taken.contains(&label) {
+                return label;
+            }
+        }
+    }
+
+    fn define(&mut self, label: String, body: Vec<Event<'static>>) {
+        let tag = Tag::FootnoteDefinition(Cow::Owned(label));
+        let end = tag.to_end();
+        self.definitions.push(Event::Start(tag));
+        self.definitions.push(Event::Start(Tag::Paragraph));
+        self.definitions.extend(body);
+        self.definitions.push(Event::End(TagEnd::Paragraph));
+        self.definitions.push(Event::End(end));
+    }
+
+    fn track(&mut self, event: &Event<'_>) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => self.code_depth += 1,
+            Event::End(TagEnd::CodeBlock) => self.code_depth = self.code_depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+}
+
+impl<I> Iterator for InlineFootnotes<I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    type Item = Event<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            match self.inner.next() {
+                Some(event @ Event::Text(_)) if self.code_depth == 0 && may_open(&event) => {
+                    self.run(event)
+                }
+                Some(event) => {
+                    self.track(&event);
+                    return Some(event);
+                }
+                None if !self.definitions.is_empty() => {
+                    self.pending.extend(self.definitions.drain(..));
+                }
+                None => return None,
+            }
+        }
+    }
+}
+
+/// A note's content and what follows its closing bracket.
+struct Note {
+    body: Vec<Event<'static>>,
+    /// Text after the closing bracket.
+    rest: String,
+    /// Events taken from the queue, including the one holding the closing bracket.
+    consumed: usize,
+}
+
+/// Finds the bracket closing a note whose content starts with `text` and continues with
+/// `queue`.
+fn close_note(text: &str, queue: &VecDeque<Event<'static>>) -> Option<Note> {
+    let mut body = Vec::new();
+    let mut brackets = 1usize;
+    let mut tags = 0usize;
+    for (consumed, event) in std::iter::once(None)
+        .chain(queue.iter().map(Some))
+        .enumerate()
+    {
+        let text = match event {
+            None => text,
+            Some(Event::Text(text)) => text.as_ref(),
+            Some(event) => {
+                match event {
+                    Event::Start(_) => tags += 1,
+                    Event::End(_) => tags = tags.checked_sub(1)?,
+                    _ => {}
+                }
+                body.push(event.clone());
+                continue;
+            }
+        };
+        for (idx, byte) in text.bytes().enumerate() {
+            match byte {
+                b'[' => brackets += 1,
+                b']' => brackets -= 1,
+                _ => continue,
+            }
+            if brackets > 0 {
+                continue;
+            }
+            if tags > 0 {
+                return None;
+            }
+            if !text[..idx].is_empty() {
+                body.push(Event::Text(Cow::Owned(text[..idx].to_string())));
+            }
+            if body.is_empty() {
+                return None;
+            }
+            return Some(Note {
+                body,
+                rest: text[idx + 1..].to_string(),
+                consumed,
+            });
+        }
+        if !text.is_empty() {
+            body.push(Event::Text(Cow::Owned(text.to_string())));
+        }
+    }
+    None
+}
+
+/// Whether `event` could hold or begin a `^[` split across text events.
+fn may_open(event: &Event<'_>) -> bool {
+    matches!(event, Event::Text(text) if text.contains('^'))
+}
+
+/// Labels of the `[^label]` footnotes in `source`, lowercased as both parsers match them.
+fn footnote_labels(source: &str) -> HashSet<String> {
+    let mut labels = HashSet::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("[^") {
+        rest = &rest[start + 2..];
+        if let Some(end) = rest.find([']', '\n']) {
+            labels.insert(rest[..end].trim().to_lowercase());
+        }
+    }
+    labels
+}
+
+#[cfg(all(test, feature = "footnotes"))]
+mod tests {
+    use crate::{ParserBackend, RenderOptions, render};
+
+    #[test]
+    fn synthesizes_inline_footnotes() {
+        let input = "One^[An *inline* note.] and two[^1].^[With [brackets] too.]\n\n\
+                     `^[code]` ^[open\n\n```\n^[fenced]\n```\n\n[^1]: Explicit.";
+        let expected = [
+            "<p>One<sup class=\"footnote-ref\"><a href=\"#fn-2\" id=\"fnref-2\">2</a></sup> and two",
+            ".<sup class=\"footnote-ref\"><a href=\"#fn-3\" id=\"fnref-3\">3</a></sup></p>",
+            "<p><code>^[code]</code> ^[open</p>",
+            "<code>^[fenced]",
+            "<section class=\"footnote\" id=\"fn-2\"><p>An <em>inline</em> note.</p>\n</section>\n\
+             <section class=\"footnote\" id=\"fn-3\"><p>With [brackets] too.</p>\n</section>\n",
+        ];
+
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                ..RenderOptions::default()
+            };
+            let html = render(input, &options).unwrap().html;
+            for fragment in expected {
+                assert!(
+                    html.contains(fragment),
+                    "{backend:?}: missing {fragment:?} in {html}"
+                );
+            }
+            assert!(html.ends_with("</section>\n"), "{backend:?}: {html}");
+        }
+    }
+}
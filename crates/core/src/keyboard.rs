@@ -0,0 +1,78 @@
+//! `[[Ctrl]]+[[C]]` keyboard keys, rendered as `<kbd>`.
+
+use crate::event::{Event, Tag, TagEnd};
+use crate::text_runs::{Expansion, TextRuns};
+
+/// Wraps `[[Key]]` in text outside code blocks and image descriptions in [`Tag::Keyboard`].
+pub(crate) fn keyboard_keys<I>(inner: I) -> impl Iterator<Item = Event<'static>>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    TextRuns::new(inner, expand)
+}
+
+fn expand(text: &str, out: &mut Expansion<'_>) -> bool {
+    if !text.contains("[[") {
+        return false;
+    }
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
+        };
+        let key = after[..end].trim();
+        if key.is_empty() || key.contains(['[', ']', '|', '\n']) {
+            out.push_text(&rest[..start + 2]);
+            rest = after;
+            continue;
+        }
+        out.push_text(&rest[..start]);
+        out.push(Event::Start(Tag::Keyboard));
+        out.push_text(key);
+        out.push(Event::End(TagEnd::Keyboard));
+        rest = &after[end + 2..];
+    }
+    out.push_text(rest);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParserBackend, RenderOptions, WikiLinkOptions, render};
+
+    #[test]
+    fn renders_keyboard_keys() {
+        let cases = [
+            (
+                "Press [[Ctrl]]+[[Shift]]+[[<]] or [[ Page Up ]].",
+                "<p>Press <kbd>Ctrl</kbd>+<kbd>Shift</kbd>+<kbd>&lt;</kbd> or <kbd>Page Up</kbd>.</p>",
+            ),
+            (
+                "`[[Esc]]` [[]] [[a|b]] [see [[F1]]](/k) ![[[Tab]]](t.png) [[open",
+                "<p><code>[[Esc]]</code> [[]] [[a|b]] <a href=\"/k\">see <kbd>F1</kbd></a> \
+                 <img src=\"t.png\" alt=\"[[Tab]]\" loading=\"lazy\" /> [[open</p>",
+            ),
+        ];
+
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            for (input, expected) in cases {
+                let options = RenderOptions {
+                    backend,
+                    keyboard_keys: true,
+                    ..RenderOptions::default()
+                };
+                let html = render(input, &options).unwrap().html;
+                assert_eq!(html.trim_end(), expected, "{backend:?}: {input:?}");
+            }
+        }
+
+        let options = RenderOptions {
+            keyboard_keys: true,
+            wiki_links: Some(WikiLinkOptions::default()),
+            ..RenderOptions::default()
+        };
+        let html = render("[[Enter]] [[Page|label]]", &options).unwrap().html;
+        assert_eq!(html, "<p><kbd>Enter</kbd> <a href=\"page\">label</a></p>\n");
+    }
+}
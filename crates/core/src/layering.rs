@@ -0,0 +1,719 @@
+//! Layered configuration: global defaults, then a collection's settings, then a document's
+//! frontmatter, each layer applied over the previous one with [`RenderOptions::apply`], or
+//! merged with [`RenderOptions::merge`] when layers are full option sets.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+
+use crate::anchors::AnchorOptions;
+use crate::backend::{ParserBackend, SyntaxOptions};
+use crate::figures::FigureOptions;
+use crate::footnotes::FootnoteStyle;
+use crate::html_renderer::{HtmlOptions, ImageFigures, MdxExpressionOutput, MdxJsxOutput};
+use crate::limits::Limits;
+use crate::render::RenderOptions;
+use crate::rewrite_options::{
+    ChunkPolicy, ExternalLinks, LinkHardening, PictureSources, RewriteOptions, RewriteRule,
+    SanitizePolicy,
+};
+use crate::slug::SlugStyle;
+use crate::smart_punct::{CjkPunctuation, SmartPunctuation};
+use crate::urls::DataUriPolicy;
+use crate::wiki_links::WikiLinkOptions;
+
+/// One option that differs between two [`RenderOptions`], as reported by
+/// [`RenderOptions::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionChange {
+    /// Dotted field path, e.g. `html.sanitize_urls`.
+    pub path: String,
+    /// The old value as JSON.
+    pub from: String,
+    /// The new value as JSON.
+    pub to: String,
+}
+
+impl fmt::Display for OptionChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.path, self.from, self.to)
+    }
+}
+
+impl RenderOptions {
+    /// Returns `self` with `overrides` layered on top.
+    ///
+    /// Precedence is field by field: a field of `overrides` wins when it differs from
+    /// [`RenderOptions::default`], and otherwise `self` keeps its value. Nested option structs
+    /// (`syntax`, `html`, `rewrite`, `limits`, ...) are merged the same way, field by field;
+    /// enums and optional option sets (`wiki_links`, `anchors`, ...) are replaced whole. An
+    /// override therefore cannot reset a field to its default; use [`RenderOptions::apply`]
+    /// with an [`OptionsOverride`] for layers that need to turn options off.
+    ///
+    /// [`RewriteOptions::rewrite_urls`], which has no JSON form, comes from `overrides` when it
+    /// sets one and from `self` otherwise.
+    pub fn merge(&self, overrides: &RenderOptions) -> RenderOptions {
+        let mut merged = to_json(self);
+        layer(
+            &mut merged,
+            &to_json(overrides),
+            &to_json(&RenderOptions::default()),
+        );
+        let mut merged: RenderOptions =
+            serde_json::from_value(merged).expect("merged options keep the serialized shape");
+        merged.rewrite.rewrite_urls = overrides
+            .rewrite
+            .rewrite_urls
+            .clone()
+            .or_else(|| self.rewrite.rewrite_urls.clone());
+        merged
+    }
+
+    /// Returns `self` with every field that `overrides` sets replaced, including fields set back
+    /// to their defaults.
+    pub fn apply(&self, overrides: &OptionsOverride) -> RenderOptions {
+        let mut applied = self.clone();
+        overrides.apply_to(&mut applied);
+        applied
+    }
+
+    /// Lists the fields whose values differ in `other`, sorted by path, for logging what a
+    /// configuration layer changed.
+    pub fn diff(&self, other: &RenderOptions) -> Vec<OptionChange> {
+        let mut changes = Vec::new();
+        compare("", &to_json(self), &to_json(other), &mut changes);
+        changes
+    }
+}
+
+/// A configuration layer for [`RenderOptions::apply`]: every field is optional, and a field that is
+/// set replaces the value below it, even when it sets the default. Optional option sets take
+/// `Some(None)` (`null` in JSON) to turn them off. Deserializes from partial JSON or frontmatter.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OptionsOverride {
+    /// Overrides [`RenderOptions::backend`].
+    pub backend: Option<ParserBackend>,
+    /// Overrides [`RenderOptions::syntax`].
+    pub syntax: SyntaxOverride,
+    /// Overrides [`RenderOptions::html`].
+    pub html: HtmlOverride,
+    /// Overrides [`RenderOptions::slug_style`].
+    pub slug_style: Option<SlugStyle>,
+    /// Overrides [`RenderOptions::rewrite`].
+    pub rewrite: RewriteOverride,
+    /// Overrides [`RenderOptions::limits`].
+    pub limits: LimitsOverride,
+    /// Overrides [`RenderOptions::script_stats`].
+    pub script_stats: Option<bool>,
+    /// Overrides [`RenderOptions::cjk_punctuation`].
+    #[serde(deserialize_with = "present")]
+    pub cjk_punctuation: Option<Option<CjkPunctuation>>,
+    /// Overrides [`RenderOptions::smart_punctuation`].
+    #[serde(deserialize_with = "present")]
+    pub smart_punctuation: Option<Option<SmartPunctuation>>,
+    /// Overrides [`RenderOptions::normalize_nfc`].
+    pub normalize_nfc: Option<bool>,
+    /// Overrides [`RenderOptions::footnote_style`].
+    pub footnote_style: Option<FootnoteStyle>,
+    /// Overrides [`RenderOptions::callouts`].
+    pub callouts: Option<bool>,
+    /// Overrides [`RenderOptions::details`].
+    pub details: Option<bool>,
+    /// Overrides [`RenderOptions::csv_tables`].
+    pub csv_tables: Option<bool>,
+    /// Overrides [`RenderOptions::shortcodes`].
+    pub shortcodes: Option<bool>,
+    /// Overrides [`RenderOptions::keyboard_keys`].
+    pub keyboard_keys: Option<bool>,
+    /// Overrides [`RenderOptions::ruby`].
+    pub ruby: Option<bool>,
+    /// Overrides [`RenderOptions::wiki_links`].
+    #[serde(deserialize_with = "present")]
+    pub wiki_links: Option<Option<WikiLinkOptions>>,
+    /// Overrides [`RenderOptions::figures`].
+    #[serde(deserialize_with = "present")]
+    pub figures: Option<Option<FigureOptions>>,
+    /// Overrides [`RenderOptions::extract_title`].
+    pub extract_title: Option<bool>,
+    /// Overrides [`RenderOptions::remove_title`].
+    pub remove_title: Option<bool>,
+    /// Overrides [`RenderOptions::number_headings`].
+    pub number_headings: Option<bool>,
+    /// Overrides [`RenderOptions::headings`].
+    pub headings: Option<bool>,
+    /// Overrides [`RenderOptions::anchors`].
+    #[serde(deserialize_with = "present")]
+    pub anchors: Option<Option<AnchorOptions>>,
+    /// Overrides [`RenderOptions::toc`].
+    pub toc: Option<bool>,
+    /// Overrides [`RenderOptions::toc_marker`].
+    pub toc_marker: Option<bool>,
+    /// Overrides [`RenderOptions::assets`].
+    pub assets: Option<bool>,
+    /// Overrides [`RenderOptions::diagnostics`].
+    pub diagnostics: Option<bool>,
+    /// Overrides [`RenderOptions::id_prefix`].
+    pub id_prefix: Option<String>,
+}
+
+impl OptionsOverride {
+    fn apply_to(&self, options: &mut RenderOptions) {
+        let Self {
+            backend,
+            syntax,
+            html,
+            slug_style,
+            rewrite,
+            limits,
+            script_stats,
+            cjk_punctuation,
+            smart_punctuation,
+            normalize_nfc,
+            footnote_style,
+            callouts,
+            details,
+            csv_tables,
+            shortcodes,
+            keyboard_keys,
+            ruby,
+            wiki_links,
+            figures,
+            extract_title,
+            remove_title,
+            number_headings,
+            headings,
+            anchors,
+            toc,
+            toc_marker,
+            assets,
+            diagnostics,
+            id_prefix,
+        } = self;
+        set(&mut options.backend, backend);
+        syntax.apply_to(&mut options.syntax);
+        html.apply_to(&mut options.html);
+        set(&mut options.slug_style, slug_style);
+        rewrite.apply_to(&mut options.rewrite);
+        limits.apply_to(&mut options.limits);
+        set(&mut options.script_stats, script_stats);
+        set(&mut options.cjk_punctuation, cjk_punctuation);
+        set(&mut options.smart_punctuation, smart_punctuation);
+        set(&mut options.normalize_nfc, normalize_nfc);
+        set(&mut options.footnote_style, footnote_style);
+        set(&mut options.callouts, callouts);
+        set(&mut options.details, details);
+        set(&mut options.csv_tables, csv_tables);
+        set(&mut options.shortcodes, shortcodes);
+        set(&mut options.keyboard_keys, keyboard_keys);
+        set(&mut options.ruby, ruby);
+        set(&mut options.wiki_links, wiki_links);
+        set(&mut options.figures, figures);
+        set(&mut options.extract_title, extract_title);
+        set(&mut options.remove_title, remove_title);
+        set(&mut options.number_headings, number_headings);
+        set(&mut options.headings, headings);
+        set(&mut options.anchors, anchors);
+        set(&mut options.toc, toc);
+        set(&mut options.toc_marker, toc_marker);
+        set(&mut options.assets, assets);
+        set(&mut options.diagnostics, diagnostics);
+        set(&mut options.id_prefix, id_prefix);
+    }
+}
+
+/// Fields of [`SyntaxOptions`] to override, see [`OptionsOverride`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyntaxOverride {
+    /// Overrides [`SyntaxOptions::autolinks`].
+    pub autolinks: Option<bool>,
+    /// Overrides [`SyntaxOptions::math`].
+    pub math: Option<bool>,
+    /// Overrides [`SyntaxOptions::footnotes`].
+    pub footnotes: Option<bool>,
+    /// Overrides [`SyntaxOptions::frontmatter`].
+    pub frontmatter: Option<bool>,
+    /// Overrides [`SyntaxOptions::heading_attributes`].
+    pub heading_attributes: Option<bool>,
+    /// Overrides [`SyntaxOptions::highlight`].
+    pub highlight: Option<bool>,
+    /// Overrides [`SyntaxOptions::superscript_subscript`].
+    pub superscript_subscript: Option<bool>,
+    /// Overrides [`SyntaxOptions::breaks`].
+    pub breaks: Option<bool>,
+    /// Overrides [`SyntaxOptions::mdx`].
+    pub mdx: Option<bool>,
+    /// Overrides [`SyntaxOptions::image_sizes`].
+    pub image_sizes: Option<bool>,
+}
+
+impl SyntaxOverride {
+    fn apply_to(&self, options: &mut SyntaxOptions) {
+        let Self {
+            autolinks,
+            math,
+            footnotes,
+            frontmatter,
+            heading_attributes,
+            highlight,
+            superscript_subscript,
+            breaks,
+            mdx,
+            image_sizes,
+        } = self;
+        set(&mut options.autolinks, autolinks);
+        set(&mut options.math, math);
+        set(&mut options.footnotes, footnotes);
+        set(&mut options.frontmatter, frontmatter);
+        set(&mut options.heading_attributes, heading_attributes);
+        set(&mut options.highlight, highlight);
+        set(&mut options.superscript_subscript, superscript_subscript);
+        set(&mut options.breaks, breaks);
+        set(&mut options.mdx, mdx);
+        set(&mut options.image_sizes, image_sizes);
+    }
+}
+
+/// Fields of [`HtmlOptions`] to override, see [`OptionsOverride`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HtmlOverride {
+    /// Overrides [`HtmlOptions::table_header_ids`].
+    #[serde(deserialize_with = "present")]
+    pub table_header_ids: Option<Option<usize>>,
+    /// Overrides [`HtmlOptions::table_wrapper_class`].
+    #[serde(deserialize_with = "present")]
+    pub table_wrapper_class: Option<Option<String>>,
+    /// Overrides [`HtmlOptions::table_colgroup`].
+    pub table_colgroup: Option<bool>,
+    /// Overrides [`HtmlOptions::runnable_snippets`].
+    pub runnable_snippets: Option<bool>,
+    /// Overrides [`HtmlOptions::task_marker_in_paragraph`].
+    pub task_marker_in_paragraph: Option<bool>,
+    /// Overrides [`HtmlOptions::escape_raw_html`].
+    pub escape_raw_html: Option<bool>,
+    /// Overrides [`HtmlOptions::strip_comments`].
+    pub strip_comments: Option<bool>,
+    /// Overrides [`HtmlOptions::sanitize_urls`].
+    pub sanitize_urls: Option<bool>,
+    /// Overrides [`HtmlOptions::data_uri_images`].
+    pub data_uri_images: Option<DataUriPolicy>,
+    /// Overrides [`HtmlOptions::heading_anchor_links`].
+    pub heading_anchor_links: Option<bool>,
+    /// Overrides [`HtmlOptions::heading_id_prefix`].
+    pub heading_id_prefix: Option<String>,
+    /// Overrides [`HtmlOptions::id_prefix`].
+    pub id_prefix: Option<String>,
+    /// Overrides [`HtmlOptions::code_meta`].
+    pub code_meta: Option<bool>,
+    /// Overrides [`HtmlOptions::code_lines`].
+    pub code_lines: Option<bool>,
+    /// Overrides [`HtmlOptions::code_figures`].
+    pub code_figures: Option<bool>,
+    /// Overrides [`HtmlOptions::diagram_languages`].
+    pub diagram_languages: Option<Vec<String>>,
+    /// Overrides [`HtmlOptions::language_aliases`].
+    pub language_aliases: Option<BTreeMap<String, String>>,
+    /// Overrides [`HtmlOptions::sections`].
+    pub sections: Option<bool>,
+    /// Overrides [`HtmlOptions::image_figures`].
+    pub image_figures: Option<ImageFigures>,
+    /// Overrides [`HtmlOptions::mdx_jsx`].
+    pub mdx_jsx: Option<MdxJsxOutput>,
+    /// Overrides [`HtmlOptions::mdx_expressions`].
+    pub mdx_expressions: Option<MdxExpressionOutput>,
+    /// Overrides [`HtmlOptions::footnote_backref`].
+    #[serde(deserialize_with = "present")]
+    pub footnote_backref: Option<Option<String>>,
+}
+
+impl HtmlOverride {
+    fn apply_to(&self, options: &mut HtmlOptions) {
+        let Self {
+            table_header_ids,
+            table_wrapper_class,
+            table_colgroup,
+            runnable_snippets,
+            task_marker_in_paragraph,
+            escape_raw_html,
+            strip_comments,
+            sanitize_urls,
+            data_uri_images,
+            heading_anchor_links,
+            heading_id_prefix,
+            id_prefix,
+            code_meta,
+            code_lines,
+            code_figures,
+            diagram_languages,
+            language_aliases,
+            sections,
+            image_figures,
+            mdx_jsx,
+            mdx_expressions,
+            footnote_backref,
+        } = self;
+        set(&mut options.table_header_ids, table_header_ids);
+        set(&mut options.table_wrapper_class, table_wrapper_class);
+        set(&mut options.table_colgroup, table_colgroup);
+        set(&mut options.runnable_snippets, runnable_snippets);
+        set(
+            &mut options.task_marker_in_paragraph,
+            task_marker_in_paragraph,
+        );
+        set(&mut options.escape_raw_html, escape_raw_html);
+        set(&mut options.strip_comments, strip_comments);
+        set(&mut options.sanitize_urls, sanitize_urls);
+        set(&mut options.data_uri_images, data_uri_images);
+        set(&mut options.heading_anchor_links, heading_anchor_links);
+        set(&mut options.heading_id_prefix, heading_id_prefix);
+        set(&mut options.id_prefix, id_prefix);
+        set(&mut options.code_meta, code_meta);
+        set(&mut options.code_lines, code_lines);
+        set(&mut options.code_figures, code_figures);
+        set(&mut options.diagram_languages, diagram_languages);
+        set(&mut options.language_aliases, language_aliases);
+        set(&mut options.sections, sections);
+        set(&mut options.image_figures, image_figures);
+        set(&mut options.mdx_jsx, mdx_jsx);
+        set(&mut options.mdx_expressions, mdx_expressions);
+        set(&mut options.footnote_backref, footnote_backref);
+    }
+}
+
+/// Fields of [`RewriteOptions`] to override, see [`OptionsOverride`]. The URL rewriter callback
+/// cannot be serialized and is always kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RewriteOverride {
+    /// Overrides [`RewriteOptions::sanitize`].
+    #[serde(deserialize_with = "present")]
+    pub sanitize: Option<Option<SanitizePolicy>>,
+    /// Overrides [`RewriteOptions::enforce_img_loading_lazy`].
+    pub enforce_img_loading_lazy: Option<bool>,
+    /// Overrides [`RewriteOptions::enforce_iframe_loading_lazy`].
+    pub enforce_iframe_loading_lazy: Option<bool>,
+    /// Overrides [`RewriteOptions::enforce_video_preload_none`].
+    pub enforce_video_preload_none: Option<bool>,
+    /// Overrides [`RewriteOptions::harden_links`].
+    pub harden_links: Option<LinkHardening>,
+    /// Overrides [`RewriteOptions::external_links`].
+    #[serde(deserialize_with = "present")]
+    pub external_links: Option<Option<ExternalLinks>>,
+    /// Overrides [`RewriteOptions::data_uri_images`].
+    pub data_uri_images: Option<DataUriPolicy>,
+    /// Overrides [`RewriteOptions::chunk_policy`].
+    pub chunk_policy: Option<ChunkPolicy>,
+    /// Overrides [`RewriteOptions::strip_comments`].
+    pub strip_comments: Option<bool>,
+    /// Overrides [`RewriteOptions::picture`].
+    #[serde(deserialize_with = "present")]
+    pub picture: Option<Option<PictureSources>>,
+    /// Overrides [`RewriteOptions::csp_nonce`].
+    #[serde(deserialize_with = "present")]
+    pub csp_nonce: Option<Option<String>>,
+    /// Overrides [`RewriteOptions::heading_ids`].
+    pub heading_ids: Option<Vec<Option<String>>>,
+    /// Overrides [`RewriteOptions::collect_internal_links`].
+    pub collect_internal_links: Option<bool>,
+    /// Overrides [`RewriteOptions::rules`].
+    pub rules: Option<Vec<RewriteRule>>,
+}
+
+impl RewriteOverride {
+    fn apply_to(&self, options: &mut RewriteOptions) {
+        let Self {
+            sanitize,
+            enforce_img_loading_lazy,
+            enforce_iframe_loading_lazy,
+            enforce_video_preload_none,
+            harden_links,
+            external_links,
+            data_uri_images,
+            chunk_policy,
+            strip_comments,
+            picture,
+            csp_nonce,
+            heading_ids,
+            collect_internal_links,
+            rules,
+        } = self;
+        set(&mut options.sanitize, sanitize);
+        set(
+            &mut options.enforce_img_loading_lazy,
+            enforce_img_loading_lazy,
+        );
+        set(
+            &mut options.enforce_iframe_loading_lazy,
+            enforce_iframe_loading_lazy,
+        );
+        set(
+            &mut options.enforce_video_preload_none,
+            enforce_video_preload_none,
+        );
+        set(&mut options.harden_links, harden_links);
+        set(&mut options.external_links, external_links);
+        set(&mut options.data_uri_images, data_uri_images);
+        set(&mut options.chunk_policy, chunk_policy);
+        set(&mut options.strip_comments, strip_comments);
+        set(&mut options.picture, picture);
+        set(&mut options.csp_nonce, csp_nonce);
+        set(&mut options.heading_ids, heading_ids);
+        set(&mut options.collect_internal_links, collect_internal_links);
+        set(&mut options.rules, rules);
+    }
+}
+
+/// Fields of [`Limits`] to override, see [`OptionsOverride`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsOverride {
+    /// Overrides [`Limits::max_input_bytes`].
+    #[serde(deserialize_with = "present")]
+    pub max_input_bytes: Option<Option<usize>>,
+    /// Overrides [`Limits::max_nesting_depth`].
+    #[serde(deserialize_with = "present")]
+    pub max_nesting_depth: Option<Option<usize>>,
+    /// Overrides [`Limits::max_output_bytes`].
+    #[serde(deserialize_with = "present")]
+    pub max_output_bytes: Option<Option<usize>>,
+}
+
+impl LimitsOverride {
+    fn apply_to(&self, options: &mut Limits) {
+        let Self {
+            max_input_bytes,
+            max_nesting_depth,
+            max_output_bytes,
+        } = self;
+        set(&mut options.max_input_bytes, max_input_bytes);
+        set(&mut options.max_nesting_depth, max_nesting_depth);
+        set(&mut options.max_output_bytes, max_output_bytes);
+    }
+}
+
+fn set<T: Clone>(field: &mut T, value: &Option<T>) {
+    if let Some(value) = value {
+        *field = value.clone();
+    }
+}
+
+/// Reads a present field, including `null`, as `Some`, so `null` clears an optional option set
+/// while an absent field (filled in by `#[serde(default)]`) leaves it alone.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+fn to_json(options: &RenderOptions) -> Value {
+    serde_json::to_value(options).expect("options serialize to JSON")
+}
+
+fn layer(base: &mut Value, overrides: &Value, defaults: &Value) {
+    let (Value::Object(base), Value::Object(overrides), Value::Object(defaults)) =
+        (base, overrides, defaults)
+    else {
+        return;
+    };
+    for (key, value) in overrides {
+        let default = defaults.get(key).unwrap_or(&Value::Null);
+        match base.get_mut(key) {
+            Some(current) if is_struct(current) && is_struct(value) && is_struct(default) => {
+                layer(current, value, default);
+            }
+            _ if value != default => {
+                base.insert(key.clone(), value.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn compare(path: &str, from: &Value, to: &Value, changes: &mut Vec<OptionChange>) {
+    if from == to {
+        return;
+    }
+    match (from, to) {
+        (Value::Object(from_fields), Value::Object(to_fields))
+            if is_struct(from) && is_struct(to) =>
+        {
+            for (key, value) in from_fields {
+                let to = to_fields.get(key).unwrap_or(&Value::Null);
+                compare(&child_path(path, key), value, to, changes);
+            }
+            for (key, value) in fields_only_in(to_fields, from_fields) {
+                compare(&child_path(path, key), &Value::Null, value, changes);
+            }
+        }
+        _ => changes.push(OptionChange {
+            path: path.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        }),
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn fields_only_in<'a>(
+    fields: &'a Map<String, Value>,
+    other: &'a Map<String, Value>,
+) -> impl Iterator<Item = (&'a String, &'a Value)> {
+    fields.iter().filter(|(key, _)| !other.contains_key(*key))
+}
+
+/// Whether `value` is a serialized struct rather than an enum variant with data, which
+/// serializes as a single-key object named after the variant.
+fn is_struct(value: &Value) -> bool {
+    match value {
+        Value::Object(fields) => {
+            fields.len() != 1
+                || fields
+                    .keys()
+                    .all(|key| !key.starts_with(|ch: char| ch.is_ascii_uppercase()))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DataUriPolicy, FootnoteStyle, HtmlOptions, OptionsOverride, Profile, RenderOptions,
+        RewriteOptions, SyntaxOptions, SyntaxOverride, UrlRewriter, WikiLinkOptions,
+    };
+
+    #[test]
+    fn merges_layers_field_by_field() {
+        let global = Profile::Docs.options();
+        let collection = RenderOptions {
+            html: HtmlOptions {
+                data_uri_images: DataUriPolicy::MaxBytes(1024),
+                ..HtmlOptions::default()
+            },
+            wiki_links: Some(WikiLinkOptions::default()),
+            ..RenderOptions::default()
+        };
+        let frontmatter = RenderOptions {
+            syntax: SyntaxOptions {
+                highlight: true,
+                ..SyntaxOptions::default()
+            },
+            footnote_style: FootnoteStyle::Sidenotes,
+            ..RenderOptions::default()
+        };
+
+        let merged = global.merge(&collection).merge(&frontmatter);
+
+        assert!(merged.syntax.heading_attributes, "kept from the profile");
+        assert!(merged.syntax.highlight);
+        assert!(merged.html.heading_anchor_links && merged.html.code_meta);
+        assert_eq!(merged.html.data_uri_images, DataUriPolicy::MaxBytes(1024));
+        assert_eq!(merged.wiki_links, Some(WikiLinkOptions::default()));
+        assert_eq!(merged.footnote_style, FootnoteStyle::Sidenotes);
+        assert!(merged.toc);
+        assert_eq!(global.merge(&RenderOptions::default()), global);
+    }
+
+    #[test]
+    fn merge_keeps_url_rewriters() {
+        let with_rewriter = |rewriter: &UrlRewriter| RenderOptions {
+            rewrite: RewriteOptions {
+                rewrite_urls: Some(rewriter.clone()),
+                ..RewriteOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let base = UrlRewriter::new(|_| None);
+        let layer = UrlRewriter::new(|url| Some(url.to_string()));
+
+        let merged = with_rewriter(&base).merge(&Profile::Docs.options());
+        assert_eq!(merged.rewrite.rewrite_urls, Some(base.clone()));
+        let merged = with_rewriter(&base).merge(&with_rewriter(&layer));
+        assert_eq!(merged.rewrite.rewrite_urls, Some(layer.clone()));
+        let merged = RenderOptions::default().merge(&with_rewriter(&layer));
+        assert_eq!(merged.rewrite.rewrite_urls, Some(layer));
+    }
+
+    #[test]
+    fn applies_overrides_that_turn_options_off() {
+        let docs = Profile::Docs.options();
+        let overrides = OptionsOverride {
+            syntax: SyntaxOverride {
+                heading_attributes: Some(false),
+                ..SyntaxOverride::default()
+            },
+            toc: Some(false),
+            ..OptionsOverride::default()
+        };
+
+        let applied = docs.apply(&overrides);
+        assert!(!applied.syntax.heading_attributes && !applied.toc);
+        assert!(applied.syntax.highlight == docs.syntax.highlight && applied.html.code_meta);
+        assert_eq!(docs.apply(&OptionsOverride::default()), docs);
+
+        let frontmatter: OptionsOverride = serde_json::from_str(
+            r#"{"html": {"code_meta": false}, "wiki_links": null, "footnote_style": "Sidenotes"}"#,
+        )
+        .unwrap();
+        let with_wiki_links = RenderOptions {
+            wiki_links: Some(WikiLinkOptions::default()),
+            ..docs.clone()
+        };
+        let applied = with_wiki_links.apply(&frontmatter);
+        assert!(!applied.html.code_meta && applied.html.heading_anchor_links);
+        assert_eq!(applied.wiki_links, None);
+        assert_eq!(applied.footnote_style, FootnoteStyle::Sidenotes);
+        assert!(serde_json::from_str::<OptionsOverride>(r#"{"tco": true}"#).is_err());
+    }
+
+    #[test]
+    fn overrides_cover_every_option() {
+        let docs = Profile::Docs.options();
+        let everything: OptionsOverride =
+            serde_json::from_value(serde_json::to_value(&docs).unwrap()).unwrap();
+        assert_eq!(Profile::Untrusted.options().apply(&everything), docs);
+    }
+
+    #[test]
+    fn lists_changed_fields() {
+        let changes: Vec<String> = Profile::Default
+            .options()
+            .diff(&Profile::Untrusted.options())
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            changes,
+            [
+                "html.escape_raw_html: false -> true",
+                "html.sanitize_urls: false -> true",
+                "limits.max_input_bytes: null -> 65536",
+                "limits.max_nesting_depth: null -> 32",
+                "limits.max_output_bytes: null -> 1048576",
+                "rewrite.harden_links: \"Off\" -> \"UserContent\"",
+                "syntax.footnotes: true -> false",
+                "syntax.frontmatter: true -> false",
+                "syntax.math: true -> false",
+            ]
+        );
+        assert!(
+            RenderOptions::default()
+                .diff(&RenderOptions::default())
+                .is_empty()
+        );
+    }
+}
@@ -3,52 +3,178 @@
 
 /// Markdown event to `io::Write` bridge utilities.
 pub mod adapter;
+/// Absolute heading anchor URLs.
+pub mod anchors;
+/// Local asset manifest extraction.
+pub mod assets;
+/// Typed document tree export.
+pub mod ast;
+#[cfg(all(feature = "tokio", feature = "rewriter"))]
+pub mod async_rewriter;
+/// Parser backend selection.
+pub mod backend;
+/// Rendering sets of documents with shared options and cross-document registries.
+pub mod batch;
+/// Block-level HTML caching hooks.
+pub mod block_cache;
+/// Enclosing headings of a source position.
+pub mod breadcrumbs;
+/// Fenced code block extraction.
+pub mod code_blocks;
+/// Rendering one input through both parser backends to find where they disagree.
+pub mod compare;
+/// Non-fatal document problems with source positions.
+pub mod diagnostics;
 /// Core event types that decouple Markflow from pulldown-cmark specifics.
 #[allow(missing_docs)]
 pub mod event;
+/// Footnote layout transforms.
+pub mod footnotes;
+/// Append-only Markdown input, parsed block by block.
+pub mod incremental;
+/// Resource limits for untrusted input.
+pub mod limits;
+/// CommonMark serialization of the event stream.
+pub mod markdown_renderer;
+/// mdast-compatible JSON export.
+pub mod mdast;
+/// Plugin hooks around the render pipeline.
+pub mod plugin;
+/// Named option presets.
+pub mod profile;
+/// One-shot rendering with metadata collection.
+pub mod render;
+/// Streaming rewriter options, available without the `rewriter` feature.
+pub mod rewrite_options;
+/// Script composition statistics for CJK-aware layout.
+pub mod script_stats;
+/// Block-aligned HTML chunks for chunked transfer and server-sent events.
+pub mod server_stream;
+/// Heading slugs and heading extraction.
+pub mod slug;
+/// Typographic punctuation passes over text events.
+pub mod smart_punct;
+/// Source positions.
+pub mod span;
+#[cfg(feature = "rewriter")]
 pub mod streaming_rewriter;
+/// Table of contents rendering.
+pub mod toc;
+/// Arrow, fraction, and symbol replacements.
+pub mod typographer;
+/// URL safety checks.
+pub mod urls;
+/// UTF-8 boundary buffering for chunked output.
+pub mod utf8_chunks;
+/// `[[Target]]` wiki links.
+pub mod wiki_links;
 
+mod callouts;
+mod csv_tables;
+mod details;
+mod error;
+mod figures;
 mod html_renderer;
+mod image_size;
+mod inline_footnotes;
+mod keyboard;
+mod layering;
+mod marks;
+mod ruby;
+mod shortcodes;
+#[cfg(test)]
+mod spec_harness;
+mod text_renderer;
+mod text_runs;
 
-pub use adapter::MarkdownStream;
-pub use streaming_rewriter::{RewriteOptions, StreamingRewriter};
+pub use adapter::{EventFilter, FilteredStream, MarkdownStream};
+pub use anchors::{AnchorOptions, HeadingAnchor};
+pub use assets::{Asset, AssetCollector, AssetKind};
+pub use ast::{AstKind, AstNode};
+#[cfg(all(feature = "tokio", feature = "rewriter"))]
+pub use async_rewriter::AsyncStreamingRewriter;
+pub use backend::{ParserBackend, SyntaxOptions};
+pub use batch::{
+    BatchDiagnostic, BatchDocument, BatchIssue, BatchRenderer, BatchResult, Document, DocumentLink,
+    SlugRegistry,
+};
+pub use block_cache::{BlockCache, IncrementalRenderer};
+pub use breadcrumbs::{Breadcrumb, breadcrumbs_for_offset};
+pub use code_blocks::{CodeBlock, CodeMeta, extract_code_blocks};
+pub use compare::{BackendComparison, HtmlDifference, compare_backends, compare_backends_with};
+pub use diagnostics::{Diagnostic, DiagnosticKind, Diagnostics};
+pub use error::MarkflowError;
+pub use figures::{FigureLabelCollision, FigureOptions};
+pub use footnotes::FootnoteStyle;
+pub use html_renderer::{HtmlOptions, ImageFigures, MdxExpressionOutput, MdxJsxOutput};
+pub use incremental::IncrementalParser;
+pub use layering::{
+    HtmlOverride, LimitsOverride, OptionChange, OptionsOverride, RewriteOverride, SyntaxOverride,
+};
+pub use limits::{LimitExceeded, LimitKind, Limits};
+pub use markdown_renderer::{MarkdownOptions, MarkdownRenderer, format};
+pub use plugin::{ImageAttributes, ImageTransformer, MarkflowPlugin, Pipeline};
+pub use profile::Profile;
+pub use render::{
+    RenderOptions, RenderResult, prewarm, render, render_ast, render_cached, render_mdast,
+    render_text,
+};
+pub use rewrite_options::{
+    ChunkPolicy, ExternalLinks, InternalLink, LinkHardening, PictureSources, RewriteAction,
+    RewriteOptions, RewriteRule, RewriteStats, RewriteThroughput, SanitizePolicy, TextRewrite,
+    UrlRewriter,
+};
+pub use script_stats::{Script, ScriptStats};
+pub use server_stream::{HtmlChunks, SseFrames, sse_frame};
+pub use slug::{Heading, HeadingCollector, SlugStyle, Slugger, slugify, slugify_github};
+pub use smart_punct::{CjkLocale, CjkPunctuation, QuoteStyle, SmartPunctuation};
+pub use span::SourceSpan;
+#[cfg(feature = "rewriter")]
+pub use streaming_rewriter::{
+    ImagePlaceholder, RewriterFactory, StreamingRewriter, StreamingRewriterBuilder,
+};
+pub use typographer::Typographer;
+pub use urls::{BlockedDataUri, DataUriPolicy};
+pub use utf8_chunks::Utf8ChunkWriter;
+pub use wiki_links::WikiLinkOptions;
 
-use thiserror::Error;
+use std::io::{Read, Write};
 
 mod markdown_adapter;
-
-/// Errors that can occur during Markdown processing.
-#[derive(Debug, Error)]
-pub enum MarkflowError {
-    /// IO error during streaming.
-    #[error("IO error: {0}")]
-    IoError(#[from] std::io::Error),
-    /// UTF-8 encoding error.
-    #[error("Encoding error: {0}")]
-    EncodingError(#[from] std::string::FromUtf8Error),
-    /// markdown-rs parser error surfaced through the adapter.
-    #[error("markdown-rs error: {0}")]
-    MarkdownAdapter(String),
-}
+mod pulldown_adapter;
 
 /// Returns an iterator over Markdown events backed by `markdown-rs`.
 pub fn get_event_iterator(
     input: &str,
 ) -> Result<markdown_adapter::MarkdownRsEventIter, MarkflowError> {
-    markdown_adapter::MarkdownRsEventIter::new(input)
-        .map_err(|err| MarkflowError::MarkdownAdapter(err.to_string()))
+    markdown_adapter::MarkdownRsEventIter::new(input).map_err(MarkflowError::parse)
 }
 
 /// parses Markdown and rewrites the resulting HTML stream with the default rewrite options.
 pub fn parse(input: &str) -> Result<String, MarkflowError> {
-    let events = get_event_iterator(input)?;
-    let rewriter = StreamingRewriter::new(Vec::new(), RewriteOptions::default());
+    render(input, &RenderOptions::default()).map(|result| result.html)
+}
 
-    let rewriter = events.stream_to_writer(rewriter)?;
+/// Same as [`parse`] for Markdown read from `reader`, which is consumed in chunks and rendered
+/// block by block instead of being loaded whole. References resolve as in [`parse`]: a block
+/// that uses a link label with no definition yet is held back, with the blocks after it, until
+/// the definition arrives, and from the first footnote on the rest of the input is held and
+/// rendered at the end. Documents that reference definitions far ahead, or that use footnotes,
+/// therefore hold that part of the input in memory, and so does text in brackets that only
+/// looks like a reference, such as `[sic]`. At most 1 MiB is held: past that the held blocks
+/// are rendered, and references in them to definitions further on stay literal text. A reader
+/// failure or input that is not UTF-8 is reported as [`MarkflowError::Input`].
+pub fn parse_reader(reader: impl Read) -> Result<String, MarkflowError> {
+    Ok(String::from_utf8(parse_reader_to_writer(
+        reader,
+        Vec::new(),
+    )?)?)
+}
 
-    let output = rewriter.into_inner()?;
-    let string = String::from_utf8(output)?;
-    Ok(string)
+/// Same as [`parse_reader`], writing the HTML to `writer` as each block is rendered so neither
+/// the input nor the output is held in memory whole. Returns the writer on success.
+pub fn parse_reader_to_writer<W: Write>(reader: impl Read, writer: W) -> Result<W, MarkflowError> {
+    incremental::render_reader(reader, writer)
 }
 
 /// Iterator alias so callers don't need to depend on the adapter module path.
@@ -81,6 +207,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(all(feature = "tables", feature = "math"))]
     fn test_parse_table_alignment_and_math() {
         let input = "| A | B |\n|:-|:-:|\n| $x$ | $$y$$ |";
         let output = parse(input).unwrap();
@@ -94,10 +221,992 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "tables")]
+    fn test_parse_table_header_row() {
+        let input = "| A | B |\n|---|---|\n| 1 | 2 |";
+        let output = parse(input).unwrap();
+        assert!(output.contains("<thead><tr><th>A</th><th>B</th></tr>\n</thead>"));
+        assert!(
+            output.contains("</thead>\n<tbody>\n<tr><td>1</td><td>2</td></tr>\n</tbody>\n</table>")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn test_render_table_wrapper() {
+        let input = "| A |\n|---|\n| 1 |";
+        let cases = [
+            (
+                Some("table-wrapper"),
+                "<div class=\"table-wrapper\"><table><thead>",
+                "</table>\n</div>\n",
+            ),
+            (Some("a\"b"), "<div class=\"a&quot;b\"><table>", "</div>\n"),
+            (None, "<table><thead>", "</tbody>\n</table>\n"),
+        ];
+
+        for (class, start, end) in cases {
+            let options = RenderOptions {
+                html: HtmlOptions {
+                    table_wrapper_class: class.map(str::to_string),
+                    ..HtmlOptions::default()
+                },
+                ..RenderOptions::default()
+            };
+            let html = render(input, &options).unwrap().html;
+            assert!(html.starts_with(start), "{class:?}: {html}");
+            assert!(html.ends_with(end), "{class:?}: {html}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn test_render_table_colgroup() {
+        let input = "| A | B | C | D |\n|:--|:-:|--:|---|\n| 1 | 2 | 3 | 4 |";
+        let options = RenderOptions {
+            html: HtmlOptions {
+                table_colgroup: true,
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render(input, &options).unwrap().html;
+
+        assert!(html.starts_with(
+            "<table><colgroup><col class=\"align-left\" /><col class=\"align-center\" />\
+             <col class=\"align-right\" /><col /></colgroup><thead><tr><th>A</th>"
+        ));
+        assert!(html.contains("<tr><td>1</td><td>2</td><td>3</td><td>4</td></tr>"));
+        assert!(!html.contains("style="));
+    }
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn test_render_table_header_ids() {
+        let input = "| A | B |\n|---|--:|\n| 1 | 2 |\n\n| C |\n|---|\n| 3 |";
+        let options = RenderOptions {
+            html: HtmlOptions {
+                table_header_ids: Some(2),
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render(input, &options).unwrap().html;
+
+        assert!(html.contains("<th id=\"table-1-col-1\">A</th>"));
+        assert!(html.contains("<th id=\"table-1-col-2\" style=\"text-align:right\">B</th>"));
+        assert!(html.contains("<td headers=\"table-1-col-1\">1</td>"));
+        assert!(html.contains("<td headers=\"table-1-col-2\" style=\"text-align:right\">2</td>"));
+        // Single-column tables stay below the threshold.
+        assert!(html.contains("<th>C</th>"));
+        assert!(html.contains("<td>3</td>"));
+    }
+
+    #[test]
+    fn test_render_enforces_limits() {
+        let cases: [(&str, Limits, LimitKind); 3] = [
+            (
+                "# Too long",
+                Limits {
+                    max_input_bytes: Some(4),
+                    ..Limits::default()
+                },
+                LimitKind::InputBytes,
+            ),
+            (
+                "> > > > > deep",
+                Limits {
+                    max_nesting_depth: Some(3),
+                    ..Limits::default()
+                },
+                LimitKind::NestingDepth,
+            ),
+            (
+                "[a]: https://example.com\n\n[a] [a] [a] [a] [a] [a]",
+                Limits {
+                    max_output_bytes: Some(32),
+                    ..Limits::default()
+                },
+                LimitKind::OutputBytes,
+            ),
+        ];
+
+        for (input, limits, expected) in cases {
+            let options = RenderOptions {
+                limits,
+                ..RenderOptions::default()
+            };
+            match render(input, &options) {
+                Err(MarkflowError::Limit { source: err, .. }) => assert_eq!(err.kind, expected),
+                other => panic!("expected {expected} limit for {input:?}, got {other:?}"),
+            }
+        }
+
+        let relaxed = RenderOptions {
+            limits: Limits {
+                max_input_bytes: Some(1024),
+                max_nesting_depth: Some(8),
+                max_output_bytes: Some(1024),
+            },
+            ..RenderOptions::default()
+        };
+        assert!(render("> > > > > deep", &relaxed).is_ok());
+    }
+
+    #[test]
+    fn test_render_cjk_punctuation() {
+        let input = "彼は\"はい\"と言った...\n\nShe said \"yes\"...";
+        let options = RenderOptions {
+            cjk_punctuation: Some(CjkPunctuation::default()),
+            ..RenderOptions::default()
+        };
+        let html = render(input, &options).unwrap().html;
+
+        assert!(html.contains("<p>彼は「はい」と言った……</p>"));
+        assert!(html.contains("<p>She said &quot;yes&quot;...</p>"));
+    }
+
+    #[test]
+    #[cfg(feature = "footnotes")]
+    fn test_render_sidenotes() {
+        let input = "Claim.[^src]\n\n[^src]: Primary *source*.";
+        let options = RenderOptions {
+            footnote_style: FootnoteStyle::Sidenotes,
+            ..RenderOptions::default()
+        };
+        let html = render(input, &options).unwrap().html;
+
+        assert!(html.contains(
+            "<p>Claim.<label for=\"sn-src\" class=\"margin-toggle sidenote-number\"></label>"
+        ));
+        assert!(html.contains("<span class=\"sidenote\">Primary <em>source</em>.</span></p>"));
+        assert!(!html.contains("<section class=\"footnote\""));
+
+        let blocks =
+            "Claim.[^n]\n\n[^n]: Intro.\n\n    - one\n    - two\n\n    ```\n    code\n    ```\n";
+        let html = render(blocks, &options).unwrap().html;
+        assert!(html.contains(
+            "<span class=\"sidenote\">Intro.<br />\none<br />\ntwo<br />\n<code>code</code></span></p>"
+        ));
+
+        let escaped = RenderOptions {
+            html: HtmlOptions {
+                escape_raw_html: true,
+                ..HtmlOptions::default()
+            },
+            ..options.clone()
+        };
+        let html = render("Claim.[^n]\n\n[^n]: A <b>", &escaped).unwrap().html;
+        assert_eq!(
+            html,
+            "<p>Claim.<label for=\"sn-n\" class=\"margin-toggle sidenote-number\"></label>\
+             <input type=\"checkbox\" id=\"sn-n\" class=\"margin-toggle\" />\
+             <span class=\"sidenote\">A &lt;b&gt;</span></p>\n"
+        );
+
+        let default_html = parse(input).unwrap();
+        assert!(default_html.contains("<section class=\"footnote\" id=\"fn-src\">"));
+    }
+
+    #[test]
+    #[cfg(feature = "footnotes")]
+    fn test_render_footnote_backrefs() {
+        let cases = [
+            (
+                "A[^a]\n\n[^a]: Note.",
+                Some("↩"),
+                "<section class=\"footnote\" id=\"fn-a\"><p>Note. <a href=\"#fnref-a\" class=\"footnote-backref\">↩</a></p>\n</section>\n",
+            ),
+            (
+                "A[^a]\n\n[^a]: First.\n\n        code",
+                Some("<back>"),
+                "</code></pre>\n <a href=\"#fnref-a\" class=\"footnote-backref\">&lt;back&gt;</a></section>\n",
+            ),
+            (
+                "A[^a]\n\n[^a]: Note.",
+                None,
+                "<section class=\"footnote\" id=\"fn-a\"><p>Note.</p>\n</section>\n",
+            ),
+            (
+                "A[^a\"onx=1]\n\n[^a\"onx=1]: Note.",
+                Some("↩"),
+                "<a href=\"#fnref-a&quot;onx=1\" class=\"footnote-backref\">↩</a>",
+            ),
+            (
+                "A[^a\"<i>]\n\n[^a\"<i>]: Note.",
+                None,
+                "<section class=\"footnote\" id=\"fn-a&quot;&lt;i&gt;\">",
+            ),
+            (
+                "A[^a\"<i>]\n\n[^a\"<i>]: Note.",
+                None,
+                "<a href=\"#fn-a&quot;&lt;i&gt;\" id=\"fnref-a&quot;&lt;i&gt;\">a&quot;&lt;i&gt;</a>",
+            ),
+        ];
+
+        for (input, label, expected) in cases {
+            let options = RenderOptions {
+                html: HtmlOptions {
+                    footnote_backref: label.map(str::to_string),
+                    ..HtmlOptions::default()
+                },
+                ..RenderOptions::default()
+            };
+            let html = render(input, &options).unwrap().html;
+            assert!(html.contains(expected), "{input:?} => {html}");
+        }
+    }
+
+    #[test]
+    fn test_render_number_headings() {
+        let input = "# Intro\n\n## Setup\n\n#### Deep\n\n### Step\n\n## Use\n\n# Next";
+        let options = RenderOptions {
+            number_headings: true,
+            headings: true,
+            toc: true,
+            ..RenderOptions::default()
+        };
+        let result = render(input, &options).unwrap();
+
+        assert!(result.html.starts_with(
+            "<h1 id=\"intro\" data-number=\"1\"><span class=\"heading-number\">1</span> Intro</h1>"
+        ));
+        let numbers: Vec<_> = result
+            .headings
+            .iter()
+            .map(|heading| (heading.number.as_deref().unwrap(), heading.text.as_str()))
+            .collect();
+        assert_eq!(
+            numbers,
+            [
+                ("1", "Intro"),
+                ("1.1", "Setup"),
+                ("1.1.1", "Deep"),
+                ("1.1.2", "Step"),
+                ("1.2", "Use"),
+                ("2", "Next"),
+            ]
+        );
+        assert!(
+            result
+                .toc
+                .unwrap()
+                .contains("<a href=\"#setup\"><span class=\"heading-number\">1.1</span> Setup</a>")
+        );
+
+        let escaped = RenderOptions {
+            html: HtmlOptions {
+                escape_raw_html: true,
+                ..HtmlOptions::default()
+            },
+            ..options
+        };
+        assert!(render(input, &escaped).unwrap().html.starts_with(
+            "<h1 id=\"intro\" data-number=\"1\"><span class=\"heading-number\">1</span> Intro</h1>"
+        ));
+
+        let plain = render(input, &RenderOptions::default()).unwrap().html;
+        assert!(!plain.contains("heading-number"));
+    }
+
+    #[test]
+    #[cfg(feature = "footnotes")]
+    fn test_render_id_prefix() {
+        let input = "# Intro\n\nSee [below](#intro) and [site](/#top).[^a]\n\n\
+                     ![Chart](c.png){#fig:chart}\n\nAs @fig:chart shows.\n\n\
+                     | a | b |\n|---|---|\n| 1 | 2 |\n\n```rust {#main}\nx\n```\n\n[^a]: Note.";
+        let cases = [
+            (
+                FootnoteStyle::Definitions,
+                vec![
+                    "<h1 id=\"doc1-intro\"><a class=\"heading-anchor\" href=\"#doc1-intro\"",
+                    "<a href=\"#doc1-intro\">below</a> and <a href=\"/#top\">site</a>",
+                    "<a href=\"#doc1-fn-a\" id=\"doc1-fnref-a\">a</a>",
+                    "<section class=\"footnote\" id=\"doc1-fn-a\"><p>Note. <a href=\"#doc1-fnref-a\"",
+                    "<figure id=\"doc1-fig:chart\">",
+                    "As <a href=\"#doc1-fig:chart\">Figure 1</a> shows.",
+                    "<th id=\"doc1-table-1-col-1\">a</th>",
+                    "<td headers=\"doc1-table-1-col-1\">1</td>",
+                    "<pre id=\"doc1-main\"><code class=\"language-rust\">",
+                ],
+            ),
+            (
+                FootnoteStyle::Gfm,
+                vec![
+                    "<a href=\"#doc1-fn-1\" id=\"doc1-fnref-1\" data-footnote-ref>1</a>",
+                    "<li id=\"doc1-fn-1\">",
+                    "<a href=\"#doc1-fnref-1\" class=\"footnote-backref\" data-footnote-backref",
+                ],
+            ),
+            (
+                FootnoteStyle::Sidenotes,
+                vec!["<input type=\"checkbox\" id=\"doc1-sn-a\" class=\"margin-toggle\" />"],
+            ),
+        ];
+
+        for (footnote_style, expected) in cases {
+            let options = RenderOptions {
+                html: HtmlOptions {
+                    heading_anchor_links: true,
+                    table_header_ids: Some(2),
+                    footnote_backref: Some("↩".to_string()),
+                    ..HtmlOptions::default()
+                },
+                syntax: SyntaxOptions {
+                    heading_attributes: true,
+                    ..SyntaxOptions::default()
+                },
+                footnote_style,
+                figures: Some(FigureOptions::default()),
+                toc: true,
+                id_prefix: "doc1-".to_string(),
+                ..RenderOptions::default()
+            };
+            let result = render(input, &options).unwrap();
+            for fragment in expected {
+                assert!(
+                    result.html.contains(fragment),
+                    "{footnote_style:?}: missing {fragment:?} in {}",
+                    result.html
+                );
+            }
+            assert_eq!(
+                result.toc.as_deref(),
+                Some("<ul><li><a href=\"#doc1-intro\">Intro</a></li></ul>")
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_strips_comments() {
+        let input =
+            "<!-- draft\nnotes -->\n\nText <!-- inline --> here.\n\n<div>\n<!-- a -->b\n</div>";
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                html: HtmlOptions {
+                    strip_comments: true,
+                    ..HtmlOptions::default()
+                },
+                ..RenderOptions::default()
+            };
+            let html = render(input, &options).unwrap().html;
+            assert!(!html.contains("<!--"), "{backend:?}: {html}");
+            assert!(!html.contains("notes"), "{backend:?}: {html}");
+            assert!(html.contains("<p>Text  here.</p>"), "{backend:?}: {html}");
+            assert!(html.contains("<div>\nb\n</div>"), "{backend:?}: {html}");
+
+            let html = render(
+                "<!-->a <!--->b\n\n<div>\n<!-- open\n</div>\n\n<p>kept</p>",
+                &options,
+            )
+            .unwrap()
+            .html;
+            assert!(html.contains("a b"), "{backend:?}: {html}");
+            assert!(html.contains("<p>kept</p>"), "{backend:?}: {html}");
+            assert!(!html.contains("open"), "{backend:?}: {html}");
+        }
+    }
+
+    #[test]
+    fn test_render_normalizes_nfc() {
+        let input =
+            "# Cafe\u{301}\n\nre\u{301}sume\u{301} [top](#cafe\u{301})\n\n```\ne\u{301}\n```";
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                normalize_nfc: true,
+                ..RenderOptions::default()
+            };
+            let html = render(input, &options).unwrap().html;
+            assert_eq!(
+                html,
+                "<h1 id=\"caf\u{e9}\">Caf\u{e9}</h1>\n\
+                 <p>r\u{e9}sum\u{e9} <a href=\"#caf\u{e9}\">top</a></p>\n\
+                 <pre><code>e\u{301}</code></pre>\n",
+                "{backend:?}"
+            );
+
+            let options = RenderOptions {
+                backend,
+                ..RenderOptions::default()
+            };
+            let html = render(input, &options).unwrap().html;
+            assert!(
+                html.starts_with("<h1 id=\"cafe\">Cafe\u{301}</h1>"),
+                "{backend:?}: {html}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_ast_json() {
+        let input = "# Title\n\n- [x] **done** [link](/a \"T\")\n\n```rust\nfn main() {}\n```\n\n![alt *text*](i.png)";
+        let tree = render_ast(input, &RenderOptions::default()).unwrap();
+        let json = tree.to_json();
+
+        assert_eq!(json["type"], "document");
+        assert_eq!(json["children"][0]["type"], "heading");
+        assert_eq!(json["children"][0]["attrs"]["level"], 1);
+        assert_eq!(json["children"][0]["attrs"]["id"], "title");
+        assert_eq!(json["children"][0]["children"][0]["value"], "Title");
+
+        let item = &json["children"][1]["children"][0];
+        assert_eq!(item["children"][0]["type"], "taskListMarker");
+        assert_eq!(item["children"][0]["attrs"]["checked"], true);
+        assert_eq!(item["children"][1]["type"], "strong");
+        assert_eq!(item["children"][3]["attrs"]["url"], "/a");
+        assert_eq!(item["children"][3]["attrs"]["title"], "T");
+
+        assert_eq!(json["children"][2]["attrs"]["lang"], "rust");
+        assert_eq!(json["children"][2]["value"], "fn main() {}");
+        assert_eq!(
+            json["children"][3]["children"][0]["attrs"]["alt"],
+            "alt text"
+        );
+        assert_eq!(tree.children[0].text(), "Title");
+    }
+
+    #[test]
+    fn test_render_ast_code_meta() {
+        let input =
+            "```rust title=\"main.rs\" {1,3-5} no-run\nfn main() {}\n```\n\n```\nplain\n```";
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                ..RenderOptions::default()
+            };
+            let json = render_ast(input, &options).unwrap().to_json();
+            let attrs = &json["children"][0]["attrs"];
+            assert_eq!(attrs["lang"], "rust", "{backend:?}");
+            assert_eq!(attrs["meta"], "title=\"main.rs\" {1,3-5} no-run");
+            assert_eq!(attrs["title"], "main.rs");
+            assert_eq!(attrs["highlight"], serde_json::json!([[1, 1], [3, 5]]));
+            assert!(json["children"][1]["attrs"].get("title").is_none());
+        }
+
+        let kind = event::CodeBlockKind::fenced("  js  run {2} ".into());
+        assert_eq!(kind.lang(), Some("js"));
+        assert_eq!(kind.meta(), Some("run {2}"));
+        assert!(kind.has_flag("run") && !kind.has_flag("{2}"));
+        assert_eq!(kind.code_meta().unwrap().highlight, [2..=2]);
+        assert_eq!(event::CodeBlockKind::fenced("".into()).lang(), None);
+    }
+
+    #[test]
+    fn test_render_code_lines() {
+        let options = RenderOptions {
+            html: HtmlOptions {
+                code_lines: true,
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let cases = [
+            (
+                "```rust {1,3-4}\nlet a;\nlet b;\nlet c = \"<\";\nlet d;\n```",
+                "<pre><code class=\"language-rust\"><span class=\"line highlighted\">let a;</span>\n\
+                 <span class=\"line\">let b;</span>\n\
+                 <span class=\"line highlighted\">let c = &quot;&lt;&quot;;</span>\n\
+                 <span class=\"line highlighted\">let d;</span></code></pre>\n",
+            ),
+            (
+                "    one\n    two",
+                "<pre><code><span class=\"line\">one</span>\n<span class=\"line\">two</span></code></pre>\n",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(render(input, &options).unwrap().html, expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_render_diff_blocks() {
+        let input = "```diff {2}\n--- a.rs\n+++ b.rs\n@@ -1 +1 @@\n-old <a>\n+new\n same\n```";
+        let html = render(input, &RenderOptions::default()).unwrap().html;
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-diff\">--- a.rs\n+++ b.rs\n@@ -1 +1 @@\n\
+             <span class=\"diff-del\">-old &lt;a&gt;</span>\n<span class=\"diff-add\">+new</span>\n\
+             \u{20}same</code></pre>\n"
+        );
+
+        let options = RenderOptions {
+            html: HtmlOptions {
+                code_lines: true,
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render(input, &options).unwrap().html;
+        assert!(html.contains("<span class=\"line highlighted\">+++ b.rs</span>"));
+        assert!(html.contains("<span class=\"line diff-add\">+new</span>"));
+    }
+
+    #[test]
+    fn test_render_code_figures() {
+        let input = "```js run\nif (a < b) {}\n```\n\n    plain";
+        let options = RenderOptions {
+            html: HtmlOptions {
+                code_figures: true,
+                runnable_snippets: true,
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render(input, &options).unwrap().html;
+
+        assert!(html.starts_with(
+            "<figure class=\"code-block\" data-lang=\"js\"><div class=\"runnable\" data-runnable=\"run\">\
+             <pre><code class=\"language-js\">if (a &lt; b) {}</code></pre>"
+        ));
+        assert!(html.contains(
+            "</script></div><template class=\"code-source\">if (a &lt; b) {}</template></figure>\n"
+        ));
+        assert!(html.ends_with(
+            "<figure class=\"code-block\"><pre><code>plain</code></pre>\
+             <template class=\"code-source\">plain</template></figure>\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_diagram_blocks() {
+        let input = "```mermaid\ngraph TD\n  A --> B\n```\n\n```dot\na -> b\n```";
+        let options = RenderOptions {
+            html: HtmlOptions {
+                diagram_languages: vec!["mermaid".to_string(), "plantuml".to_string()],
+                code_figures: true,
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render(input, &options).unwrap().html;
+
+        assert!(html.starts_with("<pre class=\"mermaid\">graph TD\n  A --&gt; B</pre>\n<figure"));
+        assert!(html.contains("<pre><code class=\"language-dot\">a -&gt; b</code></pre>"));
+    }
+
+    #[test]
+    fn test_render_language_aliases() {
+        let input = "```js\nx\n```\n\n```yml\na: 1\n```\n\n```mmd\ngraph\n```\n\n```cobol\n```";
+        let mut language_aliases = HtmlOptions::common_language_aliases();
+        language_aliases.insert("mmd".to_string(), "mermaid".to_string());
+        let options = RenderOptions {
+            html: HtmlOptions {
+                language_aliases,
+                diagram_languages: vec!["mermaid".to_string()],
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render(input, &options).unwrap().html;
+
+        assert!(html.contains("<code class=\"language-javascript\">x</code>"));
+        assert!(html.contains("<code class=\"language-yaml\">a: 1</code>"));
+        assert!(html.contains("<pre class=\"mermaid\">graph</pre>"));
+        assert!(html.contains("<code class=\"language-cobol\">"));
+        assert!(
+            parse(input)
+                .unwrap()
+                .contains("<code class=\"language-js\">x</code>")
+        );
+    }
+
+    #[test]
+    fn test_render_sections() {
+        let input = "Intro\n\n# A\n\ntext\n\n## B\n\n> ## Quoted\n\n### C\n\n## D\n\n# E";
+        let options = RenderOptions {
+            html: HtmlOptions {
+                sections: true,
+                heading_id_prefix: "user-content-".to_string(),
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let expected = "<p>Intro</p>\n\
+            <section id=\"user-content-a\">\n<h1>A</h1>\n<p>text</p>\n\
+            <section id=\"user-content-b\">\n<h2>B</h2>\n\
+            <blockquote><h2 id=\"user-content-quoted\">Quoted</h2>\n</blockquote>\n\
+            <section id=\"user-content-c\">\n<h3>C</h3>\n</section>\n</section>\n\
+            <section id=\"user-content-d\">\n<h2>D</h2>\n</section>\n</section>\n\
+            <section id=\"user-content-e\">\n<h1>E</h1>\n</section>\n";
+
+        assert_eq!(render(input, &options).unwrap().html, expected);
+        let mut cache = std::collections::HashMap::new();
+        for _ in 0..2 {
+            assert_eq!(
+                render_cached(input, &options, &mut cache).unwrap().html,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_render_runnable_snippets() {
+        let input = "```js run\nconsole.log(\"</script>\" < 1);\n```\n\n```js\nplain();\n```";
+        let options = RenderOptions {
+            html: HtmlOptions {
+                runnable_snippets: true,
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render(input, &options).unwrap().html;
+
+        assert!(html.contains(
+            "<div class=\"runnable\" data-runnable=\"run\"><pre><code class=\"language-js\">"
+        ));
+        assert!(html.contains(
+            "<script type=\"text/plain\" data-lang=\"js\">console.log(\"<\\/script>\" < 1);</script></div>"
+        ));
+        assert!(html.contains("<pre><code class=\"language-js\">plain();</code></pre>"));
+        assert_eq!(html.matches("data-runnable").count(), 1);
+
+        for closer in ["</SCRIPT>", "</ScRiPt >", "<!--<script>"] {
+            let input = format!("```js run\nalert(1){closer}<img src=x onerror=alert(2)>\n```");
+            let html = render(&input, &options).unwrap().html;
+            // The only script end tag is the renderer's own, after the source.
+            let lower = html.to_ascii_lowercase();
+            assert_eq!(lower.matches("</script").count(), 1, "{closer}: {html}");
+            assert!(html.ends_with("</script></div>\n"), "{closer}: {html}");
+        }
+
+        // The meta string never leaks into the language class.
+        assert!(
+            parse(input)
+                .unwrap()
+                .contains("<code class=\"language-js\">console")
+        );
+    }
+
+    #[test]
+    fn test_render_image_figures() {
+        let input = "![Chart](c.png \"Sales <2024>\")\n\n![Logo](l.png)\n\nSee ![Icon](i.png \"Icon\") here";
+        let cases = [
+            (
+                ImageFigures::Titled,
+                "<figure>\n<img src=\"c.png\" alt=\"Chart\" loading=\"lazy\" />\n\
+                 <figcaption>Sales &lt;2024&gt;</figcaption>\n</figure>\n\
+                 <p><img src=\"l.png\" alt=\"Logo\" loading=\"lazy\" /></p>\n\
+                 <p>See <img src=\"i.png\" alt=\"Icon\" title=\"Icon\" loading=\"lazy\" /> here</p>\n",
+            ),
+            (
+                ImageFigures::Standalone,
+                "<figure>\n<img src=\"c.png\" alt=\"Chart\" loading=\"lazy\" />\n\
+                 <figcaption>Sales &lt;2024&gt;</figcaption>\n</figure>\n\
+                 <figure>\n<img src=\"l.png\" alt=\"Logo\" loading=\"lazy\" />\n</figure>\n\
+                 <p>See <img src=\"i.png\" alt=\"Icon\" title=\"Icon\" loading=\"lazy\" /> here</p>\n",
+            ),
+        ];
+
+        for (image_figures, expected) in cases {
+            for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+                let options = RenderOptions {
+                    backend,
+                    html: HtmlOptions {
+                        image_figures,
+                        ..HtmlOptions::default()
+                    },
+                    ..RenderOptions::default()
+                };
+                assert_eq!(
+                    render(input, &options).unwrap().html,
+                    expected,
+                    "{image_figures:?} {backend:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_image_sizes() {
+        let input = "![A](a.png \"Chart =640x480\") ![B](b.png?w=320&h=200) \
+                     ![C](c.png \"=x90\") ![D](d.png \"=wide\")";
+        let syntax = SyntaxOptions {
+            image_sizes: true,
+            ..SyntaxOptions::default()
+        };
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                syntax,
+                ..RenderOptions::default()
+            };
+            assert_eq!(
+                render(input, &options).unwrap().html,
+                "<p><img src=\"a.png\" alt=\"A\" title=\"Chart\" width=\"640\" height=\"480\" loading=\"lazy\" /> \
+                 <img src=\"b.png?w=320&amp;h=200\" alt=\"B\" width=\"320\" height=\"200\" loading=\"lazy\" /> \
+                 <img src=\"c.png\" alt=\"C\" height=\"90\" loading=\"lazy\" /> \
+                 <img src=\"d.png\" alt=\"D\" title=\"=wide\" loading=\"lazy\" /></p>\n",
+                "{backend:?}"
+            );
+
+            let events = backend.events(input, &syntax).unwrap();
+            let markdown = MarkdownRenderer::new(Vec::new(), MarkdownOptions::default())
+                .render(events)
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(markdown).unwrap().trim_end(),
+                input,
+                "{backend:?}"
+            );
+        }
+
+        let html = render(input, &RenderOptions::default()).unwrap().html;
+        assert!(!html.contains("width="));
+        assert!(html.contains("title=\"Chart =640x480\""));
+    }
+
+    #[test]
+    #[cfg(feature = "rewriter")]
+    fn test_render_data_uri_image_policy() {
+        let small = "data:image/png;base64,AAAA";
+        let large = format!("data:image/png;base64,{}", "A".repeat(64));
+        let input = format!("![a]({small}) ![b]({large})\n\n<img src=\"{large}\">");
+        let policy = DataUriPolicy::MaxBytes(32);
+        let options = RenderOptions {
+            html: HtmlOptions {
+                data_uri_images: policy,
+                ..HtmlOptions::default()
+            },
+            rewrite: RewriteOptions {
+                data_uri_images: policy,
+                ..RewriteOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let result = render(&input, &options).unwrap();
+
+        assert!(
+            result
+                .html
+                .contains(&format!("<img src=\"{small}\" alt=\"a\""))
+        );
+        assert!(result.html.contains("<img src=\"\" alt=\"b\""));
+        assert!(!result.html.contains(&large));
+        let blocked = BlockedDataUri {
+            media_type: "image/png".into(),
+            bytes: large.len(),
+        };
+        assert_eq!(result.blocked_data_uris, [blocked.clone(), blocked]);
+        assert!(
+            render(&input, &RenderOptions::default())
+                .unwrap()
+                .html
+                .contains(&large)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "frontmatter")]
     fn test_parse_frontmatter_passthrough() {
         let input = "---\ntitle: test\n---\n\ncontent";
         let output = parse(input).unwrap();
         assert!(output.contains("frontmatter"));
         assert!(output.contains("title: test"));
     }
+
+    #[test]
+    fn test_render_collects_script_stats() {
+        let input = "# 見出し\n\n日本語と English の文章。\n\n```\ncode is ignored\n```";
+        let options = RenderOptions {
+            script_stats: true,
+            ..RenderOptions::default()
+        };
+        let result = render(input, &options).unwrap();
+        let stats = result.script_stats.expect("stats requested");
+
+        assert_eq!(stats.count(Script::Latin), 7);
+        assert_eq!(stats.dominant(), Some(Script::Han));
+        assert!(stats.cjk_ratio() > 0.5);
+        assert!(result.html.contains("<h1"));
+        assert!(
+            render(input, &RenderOptions::default())
+                .unwrap()
+                .script_stats
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_render_extracts_title() {
+        let input = "Intro\n\n# The `markflow` *guide*\n\n# Second\n\nBody";
+        let cases = [
+            (false, false, None, true),
+            (true, false, Some("The markflow guide"), true),
+            (true, true, Some("The markflow guide"), false),
+        ];
+
+        for (extract_title, remove_title, title, keeps_h1) in cases {
+            let options = RenderOptions {
+                extract_title,
+                remove_title,
+                ..RenderOptions::default()
+            };
+            let result = render(input, &options).unwrap();
+
+            assert_eq!(result.title.as_deref(), title);
+            assert_eq!(result.html.contains("guide</em></h1>"), keeps_h1);
+            assert!(result.html.contains("<p>Intro</p>"));
+            assert!(result.html.contains("Second</h1>"));
+        }
+    }
+
+    #[test]
+    fn test_render_collects_heading_anchors() {
+        let input = "# Getting Started\n\n## Install `cargo`\n\n### 見出し";
+        let options = RenderOptions {
+            anchors: Some(AnchorOptions {
+                base_url: "https://example.com/".into(),
+                path: "/docs/guide".into(),
+            }),
+            ..RenderOptions::default()
+        };
+        let anchors = render(input, &options).unwrap().anchors;
+
+        let urls: Vec<_> = anchors.iter().map(|a| a.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/docs/guide#getting-started",
+                "https://example.com/docs/guide#install-cargo",
+                "https://example.com/docs/guide#%E8%A6%8B%E5%87%BA%E3%81%97",
+            ]
+        );
+        assert_eq!(anchors[1].level, 2);
+        assert_eq!(anchors[1].text, "Install cargo");
+        assert!(
+            render(input, &RenderOptions::default())
+                .unwrap()
+                .anchors
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_render_collects_headings() {
+        let input = "# Guide\n\nIntro\n\n## Install `cargo`\n\n### 見出し";
+        let options = RenderOptions {
+            headings: true,
+            ..RenderOptions::default()
+        };
+        let result = render(input, &options).unwrap();
+
+        let headings: Vec<_> = result
+            .headings
+            .iter()
+            .map(|h| (h.level, h.text.as_str(), h.id.as_deref()))
+            .collect();
+        assert_eq!(
+            headings,
+            [
+                (1, "Guide", Some("guide")),
+                (2, "Install cargo", Some("install-cargo")),
+                (3, "見出し", Some("見出し")),
+            ]
+        );
+        assert!(result.html.contains("<h2 id=\"install-cargo\">"));
+        assert!(result.anchors.is_empty());
+    }
+
+    #[test]
+    fn test_render_task_marker_placement() {
+        let checkbox = "<input type=\"checkbox\" disabled=\"\" checked=\"\" />";
+        let cases = [
+            (
+                "- [x] done\n- [ ] todo",
+                false,
+                format!("<li>{checkbox}done</li>"),
+            ),
+            (
+                "- [x] done\n- [ ] todo",
+                true,
+                format!("<li>{checkbox}done</li>"),
+            ),
+            (
+                "- [x] done\n\n- [ ] todo",
+                false,
+                format!("<li>{checkbox}<p>done</p>"),
+            ),
+            (
+                "- [x] done\n\n- [ ] todo",
+                true,
+                format!("<li><p>{checkbox}done</p>"),
+            ),
+        ];
+
+        for (input, in_paragraph, expected) in cases {
+            let options = RenderOptions {
+                html: HtmlOptions {
+                    task_marker_in_paragraph: in_paragraph,
+                    ..HtmlOptions::default()
+                },
+                ..RenderOptions::default()
+            };
+            let html = render(input, &options).unwrap().html;
+            assert!(html.contains(&expected), "{input:?} => {html}");
+        }
+    }
+
+    #[test]
+    fn test_render_mdx_jsx_elements() {
+        let input = "<Note kind=\"tip\" open count={2} {...rest}>\n\nRead *this*.\n\n</Note>\n\nSee <Badge /> and <>frag</>.";
+        let cases = [
+            (
+                MdxJsxOutput::Verbatim,
+                "<Note kind=\"tip\" open count={2} {...rest}><p>Read <em>this</em>.</p>\n</Note><p>See <Badge /> and <>frag</>.</p>\n",
+            ),
+            (
+                MdxJsxOutput::Escape,
+                "&lt;Note kind=&quot;tip&quot; open count={2} {...rest}&gt;<p>Read <em>this</em>.</p>\n&lt;/Note&gt;<p>See &lt;Badge /&gt; and &lt;&gt;frag&lt;/&gt;.</p>\n",
+            ),
+        ];
+
+        for (mdx_jsx, expected) in cases {
+            let options = RenderOptions {
+                syntax: SyntaxOptions {
+                    mdx: true,
+                    ..SyntaxOptions::default()
+                },
+                html: HtmlOptions {
+                    mdx_jsx,
+                    ..HtmlOptions::default()
+                },
+                ..RenderOptions::default()
+            };
+            assert_eq!(render(input, &options).unwrap().html, expected);
+        }
+    }
+
+    #[test]
+    fn test_render_mdx_expressions() {
+        let input = "{/* a --> b */}\n\nHello {props.name}!";
+        let cases = [
+            (
+                MdxExpressionOutput::Passthrough,
+                "{/* a --> b */}<p>Hello {props.name}!</p>\n",
+            ),
+            (
+                MdxExpressionOutput::Comment,
+                "<!-- {/* a - -> b */} --><p>Hello <!-- {props.name} -->!</p>\n",
+            ),
+            (MdxExpressionOutput::Drop, "<p>Hello !</p>\n"),
+        ];
+
+        for (mdx_expressions, expected) in cases {
+            let options = RenderOptions {
+                syntax: SyntaxOptions {
+                    mdx: true,
+                    ..SyntaxOptions::default()
+                },
+                html: HtmlOptions {
+                    mdx_expressions,
+                    ..HtmlOptions::default()
+                },
+                ..RenderOptions::default()
+            };
+            assert_eq!(render(input, &options).unwrap().html, expected);
+        }
+    }
 }
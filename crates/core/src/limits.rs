@@ -0,0 +1,346 @@
+//! Resource limits that keep hostile documents from exhausting memory during rendering.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::event::Event;
+
+/// Upper bounds enforced by [`crate::render`]; `None` disables the corresponding check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Maximum size of the Markdown source in bytes.
+    pub max_input_bytes: Option<usize>,
+    /// Maximum number of simultaneously open container tags (blockquotes, lists, emphasis, ...).
+    pub max_nesting_depth: Option<usize>,
+    /// Maximum size of the rendered HTML in bytes.
+    pub max_output_bytes: Option<usize>,
+}
+
+/// The limit that tripped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    /// [`Limits::max_input_bytes`].
+    InputBytes,
+    /// [`Limits::max_nesting_depth`].
+    NestingDepth,
+    /// [`Limits::max_output_bytes`].
+    OutputBytes,
+}
+
+impl fmt::Display for LimitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LimitKind::InputBytes => "input size",
+            LimitKind::NestingDepth => "nesting depth",
+            LimitKind::OutputBytes => "output size",
+        })
+    }
+}
+
+/// Raised when a document exceeds one of the configured [`Limits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("{kind} limit of {limit} exceeded")]
+pub struct LimitExceeded {
+    /// Which limit tripped.
+    pub kind: LimitKind,
+    /// The configured maximum.
+    pub limit: usize,
+}
+
+impl Limits {
+    pub(crate) fn check_input(&self, input: &str) -> Result<(), LimitExceeded> {
+        match self.max_input_bytes {
+            Some(limit) if input.len() > limit => Err(LimitExceeded {
+                kind: LimitKind::InputBytes,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Rejects sources whose block containers alone already nest deeper than
+    /// [`max_nesting_depth`](Self::max_nesting_depth), before the parser builds a tree for them.
+    /// [`NestingGuard`] still checks the events, which also count inline nesting.
+    pub(crate) fn check_nesting(&self, input: &str) -> Result<(), LimitExceeded> {
+        match self.max_nesting_depth {
+            Some(limit) if container_depth(input) > limit => Err(LimitExceeded {
+                kind: LimitKind::NestingDepth,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Deepest block container nesting of any line in `input`, counting a block quote marker as one
+/// open tag and a list marker as two (list and item). Only markers written out on the line
+/// count, and lines in fenced code or HTML blocks are skipped, so the estimate never exceeds the
+/// depth of the parsed events.
+fn container_depth(input: &str) -> usize {
+    let mut max = 0;
+    // Closing fence character and minimum length of the open fenced code block.
+    let mut fence: Option<(char, usize)> = None;
+    // What ends the open HTML block: a blank line (`""`) or a closing tag.
+    let mut html_end: Option<&str> = None;
+    let mut after_blank = true;
+
+    for line in input.lines() {
+        let blank = line.trim().is_empty();
+        if let Some(end) = html_end {
+            if (end.is_empty() && blank)
+                || (!end.is_empty() && line.to_ascii_lowercase().contains(end))
+            {
+                html_end = None;
+            }
+            after_blank = blank;
+            continue;
+        }
+
+        let (depth, rest) = line_containers(line, after_blank);
+        after_blank = blank;
+        if let Some((ch, len)) = fence {
+            if fence_len(rest, ch).is_some_and(|closing| closing >= len) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(ch) = rest.chars().next().filter(|ch| matches!(ch, '`' | '~'))
+            && let Some(len) = fence_len(rest, ch)
+        {
+            fence = Some((ch, len));
+        } else if rest.starts_with('<') {
+            html_end = Some(html_block_end(rest));
+        }
+        max = max.max(depth);
+    }
+    max
+}
+
+/// Open tags from the container markers at the start of `line`, and the content after them.
+/// Stops at anything that is not plainly a marker, such as four spaces of indentation.
+fn line_containers(line: &str, after_blank: bool) -> (usize, &str) {
+    let mut depth = 0;
+    let mut rest = line;
+    loop {
+        let trimmed = rest.trim_start_matches(' ');
+        if rest.len() - trimmed.len() > 3 {
+            return (depth, rest);
+        }
+        if let Some(after) = trimmed.strip_prefix('>') {
+            depth += 1;
+            rest = after;
+        } else if let Some(after) = list_marker(trimmed, after_blank || depth > 0) {
+            depth += 2;
+            rest = after;
+        } else {
+            return (depth, trimmed);
+        }
+    }
+}
+
+/// Content after the list marker `text` starts with. Ordered markers other than `1` cannot
+/// interrupt a paragraph, so they only count when `may_start_list`, and a line that is a
+/// thematic break (`- - -`) is never a list item.
+fn list_marker(text: &str, may_start_list: bool) -> Option<&str> {
+    if is_thematic_break(text) {
+        return None;
+    }
+    let digits = text.bytes().take_while(u8::is_ascii_digit).count();
+    let marker = match text.as_bytes().get(digits) {
+        Some(b'-' | b'*' | b'+') if digits == 0 => 1,
+        Some(b'.' | b')')
+            if (1..=9).contains(&digits) && (may_start_list || &text[..digits] == "1") =>
+        {
+            digits + 1
+        }
+        _ => return None,
+    };
+    text[marker..].strip_prefix(' ')
+}
+
+fn is_thematic_break(text: &str) -> bool {
+    let mut marks = text.chars().filter(|ch| !ch.is_whitespace());
+    let Some(first) = marks.next().filter(|ch| matches!(ch, '-' | '*' | '_')) else {
+        return false;
+    };
+    let mut count = 1;
+    for ch in marks {
+        if ch != first {
+            return false;
+        }
+        count += 1;
+    }
+    count >= 3
+}
+
+/// Length of the run of at least three `ch` that `text` starts with.
+fn fence_len(text: &str, ch: char) -> Option<usize> {
+    let len = text.chars().take_while(|&c| c == ch).count();
+    (len >= 3).then_some(len)
+}
+
+/// What ends an HTML block starting with `text`: the closing tag of a raw text element, the end
+/// of a comment, or else a blank line.
+fn html_block_end(text: &str) -> &'static str {
+    let lower = text.to_ascii_lowercase();
+    for (open, close) in [
+        ("<pre", "</pre>"),
+        ("<script", "</script>"),
+        ("<style", "</style>"),
+        ("<textarea", "</textarea>"),
+        ("<!--", "-->"),
+    ] {
+        if let Some(rest) = lower.strip_prefix(open) {
+            return if rest.contains(close) { "" } else { close };
+        }
+    }
+    ""
+}
+
+/// Stops the event stream as soon as the open-tag depth exceeds the configured maximum, recording
+/// the violation in `tripped` so the caller can turn the truncated render into an error.
+pub(crate) struct NestingGuard<'t, I> {
+    inner: I,
+    depth: usize,
+    max_depth: Option<usize>,
+    tripped: &'t mut Option<LimitExceeded>,
+}
+
+impl<'t, I> NestingGuard<'t, I> {
+    pub(crate) fn new(
+        inner: I,
+        max_depth: Option<usize>,
+        tripped: &'t mut Option<LimitExceeded>,
+    ) -> Self {
+        Self {
+            inner,
+            depth: 0,
+            max_depth,
+            tripped,
+        }
+    }
+}
+
+impl<'a, I> Iterator for NestingGuard<'_, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.tripped.is_some() {
+            return None;
+        }
+
+        let event = self.inner.next()?;
+        match &event {
+            Event::Start(_) => {
+                self.depth += 1;
+                if let Some(limit) = self.max_depth
+                    && self.depth > limit
+                {
+                    *self.tripped = Some(LimitExceeded {
+                        kind: LimitKind::NestingDepth,
+                        limit,
+                    });
+                    return None;
+                }
+            }
+            Event::End(_) => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+        Some(event)
+    }
+}
+
+/// Writer that fails with a [`LimitExceeded`] payload once more than `max_bytes` were written.
+pub(crate) struct LimitedWriter<W: Write> {
+    inner: W,
+    written: usize,
+    max_bytes: Option<usize>,
+}
+
+impl<W: Write> LimitedWriter<W> {
+    pub(crate) fn new(inner: W, max_bytes: Option<usize>) -> Self {
+        Self {
+            inner,
+            written: 0,
+            max_bytes,
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(limit) = self.max_bytes
+            && self.written + buf.len() > limit
+        {
+            return Err(io::Error::other(LimitExceeded {
+                kind: LimitKind::OutputBytes,
+                limit,
+            }));
+        }
+
+        let written = self.inner.write(buf)?;
+        self.written += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Recovers a [`LimitExceeded`] that travelled through the `io::Write` chain as an `io::Error`.
+pub(crate) fn limit_from_io(err: &io::Error) -> Option<LimitExceeded> {
+    err.get_ref()
+        .and_then(|inner| inner.downcast_ref::<LimitExceeded>())
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::container_depth;
+    use crate::event::Event;
+
+    #[test]
+    fn estimates_container_depth_from_markers() {
+        let cases = [
+            ("> > > > > deep", 5),
+            ("- > 1. item", 5),
+            ("- - - -", 0),
+            ("- * * *", 2),
+            ("- a\n  - b\n    - c", 2),
+            ("    > > > > code", 0),
+            ("```\n> > > >\n```\n> x", 1),
+            ("~~~~\n> > >\n~~~\n> > >\n~~~~", 0),
+            ("<pre>\n> > > >\n\n> > >\n</pre>\n> x", 1),
+            ("<div>\n> > > >\n\n> > x", 2),
+            ("text\n2. > > x", 0),
+            ("text\n\n2. > > x", 4),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(container_depth(input), expected, "{input:?}");
+
+            let mut depth = 0usize;
+            let mut deepest = 0;
+            for event in crate::get_event_iterator(input).unwrap() {
+                match event {
+                    Event::Start(_) => depth += 1,
+                    Event::End(_) => depth -= 1,
+                    _ => {}
+                }
+                deepest = deepest.max(depth);
+            }
+            assert!(expected <= deepest, "{input:?} nests {deepest} deep");
+        }
+    }
+}
@@ -1,219 +1,451 @@
 //! Adapter that exposes `markdown-rs` AST nodes as Markflow core events.
 
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::convert::TryFrom;
+use std::rc::Rc;
 
-use html_escape::encode_text_to_string;
-use log::warn;
+#[cfg(feature = "frontmatter")]
+use html_escape::{decode_html_entities, encode_text_to_string};
 use markdown::{ParseOptions, mdast, message::Message, to_mdast};
 
-use crate::event::{Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Tag};
+use crate::backend::SyntaxOptions;
+use crate::code_blocks::CodeMeta;
+use crate::diagnostics::{self, DiagnosticKind, Diagnostics};
+#[cfg(feature = "tables")]
+use crate::event::Alignment;
+use crate::event::{CodeBlockKind, Event, HeadingLevel, LinkType, MdxJsxAttribute, Tag};
+use crate::image_size;
+use crate::slug::Slugger;
+use crate::span::SourceSpan;
 
+/// Parses `input` into an mdast tree with the constructs every Markflow entry point enables.
+pub(crate) fn parse_tree(input: &str) -> Result<mdast::Node, Message> {
+    parse_tree_with(input, &SyntaxOptions::default())
+}
+
+/// Same as [`parse_tree`], with the optional constructs selected by `syntax`.
+pub(crate) fn parse_tree_with(input: &str, syntax: &SyntaxOptions) -> Result<mdast::Node, Message> {
+    let mut options = ParseOptions::gfm();
+    if syntax.mdx {
+        options.constructs.autolink = false;
+        options.constructs.code_indented = false;
+        options.constructs.html_flow = false;
+        options.constructs.html_text = false;
+        options.constructs.mdx_esm = true;
+        options.constructs.mdx_expression_flow = true;
+        options.constructs.mdx_expression_text = true;
+        options.constructs.mdx_jsx_flow = true;
+        options.constructs.mdx_jsx_text = true;
+    }
+    options.constructs.gfm_autolink_literal = syntax.autolinks;
+    options.constructs.gfm_footnote_definition = syntax.footnotes && cfg!(feature = "footnotes");
+    options.constructs.gfm_label_start_footnote = syntax.footnotes && cfg!(feature = "footnotes");
+    options.constructs.gfm_table = cfg!(feature = "tables");
+    // `~sub~` must reach the event stream as text for the subscript transform.
+    options.gfm_strikethrough_single_tilde = !syntax.superscript_subscript;
+    options.constructs.frontmatter = syntax.frontmatter && cfg!(feature = "frontmatter");
+    options.constructs.math_flow = syntax.math && cfg!(feature = "math");
+    options.constructs.math_text = syntax.math && cfg!(feature = "math");
+    to_mdast(input, &options)
+}
+
+/// Streams Markflow events out of a `markdown-rs` syntax tree.
+///
+/// The iterator owns the parsed tree and walks it lazily with an explicit frame stack. Nodes are
+/// moved out of the tree as they are visited, so text is handed to the renderer without cloning and
+/// no intermediate `Vec<Event>` is materialized. Only leaves that expand into several events (code
+/// blocks, images, list items) stage a handful of events in `pending`.
+///
+/// Paragraphs that are direct children of tight list items are unwrapped (as CommonMark renders
+/// them), while loose items keep their `<p>` tags. Task list markers are emitted right after
+/// `Start(Item)`.
 pub struct MarkdownRsEventIter {
-    events: Vec<Event<'static>>,
-    cursor: usize,
+    stack: Vec<Frame>,
+    pending: VecDeque<Event<'static>>,
+    /// Looseness of each open list, innermost last.
+    loose_lists: Vec<bool>,
+    /// Link reference definitions by normalized identifier.
+    definitions: HashMap<String, LinkDefinition>,
+    /// Heading ids handed out so far, so repeated headings get unique ids.
+    slugger: Slugger,
+    /// Parse trailing `{#id .class key=value}` blocks on headings and keep them in code meta.
+    heading_attributes: bool,
+    /// Turn the line breaks inside text into hard breaks.
+    hard_breaks: bool,
+    /// Read size hints into images.
+    image_sizes: bool,
+    /// Collector for the problems found while the tree is walked.
+    diagnostics: Option<Rc<RefCell<Diagnostics>>>,
+}
+
+/// Destination of a `[label]: url "title"` definition.
+struct LinkDefinition {
+    url: String,
+    title: Option<String>,
+}
+
+/// A container whose children are still being visited.
+struct Frame {
+    children: std::vec::IntoIter<mdast::Node>,
+    /// Event emitted once all children were visited; `None` for unwrapped containers.
+    end: Option<Event<'static>>,
+    kind: FrameKind,
+}
+
+/// Frame roles that matter for list tightness.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Plain,
+    List,
+    TightItem,
+}
+
+impl Frame {
+    fn new(children: Vec<mdast::Node>, end: Option<Event<'static>>) -> Self {
+        Self {
+            children: children.into_iter(),
+            end,
+            kind: FrameKind::Plain,
+        }
+    }
 }
 
 impl MarkdownRsEventIter {
     pub fn new(input: &str) -> Result<Self, Message> {
-        let mut options = ParseOptions::gfm();
-        options.constructs.frontmatter = true;
-        options.constructs.math_flow = true;
-        options.constructs.math_text = true;
-        let tree = to_mdast(input, &options)?;
-        let mut builder = EventBuilder::default();
-        builder.visit(&tree);
+        Self::with_syntax(input, &SyntaxOptions::default())
+    }
+
+    /// Parses `input` with the optional constructs selected by `syntax`.
+    pub fn with_syntax(input: &str, syntax: &SyntaxOptions) -> Result<Self, Message> {
+        let tree = parse_tree_with(input, syntax)?;
+        let mut definitions = HashMap::new();
+        collect_definitions(&tree, &mut definitions);
         Ok(Self {
-            events: builder.events,
-            cursor: 0,
+            stack: vec![Frame::new(vec![tree], None)],
+            pending: VecDeque::new(),
+            loose_lists: Vec::new(),
+            definitions,
+            slugger: Slugger::new(),
+            heading_attributes: syntax.heading_attributes,
+            hard_breaks: syntax.breaks,
+            image_sizes: syntax.image_sizes,
+            diagnostics: None,
         })
     }
-}
 
-impl Iterator for MarkdownRsEventIter {
-    type Item = Event<'static>;
+    /// Continues numbering heading ids from `slugger`, e.g. to keep ids unique across documents
+    /// rendered into one page.
+    pub fn with_slugger(mut self, slugger: Slugger) -> Self {
+        self.slugger = slugger;
+        self
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.cursor >= self.events.len() {
-            None
-        } else {
-            let event = self.events[self.cursor].clone();
-            self.cursor += 1;
-            Some(event)
-        }
+    /// Heading ids handed out so far; pass it to the next document's iterator to continue.
+    pub fn into_slugger(self) -> Slugger {
+        self.slugger
     }
-}
 
-#[derive(Default)]
-struct EventBuilder {
-    events: Vec<Event<'static>>,
-    tight_list_depth: usize,
-}
+    /// Records duplicate heading ids, images without alt text, unresolved references, and
+    /// unsupported nodes into `diagnostics` as their events are produced.
+    pub(crate) fn with_diagnostics(
+        mut self,
+        diagnostics: Option<Rc<RefCell<Diagnostics>>>,
+    ) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
 
-impl EventBuilder {
     #[allow(unreachable_patterns)]
-    fn visit(&mut self, node: &mdast::Node) {
+    fn enter(&mut self, node: mdast::Node) {
+        let span = node.position().map(SourceSpan::from);
+        if let (Some(diagnostics), mdast::Node::Text(text)) = (&self.diagnostics, &node) {
+            for label in diagnostics::reference_labels(&text.value) {
+                if !self.definitions.contains_key(&normalize_label(label)) {
+                    diagnostics.borrow_mut().unresolved_reference(label, span);
+                }
+            }
+        }
         match node {
-            mdast::Node::Root(root) => self.visit_children(&root.children),
+            mdast::Node::Root(root) => self.push_frame(root.children, None),
             mdast::Node::Paragraph(paragraph) => {
-                if self.tight_list_depth > 0 {
-                    self.visit_children(&paragraph.children);
+                let in_tight_item = self
+                    .stack
+                    .last()
+                    .is_some_and(|parent| parent.kind == FrameKind::TightItem);
+                if in_tight_item {
+                    self.push_frame(paragraph.children, None);
                 } else {
-                    self.with_tag(Tag::Paragraph, &paragraph.children)
+                    self.open(Tag::Paragraph, paragraph.children);
                 }
             }
-            mdast::Node::Heading(heading) => {
-                let heading_id = heading_slug(&heading.children);
+            mdast::Node::Heading(mut heading) => {
+                let block = if self.heading_attributes {
+                    take_attribute_block(&mut heading.children)
+                } else {
+                    None
+                };
+                let AttributeBlock { id, classes, attrs } = block.unwrap_or_default();
+                let heading_id = match id {
+                    Some(id) => {
+                        let unique = self.slugger.unique(id.clone());
+                        if let Some(diagnostics) = &self.diagnostics {
+                            diagnostics.borrow_mut().duplicate_id(&id, &unique, span);
+                        }
+                        Some(unique)
+                    }
+                    None => {
+                        let mut raw = String::new();
+                        collect_text(&heading.children, &mut raw);
+                        self.slugger.slug(&raw)
+                    }
+                };
                 let tag = Tag::Heading {
                     level: HeadingLevel::try_from(heading.depth as usize)
                         .unwrap_or(HeadingLevel::H6),
                     id: heading_id.map(Cow::Owned),
-                    classes: Vec::new(),
-                    attrs: Vec::new(),
+                    classes: classes.into_iter().map(Cow::Owned).collect(),
+                    attrs: attrs
+                        .into_iter()
+                        .map(|(key, value)| (Cow::Owned(key), value.map(Cow::Owned)))
+                        .collect(),
                 };
-                self.with_tag(tag, &heading.children)
+                self.open(tag, heading.children)
             }
-            mdast::Node::Blockquote(block) => self.with_tag(Tag::BlockQuote, &block.children),
+            mdast::Node::Blockquote(block) => self.open(Tag::BlockQuote, block.children),
             mdast::Node::List(list) => {
                 let start = if list.ordered {
                     Some(list.start.unwrap_or(1) as u64)
                 } else {
                     None
                 };
-                self.with_tag(Tag::List(start), &list.children)
+                // CommonMark: a list is loose when any item is separated by a blank line, which
+                // markdown-rs records on the list, or any item spreads its own children.
+                let loose = list.spread
+                    || list
+                        .children
+                        .iter()
+                        .any(|child| matches!(child, mdast::Node::ListItem(item) if item.spread));
+                self.loose_lists.push(loose);
+                self.open(Tag::List(start), list.children);
+                if let Some(frame) = self.stack.last_mut() {
+                    frame.kind = FrameKind::List;
+                }
             }
             mdast::Node::ListItem(item) => {
-                self.events.push(Event::Start(Tag::Item));
+                self.emit(Event::Start(Tag::Item));
                 if let Some(checked) = item.checked {
-                    self.events.push(Event::TaskListMarker(checked));
-                }
-                let is_tight = !item.spread;
-                if is_tight {
-                    self.tight_list_depth += 1;
+                    self.emit(Event::TaskListMarker(checked));
                 }
-                self.visit_children(&item.children);
-                if is_tight {
-                    self.tight_list_depth -= 1;
+                let mut frame = Frame::new(item.children, Some(Event::End(Tag::Item.to_end())));
+                let loose = self.loose_lists.last().copied().unwrap_or(item.spread);
+                if !loose {
+                    frame.kind = FrameKind::TightItem;
                 }
-                self.events.push(Event::End(Tag::Item.to_end()));
+                self.stack.push(frame);
             }
-            mdast::Node::ThematicBreak(_) => self.events.push(Event::Rule),
+            mdast::Node::ThematicBreak(_) => self.emit(Event::Rule),
             mdast::Node::Code(code) => {
-                let tag = Tag::CodeBlock(match &code.lang {
-                    Some(lang) => CodeBlockKind::Fenced(Cow::Owned(lang.clone())),
+                let mut kind = match code.lang {
+                    Some(lang) => CodeBlockKind::Fenced {
+                        lang: Some(Cow::Owned(lang)),
+                        meta: CodeMeta::parse(code.meta.unwrap_or_default()),
+                    },
                     None => CodeBlockKind::Indented,
-                });
-                self.events.push(Event::Start(tag.clone()));
-                self.events
-                    .push(Event::Text(Cow::Owned(code.value.clone())));
-                self.events.push(Event::End(tag.to_end()));
-            }
-            mdast::Node::Text(text) => {
-                self.events
-                    .push(Event::Text(Cow::Owned(text.value.clone())));
-            }
-            mdast::Node::Emphasis(emphasis) => self.with_tag(Tag::Emphasis, &emphasis.children),
-            mdast::Node::Strong(strong) => self.with_tag(Tag::Strong, &strong.children),
-            mdast::Node::Delete(delete) => self.with_tag(Tag::Strikethrough, &delete.children),
-            mdast::Node::InlineCode(code) => {
-                self.events
-                    .push(Event::Code(Cow::Owned(code.value.clone())));
+                };
+                if !self.heading_attributes {
+                    kind.clear_attribute_block();
+                }
+                let tag = Tag::CodeBlock(kind);
+                let end = tag.to_end();
+                self.emit(Event::Start(tag));
+                self.emit(Event::Text(Cow::Owned(code.value)));
+                self.emit(Event::End(end));
             }
-            mdast::Node::InlineMath(math) => {
-                self.events
-                    .push(Event::InlineMath(Cow::Owned(math.value.clone())));
+            mdast::Node::Text(text) if self.hard_breaks && text.value.contains('\n') => {
+                for (idx, line) in text.value.split('\n').enumerate() {
+                    if idx > 0 {
+                        self.emit(Event::HardBreak);
+                    }
+                    if !line.is_empty() {
+                        self.emit(Event::Text(Cow::Owned(line.to_string())));
+                    }
+                }
             }
-            mdast::Node::Math(math) => {
-                self.events
-                    .push(Event::DisplayMath(Cow::Owned(math.value.clone())));
+            mdast::Node::Text(text) => self.emit(Event::Text(Cow::Owned(text.value))),
+            mdast::Node::Emphasis(emphasis) => self.open(Tag::Emphasis, emphasis.children),
+            mdast::Node::Strong(strong) => self.open(Tag::Strong, strong.children),
+            mdast::Node::Delete(delete) => self.open(Tag::Strikethrough, delete.children),
+            mdast::Node::InlineCode(code) => self.emit(Event::Code(Cow::Owned(code.value))),
+            #[cfg(feature = "math")]
+            mdast::Node::InlineMath(math) => self.emit(Event::InlineMath(Cow::Owned(math.value))),
+            #[cfg(feature = "math")]
+            mdast::Node::Math(math) => self.emit(Event::DisplayMath(Cow::Owned(math.value))),
+            mdast::Node::Break(_) => self.emit(Event::HardBreak),
+            mdast::Node::Link(link) => {
+                let tag = Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: Cow::Owned(link.url),
+                    title: link.title.map_or(Cow::Borrowed(""), Cow::Owned),
+                    id: Cow::Borrowed(""),
+                };
+                self.open(tag, link.children)
             }
-            mdast::Node::Break(_) => self.events.push(Event::HardBreak),
-            mdast::Node::Link(link) => self.handle_link(link),
-            mdast::Node::Image(image) => self.handle_image(image),
-            mdast::Node::Html(html) => {
-                self.events
-                    .push(Event::Html(Cow::Owned(html.value.clone())));
+            mdast::Node::Image(image) => {
+                let tag = Tag::Image {
+                    link_type: LinkType::Inline,
+                    dest_url: Cow::Owned(image.url),
+                    title: image.title.map_or(Cow::Borrowed(""), Cow::Owned),
+                    id: Cow::Borrowed(""),
+                    width: None,
+                    height: None,
+                    attrs: Vec::new(),
+                };
+                self.emit_image(tag, image.alt, span);
             }
-            mdast::Node::Table(table) => self.handle_table(table),
-            mdast::Node::TableRow(row) => self.with_tag(Tag::TableRow, &row.children),
-            mdast::Node::TableCell(cell) => self.with_tag(Tag::TableCell, &cell.children),
-            mdast::Node::FootnoteDefinition(def) => self.with_tag(
-                Tag::FootnoteDefinition(Cow::Owned(def.identifier.clone())),
-                &def.children,
+            mdast::Node::Html(html) => self.emit(Event::Html(Cow::Owned(html.value))),
+            #[cfg(feature = "tables")]
+            mdast::Node::Table(table) => self.enter_table(table),
+            #[cfg(feature = "tables")]
+            mdast::Node::TableRow(row) => self.open(Tag::TableRow, row.children),
+            #[cfg(feature = "tables")]
+            mdast::Node::TableCell(cell) => self.open(Tag::TableCell, cell.children),
+            mdast::Node::FootnoteDefinition(def) => self.open(
+                Tag::FootnoteDefinition(Cow::Owned(def.identifier)),
+                def.children,
             ),
             mdast::Node::FootnoteReference(reference) => {
-                self.events.push(Event::FootnoteReference(Cow::Owned(
-                    reference.identifier.clone(),
-                )));
+                self.emit(Event::FootnoteReference(Cow::Owned(reference.identifier)));
+            }
+            mdast::Node::LinkReference(link) => {
+                let (dest_url, title) = self.resolve(&link.identifier);
+                let tag = Tag::Link {
+                    link_type: reference_link_type(link.reference_kind),
+                    dest_url,
+                    title,
+                    id: Cow::Owned(link.label.unwrap_or(link.identifier)),
+                };
+                self.open(tag, link.children)
             }
-            mdast::Node::LinkReference(link) => self.handle_link_reference(link),
-            mdast::Node::ImageReference(image) => self.handle_image_reference(image),
-            mdast::Node::Definition(_) => self.warn_unsupported("definition"),
+            mdast::Node::ImageReference(image) => {
+                let (dest_url, title) = self.resolve(&image.identifier);
+                let tag = Tag::Image {
+                    link_type: reference_link_type(image.reference_kind),
+                    dest_url,
+                    title,
+                    id: Cow::Owned(image.label.unwrap_or(image.identifier)),
+                    width: None,
+                    height: None,
+                    attrs: Vec::new(),
+                };
+                self.emit_image(tag, image.alt, span);
+            }
+            // Resolved into the references above; definitions render nothing themselves.
+            mdast::Node::Definition(_) => {}
+            #[cfg(feature = "frontmatter")]
             mdast::Node::Toml(doc) => {
-                self.events.push(Event::Html(Cow::Owned(format_frontmatter(
+                self.emit(Event::Html(Cow::Owned(format_frontmatter(
                     "toml", &doc.value,
                 ))));
             }
+            #[cfg(feature = "frontmatter")]
             mdast::Node::Yaml(doc) => {
-                self.events.push(Event::Html(Cow::Owned(format_frontmatter(
+                self.emit(Event::Html(Cow::Owned(format_frontmatter(
                     "yaml", &doc.value,
                 ))));
             }
-            mdast::Node::MdxjsEsm(doc) => {
-                self.events.push(Event::Html(Cow::Owned(doc.value.clone())));
+            mdast::Node::MdxjsEsm(doc) => self.emit(Event::Html(Cow::Owned(doc.value))),
+            mdast::Node::MdxFlowExpression(expression) => {
+                self.emit(Event::MdxExpression(Cow::Owned(expression.value)))
             }
-            mdast::Node::MdxFlowExpression(_) => self.warn_unsupported("mdxFlowExpression"),
-            mdast::Node::MdxTextExpression(_) => self.warn_unsupported("mdxTextExpression"),
-            mdast::Node::MdxJsxFlowElement(_) => self.warn_unsupported("mdxJsxFlowElement"),
-            mdast::Node::MdxJsxTextElement(_) => self.warn_unsupported("mdxJsxTextElement"),
-            other => {
-                if let Some(children) = other.children() {
-                    self.visit_children(children);
-                } else {
-                    self.warn_unsupported("unknown");
+            mdast::Node::MdxTextExpression(expression) => {
+                self.emit(Event::MdxExpression(Cow::Owned(expression.value)))
+            }
+            mdast::Node::MdxJsxFlowElement(element) => {
+                self.open_jsx(element.name, element.attributes, element.children)
+            }
+            mdast::Node::MdxJsxTextElement(element) => {
+                self.open_jsx(element.name, element.attributes, element.children)
+            }
+            mut other => {
+                if let Some(children) = other.children_mut() {
+                    let children = std::mem::take(children);
+                    self.push_frame(children, None);
+                } else if let Some(diagnostics) = &self.diagnostics {
+                    diagnostics.borrow_mut().push(
+                        DiagnosticKind::UnsupportedNode,
+                        "unsupported Markdown node is left out",
+                        span,
+                    );
                 }
             }
         }
     }
 
-    fn visit_children(&mut self, children: &[mdast::Node]) {
-        for child in children {
-            self.visit(child);
+    /// Destination and title of the definition `identifier` refers to.
+    fn resolve(&self, identifier: &str) -> (Cow<'static, str>, Cow<'static, str>) {
+        match self.definitions.get(identifier) {
+            Some(definition) => (
+                Cow::Owned(definition.url.clone()),
+                definition
+                    .title
+                    .clone()
+                    .map_or(Cow::Borrowed(""), Cow::Owned),
+            ),
+            None => (Cow::Borrowed(""), Cow::Borrowed("")),
         }
     }
 
-    fn with_tag(&mut self, tag: Tag<'static>, children: &[mdast::Node]) {
+    fn emit(&mut self, event: Event<'static>) {
+        self.pending.push_back(event);
+    }
+
+    fn push_frame(&mut self, children: Vec<mdast::Node>, end: Option<Event<'static>>) {
+        self.stack.push(Frame::new(children, end));
+    }
+
+    fn open(&mut self, tag: Tag<'static>, children: Vec<mdast::Node>) {
         let end = tag.to_end();
-        self.events.push(Event::Start(tag));
-        self.visit_children(children);
-        self.events.push(Event::End(end));
+        self.emit(Event::Start(tag));
+        self.push_frame(children, Some(Event::End(end)));
     }
 
-    fn handle_link(&mut self, link: &mdast::Link) {
-        let tag = Tag::Link {
-            link_type: LinkType::Inline,
-            dest_url: Cow::Owned(link.url.clone()),
-            title: link.title.clone().map_or(Cow::Borrowed(""), Cow::Owned),
-            id: Cow::Owned(String::new()),
+    fn open_jsx(
+        &mut self,
+        name: Option<String>,
+        attributes: Vec<mdast::AttributeContent>,
+        children: Vec<mdast::Node>,
+    ) {
+        // mdast does not record `<X />` vs `<X></X>`; childless named elements self-close.
+        let self_closing = name.is_some() && children.is_empty();
+        let tag = Tag::MdxJsxElement {
+            name: name.map(Cow::Owned),
+            attributes: attributes.into_iter().map(jsx_attribute).collect(),
+            self_closing,
         };
-        self.with_tag(tag, &link.children);
+        self.open(tag, children);
     }
 
-    fn handle_image(&mut self, image: &mdast::Image) {
-        let tag = Tag::Image {
-            link_type: LinkType::Inline,
-            dest_url: Cow::Owned(image.url.clone()),
-            title: image.title.clone().map_or(Cow::Borrowed(""), Cow::Owned),
-            id: Cow::Owned(String::new()),
+    fn emit_image(&mut self, tag: Tag<'static>, alt: String, span: Option<SourceSpan>) {
+        if let Some(diagnostics) = &self.diagnostics {
+            diagnostics.borrow_mut().image(&alt, span);
+        }
+        let tag = if self.image_sizes {
+            image_size::apply(tag)
+        } else {
+            tag
         };
-        self.events.push(Event::Start(tag.clone()));
-        if !image.alt.is_empty() {
-            self.events.push(Event::Text(Cow::Owned(image.alt.clone())));
+        let end = tag.to_end();
+        self.emit(Event::Start(tag));
+        if !alt.is_empty() {
+            self.emit(Event::Text(Cow::Owned(alt)));
         }
-        self.events.push(Event::End(tag.to_end()));
+        self.emit(Event::End(end));
     }
 
-    fn handle_table(&mut self, table: &mdast::Table) {
+    #[cfg(feature = "tables")]
+    fn enter_table(&mut self, table: mdast::Table) {
         let alignments: Vec<Alignment> = table
             .align
             .iter()
@@ -224,68 +456,153 @@ impl EventBuilder {
                 mdast::AlignKind::None => Alignment::None,
             })
             .collect();
-        self.with_tag(Tag::Table(alignments), &table.children);
-    }
+        let tag = Tag::Table(alignments);
+        let end = tag.to_end();
+        self.emit(Event::Start(tag));
 
-    fn handle_link_reference(&mut self, link: &mdast::LinkReference) {
-        let tag = Tag::Link {
-            link_type: LinkType::Reference,
-            dest_url: Cow::Borrowed(""),
-            title: Cow::Borrowed(""),
-            id: Cow::Owned(link.identifier.clone()),
-        };
-        self.with_tag(tag, &link.children);
+        let mut rows = table.children;
+        if rows.is_empty() {
+            self.push_frame(rows, Some(Event::End(end)));
+            return;
+        }
+        // GFM tables always open with the header row; wrap it so renderers can emit `<th>`.
+        let body = rows.split_off(1);
+        self.push_frame(body, Some(Event::End(end)));
+        self.emit(Event::Start(Tag::TableHead));
+        self.push_frame(rows, Some(Event::End(Tag::TableHead.to_end())));
     }
+}
 
-    fn handle_image_reference(&mut self, image: &mdast::ImageReference) {
-        let tag = Tag::Image {
-            link_type: LinkType::Reference,
-            dest_url: Cow::Borrowed(""),
-            title: Cow::Borrowed(""),
-            id: Cow::Owned(image.identifier.clone()),
-        };
-        self.events.push(Event::Start(tag.clone()));
-        if !image.alt.is_empty() {
-            self.events.push(Event::Text(Cow::Owned(image.alt.clone())));
+impl Iterator for MarkdownRsEventIter {
+    type Item = Event<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+
+            let frame = self.stack.last_mut()?;
+            match frame.children.next() {
+                Some(node) => self.enter(node),
+                None => {
+                    let frame = self.stack.pop()?;
+                    if frame.kind == FrameKind::List {
+                        self.loose_lists.pop();
+                    }
+                    if let Some(end) = frame.end {
+                        return Some(end);
+                    }
+                }
+            }
         }
-        self.events.push(Event::End(tag.to_end()));
     }
+}
 
-    fn warn_unsupported(&self, node_name: &str) {
-        warn!("Skipping unsupported markdown node: {node_name}");
+/// Case-folded label with whitespace runs collapsed, as CommonMark matches labels.
+pub(crate) fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Records every definition in `node`'s subtree; the first definition of an identifier wins.
+fn collect_definitions(node: &mdast::Node, definitions: &mut HashMap<String, LinkDefinition>) {
+    if let mdast::Node::Definition(definition) = node {
+        definitions
+            .entry(definition.identifier.clone())
+            .or_insert_with(|| LinkDefinition {
+                url: definition.url.clone(),
+                title: definition.title.clone(),
+            });
+    }
+    for child in node.children().into_iter().flatten() {
+        collect_definitions(child, definitions);
     }
 }
 
-fn heading_slug(children: &[mdast::Node]) -> Option<String> {
-    let mut raw = String::new();
-    collect_text(children, &mut raw);
+/// Parsed `{#id .class key=value}` block.
+#[derive(Default)]
+pub(crate) struct AttributeBlock {
+    pub(crate) id: Option<String>,
+    pub(crate) classes: Vec<String>,
+    pub(crate) attrs: Vec<(String, Option<String>)>,
+}
 
-    let mut slug = String::new();
-    let mut last_dash = false;
+/// Removes a trailing attribute block from the heading's last text node, as pulldown-cmark's
+/// heading attribute extension does.
+fn take_attribute_block(children: &mut Vec<mdast::Node>) -> Option<AttributeBlock> {
+    let Some(mdast::Node::Text(text)) = children.last_mut() else {
+        return None;
+    };
+    let trimmed = text.value.trim_end().strip_suffix('}')?;
+    let open = trimmed.rfind('{')?;
+    let body = &trimmed[open + 1..];
+    if body.contains('}') {
+        return None;
+    }
 
-    for ch in raw.chars() {
-        if ch.is_alphanumeric() {
-            for lower in ch.to_lowercase() {
-                slug.push(lower);
-            }
-            last_dash = false;
-        } else if (ch.is_whitespace() || matches!(ch, '-' | '_' | ':' | '.'))
-            && !slug.is_empty()
-            && !last_dash
-        {
-            slug.push('-');
-            last_dash = true;
+    let block = parse_attribute_block(body);
+    let rest_len = trimmed[..open].trim_end().len();
+    text.value.truncate(rest_len);
+    if text.value.is_empty() {
+        children.pop();
+    }
+    Some(block)
+}
+
+/// Parses the inside of a `{#id .class key=value}` block. Quoted values are unquoted but cannot
+/// contain spaces.
+pub(crate) fn parse_attribute_block(body: &str) -> AttributeBlock {
+    let mut block = AttributeBlock::default();
+    for token in body.split_whitespace() {
+        if let Some(id) = token.strip_prefix('#') {
+            block.id = Some(id.to_string());
+        } else if let Some(class) = token.strip_prefix('.') {
+            block.classes.push(class.to_string());
+        } else if let Some((key, value)) = token.split_once('=') {
+            let value = value.trim_matches(|ch| ch == '"' || ch == '\'');
+            block.attrs.push((key.to_string(), Some(value.to_string())));
+        } else {
+            block.attrs.push((token.to_string(), None));
         }
     }
+    block
+}
 
-    while slug.ends_with('-') {
-        slug.pop();
+fn reference_link_type(kind: mdast::ReferenceKind) -> LinkType {
+    match kind {
+        mdast::ReferenceKind::Full => LinkType::Reference,
+        mdast::ReferenceKind::Collapsed => LinkType::Collapsed,
+        mdast::ReferenceKind::Shortcut => LinkType::Shortcut,
     }
+}
 
-    if slug.is_empty() { None } else { Some(slug) }
+fn jsx_attribute(attribute: mdast::AttributeContent) -> MdxJsxAttribute<'static> {
+    match attribute {
+        mdast::AttributeContent::Property(property) => match property.value {
+            None => MdxJsxAttribute::Property {
+                name: Cow::Owned(property.name),
+                value: None,
+            },
+            Some(mdast::AttributeValue::Literal(value)) => MdxJsxAttribute::Property {
+                name: Cow::Owned(property.name),
+                value: Some(Cow::Owned(value)),
+            },
+            Some(mdast::AttributeValue::Expression(expression)) => MdxJsxAttribute::Expression {
+                name: Cow::Owned(property.name),
+                value: Cow::Owned(expression.value),
+            },
+        },
+        mdast::AttributeContent::Expression(expression) => {
+            MdxJsxAttribute::Spread(Cow::Owned(expression.value))
+        }
+    }
 }
 
-fn collect_text(nodes: &[mdast::Node], buf: &mut String) {
+pub(crate) fn collect_text(nodes: &[mdast::Node], buf: &mut String) {
     for node in nodes {
         match node {
             mdast::Node::Text(text) => buf.push_str(&text.value),
@@ -319,7 +636,8 @@ fn collect_text(nodes: &[mdast::Node], buf: &mut String) {
     }
 }
 
-fn format_frontmatter(kind: &str, value: &str) -> String {
+#[cfg(feature = "frontmatter")]
+pub(crate) fn format_frontmatter(kind: &str, value: &str) -> String {
     let mut output = String::new();
     output.push_str("<pre class=\"frontmatter\" data-kind=\"");
     output.push_str(kind);
@@ -328,3 +646,123 @@ fn format_frontmatter(kind: &str, value: &str) -> String {
     output.push_str("</pre>");
     output
 }
+
+/// Inverse of [`format_frontmatter`]: recovers `(kind, value)` from a frontmatter HTML event.
+#[cfg(feature = "frontmatter")]
+pub(crate) fn parse_frontmatter(html: &str) -> Option<(&str, String)> {
+    let rest = html.strip_prefix("<pre class=\"frontmatter\" data-kind=\"")?;
+    let (kind, rest) = rest.split_once("\">")?;
+    let value = decode_html_entities(rest.strip_suffix("</pre>")?);
+    Some((kind, value.into_owned()))
+}
+
+/// Without the `frontmatter` feature no event is frontmatter.
+#[cfg(not(feature = "frontmatter"))]
+pub(crate) fn parse_frontmatter(_html: &str) -> Option<(&str, String)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::TagEnd;
+
+    fn events(input: &str) -> Vec<Event<'static>> {
+        MarkdownRsEventIter::new(input).unwrap().collect()
+    }
+
+    #[test]
+    fn tight_list_strips_paragraphs() {
+        let events = events("- one\n- two");
+        assert!(!events.contains(&Event::Start(Tag::Paragraph)));
+        assert_eq!(
+            events,
+            vec![
+                Event::Start(Tag::List(None)),
+                Event::Start(Tag::Item),
+                Event::Text(Cow::Borrowed("one")),
+                Event::End(TagEnd::Item),
+                Event::Start(Tag::Item),
+                Event::Text(Cow::Borrowed("two")),
+                Event::End(TagEnd::Item),
+                Event::End(TagEnd::List(false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn loose_list_retains_paragraphs() {
+        let cases = [
+            ("- one\n\n- two\n\n> quoted", 3),
+            ("- tight\n  > quoted", 1),
+            ("- outer\n  - a\n\n  - b", 2),
+        ];
+        for (input, expected) in cases {
+            let paragraphs = events(input)
+                .iter()
+                .filter(|event| matches!(event, Event::Start(Tag::Paragraph)))
+                .count();
+            assert_eq!(paragraphs, expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn task_list_emits_marker_before_text() {
+        let events = events("- [x] done\n- [ ] todo");
+        assert_eq!(events[1], Event::Start(Tag::Item));
+        assert_eq!(events[2], Event::TaskListMarker(true));
+        assert_eq!(events[3], Event::Text(Cow::Borrowed("done")));
+        assert_eq!(events[6], Event::TaskListMarker(false));
+    }
+
+    #[test]
+    fn nested_containers_close_in_order() {
+        let events = events("> | a |\n> |---|\n> | *b* |");
+        let mut depth = 0i32;
+        for event in &events {
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                _ => {}
+            }
+            assert!(depth >= 0);
+        }
+        assert_eq!(depth, 0);
+        assert_eq!(events.last(), Some(&Event::End(TagEnd::BlockQuote)));
+    }
+
+    #[test]
+    fn resolves_reference_links_and_images() {
+        let events = events(
+            "[Full][Docs] [collapsed][] [shortcut]\n\n![logo]\n\n> [docs]: /docs \"Docs\"\n\n[collapsed]: /c\n[shortcut]: /s\n[logo]: /logo.png\n[DOCS]: /ignored",
+        );
+        let links: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    ..
+                })
+                | Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    ..
+                }) => Some((*link_type, dest_url.as_ref(), title.as_ref())),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            links,
+            [
+                (LinkType::Reference, "/docs", "Docs"),
+                (LinkType::Collapsed, "/c", ""),
+                (LinkType::Shortcut, "/s", ""),
+                (LinkType::Shortcut, "/logo.png", ""),
+            ]
+        );
+    }
+}
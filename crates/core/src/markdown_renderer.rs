@@ -0,0 +1,749 @@
+//! CommonMark serializer for the event stream, powering a `format` capability.
+//!
+//! Events are folded into an [`AstNode`] tree first: block prefixes (`> `, list indentation) are
+//! much easier to get right recursively than with a streaming writer, and documents are small
+//! compared to the HTML they produce.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use serde_json::Value;
+
+use crate::ast::{self, AstKind, AstNode};
+use crate::event::{Event, MdxJsxAttribute, jsx_open_tag};
+use crate::image_size;
+use crate::markdown_adapter::{normalize_label, parse_frontmatter};
+use crate::{MarkflowError, get_event_iterator};
+
+/// Style choices applied by [`MarkdownRenderer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarkdownOptions {
+    /// Bullet for unordered lists: `-`, `*`, or `+`.
+    pub bullet: char,
+    /// Emphasis delimiter: `*` or `_`.
+    pub emphasis: char,
+    /// Strong emphasis delimiter, written twice: `*` or `_`.
+    pub strong: char,
+    /// Code fence character: `` ` `` or `~`.
+    pub fence: char,
+    /// Pad table cells so columns line up.
+    pub align_tables: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        MarkdownOptions {
+            bullet: '-',
+            emphasis: '*',
+            strong: '*',
+            fence: '`',
+            align_tables: true,
+        }
+    }
+}
+
+/// Renders Markflow events back into normalized CommonMark.
+pub struct MarkdownRenderer<W: Write> {
+    writer: W,
+    options: MarkdownOptions,
+}
+
+impl<W: Write> MarkdownRenderer<W> {
+    /// Creates a renderer writing into `writer`.
+    pub fn new(writer: W, options: MarkdownOptions) -> Self {
+        Self { writer, options }
+    }
+
+    /// Consumes `iter` and writes the formatted document, returning the writer.
+    pub fn render<'a, I>(mut self, iter: I) -> io::Result<W>
+    where
+        I: IntoIterator<Item = Event<'a>>,
+    {
+        let tree = ast::build(iter);
+        let mut output = Serializer {
+            options: &self.options,
+        }
+        .blocks(&tree.children, false);
+        let definitions = definitions(&tree);
+        if !definitions.is_empty() {
+            if !output.is_empty() {
+                output.push_str("\n\n");
+            }
+            output.push_str(&definitions);
+        }
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        self.writer.write_all(output.as_bytes())?;
+        Ok(self.writer)
+    }
+}
+
+/// Parses `input` and re-serializes it with consistent bullets, fences, emphasis, and table
+/// layout.
+pub fn format(input: &str, options: &MarkdownOptions) -> Result<String, MarkflowError> {
+    let events = get_event_iterator(input)?;
+    let output = MarkdownRenderer::new(Vec::new(), *options).render(events)?;
+    Ok(String::from_utf8(output)?)
+}
+
+struct Serializer<'o> {
+    options: &'o MarkdownOptions,
+}
+
+impl Serializer<'_> {
+    /// Serializes sibling blocks; tight lists separate items with single newlines, everything
+    /// else with a blank line. Runs of inline nodes (tight list items) form one implicit paragraph.
+    fn blocks(&self, nodes: &[AstNode], tight: bool) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        let mut inline_run: Vec<&AstNode> = Vec::new();
+        // Whether the previous block was a list, and if so whether it was ordered and written
+        // with the alternate marker.
+        let mut previous_list: Option<(bool, bool)> = None;
+
+        for node in nodes {
+            if is_inline(node.kind) {
+                inline_run.push(node);
+                continue;
+            }
+            if !inline_run.is_empty() {
+                parts.push(self.inlines(inline_run.drain(..)));
+                previous_list = None;
+            }
+            if node.kind == AstKind::List {
+                // A list right after another of the same kind needs a different marker, or
+                // the two would be read back as one list.
+                let ordered = node.attrs.contains_key("start");
+                let alternate = previous_list == Some((ordered, false));
+                parts.push(self.list(node, alternate));
+                previous_list = Some((ordered, alternate));
+                continue;
+            }
+            previous_list = None;
+            parts.push(self.block(node));
+        }
+        if !inline_run.is_empty() {
+            parts.push(self.inlines(inline_run.drain(..)));
+        }
+
+        parts.join(if tight { "\n" } else { "\n\n" })
+    }
+
+    fn block(&self, node: &AstNode) -> String {
+        match node.kind {
+            AstKind::Paragraph => self.inlines(node.children.iter()),
+            AstKind::Heading => {
+                let level = node.attrs.get("level").and_then(Value::as_u64).unwrap_or(1);
+                // An ATX heading is one line, so the lines of a setext heading are joined.
+                format!(
+                    "{} {}",
+                    "#".repeat(level as usize),
+                    self.inlines(node.children.iter()).replace('\n', " ")
+                )
+            }
+            AstKind::BlockQuote => prefix_lines(&self.blocks(&node.children, false), "> ", "> "),
+            AstKind::Callout => {
+                let kind = str_attr(node, "kind")
+                    .unwrap_or("note")
+                    .to_ascii_uppercase();
+                let body = self.blocks(&node.children, false);
+                prefix_lines(&format!("[!{kind}]\n{body}"), "> ", "> ")
+            }
+            AstKind::Details => {
+                let (summary, body) = match node.children.split_first() {
+                    Some((first, rest)) if first.kind == AstKind::Summary => {
+                        (self.inlines(first.children.iter()), rest)
+                    }
+                    _ => (String::new(), node.children.as_slice()),
+                };
+                match self.blocks(body, false) {
+                    body if body.is_empty() => format!(
+                        ":::details {summary}
+:::"
+                    ),
+                    body => format!(
+                        ":::details {summary}
+
+{body}
+
+:::"
+                    ),
+                }
+            }
+            AstKind::Figure => {
+                let image = node
+                    .children
+                    .iter()
+                    .filter(|child| child.kind != AstKind::FigureCaption);
+                let mut out = self.inlines(image);
+                push_figure_label(node, &mut out);
+                out
+            }
+            AstKind::CodeBlock => self.code_block(node),
+            AstKind::List => self.list(node, false),
+            AstKind::Table => self.table(node),
+            AstKind::Rule => "---".to_string(),
+            AstKind::Html => frontmatter(node).unwrap_or_else(|| literal(node).trim_end().into()),
+            AstKind::DisplayMath => format!("$$\n{}\n$$", literal(node)),
+            AstKind::FootnoteList => self.blocks(&node.children, false),
+            AstKind::FootnoteDefinition => {
+                let label = str_attr(node, "label").unwrap_or_default();
+                let body = self.blocks(&node.children, false);
+                prefix_lines(&body, &format!("[^{label}]: "), "    ")
+            }
+            AstKind::MdxJsxElement => {
+                let (open, close) = jsx_tags(node);
+                match close {
+                    Some(close) if !node.children.is_empty() => {
+                        format!(
+                            "{open}\n\n{}\n\n{close}",
+                            self.blocks(&node.children, false)
+                        )
+                    }
+                    close => format!("{open}{}", close.unwrap_or_default()),
+                }
+            }
+            _ => self.inlines(std::iter::once(node)),
+        }
+    }
+
+    fn code_block(&self, node: &AstNode) -> String {
+        let code = literal(node);
+        let longest = longest_run(code, self.options.fence);
+        let fence = self.options.fence.to_string().repeat(longest.max(2) + 1);
+        let mut info = str_attr(node, "lang").unwrap_or_default().to_string();
+        if let Some(meta) = str_attr(node, "meta") {
+            info.push(' ');
+            info.push_str(meta);
+        }
+        format!("{fence}{info}\n{code}\n{fence}")
+    }
+
+    /// Serializes a list with `-` (or the configured bullet) and `1.` markers, or with `*` (`-`
+    /// when `*` is the bullet) and `1)` when `alternate`.
+    fn list(&self, node: &AstNode, alternate: bool) -> String {
+        let start = node.attrs.get("start").and_then(Value::as_u64);
+        let (bullet, delimiter) = match (alternate, self.options.bullet) {
+            (false, bullet) => (bullet, '.'),
+            (true, '*') => ('-', ')'),
+            (true, _) => ('*', ')'),
+        };
+        // Tight items hold inline content directly; loose items wrap it in paragraphs.
+        let tight = !node.children.iter().any(|item| {
+            item.children
+                .iter()
+                .any(|child| child.kind == AstKind::Paragraph)
+        });
+
+        let items: Vec<String> = node
+            .children
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| {
+                let marker = match start {
+                    Some(start) => format!("{}{delimiter} ", start + idx as u64),
+                    None => format!("{bullet} "),
+                };
+                let (task, children) = match item.children.split_first() {
+                    Some((first, rest)) if first.kind == AstKind::TaskListMarker => {
+                        let checked = first.attrs.get("checked") == Some(&Value::Bool(true));
+                        (if checked { "[x] " } else { "[ ] " }, rest)
+                    }
+                    _ => ("", item.children.as_slice()),
+                };
+                let body = format!("{task}{}", self.blocks(children, tight));
+                prefix_lines(&body, &marker, &" ".repeat(marker.len()))
+            })
+            .collect();
+
+        items.join(if tight { "\n" } else { "\n\n" })
+    }
+
+    fn table(&self, node: &AstNode) -> String {
+        let align: Vec<Option<&str>> = match node.attrs.get("align") {
+            Some(Value::Array(values)) => values.iter().map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let rows: Vec<Vec<String>> = node
+            .children
+            .iter()
+            .flat_map(|section| match section.kind {
+                AstKind::TableHead => section.children.iter().collect::<Vec<_>>(),
+                _ => vec![section],
+            })
+            .map(|row| {
+                row.children
+                    .iter()
+                    .map(|cell| self.inlines(cell.children.iter()).replace('|', "\\|"))
+                    .collect()
+            })
+            .collect();
+
+        // The header and delimiter rows have one cell per column; body rows may have more.
+        let columns = match align.len() {
+            0 => rows.first().map_or(0, Vec::len),
+            columns => columns,
+        };
+        let widest = columns.max(rows.iter().map(Vec::len).max().unwrap_or(0));
+        let widths: Vec<usize> = (0..widest)
+            .map(|col| {
+                if !self.options.align_tables {
+                    return 3;
+                }
+                rows.iter()
+                    .filter_map(|row| row.get(col))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(3)
+            })
+            .collect();
+
+        let format_row = |cells: &[String]| {
+            let padded: Vec<String> = (0..columns.max(cells.len()))
+                .map(|col| {
+                    let cell = cells.get(col).map(String::as_str).unwrap_or("");
+                    if self.options.align_tables {
+                        let pad = widths[col].saturating_sub(cell.chars().count());
+                        format!("{cell}{}", " ".repeat(pad))
+                    } else {
+                        cell.to_string()
+                    }
+                })
+                .collect();
+            format!("| {} |", padded.join(" | "))
+        };
+
+        let delimiter: Vec<String> = (0..columns)
+            .map(|col| {
+                let width = widths[col];
+                match align.get(col).copied().flatten() {
+                    Some("left") => format!(":{}", "-".repeat(width - 1)),
+                    Some("center") => format!(":{}:", "-".repeat(width - 2)),
+                    Some("right") => format!("{}:", "-".repeat(width - 1)),
+                    _ => "-".repeat(width),
+                }
+            })
+            .collect();
+
+        let mut lines = Vec::with_capacity(rows.len() + 1);
+        let mut rows = rows.iter();
+        if let Some(head) = rows.next() {
+            lines.push(format_row(head));
+        }
+        lines.push(format!("| {} |", delimiter.join(" | ")));
+        lines.extend(rows.map(|row| format_row(row)));
+        lines.join("\n")
+    }
+
+    fn inlines<'n>(&self, nodes: impl Iterator<Item = &'n AstNode>) -> String {
+        let mut out = String::new();
+        for node in nodes {
+            self.inline(node, &mut out);
+        }
+        escape_line_starts(&out)
+    }
+
+    fn inline(&self, node: &AstNode, out: &mut String) {
+        let wrap = |out: &mut String, delimiter: &str, children: &[AstNode]| {
+            out.push_str(delimiter);
+            for child in children {
+                self.inline(child, out);
+            }
+            out.push_str(delimiter);
+        };
+
+        match node.kind {
+            AstKind::Text => escape_text(literal(node), out),
+            AstKind::Code => {
+                let code = literal(node);
+                let ticks = "`".repeat(longest_run(code, '`') + 1);
+                let pad = if code.starts_with('`') || code.ends_with('`') {
+                    " "
+                } else {
+                    ""
+                };
+                out.push_str(&format!("{ticks}{pad}{code}{pad}{ticks}"));
+            }
+            AstKind::Emphasis => wrap(out, &self.options.emphasis.to_string(), &node.children),
+            AstKind::Strong => wrap(
+                out,
+                &self.options.strong.to_string().repeat(2),
+                &node.children,
+            ),
+            AstKind::Strikethrough => wrap(out, "~~", &node.children),
+            AstKind::Highlight => wrap(out, "==", &node.children),
+            AstKind::Superscript => wrap(out, "^", &node.children),
+            AstKind::Subscript => wrap(out, "~", &node.children),
+            AstKind::Keyboard => out.push_str(&format!("[[{}]]", node.text())),
+            AstKind::HeadingNumber => out.push_str(&format!("{} ", node.text())),
+            AstKind::InlineFigure => {
+                for child in &node.children {
+                    self.inline(child, out);
+                }
+                push_figure_label(node, out);
+            }
+            AstKind::Ruby => {
+                let (readings, base): (Vec<_>, Vec<_>) = node
+                    .children
+                    .iter()
+                    .partition(|child| child.kind == AstKind::RubyText);
+                let base: String = base.iter().map(|child| child.text()).collect();
+                out.push('{');
+                out.push_str(&base);
+                for reading in readings {
+                    out.push('|');
+                    out.push_str(&reading.text());
+                }
+                out.push('}');
+            }
+            AstKind::Link => {
+                let url = str_attr(node, "url").unwrap_or_default();
+                let text = node.text();
+                if node.attrs.get("title").is_none()
+                    && !node.attrs.contains_key("reference")
+                    && text == url
+                    && url.contains(':')
+                {
+                    out.push_str(&format!("<{url}>"));
+                    return;
+                }
+                out.push('[');
+                for child in &node.children {
+                    self.inline(child, out);
+                }
+                out.push(']');
+                push_target(node, out);
+            }
+            AstKind::Image => {
+                out.push_str("![");
+                escape_text(str_attr(node, "alt").unwrap_or_default(), out);
+                out.push(']');
+                push_target(node, out);
+            }
+            AstKind::InlineHtml | AstKind::Html => out.push_str(literal(node)),
+            AstKind::InlineMath => out.push_str(&format!("${}$", literal(node))),
+            AstKind::MdxExpression => out.push_str(&format!("{{{}}}", literal(node))),
+            AstKind::DisplayMath => out.push_str(&format!("$${}$$", literal(node))),
+            AstKind::FootnoteReference | AstKind::Sidenote => {
+                out.push_str(&format!(
+                    "[^{}]",
+                    str_attr(node, "label").unwrap_or_default()
+                ));
+            }
+            AstKind::MdxJsxElement => {
+                let (open, close) = jsx_tags(node);
+                out.push_str(&open);
+                for child in &node.children {
+                    self.inline(child, out);
+                }
+                out.push_str(&close.unwrap_or_default());
+            }
+            AstKind::HardBreak => out.push_str("\\\n"),
+            AstKind::SoftBreak => out.push('\n'),
+            AstKind::TaskListMarker | AstKind::FootnoteBackref => {}
+            _ => {
+                for child in &node.children {
+                    self.inline(child, out);
+                }
+            }
+        }
+    }
+}
+
+fn is_inline(kind: AstKind) -> bool {
+    matches!(
+        kind,
+        AstKind::Text
+            | AstKind::Code
+            | AstKind::Emphasis
+            | AstKind::Strong
+            | AstKind::Strikethrough
+            | AstKind::Highlight
+            | AstKind::Superscript
+            | AstKind::Keyboard
+            | AstKind::Ruby
+            | AstKind::Subscript
+            | AstKind::HeadingNumber
+            | AstKind::InlineFigure
+            | AstKind::Link
+            | AstKind::Image
+            | AstKind::InlineHtml
+            | AstKind::InlineMath
+            | AstKind::FootnoteReference
+            | AstKind::FootnoteBackref
+            | AstKind::Sidenote
+            | AstKind::HardBreak
+            | AstKind::SoftBreak
+            | AstKind::TaskListMarker
+    )
+}
+
+fn literal(node: &AstNode) -> &str {
+    node.value.as_deref().unwrap_or("")
+}
+
+fn str_attr<'n>(node: &'n AstNode, key: &str) -> Option<&'n str> {
+    node.attrs.get(key).and_then(Value::as_str)
+}
+
+/// Writes what follows a link's or image's text: `[label]`, `[]`, or nothing when it refers to a
+/// definition, and `(destination)` otherwise.
+fn push_target(node: &AstNode, out: &mut String) {
+    match str_attr(node, "reference") {
+        Some("full") => {
+            out.push('[');
+            out.push_str(str_attr(node, "label").unwrap_or_default());
+            out.push(']');
+        }
+        Some("collapsed") => out.push_str("[]"),
+        Some(_) => {}
+        None => {
+            out.push('(');
+            out.push_str(&destination(node));
+            out.push(')');
+        }
+    }
+}
+
+/// Writes the `{#fig:label}` block that numbers a figure.
+fn push_figure_label(node: &AstNode, out: &mut String) {
+    if let Some(id) = str_attr(node, "id") {
+        out.push_str(&format!("{{#{id}}}"));
+    }
+}
+
+/// `[label]: destination` lines for the definitions links and images refer to, in order of first
+/// use. Definitions nothing refers to never reach the event stream.
+fn definitions(root: &AstNode) -> String {
+    fn collect(node: &AstNode, seen: &mut HashSet<String>, lines: &mut Vec<String>) {
+        if node.attrs.contains_key("reference")
+            && let Some(label) = str_attr(node, "label")
+            && seen.insert(normalize_label(label))
+        {
+            lines.push(format!("[{label}]: {}", destination(node)));
+        }
+        for child in &node.children {
+            collect(child, seen, lines);
+        }
+    }
+
+    let mut lines = Vec::new();
+    collect(root, &mut HashSet::new(), &mut lines);
+    lines.join("\n")
+}
+
+/// URL, in `<>` when it is empty or has spaces or parentheses, then the quoted title with any size
+/// hint the URL does not carry.
+fn destination(node: &AstNode) -> String {
+    let url = str_attr(node, "url").unwrap_or_default();
+    let mut out = if url.is_empty() || url.contains([' ', '(', ')']) {
+        format!("<{url}>")
+    } else {
+        url.to_string()
+    };
+    let mut title = str_attr(node, "title").unwrap_or_default().to_string();
+    let size = (size_attr(node, "width"), size_attr(node, "height"));
+    // A hint the URL's query already carries is read back from there.
+    if size != (None, None) && size != image_size::query_hint(url) {
+        if !title.is_empty() {
+            title.push(' ');
+        }
+        title.push('=');
+        title.extend(size.0.map(|width| width.to_string()));
+        title.push('x');
+        title.extend(size.1.map(|height| height.to_string()));
+    }
+    if !title.is_empty() {
+        out.push_str(&format!(" \"{}\"", title.replace('"', "\\\"")));
+    }
+    out
+}
+
+fn size_attr(node: &AstNode, key: &str) -> Option<u32> {
+    node.attrs
+        .get(key)
+        .and_then(Value::as_u64)
+        .and_then(|value| u32::try_from(value).ok())
+}
+
+/// Opening and (unless self-closing) closing JSX source of an MDX element node.
+fn jsx_tags(node: &AstNode) -> (String, Option<String>) {
+    let name = str_attr(node, "name");
+    let self_closing = node.attrs.get("selfClosing") == Some(&Value::Bool(true));
+    let attributes: Vec<MdxJsxAttribute<'_>> = match node.attrs.get("attributes") {
+        Some(Value::Array(values)) => values.iter().filter_map(jsx_attribute).collect(),
+        _ => Vec::new(),
+    };
+    let open = jsx_open_tag(name, &attributes, self_closing);
+    let close = (!self_closing).then(|| format!("</{}>", name.unwrap_or_default()));
+    (open, close)
+}
+
+/// Inverse of the attribute objects [`ast`] stores on MDX elements.
+fn jsx_attribute(value: &Value) -> Option<MdxJsxAttribute<'_>> {
+    let name = value.get("name").and_then(Value::as_str);
+    let text = value.get("value").and_then(Value::as_str);
+    Some(match value.get("type")?.as_str()? {
+        "property" => MdxJsxAttribute::Property {
+            name: name?.into(),
+            value: text.map(Into::into),
+        },
+        "expression" => MdxJsxAttribute::Expression {
+            name: name?.into(),
+            value: text?.into(),
+        },
+        "spread" => MdxJsxAttribute::Spread(text?.into()),
+        _ => return None,
+    })
+}
+
+/// Restores frontmatter that the adapter turned into `<pre class="frontmatter">`.
+fn frontmatter(node: &AstNode) -> Option<String> {
+    let (kind, body) = parse_frontmatter(literal(node))?;
+    let fence = if kind == "toml" { "+++" } else { "---" };
+    Some(format!("{fence}\n{body}\n{fence}"))
+}
+
+fn escape_text(text: &str, out: &mut String) {
+    for ch in text.chars() {
+        if matches!(ch, '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '~' | '$') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+}
+
+/// Escapes characters that would turn the start of a line into block syntax.
+fn escape_line_starts(text: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            let needs_escape = line.starts_with(['#', '>', '+', '-', '='])
+                || line
+                    .find(|ch: char| !ch.is_ascii_digit())
+                    .is_some_and(|idx| idx > 0 && line[idx..].starts_with(['.', ')']));
+            if !needs_escape {
+                return line.to_string();
+            }
+            match line.find(|ch: char| !ch.is_ascii_digit()) {
+                Some(idx) if idx > 0 => format!("{}\\{}", &line[..idx], &line[idx..]),
+                _ => format!("\\{line}"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn prefix_lines(text: &str, first: &str, rest: &str) -> String {
+    text.split('\n')
+        .enumerate()
+        .map(|(idx, line)| {
+            let prefix = if idx == 0 { first } else { rest };
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{prefix}{line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn longest_run(text: &str, target: char) -> usize {
+    let (mut longest, mut current) = (0, 0);
+    for ch in text.chars() {
+        if ch == target {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn normalizes_bullets_fences_and_tables() {
+        let input = "Title\n=====\n\n* one\n* two\n    + nested *em* __strong__\n\n~~~js run\nlet a = 1;\n~~~\n\n|a|long header|\n|:-:|--:|\n|x|y|\n\n> quote\n> more";
+        let expected = "# Title\n\n- one\n- two\n  - nested *em* **strong**\n\n```js run\nlet a = 1;\n```\n\n| a   | long header |\n| :-: | ----------: |\n| x   | y           |\n\n> quote\n> more\n";
+
+        let formatted = format(input, &MarkdownOptions::default()).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(
+            format(&formatted, &MarkdownOptions::default()).unwrap(),
+            formatted
+        );
+    }
+
+    #[test]
+    fn round_trips_to_identical_html() {
+        let input = "---\ntitle: x\n---\n\n1. [x] done\n2. [ ] `code` and [link](/a \"T\") ![img](i.png)\n\n---\n\nText with \\* star and 1. not list[^n]\n\n[^n]: Note.\n\n```\n```inner\n```";
+        let formatted = format(input, &MarkdownOptions::default()).unwrap();
+
+        assert_eq!(
+            crate::parse(&formatted).unwrap(),
+            crate::parse(input).unwrap()
+        );
+    }
+    #[test]
+    fn round_trips_adjacent_lists_and_setext_headings() {
+        let cases = [
+            ("+ a\n+ b\n\n* c\n\n- d", "- a\n- b\n\n* c\n\n- d\n"),
+            ("1. a\n\n1) b\n\n- c", "1. a\n\n1) b\n\n- c\n"),
+        ];
+        for (input, expected) in cases {
+            let formatted = format(input, &MarkdownOptions::default()).unwrap();
+            assert_eq!(formatted, expected, "{input:?}");
+            assert_eq!(
+                crate::parse(&formatted).unwrap(),
+                crate::parse(input).unwrap(),
+                "{input:?}"
+            );
+        }
+
+        let starred = MarkdownOptions {
+            bullet: '*',
+            ..MarkdownOptions::default()
+        };
+        assert_eq!(format("- a\n\n* b", &starred).unwrap(), "* a\n\n- b\n");
+
+        // The heading keeps its content, with the line break as a space.
+        let formatted = format("multi\n*line*\n===", &MarkdownOptions::default()).unwrap();
+        assert_eq!(formatted, "# multi *line*\n");
+        assert_eq!(
+            crate::parse(&formatted).unwrap(),
+            "<h1 id=\"multi-line\">multi <em>line</em></h1>\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn keeps_the_header_width_of_tables() {
+        let input = "| a |\n|---|\n| b | c |";
+        let formatted = format(input, &MarkdownOptions::default()).unwrap();
+
+        assert_eq!(formatted, "| a   |\n| --- |\n| b   | c   |\n");
+        assert_eq!(
+            crate::parse(&formatted).unwrap(),
+            crate::parse(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn keeps_link_reference_definitions() {
+        let input = "See [the docs][Docs], [Docs][], and ![logo] [again][docs].\n\n[docs]: </a b> \"Title\"\n[logo]: /logo.png\n[unused]: /u\n";
+        let expected = "See [the docs][Docs], [Docs][], and ![logo] [again][docs].\n\n[Docs]: </a b> \"Title\"\n[logo]: /logo.png\n";
+
+        let formatted = format(input, &MarkdownOptions::default()).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(
+            crate::parse(&formatted).unwrap(),
+            crate::parse(input).unwrap()
+        );
+    }
+}
@@ -0,0 +1,368 @@
+//! Inline marks the parsers leave as text: `==marked==` (Obsidian, markdown-it), `^superscript^`
+//! and `~subscript~` (Pandoc).
+//!
+//! The delimiters arrive inside text events. [`Marks`] buffers each run of inline events that
+//! contains one, pairs the delimiters like emphasis delimiters, and emits the mark's tag around
+//! the marked text.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::event::{Event, Tag, TagEnd};
+
+/// An inline mark written between two identical delimiters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mark {
+    /// `==text==`, [`Tag::Highlight`].
+    Highlight,
+    /// `^text^`, [`Tag::Superscript`].
+    Superscript,
+    /// `~text~`, [`Tag::Subscript`].
+    Subscript,
+}
+
+impl Mark {
+    fn delimiter(self) -> &'static str {
+        match self {
+            Mark::Highlight => "==",
+            Mark::Superscript => "^",
+            Mark::Subscript => "~",
+        }
+    }
+
+    fn start(self) -> Event<'static> {
+        Event::Start(match self {
+            Mark::Highlight => Tag::Highlight,
+            Mark::Superscript => Tag::Superscript,
+            Mark::Subscript => Tag::Subscript,
+        })
+    }
+
+    fn end(self) -> Event<'static> {
+        Event::End(match self {
+            Mark::Highlight => TagEnd::Highlight,
+            Mark::Superscript => TagEnd::Superscript,
+            Mark::Subscript => TagEnd::Subscript,
+        })
+    }
+}
+
+/// Turns paired delimiters of the given marks into tag events.
+///
+/// An opening delimiter must be followed by non-whitespace and a closing one preceded by it;
+/// both must sit inside the same inline container, and longer runs of the delimiter character
+/// (`===`, `~~`) are left alone. Unpaired delimiters stay literal text, and so does the text of
+/// code spans and code blocks for every mark.
+pub(crate) struct Marks<I> {
+    inner: I,
+    marks: Vec<Mark>,
+    pending: VecDeque<Event<'static>>,
+    /// Open code blocks, whose text is never marked up.
+    code_depth: usize,
+}
+
+impl<I> Marks<I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    pub(crate) fn new(inner: I, marks: Vec<Mark>) -> Self {
+        Self {
+            inner,
+            marks,
+            pending: VecDeque::new(),
+            code_depth: 0,
+        }
+    }
+
+    fn has_delimiter(&self, event: &Event<'_>) -> bool {
+        matches!(event, Event::Text(text) if self.marks.iter().any(|mark| text.contains(mark.delimiter())))
+    }
+
+    /// Buffers the inline events starting at `first` and queues them with delimiters paired.
+    fn run(&mut self, first: Event<'static>) {
+        let mut run = vec![first];
+        let mut after = None;
+        for event in self.inner.by_ref() {
+            if is_inline(&event) {
+                run.push(event);
+            } else {
+                after = Some(event);
+                break;
+            }
+        }
+        let run = self.marks.iter().fold(run, |run, &mark| pair(run, mark));
+        self.pending.extend(run);
+        self.pending.extend(after);
+    }
+}
+
+impl<I> Iterator for Marks<I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    type Item = Event<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(event);
+        }
+        match self.inner.next()? {
+            event @ Event::Text(_) if self.code_depth == 0 && self.has_delimiter(&event) => {
+                self.run(event);
+                self.pending.pop_front()
+            }
+            event => {
+                match &event {
+                    Event::Start(Tag::CodeBlock(_)) => self.code_depth += 1,
+                    Event::End(TagEnd::CodeBlock) => {
+                        self.code_depth = self.code_depth.saturating_sub(1)
+                    }
+                    _ => {}
+                }
+                Some(event)
+            }
+        }
+    }
+}
+
+enum Piece {
+    Event(Event<'static>),
+    Delimiter { can_open: bool, can_close: bool },
+}
+
+/// Splits the text of `run` at `mark`'s delimiters, pairs them, and rebuilds the events.
+fn pair(run: Vec<Event<'static>>, mark: Mark) -> Vec<Event<'static>> {
+    let run = merge_text(run);
+    let mut pieces = Vec::new();
+    for (idx, event) in run.iter().enumerate() {
+        let Event::Text(text) = event else {
+            pieces.push(Piece::Event(event.clone()));
+            continue;
+        };
+        // Tags on either side of a text count as non-whitespace, so `==**b**==` pairs.
+        let before_tag = idx > 0 && !is_break(&run[idx - 1]);
+        let after_tag = run.get(idx + 1).is_some_and(|next| !is_break(next));
+        split_delimiters(text, mark.delimiter(), before_tag, after_tag, &mut pieces);
+    }
+
+    // Indices of delimiters that open or close a highlight.
+    let mut openers: Vec<(usize, i32)> = Vec::new();
+    let mut matched = vec![None; pieces.len()];
+    let mut depth = 0i32;
+    for (idx, piece) in pieces.iter().enumerate() {
+        match piece {
+            Piece::Event(Event::Start(_)) => depth += 1,
+            Piece::Event(Event::End(_)) => {
+                depth -= 1;
+                openers.retain(|&(_, opener_depth)| opener_depth <= depth);
+            }
+            Piece::Event(_) => {}
+            Piece::Delimiter {
+                can_open,
+                can_close,
+            } => {
+                if *can_close
+                    && let Some(&(opener, opener_depth)) = openers.last()
+                    && opener_depth == depth
+                {
+                    openers.pop();
+                    matched[opener] = Some(true);
+                    matched[idx] = Some(false);
+                } else if *can_open {
+                    openers.push((idx, depth));
+                }
+            }
+        }
+    }
+
+    let events = pieces
+        .into_iter()
+        .zip(matched)
+        .map(|(piece, matched)| match (piece, matched) {
+            (Piece::Event(event), _) => event,
+            (Piece::Delimiter { .. }, Some(true)) => mark.start(),
+            (Piece::Delimiter { .. }, Some(false)) => mark.end(),
+            (Piece::Delimiter { .. }, None) => Event::Text(Cow::Borrowed(mark.delimiter())),
+        })
+        .collect();
+    merge_text(events)
+}
+
+/// Pushes the text and `delimiter`s of `text` onto `pieces`.
+fn split_delimiters(
+    text: &str,
+    delimiter: &str,
+    before_tag: bool,
+    after_tag: bool,
+    pieces: &mut Vec<Piece>,
+) {
+    let byte = delimiter.as_bytes()[0];
+    let mut literal_start = 0;
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find(char::from(byte)) {
+        let start = pos + offset;
+        let len = text[start..].bytes().take_while(|&b| b == byte).count();
+        pos = start + len;
+        if len != delimiter.len() {
+            continue;
+        }
+        let prev = text[..start].chars().next_back();
+        let next = text[pos..].chars().next();
+        let can_close = prev.map_or(before_tag, |ch| !ch.is_whitespace());
+        let can_open = next.map_or(after_tag, |ch| !ch.is_whitespace());
+        if !can_open && !can_close {
+            continue;
+        }
+        if start > literal_start {
+            pieces.push(Piece::Event(Event::Text(Cow::Owned(
+                text[literal_start..start].to_string(),
+            ))));
+        }
+        pieces.push(Piece::Delimiter {
+            can_open,
+            can_close,
+        });
+        literal_start = pos;
+    }
+    if literal_start < text.len() {
+        pieces.push(Piece::Event(Event::Text(Cow::Owned(
+            text[literal_start..].to_string(),
+        ))));
+    }
+}
+
+/// Joins adjacent text events; pulldown-cmark splits text around `=`, `~`, and brackets.
+pub(crate) fn merge_text(events: Vec<Event<'static>>) -> Vec<Event<'static>> {
+    let mut merged: Vec<Event<'static>> = Vec::with_capacity(events.len());
+    for event in events {
+        if let (Some(Event::Text(last)), Event::Text(text)) = (merged.last_mut(), &event) {
+            last.to_mut().push_str(text);
+            continue;
+        }
+        merged.push(event);
+    }
+    merged
+}
+
+fn is_break(event: &Event<'_>) -> bool {
+    matches!(event, Event::SoftBreak | Event::HardBreak)
+}
+
+/// Events that stay inside one paragraph, heading, or table cell.
+pub(crate) fn is_inline(event: &Event<'_>) -> bool {
+    match event {
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Emphasis
+                | Tag::Strong
+                | Tag::Strikethrough
+                | Tag::Highlight
+                | Tag::Superscript
+                | Tag::Subscript
+                | Tag::Link { .. }
+                | Tag::Image { .. }
+        ),
+        Event::End(end) => matches!(
+            end,
+            TagEnd::Emphasis
+                | TagEnd::Strong
+                | TagEnd::Strikethrough
+                | TagEnd::Highlight
+                | TagEnd::Superscript
+                | TagEnd::Subscript
+                | TagEnd::Link
+                | TagEnd::Image
+        ),
+        Event::Text(_)
+        | Event::Code(_)
+        | Event::InlineMath(_)
+        | Event::InlineHtml(_)
+        | Event::FootnoteReference(_)
+        | Event::SoftBreak
+        | Event::HardBreak => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParserBackend, RenderOptions, SyntaxOptions, render};
+
+    #[test]
+    fn marks_highlighted_text() {
+        let cases = [
+            (
+                "Some ==marked== text.",
+                "<p>Some <mark>marked</mark> text.</p>",
+            ),
+            (
+                "==**bold** and *em*== ==a\nb==",
+                "<p><mark><strong>bold</strong> and <em>em</em></mark> <mark>a\nb</mark></p>",
+            ),
+            (
+                "a == b, x === y, == spaced ==, ==open",
+                "<p>a == b, x === y, == spaced ==, ==open</p>",
+            ),
+            ("==a *b== c*", "<p>==a <em>b== c</em></p>"),
+            (
+                "`==code==` [==link==](/x)",
+                "<p><code>==code==</code> <a href=\"/x\"><mark>link</mark></a></p>",
+            ),
+            (
+                "```\n==code==\n```\n\n    ==indented==",
+                "<pre><code>==code==</code></pre>\n<pre><code>==indented==</code></pre>",
+            ),
+        ];
+
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            for (input, expected) in cases {
+                let options = RenderOptions {
+                    backend,
+                    syntax: SyntaxOptions {
+                        highlight: true,
+                        ..SyntaxOptions::default()
+                    },
+                    ..RenderOptions::default()
+                };
+                let html = render(input, &options).unwrap().html;
+                assert_eq!(html.trim_end(), expected, "{backend:?}: {input:?}");
+            }
+        }
+        let html = render("==x==", &RenderOptions::default()).unwrap().html;
+        assert_eq!(html, "<p>==x==</p>\n");
+    }
+
+    #[test]
+    fn marks_superscript_and_subscript() {
+        let cases = [
+            ("x^2^ and H~2~O", "<p>x<sup>2</sup> and H<sub>2</sub>O</p>"),
+            (
+                "~~gone~~ ^*em*^ ==a^b^==",
+                "<p><del>gone</del> <sup><em>em</em></sup> <mark>a<sup>b</sup></mark></p>",
+            ),
+            ("a ^ b ~ c, `^x^`", "<p>a ^ b ~ c, <code>^x^</code></p>"),
+            (
+                "```\nx^2^ H~2~O ==a==\n```\n\n    ^up^ ~down~",
+                "<pre><code>x^2^ H~2~O ==a==</code></pre>\n<pre><code>^up^ ~down~</code></pre>",
+            ),
+        ];
+
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            for (input, expected) in cases {
+                let options = RenderOptions {
+                    backend,
+                    syntax: SyntaxOptions {
+                        highlight: true,
+                        superscript_subscript: true,
+                        ..SyntaxOptions::default()
+                    },
+                    ..RenderOptions::default()
+                };
+                let html = render(input, &options).unwrap().html;
+                assert_eq!(html.trim_end(), expected, "{backend:?}: {input:?}");
+            }
+        }
+        let html = render("x^2^ ~a~", &RenderOptions::default()).unwrap().html;
+        assert_eq!(html, "<p>x^2^ <del>a</del></p>\n");
+    }
+}
@@ -0,0 +1,529 @@
+//! [mdast](https://github.com/syntax-tree/mdast)-shaped JSON for interop with unified/remark
+//! tooling.
+//!
+//! The tree is derived from the event stream, so source positions are not available and
+//! transforms applied by the pipeline (sidenotes, CJK punctuation) are already reflected.
+
+use serde_json::{Map, Value, json};
+
+use crate::ast::{AstKind, AstNode};
+use crate::markdown_adapter::parse_frontmatter;
+
+/// Converts a document tree into an mdast `root` node.
+pub fn to_mdast(root: &AstNode) -> Value {
+    node(root, false).unwrap_or_else(|| json!({ "type": "root", "children": [] }))
+}
+
+/// Converts one node; `None` for nodes folded into their parent (task markers, table heads).
+/// `inline` tells whether the node sits in phrasing content, which decides the mdast type of MDX
+/// JSX elements.
+fn node(ast: &AstNode, inline: bool) -> Option<Value> {
+    let mut out = Map::new();
+    let kind = match ast.kind {
+        AstKind::Document => "root",
+        AstKind::Paragraph => "paragraph",
+        AstKind::Heading => {
+            out.insert("depth".into(), attr(ast, "level"));
+            "heading"
+        }
+        AstKind::BlockQuote => "blockquote",
+        AstKind::CodeBlock => {
+            out.insert("lang".into(), attr(ast, "lang"));
+            out.insert("meta".into(), attr(ast, "meta"));
+            out.insert("value".into(), value(ast));
+            "code"
+        }
+        AstKind::Callout => {
+            let marker = match ast.attrs.get("kind").and_then(Value::as_str) {
+                Some(kind) => format!("[!{}]", kind.to_ascii_uppercase()),
+                None => "[!NOTE]".to_string(),
+            };
+            let mut blocks = vec![json!({
+                "type": "paragraph",
+                "children": [{ "type": "text", "value": marker }],
+            })];
+            blocks.extend(children(&ast.children, false));
+            out.insert("children".into(), blocks.into());
+            return Some(finish("blockquote", out));
+        }
+        AstKind::HeadingNumber => {
+            return Some(json!({ "type": "text", "value": format!("{} ", ast.text()) }));
+        }
+        AstKind::List => return Some(list(ast)),
+        AstKind::Item => return Some(list_item(ast, !has_paragraph(ast))),
+        AstKind::FootnoteDefinition | AstKind::FootnoteReference => {
+            let label = attr(ast, "label");
+            out.insert("identifier".into(), identifier(&label));
+            out.insert("label".into(), label);
+            if ast.kind == AstKind::FootnoteReference {
+                "footnoteReference"
+            } else {
+                "footnoteDefinition"
+            }
+        }
+        AstKind::Table => {
+            out.insert("align".into(), attr(ast, "align"));
+            let rows: Vec<Value> = ast
+                .children
+                .iter()
+                .flat_map(|child| match child.kind {
+                    AstKind::TableHead => child.children.iter().collect::<Vec<_>>(),
+                    _ => vec![child],
+                })
+                .filter_map(|row| node(row, false))
+                .collect();
+            out.insert("children".into(), rows.into());
+            return Some(finish("table", out));
+        }
+        AstKind::TableHead | AstKind::FootnoteList | AstKind::FootnoteBackref => return None,
+        AstKind::TableRow => "tableRow",
+        AstKind::TableCell => "tableCell",
+        AstKind::Emphasis => "emphasis",
+        AstKind::Strong => "strong",
+        AstKind::Strikethrough => "delete",
+        AstKind::Highlight => "mark",
+        AstKind::Superscript => "sup",
+        AstKind::Keyboard => "kbd",
+        AstKind::Ruby => "ruby",
+        AstKind::RubyText => "rt",
+        AstKind::Subscript => "sub",
+        AstKind::Details => {
+            out.insert("name".into(), "details".into());
+            out.insert("attributes".into(), json!({}));
+            "containerDirective"
+        }
+        AstKind::Summary => {
+            out.insert("data".into(), json!({ "directiveLabel": true }));
+            "paragraph"
+        }
+        AstKind::Figure => {
+            out.insert("name".into(), "figure".into());
+            out.insert("attributes".into(), figure_attributes(ast));
+            // The caption becomes the directive label, which comes first.
+            let (caption, image): (Vec<_>, Vec<_>) = ast
+                .children
+                .iter()
+                .cloned()
+                .partition(|child| child.kind == AstKind::FigureCaption);
+            let mut blocks: Vec<Value> = caption.iter().filter_map(|c| node(c, false)).collect();
+            blocks.push(paragraph(image));
+            out.insert("children".into(), blocks.into());
+            return Some(finish("containerDirective", out));
+        }
+        AstKind::FigureCaption => {
+            out.insert("data".into(), json!({ "directiveLabel": true }));
+            "paragraph"
+        }
+        AstKind::Sidenote => {
+            out.insert("name".into(), "sidenote".into());
+            out.insert("attributes".into(), json!({ "label": attr(ast, "label") }));
+            "textDirective"
+        }
+        AstKind::InlineFigure => {
+            out.insert("name".into(), "figure".into());
+            out.insert("attributes".into(), figure_attributes(ast));
+            "textDirective"
+        }
+        AstKind::Link | AstKind::Image => {
+            out.insert("url".into(), attr(ast, "url"));
+            out.insert("title".into(), attr(ast, "title"));
+            if ast.kind == AstKind::Link {
+                "link"
+            } else {
+                out.insert("alt".into(), attr(ast, "alt"));
+                "image"
+            }
+        }
+        AstKind::Text => {
+            out.insert("value".into(), value(ast));
+            "text"
+        }
+        AstKind::Code => {
+            out.insert("value".into(), value(ast));
+            "inlineCode"
+        }
+        AstKind::Html | AstKind::InlineHtml => {
+            let literal = ast.value.as_deref().unwrap_or("");
+            match parse_frontmatter(literal) {
+                Some((kind, body)) => {
+                    out.insert("value".into(), body.into());
+                    return Some(finish(if kind == "toml" { "toml" } else { "yaml" }, out));
+                }
+                None => {
+                    out.insert("value".into(), literal.into());
+                    "html"
+                }
+            }
+        }
+        AstKind::InlineMath => {
+            out.insert("value".into(), value(ast));
+            "inlineMath"
+        }
+        AstKind::DisplayMath => {
+            out.insert("value".into(), value(ast));
+            "math"
+        }
+        AstKind::TaskListMarker => return None,
+        AstKind::Rule => "thematicBreak",
+        AstKind::HardBreak => "break",
+        AstKind::SoftBreak => {
+            out.insert("value".into(), "\n".into());
+            "text"
+        }
+        AstKind::MdxExpression => {
+            out.insert("value".into(), value(ast));
+            if inline {
+                "mdxTextExpression"
+            } else {
+                "mdxFlowExpression"
+            }
+        }
+        AstKind::MdxJsxElement => {
+            out.insert("name".into(), attr(ast, "name"));
+            out.insert("attributes".into(), jsx_attributes(ast));
+            if inline {
+                "mdxJsxTextElement"
+            } else {
+                "mdxJsxFlowElement"
+            }
+        }
+    };
+
+    if !matches!(
+        ast.kind,
+        AstKind::Text
+            | AstKind::Code
+            | AstKind::CodeBlock
+            | AstKind::Html
+            | AstKind::InlineHtml
+            | AstKind::InlineMath
+            | AstKind::DisplayMath
+            | AstKind::MdxExpression
+            | AstKind::Image
+            | AstKind::FootnoteReference
+            | AstKind::Rule
+            | AstKind::HardBreak
+            | AstKind::SoftBreak
+    ) {
+        let phrasing = match ast.kind {
+            AstKind::MdxJsxElement => inline,
+            kind => is_phrasing_parent(kind),
+        };
+        out.insert("children".into(), children(&ast.children, phrasing).into());
+    }
+    Some(finish(kind, out))
+}
+
+fn finish(kind: &str, mut out: Map<String, Value>) -> Value {
+    out.insert("type".into(), kind.into());
+    Value::Object(out)
+}
+
+/// Converts children, merging adjacent text (soft breaks are plain `\n` text in mdast).
+/// Hoisted notes are lifted out of their list, since mdast has no node for it.
+fn children(nodes: &[AstNode], inline: bool) -> Vec<Value> {
+    let mut out: Vec<Value> = Vec::with_capacity(nodes.len());
+    for child in nodes {
+        if child.kind == AstKind::FootnoteList {
+            out.extend(children(&child.children, false));
+            continue;
+        }
+        let Some(value) = node(child, inline) else {
+            continue;
+        };
+        if value["type"] == "text"
+            && let Some(previous) = out.last_mut()
+            && previous["type"] == "text"
+        {
+            let merged = format!(
+                "{}{}",
+                previous["value"].as_str().unwrap_or(""),
+                value["value"].as_str().unwrap_or("")
+            );
+            previous["value"] = merged.into();
+            continue;
+        }
+        out.push(value);
+    }
+    out
+}
+
+fn list(ast: &AstNode) -> Value {
+    let start = attr(ast, "start");
+    // Items of tight lists hold inline content directly instead of paragraphs.
+    let spread = ast.children.iter().any(has_paragraph);
+    let items: Vec<Value> = ast
+        .children
+        .iter()
+        .map(|item| list_item(item, !spread))
+        .collect();
+    json!({
+        "type": "list",
+        "ordered": !start.is_null(),
+        "start": start,
+        "spread": spread,
+        "children": items,
+    })
+}
+
+fn list_item(item: &AstNode, tight: bool) -> Value {
+    let checked = item
+        .children
+        .iter()
+        .find(|child| child.kind == AstKind::TaskListMarker)
+        .map(|marker| attr(marker, "checked"))
+        .unwrap_or(Value::Null);
+
+    // mdast always wraps inline content in paragraphs; recover them for tight items.
+    let mut blocks = Vec::new();
+    let mut inline = Vec::new();
+    for child in &item.children {
+        if is_inline(child.kind) {
+            inline.push(child.clone());
+            continue;
+        }
+        if !inline.is_empty() {
+            blocks.push(paragraph(std::mem::take(&mut inline)));
+        }
+        blocks.extend(node(child, false));
+    }
+    if !inline.is_empty() {
+        blocks.push(paragraph(inline));
+    }
+
+    json!({
+        "type": "listItem",
+        "spread": !tight,
+        "checked": checked,
+        "children": blocks,
+    })
+}
+
+fn paragraph(inline: Vec<AstNode>) -> Value {
+    json!({ "type": "paragraph", "children": children(&inline, true) })
+}
+
+fn has_paragraph(item: &AstNode) -> bool {
+    item.children
+        .iter()
+        .any(|child| child.kind == AstKind::Paragraph)
+}
+
+fn is_inline(kind: AstKind) -> bool {
+    matches!(
+        kind,
+        AstKind::Text
+            | AstKind::Code
+            | AstKind::Emphasis
+            | AstKind::Strong
+            | AstKind::Strikethrough
+            | AstKind::Highlight
+            | AstKind::Superscript
+            | AstKind::Keyboard
+            | AstKind::Ruby
+            | AstKind::RubyText
+            | AstKind::Subscript
+            | AstKind::HeadingNumber
+            | AstKind::InlineFigure
+            | AstKind::Link
+            | AstKind::Image
+            | AstKind::InlineHtml
+            | AstKind::InlineMath
+            | AstKind::FootnoteReference
+            | AstKind::FootnoteBackref
+            | AstKind::Sidenote
+            | AstKind::HardBreak
+            | AstKind::SoftBreak
+    )
+}
+
+/// Containers whose children are phrasing content.
+fn is_phrasing_parent(kind: AstKind) -> bool {
+    matches!(
+        kind,
+        AstKind::Paragraph
+            | AstKind::Summary
+            | AstKind::FigureCaption
+            | AstKind::InlineFigure
+            | AstKind::Sidenote
+            | AstKind::Heading
+            | AstKind::TableCell
+            | AstKind::Emphasis
+            | AstKind::Strong
+            | AstKind::Strikethrough
+            | AstKind::Highlight
+            | AstKind::Superscript
+            | AstKind::Keyboard
+            | AstKind::Ruby
+            | AstKind::RubyText
+            | AstKind::Subscript
+            | AstKind::Link
+    )
+}
+
+/// mdast JSX attributes: `mdxJsxAttribute` nodes, with expression values wrapped in
+/// `mdxJsxAttributeValueExpression`, and spreads as `mdxJsxExpressionAttribute`.
+fn jsx_attributes(ast: &AstNode) -> Value {
+    let Some(Value::Array(attributes)) = ast.attrs.get("attributes") else {
+        return Value::Array(Vec::new());
+    };
+    attributes
+        .iter()
+        .map(|attribute| match attribute["type"].as_str() {
+            Some("spread") => json!({
+                "type": "mdxJsxExpressionAttribute",
+                "value": attribute["value"],
+            }),
+            Some("expression") => json!({
+                "type": "mdxJsxAttribute",
+                "name": attribute["name"],
+                "value": {
+                    "type": "mdxJsxAttributeValueExpression",
+                    "value": attribute["value"],
+                },
+            }),
+            _ => json!({
+                "type": "mdxJsxAttribute",
+                "name": attribute["name"],
+                "value": attribute.get("value").cloned().unwrap_or(Value::Null),
+            }),
+        })
+        .collect()
+}
+
+/// Directive attributes of a figure: its `id`, when it owns one.
+fn figure_attributes(ast: &AstNode) -> Value {
+    match ast.attrs.get("id") {
+        Some(id) => json!({ "id": id }),
+        None => json!({}),
+    }
+}
+
+fn attr(ast: &AstNode, key: &str) -> Value {
+    ast.attrs.get(key).cloned().unwrap_or(Value::Null)
+}
+
+fn value(ast: &AstNode) -> Value {
+    ast.value.clone().map(Value::String).unwrap_or(Value::Null)
+}
+
+/// mdast identifiers are normalized labels: lowercased with collapsed whitespace.
+fn identifier(label: &Value) -> Value {
+    let label = label.as_str().unwrap_or("");
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RenderOptions, render_ast};
+
+    #[cfg(all(feature = "frontmatter", feature = "tables", feature = "footnotes"))]
+    fn mdast(input: &str) -> Value {
+        to_mdast(&render_ast(input, &RenderOptions::default()).unwrap())
+    }
+
+    #[test]
+    fn types_mdx_nodes_by_context() {
+        let options = RenderOptions {
+            syntax: crate::SyntaxOptions {
+                mdx: true,
+                ..crate::SyntaxOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let tree = to_mdast(
+            &render_ast(
+                "<Chart data={x} />\n\nA <b>bold</b> {n}\n\n{total}",
+                &options,
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(
+            tree["children"][0],
+            json!({
+                "type": "mdxJsxFlowElement",
+                "name": "Chart",
+                "attributes": [{
+                    "type": "mdxJsxAttribute",
+                    "name": "data",
+                    "value": { "type": "mdxJsxAttributeValueExpression", "value": "x" },
+                }],
+                "children": [],
+            })
+        );
+        let inline = &tree["children"][1]["children"][1];
+        assert_eq!(inline["type"], "mdxJsxTextElement");
+        assert_eq!(inline["children"][0]["value"], "bold");
+        assert_eq!(
+            tree["children"][1]["children"][3],
+            json!({ "type": "mdxTextExpression", "value": "n" })
+        );
+        assert_eq!(
+            tree["children"][2],
+            json!({ "type": "mdxFlowExpression", "value": "total" })
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "frontmatter", feature = "tables", feature = "footnotes"))]
+    fn matches_mdast_node_shapes() {
+        let tree = mdast(
+            "---\ntitle: x\n---\n\n## Hi *there*\n\n- [x] done\n- next\n\n```js run\nlet a;\n```\n\n| a |\n|:-:|\n| b |\n\nline\nbreak ~~gone~~ `c`[^N]\n\n[^N]: Note",
+        );
+
+        assert_eq!(tree["type"], "root");
+        assert_eq!(
+            tree["children"][0],
+            json!({ "type": "yaml", "value": "title: x" })
+        );
+
+        let heading = &tree["children"][1];
+        assert_eq!(heading["type"], "heading");
+        assert_eq!(heading["depth"], 2);
+        assert_eq!(heading["children"][1]["type"], "emphasis");
+
+        let list = &tree["children"][2];
+        assert_eq!(list["ordered"], false);
+        assert_eq!(list["spread"], false);
+        assert_eq!(list["children"][0]["checked"], true);
+        assert_eq!(list["children"][1]["checked"], Value::Null);
+        assert_eq!(list["children"][0]["children"][0]["type"], "paragraph");
+        assert_eq!(
+            list["children"][0]["children"][0]["children"][0]["value"],
+            "done"
+        );
+
+        let code = &tree["children"][3];
+        assert_eq!(
+            (&code["type"], &code["lang"], &code["meta"], &code["value"]),
+            (
+                &json!("code"),
+                &json!("js"),
+                &json!("run"),
+                &json!("let a;")
+            )
+        );
+
+        let table = &tree["children"][4];
+        assert_eq!(table["align"], json!(["center"]));
+        assert_eq!(table["children"].as_array().unwrap().len(), 2);
+        assert_eq!(table["children"][0]["type"], "tableRow");
+
+        let paragraph = &tree["children"][5]["children"];
+        assert_eq!(
+            paragraph[0],
+            json!({ "type": "text", "value": "line\nbreak " })
+        );
+        assert_eq!(paragraph[1]["type"], "delete");
+        assert_eq!(paragraph[3]["type"], "inlineCode");
+        assert_eq!(paragraph[4]["identifier"], "n");
+        assert_eq!(tree["children"][6]["type"], "footnoteDefinition");
+    }
+}
@@ -0,0 +1,254 @@
+//! User-supplied hooks around the render pipeline.
+
+use std::borrow::Cow;
+
+use crate::MarkflowError;
+use crate::event::{Event, EventStream, Tag};
+use crate::render::{self, RenderOptions, RenderResult};
+
+/// Hooks a plugin can implement; every hook defaults to a no-op.
+///
+/// Hooks run once per render, in registration order:
+/// `pre_parse` on the Markdown source, `transform` on the event stream (after the built-in
+/// transforms, before metadata collection), and `post_render` on the final HTML.
+pub trait MarkflowPlugin {
+    /// Rewrites the Markdown source before it is parsed.
+    fn pre_parse(&mut self, _source: &mut String) {}
+
+    /// Wraps the event stream; the returned stream feeds the next plugin and then the renderer.
+    fn transform<'a>(&'a mut self, events: EventStream<'a>) -> EventStream<'a> {
+        events
+    }
+
+    /// Rewrites the rendered HTML, after the streaming rewriter has run.
+    fn post_render(&mut self, _html: &mut String) {}
+}
+
+/// Responsive-image attributes an [`ImageTransformer`] adds to an `<img>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageAttributes {
+    /// Candidate sources with their widths or densities (`a-480.webp 480w, a-960.webp 960w`).
+    pub srcset: Option<String>,
+    /// Rendered width per media condition (`(max-width: 600px) 480px, 960px`).
+    pub sizes: Option<String>,
+    /// Decoding hint: `async`, `sync`, or `auto`.
+    pub decoding: Option<String>,
+}
+
+/// Hook into a site's image pipeline: maps each Markdown image's original `src` to the
+/// responsive-image attributes written next to it.
+///
+/// Implemented for closures, so `|src: &str| ImageAttributes { … }` works directly. Images
+/// written as raw HTML are not passed through it.
+pub trait ImageTransformer {
+    /// Attributes for the image at `src`; the default value adds none.
+    fn attributes(&mut self, src: &str) -> ImageAttributes;
+}
+
+impl<F> ImageTransformer for F
+where
+    F: FnMut(&str) -> ImageAttributes,
+{
+    fn attributes(&mut self, src: &str) -> ImageAttributes {
+        self(src)
+    }
+}
+
+/// Adds the attributes `images` returns for an image start event; other events pass through.
+pub(crate) fn transform_image<'a>(
+    event: Event<'a>,
+    images: &mut dyn ImageTransformer,
+) -> Event<'a> {
+    let Event::Start(Tag::Image {
+        link_type,
+        dest_url,
+        title,
+        id,
+        width,
+        height,
+        mut attrs,
+    }) = event
+    else {
+        return event;
+    };
+    let ImageAttributes {
+        srcset,
+        sizes,
+        decoding,
+    } = images.attributes(&dest_url);
+    for (key, value) in [("srcset", srcset), ("sizes", sizes), ("decoding", decoding)] {
+        if let Some(value) = value {
+            attrs.push((Cow::Borrowed(key), Cow::Owned(value)));
+        }
+    }
+    Event::Start(Tag::Image {
+        link_type,
+        dest_url,
+        title,
+        id,
+        width,
+        height,
+        attrs,
+    })
+}
+
+/// Render options plus an ordered chain of [`MarkflowPlugin`]s.
+#[derive(Default)]
+pub struct Pipeline {
+    options: RenderOptions,
+    plugins: Vec<Box<dyn MarkflowPlugin>>,
+    images: Option<Box<dyn ImageTransformer>>,
+}
+
+impl Pipeline {
+    /// Creates a pipeline without plugins.
+    pub fn new(options: RenderOptions) -> Self {
+        Self {
+            options,
+            plugins: Vec::new(),
+            images: None,
+        }
+    }
+
+    /// Appends `plugin` to the chain.
+    pub fn with_plugin(mut self, plugin: impl MarkflowPlugin + 'static) -> Self {
+        self.register(plugin);
+        self
+    }
+
+    /// Appends `plugin` to the chain.
+    pub fn register(&mut self, plugin: impl MarkflowPlugin + 'static) -> &mut Self {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// Passes every Markdown image through `images`, after the plugin transforms.
+    pub fn with_image_transformer(mut self, images: impl ImageTransformer + 'static) -> Self {
+        self.images = Some(Box::new(images));
+        self
+    }
+
+    /// Options every render uses.
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// Renders `input` like [`crate::render`], running every plugin hook along the way.
+    pub fn render(&mut self, input: &str) -> Result<RenderResult, MarkflowError> {
+        let mut source = input.to_string();
+        for plugin in &mut self.plugins {
+            plugin.pre_parse(&mut source);
+        }
+
+        let images: Option<&mut dyn ImageTransformer> = match &mut self.images {
+            Some(images) => Some(images.as_mut()),
+            None => None,
+        };
+        let mut result =
+            render::render_with_plugins(&source, &self.options, &mut self.plugins, images)?;
+
+        for plugin in &mut self.plugins {
+            plugin.post_render(&mut result.html);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+
+    /// Expands `:name:` shortcodes before parsing.
+    struct Shortcodes;
+
+    impl MarkflowPlugin for Shortcodes {
+        fn pre_parse(&mut self, source: &mut String) {
+            *source = source.replace(":wave:", "👋");
+        }
+    }
+
+    /// Shouts every text event and counts how many it saw.
+    #[derive(Default)]
+    struct Shout {
+        seen: usize,
+    }
+
+    impl MarkflowPlugin for Shout {
+        fn transform<'a>(&'a mut self, events: EventStream<'a>) -> EventStream<'a> {
+            Box::new(events.map(|event| match event {
+                Event::Text(text) => {
+                    self.seen += 1;
+                    Event::Text(text.to_uppercase().into())
+                }
+                other => other,
+            }))
+        }
+    }
+
+    /// Appends a marker so hook order is observable.
+    struct Stamp(&'static str);
+
+    impl MarkflowPlugin for Stamp {
+        fn post_render(&mut self, html: &mut String) {
+            html.push_str(self.0);
+        }
+    }
+
+    #[test]
+    fn runs_hooks_in_registration_order() {
+        let mut pipeline = Pipeline::new(RenderOptions {
+            headings: true,
+            ..RenderOptions::default()
+        })
+        .with_plugin(Shortcodes)
+        .with_plugin(Shout::default())
+        .with_plugin(Stamp("<!--a-->"));
+        pipeline.register(Stamp("<!--b-->"));
+
+        let result = pipeline.render("# Hi :wave:\n\nsee *you*").unwrap();
+
+        assert_eq!(
+            result.html,
+            "<h1 id=\"hi\">HI 👋</h1>\n<p>SEE <em>YOU</em></p>\n<!--a--><!--b-->"
+        );
+        // Metadata collectors see the transformed stream.
+        assert_eq!(result.headings[0].text, "HI 👋");
+    }
+
+    #[test]
+    #[cfg(feature = "rewriter")]
+    fn transforms_images() {
+        let mut pipeline = Pipeline::default()
+            .with_plugin(Shout::default())
+            .with_image_transformer(|src: &str| match src.strip_suffix(".png") {
+                Some(stem) => ImageAttributes {
+                    srcset: Some(format!("{stem}-480.webp 480w, {stem}-960.webp 960w")),
+                    sizes: Some("(max-width: 600px) 480px, 960px".to_string()),
+                    decoding: Some("async".to_string()),
+                },
+                None => ImageAttributes::default(),
+            });
+
+        let html = pipeline
+            .render("![cat](/cat.png \"Cat\") ![logo](/logo.svg) <img src=\"/raw.png\">")
+            .unwrap()
+            .html;
+
+        assert_eq!(
+            html,
+            "<p><img src=\"/cat.png\" alt=\"CAT\" title=\"Cat\" \
+             srcset=\"/cat-480.webp 480w, /cat-960.webp 960w\" \
+             sizes=\"(max-width: 600px) 480px, 960px\" decoding=\"async\" loading=\"lazy\" /> \
+             <img src=\"/logo.svg\" alt=\"LOGO\" loading=\"lazy\" /> \
+             <img src=\"/raw.png\" loading=\"lazy\"></p>\n"
+        );
+    }
+
+    #[test]
+    fn pipeline_without_plugins_matches_render() {
+        let input = "# Title\n\n- [x] done";
+        let html = Pipeline::default().render(input).unwrap().html;
+        assert_eq!(html, crate::parse(input).unwrap());
+    }
+}
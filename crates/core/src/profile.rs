@@ -0,0 +1,275 @@
+//! Named option presets so every binding configures common deployments the same way.
+
+use crate::backend::SyntaxOptions;
+use crate::footnotes::FootnoteStyle;
+use crate::html_renderer::HtmlOptions;
+use crate::limits::Limits;
+use crate::render::RenderOptions;
+use crate::rewrite_options::{LinkHardening, RewriteOptions};
+use crate::slug::SlugStyle;
+
+/// A bundle of [`RenderOptions`] for a common kind of content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Profile {
+    /// [`RenderOptions::default`].
+    #[default]
+    Default,
+    /// User-generated content such as comments: raw HTML is escaped, unsafe URLs are dropped,
+    /// external links are hardened, autolinks stay on, math, footnotes, and frontmatter are
+    /// off, and input, nesting, and output sizes are capped.
+    Untrusted,
+    /// README previews that should match github.com: GitHub's heading slugs with `user-content-`
+    /// ids, alerts for `> [!NOTE]` quotes, task checkboxes inside loose items' paragraphs, and
+    /// footnotes renumbered into a list with backlinks at the end.
+    GitHub,
+    /// Trusted documentation sites: headings accept `{#id .class}` blocks, get self-links, and
+    /// feed a table of contents, callouts render as alert boxes, code meta is exposed to
+    /// highlighters, footnotes collect at the end, and external links get `noopener noreferrer`.
+    Docs,
+}
+
+impl Profile {
+    /// Limits applied by [`Profile::Untrusted`].
+    pub const UNTRUSTED_LIMITS: Limits = Limits {
+        max_input_bytes: Some(64 * 1024),
+        max_nesting_depth: Some(32),
+        max_output_bytes: Some(1024 * 1024),
+    };
+
+    /// The options this profile stands for; adjust individual fields afterwards as needed.
+    pub fn options(self) -> RenderOptions {
+        match self {
+            Profile::Default => RenderOptions::default(),
+            Profile::Untrusted => RenderOptions {
+                syntax: SyntaxOptions {
+                    autolinks: true,
+                    math: false,
+                    footnotes: false,
+                    frontmatter: false,
+                    heading_attributes: false,
+                    highlight: false,
+                    superscript_subscript: false,
+                    breaks: false,
+                    mdx: false,
+                    image_sizes: false,
+                },
+                html: HtmlOptions {
+                    escape_raw_html: true,
+                    sanitize_urls: true,
+                    ..HtmlOptions::default()
+                },
+                rewrite: RewriteOptions {
+                    harden_links: LinkHardening::UserContent,
+                    ..RewriteOptions::default()
+                },
+                limits: Self::UNTRUSTED_LIMITS,
+                ..RenderOptions::default()
+            },
+            Profile::GitHub => RenderOptions {
+                html: HtmlOptions {
+                    task_marker_in_paragraph: true,
+                    sanitize_urls: true,
+                    heading_id_prefix: "user-content-".to_string(),
+                    ..HtmlOptions::default()
+                },
+                rewrite: RewriteOptions {
+                    enforce_img_loading_lazy: false,
+                    ..RewriteOptions::default()
+                },
+                slug_style: SlugStyle::GitHub,
+                callouts: true,
+                footnote_style: FootnoteStyle::Gfm,
+                ..RenderOptions::default()
+            },
+            Profile::Docs => RenderOptions {
+                syntax: SyntaxOptions {
+                    heading_attributes: true,
+                    ..SyntaxOptions::default()
+                },
+                html: HtmlOptions {
+                    heading_anchor_links: true,
+                    code_meta: true,
+                    ..HtmlOptions::default()
+                },
+                rewrite: RewriteOptions {
+                    harden_links: LinkHardening::Trusted,
+                    ..RewriteOptions::default()
+                },
+                callouts: true,
+                footnote_style: FootnoteStyle::Endnotes,
+                headings: true,
+                toc: true,
+                ..RenderOptions::default()
+            },
+        }
+    }
+
+    /// Lowercase name used by the JS bindings.
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::Default => "default",
+            Profile::Untrusted => "untrusted",
+            Profile::GitHub => "github",
+            Profile::Docs => "docs",
+        }
+    }
+
+    /// Inverse of [`Profile::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Profile::Default,
+            Profile::Untrusted,
+            Profile::GitHub,
+            Profile::Docs,
+        ]
+        .into_iter()
+        .find(|profile| profile.name() == name)
+    }
+}
+
+impl From<Profile> for RenderOptions {
+    fn from(profile: Profile) -> Self {
+        profile.options()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LimitKind, MarkflowError, render};
+
+    #[cfg(feature = "rewriter")]
+    fn untrusted(input: &str) -> String {
+        render(input, &Profile::Untrusted.into()).unwrap().html
+    }
+
+    #[test]
+    #[cfg(feature = "rewriter")]
+    fn untrusted_profile_neutralizes_hostile_markup() {
+        let cases = [
+            (
+                "<script>alert(1)</script>",
+                "&lt;script&gt;alert(1)&lt;/script&gt;",
+            ),
+            (
+                "hi <img src=x onerror=alert(1)>",
+                "<p>hi &lt;img src=x onerror=alert(1)&gt;</p>",
+            ),
+            ("[x](javascript:alert(1))", "<p><a href=\"\">x</a></p>"),
+            (
+                "![x](data:image/svg+xml;base64,PHN2Zz4=)",
+                "<p><img src=\"\" alt=\"x\" loading=\"lazy\" /></p>",
+            ),
+            (
+                "see https://example.com",
+                "<p>see <a href=\"https://example.com\" rel=\"nofollow ugc noopener noreferrer\">https://example.com</a></p>",
+            ),
+            ("[home](/home)", "<p><a href=\"/home\">home</a></p>"),
+            ("$x$ and [^1]", "<p>$x$ and [^1]</p>"),
+        ];
+
+        for (input, expected) in cases {
+            let html = untrusted(input);
+            assert!(html.contains(expected), "{input:?} => {html}");
+        }
+    }
+
+    #[test]
+    fn untrusted_profile_caps_resources() {
+        let deep = "> ".repeat(40) + "deep";
+        match render(&deep, &Profile::Untrusted.into()) {
+            Err(MarkflowError::Limit { source, .. }) => {
+                assert_eq!(source.kind, LimitKind::NestingDepth)
+            }
+            other => panic!("expected nesting limit, got {other:?}"),
+        }
+        assert!(render(&deep, &Profile::Default.into()).is_ok());
+    }
+
+    #[test]
+    #[cfg(all(feature = "rewriter", feature = "footnotes"))]
+    fn docs_profile_bundles_documentation_features() {
+        let input = "# Guide\n\nSee [site](https://example.com) and the note.[^n]\n\n[^n]: Details.\n\n## Install\n\n> [!TIP]\n> Use cargo.\n\n```rust title=\"main.rs\"\nfn main() {}\n```";
+        let result = render(input, &Profile::Docs.into()).unwrap();
+        let html = &result.html;
+
+        let expected = [
+            "<h1 id=\"guide\"><a class=\"heading-anchor\" href=\"#guide\" aria-hidden=\"true\">#</a>Guide</h1>",
+            "<a href=\"https://example.com\" rel=\"noopener noreferrer\">site</a>",
+            "<div class=\"markdown-alert markdown-alert-tip\">",
+            "<pre data-meta=\"title=&quot;main.rs&quot;\"><code class=\"language-rust\">",
+        ];
+        for fragment in expected {
+            assert!(html.contains(fragment), "missing {fragment:?} in {html}");
+        }
+        assert!(html.ends_with(
+            "<section class=\"footnote\" id=\"fn-n\"><p>Details.</p>\n</section>\n</section>\n"
+        ));
+        assert_eq!(
+            result.toc.as_deref(),
+            Some(
+                "<ul><li><a href=\"#guide\">Guide</a><ul><li><a href=\"#install\">Install</a></li></ul></li></ul>"
+            )
+        );
+        assert_eq!(result.headings.len(), 2);
+    }
+
+    /// Renders every `fixtures/github/*.md` with [`Profile::GitHub`] and compares it with the
+    /// `.html` next to it. Set `MARKFLOW_BLESS=1` to rewrite the expectations after checking the
+    /// new output against github.com.
+    ///
+    /// The `.html` files are Markflow's reviewed output, not GitHub's: the sandboxed test run
+    /// cannot call GitHub's Markdown API. Where github.com renders differently (`<ol start="1">`,
+    /// task-list classes, heading anchor markup, footnote ids, ...) the markup is listed in
+    /// `known-differences.json`, and each listed Markflow snippet must still be in the output so
+    /// the list stays accurate.
+    #[test]
+    #[cfg(all(feature = "tables", feature = "footnotes"))]
+    fn github_profile_matches_golden_corpus() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../fixtures/github");
+        let bless = std::env::var_os("MARKFLOW_BLESS").is_some();
+        let mut inputs: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+            .collect();
+        inputs.sort();
+        assert!(!inputs.is_empty(), "no fixtures in {}", dir.display());
+        let differences: std::collections::BTreeMap<String, Vec<serde_json::Value>> =
+            serde_json::from_str(
+                &std::fs::read_to_string(dir.join("known-differences.json")).unwrap(),
+            )
+            .unwrap();
+
+        for input in inputs {
+            let source = std::fs::read_to_string(&input).unwrap();
+            let html = render(&source, &Profile::GitHub.into()).unwrap().html;
+            let golden = input.with_extension("html");
+            if bless {
+                std::fs::write(&golden, &html).unwrap();
+                continue;
+            }
+            let expected = std::fs::read_to_string(&golden).unwrap_or_default();
+            assert_eq!(html, expected, "{}", input.display());
+
+            let stem = input.file_stem().unwrap().to_str().unwrap();
+            for difference in differences.get(stem).into_iter().flatten() {
+                let ours = difference["markflow"].as_str().unwrap();
+                assert!(html.contains(ours), "{stem}: stale difference {ours:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn profiles_round_trip_names() {
+        for profile in [
+            Profile::Default,
+            Profile::Untrusted,
+            Profile::GitHub,
+            Profile::Docs,
+        ] {
+            assert_eq!(Profile::from_name(profile.name()), Some(profile));
+        }
+        assert_eq!(Profile::from_name("nope"), None);
+    }
+}
@@ -0,0 +1,487 @@
+//! Adapter from pulldown-cmark events to Markflow's [`Event`] model.
+//!
+//! The stream is normalized to match the markdown-rs adapter: headings get slug ids, code block
+//! text arrives as one event without the trailing newline, table heads wrap their cells in a row,
+//! image alt text is flattened, display math is a block, HTML blocks arrive as one event, and
+//! metadata blocks become frontmatter HTML.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::rc::Rc;
+
+use pulldown_cmark as pd;
+
+use crate::backend::SyntaxOptions;
+use crate::diagnostics::{DiagnosticKind, Diagnostics};
+use crate::event::{Alignment, CodeBlockKind, Event, HeadingLevel, LinkType, Tag, TagEnd};
+use crate::image_size;
+#[cfg(feature = "frontmatter")]
+use crate::markdown_adapter::format_frontmatter;
+use crate::slug::Slugger;
+use crate::span::SourceSpan;
+
+/// Callback pulldown-cmark calls for references without a definition.
+type BrokenLinks<'a> =
+    Box<dyn FnMut(pd::BrokenLink<'a>) -> Option<(pd::CowStr<'a>, pd::CowStr<'a>)> + 'a>;
+
+/// Lazily converts a pulldown-cmark parse into owned Markflow events.
+pub struct PulldownEventIter<'a> {
+    input: &'a str,
+    parser: pd::OffsetIter<'a, BrokenLinks<'a>>,
+    /// Raw event read ahead by [`Self::paragraph`] that still needs normal handling. It is
+    /// always the last event read, so `range` stays its own.
+    lookahead: Option<pd::Event<'a>>,
+    /// Source bytes of the last raw event.
+    range: Range<usize>,
+    pending: VecDeque<Event<'static>>,
+    /// Heading ids handed out so far, so repeated headings get unique ids.
+    slugger: Slugger,
+    /// Keep `{#id .class key=value}` blocks in code meta.
+    attribute_blocks: bool,
+    /// Report soft breaks as hard breaks.
+    hard_breaks: bool,
+    /// Read size hints into images.
+    image_sizes: bool,
+    /// Collector for the problems found while parsing.
+    diagnostics: Option<Rc<RefCell<Diagnostics>>>,
+}
+
+impl<'a> PulldownEventIter<'a> {
+    /// Parses `input` with the constructs selected by `syntax`, recording duplicate heading ids,
+    /// images without alt text, and unresolved references into `diagnostics` as the parse
+    /// reaches them. The collector is taken here because pulldown-cmark reports unresolved
+    /// references to a callback given to the parser.
+    pub fn with_syntax(
+        input: &'a str,
+        syntax: &SyntaxOptions,
+        diagnostics: Option<Rc<RefCell<Diagnostics>>>,
+    ) -> Self {
+        let mut options = pd::Options::ENABLE_STRIKETHROUGH | pd::Options::ENABLE_TASKLISTS;
+        options.set(pd::Options::ENABLE_TABLES, cfg!(feature = "tables"));
+        options.set(
+            pd::Options::ENABLE_FOOTNOTES,
+            syntax.footnotes && cfg!(feature = "footnotes"),
+        );
+        options.set(
+            pd::Options::ENABLE_MATH,
+            syntax.math && cfg!(feature = "math"),
+        );
+        options.set(
+            pd::Options::ENABLE_HEADING_ATTRIBUTES,
+            syntax.heading_attributes,
+        );
+        options.set(
+            pd::Options::ENABLE_SUPERSCRIPT | pd::Options::ENABLE_SUBSCRIPT,
+            syntax.superscript_subscript,
+        );
+        options.set(
+            pd::Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+                | pd::Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS,
+            syntax.frontmatter && cfg!(feature = "frontmatter"),
+        );
+        if syntax.mdx
+            && let Some(diagnostics) = &diagnostics
+        {
+            diagnostics.borrow_mut().push(
+                DiagnosticKind::UnsupportedNode,
+                "pulldown-cmark does not support MDX; this renders as plain Markdown",
+                None,
+            );
+        }
+        let broken_links = diagnostics.clone().map(|diagnostics| -> BrokenLinks<'a> {
+            Box::new(move |link: pd::BrokenLink<'a>| {
+                // Shortcuts such as `[x]` are usually meant as text.
+                if matches!(
+                    link.link_type,
+                    pd::LinkType::Reference | pd::LinkType::Collapsed
+                ) {
+                    diagnostics.borrow_mut().unresolved_reference(
+                        &link.reference,
+                        Some(SourceSpan::from_range(input, link.span)),
+                    );
+                }
+                None
+            })
+        });
+        Self {
+            input,
+            parser: pd::Parser::new_with_broken_link_callback(input, options, broken_links)
+                .into_offset_iter(),
+            lookahead: None,
+            range: 0..0,
+            pending: VecDeque::new(),
+            slugger: Slugger::new(),
+            attribute_blocks: syntax.heading_attributes,
+            hard_breaks: syntax.breaks,
+            image_sizes: syntax.image_sizes,
+            diagnostics,
+        }
+    }
+
+    /// Continues numbering heading ids from `slugger`.
+    pub(crate) fn with_slugger(mut self, slugger: Slugger) -> Self {
+        self.slugger = slugger;
+        self
+    }
+
+    /// Buffers a heading so its slug can be computed from the text before the start tag is
+    /// emitted.
+    fn heading(
+        &mut self,
+        level: pd::HeadingLevel,
+        id: Option<pd::CowStr<'a>>,
+        classes: Vec<pd::CowStr<'a>>,
+        attrs: Vec<(pd::CowStr<'a>, Option<pd::CowStr<'a>>)>,
+    ) {
+        let level = heading_level(level);
+        let range = self.range.clone();
+        let mut body = Vec::new();
+        let mut raw = String::new();
+        while let Some(event) = self.next_raw() {
+            match event {
+                pd::Event::End(pd::TagEnd::Heading(_)) => break,
+                pd::Event::Text(ref text) | pd::Event::Code(ref text) => raw.push_str(text),
+                _ => {}
+            }
+            body.extend(convert(event));
+        }
+
+        let id = match id {
+            Some(id) => {
+                let unique = self.slugger.unique(id.to_string());
+                self.diagnose(range, |diagnostics, span| {
+                    diagnostics.duplicate_id(&id, &unique, span)
+                });
+                Some(Cow::Owned(unique))
+            }
+            None => self.slugger.slug(&raw).map(Cow::Owned),
+        };
+        self.pending.push_back(Event::Start(Tag::Heading {
+            level,
+            id,
+            classes: classes.into_iter().map(owned).collect(),
+            attrs: attrs
+                .into_iter()
+                .map(|(key, value)| (owned(key), value.map(owned)))
+                .collect(),
+        }));
+        self.pending.extend(body);
+        self.pending.push_back(Event::End(TagEnd::Heading(level)));
+    }
+
+    /// pulldown-cmark parses `$$` blocks as display math inside a paragraph; markdown-rs makes
+    /// them a block of their own, so a paragraph holding nothing else is unwrapped.
+    #[cfg(feature = "math")]
+    fn paragraph(&mut self) -> Event<'static> {
+        match self.next_raw() {
+            Some(pd::Event::DisplayMath(math)) => match self.next_raw() {
+                Some(pd::Event::End(pd::TagEnd::Paragraph)) => {
+                    return Event::DisplayMath(Cow::Owned(math.trim_matches('\n').to_string()));
+                }
+                next => {
+                    self.pending
+                        .push_back(Event::DisplayMath(Cow::Owned(math.into_string())));
+                    self.lookahead = next;
+                }
+            },
+            next => self.lookahead = next,
+        }
+        Event::Start(Tag::Paragraph)
+    }
+
+    fn next_raw(&mut self) -> Option<pd::Event<'a>> {
+        self.lookahead.take().or_else(|| {
+            let (event, range) = self.parser.next()?;
+            self.range = range;
+            Some(event)
+        })
+    }
+
+    /// Hands the collector, if any, the span of the source bytes `range`.
+    fn diagnose(
+        &self,
+        range: Range<usize>,
+        record: impl FnOnce(&mut Diagnostics, Option<SourceSpan>),
+    ) {
+        if let Some(diagnostics) = &self.diagnostics {
+            record(
+                &mut diagnostics.borrow_mut(),
+                Some(SourceSpan::from_range(self.input, range)),
+            );
+        }
+    }
+
+    /// Flattens image content to its plain text, as markdown-rs reports alt text.
+    fn alt_text(&mut self) -> String {
+        let mut alt = String::new();
+        let mut depth = 0usize;
+        while let Some(event) = self.next_raw() {
+            match event {
+                pd::Event::Start(_) => depth += 1,
+                pd::Event::End(_) if depth == 0 => break,
+                pd::Event::End(_) => depth -= 1,
+                pd::Event::Text(text) | pd::Event::Code(text) => alt.push_str(&text),
+                _ => {}
+            }
+        }
+        alt
+    }
+
+    /// Collects the lines of an HTML block, which pulldown-cmark reports one by one, so the
+    /// block is a single `Html` event as with markdown-rs.
+    fn literal_html(&mut self) -> String {
+        let mut html = String::new();
+        while let Some(event) = self.next_raw() {
+            match event {
+                pd::Event::Html(chunk) | pd::Event::Text(chunk) => html.push_str(&chunk),
+                pd::Event::End(_) => break,
+                _ => {}
+            }
+        }
+        html
+    }
+
+    /// Collects literal text up to the matching end tag.
+    fn literal(&mut self) -> String {
+        let mut text = String::new();
+        while let Some(event) = self.next_raw() {
+            match event {
+                pd::Event::Text(chunk) => text.push_str(&chunk),
+                pd::Event::End(_) => break,
+                _ => {}
+            }
+        }
+        text
+    }
+}
+
+impl Iterator for PulldownEventIter<'_> {
+    type Item = Event<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+
+            match self.next_raw()? {
+                pd::Event::Start(pd::Tag::Heading {
+                    level,
+                    id,
+                    classes,
+                    attrs,
+                }) => self.heading(level, id, classes, attrs),
+                pd::Event::Start(pd::Tag::CodeBlock(kind)) => {
+                    let mut kind = match kind {
+                        pd::CodeBlockKind::Indented => CodeBlockKind::Indented,
+                        pd::CodeBlockKind::Fenced(info) => {
+                            CodeBlockKind::fenced(Cow::Owned(info.into_string()))
+                        }
+                    };
+                    if !self.attribute_blocks {
+                        kind.clear_attribute_block();
+                    }
+                    let mut code = self.literal();
+                    if code.ends_with('\n') {
+                        code.pop();
+                    }
+                    self.pending.push_back(Event::Start(Tag::CodeBlock(kind)));
+                    if !code.is_empty() {
+                        self.pending.push_back(Event::Text(Cow::Owned(code)));
+                    }
+                    self.pending.push_back(Event::End(TagEnd::CodeBlock));
+                }
+                #[cfg(feature = "frontmatter")]
+                pd::Event::Start(pd::Tag::MetadataBlock(kind)) => {
+                    let kind = match kind {
+                        pd::MetadataBlockKind::YamlStyle => "yaml",
+                        pd::MetadataBlockKind::PlusesStyle => "toml",
+                    };
+                    let value = self.literal();
+                    return Some(Event::Html(Cow::Owned(format_frontmatter(
+                        kind,
+                        value.trim_end_matches('\n'),
+                    ))));
+                }
+                pd::Event::Start(pd::Tag::HtmlBlock) => {
+                    return Some(Event::Html(Cow::Owned(self.literal_html())));
+                }
+                #[cfg(feature = "math")]
+                pd::Event::Start(pd::Tag::Paragraph) => return Some(self.paragraph()),
+                pd::Event::SoftBreak if self.hard_breaks => return Some(Event::HardBreak),
+                pd::Event::Start(tag @ pd::Tag::Image { .. }) => {
+                    let Some(mut tag) = convert_tag(tag) else {
+                        continue;
+                    };
+                    if self.image_sizes {
+                        tag = image_size::apply(tag);
+                    }
+                    let range = self.range.clone();
+                    let alt = self.alt_text();
+                    self.diagnose(range, |diagnostics, span| diagnostics.image(&alt, span));
+                    if !alt.is_empty() {
+                        self.pending.push_back(Event::Text(Cow::Owned(alt)));
+                    }
+                    self.pending.push_back(Event::End(TagEnd::Image));
+                    return Some(Event::Start(tag));
+                }
+                #[cfg(feature = "tables")]
+                pd::Event::Start(pd::Tag::TableHead) => {
+                    self.pending.push_back(Event::Start(Tag::TableRow));
+                    return Some(Event::Start(Tag::TableHead));
+                }
+                #[cfg(feature = "tables")]
+                pd::Event::End(pd::TagEnd::TableHead) => {
+                    self.pending.push_back(Event::End(TagEnd::TableHead));
+                    return Some(Event::End(TagEnd::TableRow));
+                }
+                event => {
+                    if let Some(event) = convert(event) {
+                        return Some(event);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn owned(text: pd::CowStr<'_>) -> Cow<'static, str> {
+    Cow::Owned(text.into_string())
+}
+
+/// One-to-one conversions; `None` for events without a Markflow counterpart.
+fn convert(event: pd::Event<'_>) -> Option<Event<'static>> {
+    Some(match event {
+        pd::Event::Start(tag) => Event::Start(convert_tag(tag)?),
+        pd::Event::End(end) => Event::End(convert_end(end)?),
+        pd::Event::Text(text) => Event::Text(owned(text)),
+        pd::Event::Code(code) => Event::Code(owned(code)),
+        pd::Event::Html(html) => Event::Html(owned(html)),
+        pd::Event::InlineHtml(html) => Event::InlineHtml(owned(html)),
+        pd::Event::InlineMath(math) => Event::InlineMath(owned(math)),
+        pd::Event::DisplayMath(math) => Event::DisplayMath(owned(math)),
+        pd::Event::FootnoteReference(label) => Event::FootnoteReference(owned(label)),
+        pd::Event::TaskListMarker(checked) => Event::TaskListMarker(checked),
+        pd::Event::Rule => Event::Rule,
+        pd::Event::HardBreak => Event::HardBreak,
+        pd::Event::SoftBreak => Event::SoftBreak,
+    })
+}
+
+fn convert_tag(tag: pd::Tag<'_>) -> Option<Tag<'static>> {
+    Some(match tag {
+        pd::Tag::Paragraph => Tag::Paragraph,
+        pd::Tag::Heading { level, id, .. } => Tag::Heading {
+            level: heading_level(level),
+            id: id.map(owned),
+            classes: Vec::new(),
+            attrs: Vec::new(),
+        },
+        pd::Tag::BlockQuote(_) => Tag::BlockQuote,
+        pd::Tag::CodeBlock(pd::CodeBlockKind::Indented) => Tag::CodeBlock(CodeBlockKind::Indented),
+        pd::Tag::CodeBlock(pd::CodeBlockKind::Fenced(info)) => {
+            Tag::CodeBlock(CodeBlockKind::fenced(owned(info)))
+        }
+        pd::Tag::List(start) => Tag::List(start),
+        pd::Tag::Item => Tag::Item,
+        pd::Tag::FootnoteDefinition(label) => Tag::FootnoteDefinition(owned(label)),
+        pd::Tag::Table(alignments) => Tag::Table(alignments.into_iter().map(alignment).collect()),
+        pd::Tag::TableHead => Tag::TableHead,
+        pd::Tag::TableRow => Tag::TableRow,
+        pd::Tag::TableCell => Tag::TableCell,
+        pd::Tag::Emphasis => Tag::Emphasis,
+        pd::Tag::Strong => Tag::Strong,
+        pd::Tag::Strikethrough => Tag::Strikethrough,
+        pd::Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        } => Tag::Link {
+            link_type: convert_link_type(link_type),
+            dest_url: owned(dest_url),
+            title: owned(title),
+            id: owned(id),
+        },
+        pd::Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        } => Tag::Image {
+            link_type: convert_link_type(link_type),
+            dest_url: owned(dest_url),
+            title: owned(title),
+            id: owned(id),
+            width: None,
+            height: None,
+            attrs: Vec::new(),
+        },
+        // HTML blocks are read whole by the iterator; the remaining tags are behind parser
+        // options this adapter leaves disabled.
+        pd::Tag::Superscript => Tag::Superscript,
+        pd::Tag::Subscript => Tag::Subscript,
+        pd::Tag::HtmlBlock
+        | pd::Tag::MetadataBlock(_)
+        | pd::Tag::DefinitionList
+        | pd::Tag::DefinitionListTitle
+        | pd::Tag::DefinitionListDefinition => return None,
+    })
+}
+
+fn convert_end(end: pd::TagEnd) -> Option<TagEnd> {
+    Some(match end {
+        pd::TagEnd::Paragraph => TagEnd::Paragraph,
+        pd::TagEnd::Heading(level) => TagEnd::Heading(heading_level(level)),
+        pd::TagEnd::BlockQuote(_) => TagEnd::BlockQuote,
+        pd::TagEnd::CodeBlock => TagEnd::CodeBlock,
+        pd::TagEnd::List(ordered) => TagEnd::List(ordered),
+        pd::TagEnd::Item => TagEnd::Item,
+        pd::TagEnd::FootnoteDefinition => TagEnd::FootnoteDefinition,
+        pd::TagEnd::Table => TagEnd::Table,
+        pd::TagEnd::TableHead => TagEnd::TableHead,
+        pd::TagEnd::TableRow => TagEnd::TableRow,
+        pd::TagEnd::TableCell => TagEnd::TableCell,
+        pd::TagEnd::Emphasis => TagEnd::Emphasis,
+        pd::TagEnd::Strong => TagEnd::Strong,
+        pd::TagEnd::Strikethrough => TagEnd::Strikethrough,
+        pd::TagEnd::Link => TagEnd::Link,
+        pd::TagEnd::Image => TagEnd::Image,
+        pd::TagEnd::Superscript => TagEnd::Superscript,
+        pd::TagEnd::Subscript => TagEnd::Subscript,
+        pd::TagEnd::HtmlBlock
+        | pd::TagEnd::MetadataBlock(_)
+        | pd::TagEnd::DefinitionList
+        | pd::TagEnd::DefinitionListTitle
+        | pd::TagEnd::DefinitionListDefinition => return None,
+    })
+}
+
+fn heading_level(level: pd::HeadingLevel) -> HeadingLevel {
+    HeadingLevel::try_from(level as usize).unwrap_or(HeadingLevel::H6)
+}
+
+fn alignment(alignment: pd::Alignment) -> Alignment {
+    match alignment {
+        pd::Alignment::None => Alignment::None,
+        pd::Alignment::Left => Alignment::Left,
+        pd::Alignment::Center => Alignment::Center,
+        pd::Alignment::Right => Alignment::Right,
+    }
+}
+
+fn convert_link_type(link_type: pd::LinkType) -> LinkType {
+    match link_type {
+        pd::LinkType::Inline | pd::LinkType::WikiLink { .. } => LinkType::Inline,
+        pd::LinkType::Reference => LinkType::Reference,
+        pd::LinkType::ReferenceUnknown => LinkType::ReferenceUnknown,
+        pd::LinkType::Collapsed => LinkType::Collapsed,
+        pd::LinkType::CollapsedUnknown => LinkType::CollapsedUnknown,
+        pd::LinkType::Shortcut => LinkType::Shortcut,
+        pd::LinkType::ShortcutUnknown => LinkType::ShortcutUnknown,
+        pd::LinkType::Autolink => LinkType::Autolink,
+        pd::LinkType::Email => LinkType::Email,
+    }
+}
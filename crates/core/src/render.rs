@@ -0,0 +1,749 @@
+//! One-shot rendering entry point that returns HTML together with document metadata.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::MarkflowError;
+use crate::adapter::MarkdownStream;
+use crate::anchors::{self, AnchorOptions, HeadingAnchor};
+use crate::assets::{Asset, AssetCollector};
+use crate::ast::{self, AstNode};
+use crate::backend::{ParserBackend, SyntaxOptions};
+use crate::block_cache::{self, BlockCache};
+use crate::callouts::Callouts;
+use crate::csv_tables::CsvTables;
+use crate::details::Details;
+use crate::diagnostics::{Diagnostic, Diagnostics};
+use crate::event::{CodeBlockKind, Event, EventStream, HeadingLevel, Tag, TagEnd};
+use crate::figures::{self, FigureLabelCollision, FigureOptions};
+#[cfg(feature = "footnotes")]
+use crate::footnotes;
+use crate::footnotes::FootnoteStyle;
+use crate::html_renderer::HtmlOptions;
+use crate::keyboard;
+use crate::limits::{LimitExceeded, LimitedWriter, Limits, NestingGuard};
+use crate::mdast;
+use crate::plugin::{self, ImageTransformer, MarkflowPlugin};
+use crate::rewrite_options::{InternalLink, RewriteOptions, SanitizePolicy};
+use crate::ruby;
+use crate::script_stats::ScriptStats;
+use crate::shortcodes::{self, RestoreShortcodes};
+use crate::slug::{self, Heading, HeadingCollector, HeadingNumbers, SlugStyle, Slugger};
+use crate::smart_punct::{CjkPunctuation, CjkPunctuator, SmartPunctuation, SmartPunctuator};
+#[cfg(feature = "rewriter")]
+use crate::streaming_rewriter::{self, StreamingRewriter};
+use crate::toc;
+use crate::urls::BlockedDataUri;
+use crate::wiki_links::{self, WikiLinkOptions};
+
+/// Options for [`render`] and [`render_ast`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderOptions {
+    /// Parser that produces the event stream.
+    pub backend: ParserBackend,
+    /// Optional Markdown constructs the parser recognizes.
+    pub syntax: SyntaxOptions,
+    /// Options forwarded to the HTML renderer.
+    pub html: HtmlOptions,
+    /// Algorithm for generated heading ids.
+    pub slug_style: SlugStyle,
+    /// Options forwarded to the streaming HTML rewriter; ignored without the `rewriter` feature.
+    pub rewrite: RewriteOptions,
+    /// Resource limits enforced while parsing and rendering.
+    pub limits: Limits,
+    /// Collect [`ScriptStats`] from text events while rendering.
+    pub script_stats: bool,
+    /// Replace straight quotes and `...` next to CJK text with locale-appropriate punctuation.
+    pub cjk_punctuation: Option<CjkPunctuation>,
+    /// Curl the remaining straight quotes and replace dashes and ellipses outside code.
+    pub smart_punctuation: Option<SmartPunctuation>,
+    /// Normalize text outside code blocks, the heading text slugs are generated from, and
+    /// `#fragment` link destinations to Unicode NFC, so documents typed in decomposed form (as on
+    /// macOS) render and link the same as precomposed ones.
+    pub normalize_nfc: bool,
+    /// Placement of footnote content.
+    pub footnote_style: FootnoteStyle,
+    /// Render GitHub-style `> [!NOTE]` block quotes as alert boxes.
+    pub callouts: bool,
+    /// Render `:::details Title` … `:::` blocks as collapsible `<details>` with a `<summary>`.
+    pub details: bool,
+    /// Render fenced ` ```csv ` and ` ```tsv ` blocks as tables whose first row is the header.
+    /// Always off without the `csv` cargo feature.
+    pub csv_tables: bool,
+    /// Pass Hugo shortcodes (`{{< … >}}`, `{{% … %}}`) and Liquid tags (`{% … %}`) through
+    /// verbatim, so a static site generator can expand them after Markflow renders the page.
+    pub shortcodes: bool,
+    /// Render `[[Ctrl]]+[[C]]` as `<kbd>` keys. While set, `[[…]]` without a `|` label is read
+    /// as a key rather than a wiki link.
+    pub keyboard_keys: bool,
+    /// Render `{漢字|かんじ}` and per-character `{漢字|かん|じ}` as `<ruby>` annotations.
+    pub ruby: bool,
+    /// Turn `[[Target]]` and `[[Target|Label]]` into links resolved with these options.
+    pub wiki_links: Option<WikiLinkOptions>,
+    /// Number images labeled `{#fig:label}` and resolve `@fig:label` references to them.
+    pub figures: Option<FigureOptions>,
+    /// Copy the text of the first H1 into [`RenderResult::title`].
+    pub extract_title: bool,
+    /// With `extract_title`, also drop that H1 from the HTML so layouts that render the page
+    /// title themselves don't show it twice.
+    pub remove_title: bool,
+    /// Give each heading its hierarchical number (`1`, `1.1`, `1.1.1`) as a `data-number`
+    /// attribute and as [`Heading::number`]. The HTML renderer starts any heading that carries
+    /// `data-number` with the number in a `<span class="heading-number">`.
+    pub number_headings: bool,
+    /// Collect every heading into [`RenderResult::headings`].
+    pub headings: bool,
+    /// Collect absolute anchor URLs for every heading into [`RenderResult::anchors`].
+    pub anchors: Option<AnchorOptions>,
+    /// Render a table of contents into [`RenderResult::toc`].
+    pub toc: bool,
+    /// Replace a paragraph of just `[[toc]]` or `[TOC]` with the table of contents. Documents
+    /// containing a marker are buffered in full, since the headings after it are needed first.
+    pub toc_marker: bool,
+    /// Collect the relative and root-relative URLs of images, file links, and raw HTML media
+    /// into [`RenderResult::assets`].
+    pub assets: bool,
+    /// Check the document for unresolved references, duplicate ids, nodes the backend cannot
+    /// render, and images without alt text, into [`RenderResult::diagnostics`]. The parser
+    /// adapter records them as it produces the events.
+    pub diagnostics: bool,
+    /// Namespace for every id the document generates: heading, footnote, sidenote, figure, and
+    /// table header ids. Same-page `#fragment` links are prefixed to match, so several rendered
+    /// documents can share one page without id collisions.
+    pub id_prefix: String,
+}
+
+impl RenderOptions {
+    /// [`RenderOptions::html`] with [`RenderOptions::id_prefix`] put in front of its own
+    /// `id_prefix`.
+    pub(crate) fn html_options(&self) -> Cow<'_, HtmlOptions> {
+        if self.id_prefix.is_empty() {
+            return Cow::Borrowed(&self.html);
+        }
+        Cow::Owned(HtmlOptions {
+            id_prefix: format!("{}{}", self.id_prefix, self.html.id_prefix),
+            ..self.html.clone()
+        })
+    }
+}
+
+/// HTML output plus the metadata gathered during the same event pass.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderResult {
+    /// Rendered and rewritten HTML.
+    pub html: String,
+    /// Script composition of the document, when [`RenderOptions::script_stats`] is enabled.
+    pub script_stats: Option<ScriptStats>,
+    /// Plain text of the first H1, when [`RenderOptions::extract_title`] is enabled and the
+    /// document has one.
+    pub title: Option<String>,
+    /// Headings in document order, when [`RenderOptions::headings`] is enabled.
+    pub headings: Vec<Heading>,
+    /// Headings with their absolute URLs, when [`RenderOptions::anchors`] is set.
+    pub anchors: Vec<HeadingAnchor>,
+    /// Nested `<ul>` table of contents, when [`RenderOptions::toc`] is enabled.
+    pub toc: Option<String>,
+    /// Local assets in order of first reference, when [`RenderOptions::assets`] is enabled.
+    pub assets: Vec<Asset>,
+    /// Image sources emptied by [`HtmlOptions::data_uri_images`] or
+    /// [`RewriteOptions::data_uri_images`], so callers can tell authors why an image is missing.
+    pub blocked_data_uris: Vec<BlockedDataUri>,
+    /// Same-page anchors and relative links, when [`RewriteOptions::collect_internal_links`] is
+    /// set and the `rewriter` feature is enabled.
+    pub internal_links: Vec<InternalLink>,
+    /// Figure labels declared more than once, when [`RenderOptions::figures`] is set.
+    pub figure_collisions: Vec<FigureLabelCollision>,
+    /// Non-fatal problems in the source, when [`RenderOptions::diagnostics`] is enabled.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Markdown source after pre-parse rewrites, plus what the event stream needs to undo them.
+struct Source<'i> {
+    text: Cow<'i, str>,
+    shortcodes: Vec<String>,
+}
+
+impl<'i> Source<'i> {
+    fn new(input: &'i str, options: &RenderOptions) -> Result<Self, MarkflowError> {
+        options.limits.check_input(input)?;
+        options.limits.check_nesting(input)?;
+        let (text, shortcodes) = if options.shortcodes {
+            let (text, shortcodes) = shortcodes::protect(input);
+            (Cow::Owned(text), shortcodes)
+        } else {
+            (Cow::Borrowed(input), Vec::new())
+        };
+        Ok(Self { text, shortcodes })
+    }
+}
+
+/// Parses `source` and applies the event transforms selected in `options`, in the order every
+/// output format shares. A nesting-limit violation truncates the stream and is recorded in
+/// `nesting_exceeded`; duplicate figure labels are appended to `figure_collisions`, and the
+/// parser adapter records what it finds into `diagnostics`.
+fn event_pipeline<'s>(
+    source: &'s Source<'_>,
+    options: &'s RenderOptions,
+    nesting_exceeded: &'s mut Option<LimitExceeded>,
+    figure_collisions: &'s mut Vec<FigureLabelCollision>,
+    diagnostics: Option<Rc<RefCell<Diagnostics>>>,
+) -> Result<EventStream<'s>, MarkflowError> {
+    let mut events = options.backend.events_with_diagnostics(
+        &source.text,
+        &options.syntax,
+        Slugger::with_style(options.slug_style).with_nfc(options.normalize_nfc),
+        diagnostics,
+    )?;
+    if options.normalize_nfc {
+        let mut in_code_block = false;
+        events = Box::new(events.map(move |event| match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code_block = true;
+                event
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code_block = false;
+                event
+            }
+            Event::Text(text) if !in_code_block => Event::Text(slug::to_nfc(text)),
+            // Heading ids are NFC too, so fragment links must be for them to match.
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) if dest_url.starts_with('#') => Event::Start(Tag::Link {
+                link_type,
+                dest_url: slug::to_nfc(dest_url),
+                title,
+                id,
+            }),
+            event => event,
+        }));
+    }
+    if options.shortcodes {
+        events = Box::new(RestoreShortcodes::new(events, &source.shortcodes));
+    }
+    let mut events: EventStream<'s> = Box::new(NestingGuard::new(
+        events,
+        options.limits.max_nesting_depth,
+        nesting_exceeded,
+    ));
+    if options.number_headings {
+        let mut numbers = HeadingNumbers::default();
+        events = Box::new(events.map(move |event| number_heading(event, &mut numbers)));
+    }
+    if options.toc_marker && toc::may_contain_marker(&source.text) {
+        events = toc::insert_toc(events);
+    }
+    if options.callouts {
+        events = Box::new(Callouts::new(events));
+    }
+    if options.details {
+        events = Box::new(Details::new(events));
+    }
+    if options.csv_tables && cfg!(feature = "csv") {
+        events = Box::new(CsvTables::new(events));
+    }
+    if options.keyboard_keys {
+        events = Box::new(keyboard::keyboard_keys(events));
+    }
+    if options.ruby {
+        events = Box::new(ruby::ruby(events));
+    }
+    if let Some(wiki_links) = &options.wiki_links {
+        events = Box::new(wiki_links::wiki_links(events, wiki_links));
+    }
+    #[cfg(feature = "footnotes")]
+    match options.footnote_style {
+        FootnoteStyle::Definitions => {}
+        FootnoteStyle::Sidenotes => events = Box::new(footnotes::sidenotes(events)),
+        FootnoteStyle::Endnotes => events = Box::new(footnotes::endnotes(events)),
+        FootnoteStyle::Gfm => events = Box::new(footnotes::gfm(events)),
+    }
+    if !options.id_prefix.is_empty() {
+        let prefix = options.id_prefix.as_str();
+        events = Box::new(events.map(move |event| prefix_ids(event, prefix)));
+    }
+    if let Some(cjk) = options.cjk_punctuation {
+        let mut punctuator = CjkPunctuator::new(cjk);
+        events = Box::new(events.map(move |event| punctuator.apply(event)));
+    }
+    if let Some(smart) = options.smart_punctuation {
+        let mut punctuator = SmartPunctuator::new(smart);
+        events = Box::new(events.map(move |event| punctuator.apply(event)));
+    }
+    if let Some(figure_options) = &options.figures {
+        events = Box::new(figures::number_figures(
+            events,
+            figure_options,
+            &options.id_prefix,
+            figure_collisions,
+        ));
+    }
+    Ok(events)
+}
+
+/// Gives a heading start its number from `numbers` as a `data-number` attribute, which the HTML
+/// renderer also writes as a `<span class="heading-number">` in front of the content.
+fn number_heading<'a>(event: Event<'a>, numbers: &mut HeadingNumbers) -> Event<'a> {
+    match event {
+        Event::Start(Tag::Heading {
+            level,
+            id,
+            classes,
+            mut attrs,
+        }) => {
+            let number = numbers.next(level as u8);
+            attrs.push((Cow::Borrowed(slug::NUMBER_ATTR), Some(Cow::Owned(number))));
+            Event::Start(Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            })
+        }
+        event => event,
+    }
+}
+
+/// Prefixes heading and code block ids and same-page link fragments with `prefix`.
+fn prefix_ids<'a>(event: Event<'a>, prefix: &str) -> Event<'a> {
+    match event {
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced { lang, mut meta })) => {
+            for (key, value) in &mut meta.block_attrs {
+                if key == "id"
+                    && let Some(id) = value
+                {
+                    id.insert_str(0, prefix);
+                }
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced { lang, meta }))
+        }
+        Event::Start(Tag::Heading {
+            level,
+            id: Some(id),
+            classes,
+            attrs,
+        }) => Event::Start(Tag::Heading {
+            level,
+            id: Some(Cow::Owned(format!("{prefix}{id}"))),
+            classes,
+            attrs,
+        }),
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) if dest_url.len() > 1 && dest_url.starts_with('#') => Event::Start(Tag::Link {
+            link_type,
+            dest_url: Cow::Owned(format!("#{prefix}{}", &dest_url[1..])),
+            title,
+            id,
+        }),
+        event => event,
+    }
+}
+
+/// Writer chain every HTML render ends in; the rewriter stage is compiled out without the
+/// `rewriter` feature.
+#[cfg(feature = "rewriter")]
+type HtmlSink = StreamingRewriter<LimitedWriter<Vec<u8>>>;
+#[cfg(not(feature = "rewriter"))]
+type HtmlSink = LimitedWriter<Vec<u8>>;
+
+/// Builds the [`HtmlSink`] for `options`.
+fn html_sink(options: &RenderOptions) -> HtmlSink {
+    let output = LimitedWriter::new(Vec::new(), options.limits.max_output_bytes);
+    #[cfg(feature = "rewriter")]
+    let output = StreamingRewriter::new(output, options.rewrite.clone());
+    output
+}
+
+/// Flushes every stage of `sink` and returns the HTML bytes, appending the images the rewriter
+/// emptied to `blocked_data_uris` and the links it recorded to `internal_links`.
+fn finish_html_sink(
+    sink: HtmlSink,
+    blocked_data_uris: &mut Vec<BlockedDataUri>,
+    internal_links: &mut Vec<InternalLink>,
+) -> io::Result<Vec<u8>> {
+    #[cfg(feature = "rewriter")]
+    let sink = {
+        let mut sink = sink;
+        io::Write::flush(&mut sink)?;
+        blocked_data_uris.extend(sink.blocked_data_uris());
+        let (sink, links) = sink.into_inner_with_links()?;
+        internal_links.extend(links);
+        sink
+    };
+    #[cfg(not(feature = "rewriter"))]
+    let _ = (blocked_data_uris, internal_links);
+    Ok(sink.into_inner())
+}
+
+/// Renders Markdown to HTML, collecting the metadata requested in `options` on the way.
+pub fn render(input: &str, options: &RenderOptions) -> Result<RenderResult, MarkflowError> {
+    render_with_plugins(input, options, &mut [], None)
+}
+
+/// [`render`] with each plugin's [`MarkflowPlugin::transform`] applied after the built-in
+/// transforms, and then `images` applied to every image.
+pub(crate) fn render_with_plugins(
+    input: &str,
+    options: &RenderOptions,
+    plugins: &mut [Box<dyn MarkflowPlugin>],
+    images: Option<&mut dyn ImageTransformer>,
+) -> Result<RenderResult, MarkflowError> {
+    render_html(
+        input,
+        options,
+        plugins,
+        images,
+        |events, sink, html_options| events.stream_to_writer_with_options(sink, html_options),
+    )
+}
+
+/// Same as [`render`], but serves unchanged top-level blocks from `cache` and hands newly
+/// rendered blocks to it, keyed by a stable block hash.
+///
+/// The cached HTML is taken before the streaming rewriter runs, so rewrite options may change
+/// without invalidating the cache.
+pub fn render_cached(
+    input: &str,
+    options: &RenderOptions,
+    cache: &mut dyn BlockCache,
+) -> Result<RenderResult, MarkflowError> {
+    render_html(
+        input,
+        options,
+        &mut [],
+        None,
+        |events, sink, html_options| block_cache::render_blocks(events, sink, html_options, cache),
+    )
+}
+
+/// Runs the shared pipeline, plugin transforms, and metadata collectors, leaving HTML
+/// generation to `write_html`.
+fn render_html<F>(
+    input: &str,
+    options: &RenderOptions,
+    plugins: &mut [Box<dyn MarkflowPlugin>],
+    images: Option<&mut dyn ImageTransformer>,
+    write_html: F,
+) -> Result<RenderResult, MarkflowError>
+where
+    F: FnOnce(EventStream<'_>, HtmlSink, &HtmlOptions) -> io::Result<HtmlSink>,
+{
+    let mut nesting_exceeded = None;
+    let source = Source::new(input, options)?;
+    let diagnostics = Rc::new(RefCell::new(Diagnostics::new()));
+    let mut figure_collisions = Vec::new();
+    let mut events = event_pipeline(
+        &source,
+        options,
+        &mut nesting_exceeded,
+        &mut figure_collisions,
+        options.diagnostics.then(|| Rc::clone(&diagnostics)),
+    )?;
+    for plugin in plugins.iter_mut() {
+        events = plugin.transform(events);
+    }
+    if let Some(images) = images {
+        events = Box::new(events.map(move |event| plugin::transform_image(event, images)));
+    }
+    let sink = html_sink(options);
+    let mut script_stats = options.script_stats.then(ScriptStats::default);
+    let mut title = None;
+    let mut blocked_data_uris = Vec::new();
+    let mut headings =
+        (options.headings || options.anchors.is_some() || options.toc).then(HeadingCollector::new);
+    let mut assets = options.assets.then(AssetCollector::new);
+
+    let events: EventStream<'_> = if options.extract_title {
+        Box::new(TitleExtractor::new(
+            events,
+            options.remove_title,
+            &mut title,
+        ))
+    } else {
+        events
+    };
+    let events = events.inspect(|event| {
+        if let Some(stats) = script_stats.as_mut() {
+            stats.observe(event);
+        }
+        if let Some(headings) = headings.as_mut() {
+            headings.observe(event);
+        }
+        if let Some(assets) = assets.as_mut() {
+            assets.observe(event);
+        }
+        if let Event::Start(Tag::Image { dest_url, .. }) = event {
+            blocked_data_uris.extend(options.html.data_uri_images.violation(dest_url));
+        }
+    });
+    let sink = write_html(Box::new(events), sink, &options.html_options())?;
+
+    if let Some(exceeded) = nesting_exceeded {
+        return Err(exceeded.into());
+    }
+
+    let mut internal_links = Vec::new();
+    let html = String::from_utf8(finish_html_sink(
+        sink,
+        &mut blocked_data_uris,
+        &mut internal_links,
+    )?)?;
+    let headings = headings.map(HeadingCollector::finish).unwrap_or_default();
+    let anchors = options
+        .anchors
+        .as_ref()
+        .map(|anchor_options| anchors::anchors_for(&headings, anchor_options))
+        .unwrap_or_default();
+    let toc = options.toc.then(|| toc::toc_html(&headings));
+    let mut diagnostics = diagnostics.take();
+    if !source.shortcodes.is_empty() {
+        diagnostics
+            .map_spans(|span| shortcodes::original_span(&source.text, &source.shortcodes, span));
+    }
+    Ok(RenderResult {
+        html,
+        script_stats,
+        title,
+        headings: if options.headings {
+            headings
+        } else {
+            Vec::new()
+        },
+        anchors,
+        toc,
+        assets: assets.map(AssetCollector::finish).unwrap_or_default(),
+        blocked_data_uris,
+        internal_links,
+        figure_collisions,
+        diagnostics: diagnostics.finish(),
+    })
+}
+
+/// Captures the text of the first H1 into `title`, optionally removing the heading's events.
+struct TitleExtractor<'t, I> {
+    inner: I,
+    remove: bool,
+    title: &'t mut Option<String>,
+    /// Text collected so far while inside the first H1.
+    capturing: Option<String>,
+}
+
+impl<'t, I> TitleExtractor<'t, I> {
+    fn new(inner: I, remove: bool, title: &'t mut Option<String>) -> Self {
+        Self {
+            inner,
+            remove,
+            title,
+            capturing: None,
+        }
+    }
+}
+
+impl<'a, I> Iterator for TitleExtractor<'_, I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let event = self.inner.next()?;
+            let inside = match &event {
+                Event::Start(Tag::Heading {
+                    level: HeadingLevel::H1,
+                    ..
+                }) if self.title.is_none() && self.capturing.is_none() => {
+                    self.capturing = Some(String::new());
+                    true
+                }
+                Event::End(TagEnd::Heading(HeadingLevel::H1)) if self.capturing.is_some() => {
+                    *self.title = self.capturing.take().map(|text| text.trim().to_string());
+                    true
+                }
+                Event::Text(text) | Event::Code(text) | Event::InlineMath(text) => {
+                    if let Some(buf) = self.capturing.as_mut() {
+                        buf.push_str(text);
+                    }
+                    self.capturing.is_some()
+                }
+                Event::SoftBreak | Event::HardBreak => {
+                    if let Some(buf) = self.capturing.as_mut() {
+                        buf.push(' ');
+                    }
+                    self.capturing.is_some()
+                }
+                _ => self.capturing.is_some(),
+            };
+            if !(inside && self.remove) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+/// Renders Markdown to plain text for search indexing and previews.
+///
+/// HTML-only options (`html`, `rewrite`, title extraction, headings, anchors, TOC) have no effect;
+/// the output limit applies to the text.
+pub fn render_text(input: &str, options: &RenderOptions) -> Result<String, MarkflowError> {
+    let mut nesting_exceeded = None;
+    let mut figure_collisions = Vec::new();
+    let source = Source::new(input, options)?;
+    let events = event_pipeline(
+        &source,
+        options,
+        &mut nesting_exceeded,
+        &mut figure_collisions,
+        None,
+    )?;
+    let output = events.stream_to_text(LimitedWriter::new(
+        Vec::new(),
+        options.limits.max_output_bytes,
+    ))?;
+
+    if let Some(exceeded) = nesting_exceeded {
+        return Err(exceeded.into());
+    }
+    Ok(String::from_utf8(output.into_inner())?)
+}
+
+/// Builds a typed document tree from the same event stream [`render`] would turn into HTML.
+///
+/// HTML-only options (`html`, `rewrite`, output limits, title extraction, headings, anchors,
+/// TOC) have no effect.
+pub fn render_ast(input: &str, options: &RenderOptions) -> Result<AstNode, MarkflowError> {
+    let mut nesting_exceeded = None;
+    let source = Source::new(input, options)?;
+    let tree = ast::build(event_pipeline(
+        &source,
+        options,
+        &mut nesting_exceeded,
+        &mut Vec::new(),
+        None,
+    )?);
+
+    match nesting_exceeded {
+        Some(exceeded) => Err(exceeded.into()),
+        None => Ok(tree),
+    }
+}
+
+/// Builds an mdast-shaped JSON tree (see [`crate::mdast`]) for unified/remark interop.
+///
+/// Takes the same options as [`render_ast`].
+pub fn render_mdast(
+    input: &str,
+    options: &RenderOptions,
+) -> Result<serde_json::Value, MarkflowError> {
+    render_ast(input, options).map(|tree| mdast::to_mdast(&tree))
+}
+
+/// Document touching every construct and pipeline stage, rendered by [`prewarm`].
+const PREWARM_SAMPLE: &str = "---\ntitle: warm\n---\n\n# Title\n\n> [!NOTE]\n> Callout with *emphasis*, **strong**, ~~strike~~, `code`, and $x$.\n\n- [x] task [link](https://example.com \"T\")\n- ![image](i.png) <b>html</b>\n\n1. 日本語のテキスト、です。\n\n| a | b |\n|:-|-:|\n| 1 | 2 |\n\n```rust title=\"main.rs\"\nfn main() {}\n```\n\nNote.[^n] https://example.org\n\n[^n]: Footnote.\n\n## More {#more}\n\n[[toc]]\n\n:::details \"Quoted\" -- summary...\n\nPress [[Ctrl]], read {漢字|かんじ}, see [[Page|page]] and @fig:a.\n\n![Figure](f.png){#fig:a}\n\n{{< note \"x\" >}}\n\n```csv\na,b\n1,2\n```\n\n:::\n";
+
+/// Pays one-time initialization costs up front: parses the rewriter's selectors and runs a
+/// sample document through both backends, once per footnote style, with the optional Markdown
+/// passes, collectors, and sanitizer enabled, so code and allocator pages are warm.
+///
+/// Call it during deploy or boot of serverless and edge functions; the first real request then
+/// renders at steady-state speed. Calling it again is harmless. What it warms is process-wide
+/// (lazy statics and code pages), not tied to any options value, so it is a free function next
+/// to [`render`] rather than a method.
+pub fn prewarm() {
+    #[cfg(feature = "rewriter")]
+    streaming_rewriter::prewarm();
+    for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+        for footnote_style in [
+            FootnoteStyle::Definitions,
+            FootnoteStyle::Sidenotes,
+            FootnoteStyle::Endnotes,
+            FootnoteStyle::Gfm,
+        ] {
+            let options = prewarm_options(backend, footnote_style);
+            // The sample is known to render; failures would only mean less warm-up.
+            let _ = render(PREWARM_SAMPLE, &options);
+            let _ = render_ast(PREWARM_SAMPLE, &options);
+        }
+    }
+}
+
+/// Options [`prewarm`] renders [`PREWARM_SAMPLE`] with.
+fn prewarm_options(backend: ParserBackend, footnote_style: FootnoteStyle) -> RenderOptions {
+    RenderOptions {
+        backend,
+        syntax: SyntaxOptions {
+            heading_attributes: true,
+            highlight: true,
+            superscript_subscript: true,
+            image_sizes: true,
+            ..SyntaxOptions::default()
+        },
+        html: HtmlOptions {
+            sanitize_urls: true,
+            heading_anchor_links: true,
+            code_meta: true,
+            ..HtmlOptions::default()
+        },
+        rewrite: RewriteOptions {
+            sanitize: Some(SanitizePolicy::default()),
+            collect_internal_links: true,
+            ..RewriteOptions::default()
+        },
+        script_stats: true,
+        cjk_punctuation: Some(CjkPunctuation::default()),
+        smart_punctuation: Some(SmartPunctuation::default()),
+        normalize_nfc: true,
+        footnote_style,
+        callouts: true,
+        details: true,
+        csv_tables: true,
+        shortcodes: true,
+        keyboard_keys: true,
+        ruby: true,
+        wiki_links: Some(WikiLinkOptions::default()),
+        figures: Some(FigureOptions::default()),
+        extract_title: true,
+        number_headings: true,
+        headings: true,
+        toc: true,
+        toc_marker: true,
+        assets: true,
+        diagnostics: true,
+        ..RenderOptions::default()
+    }
+}
+
+#[cfg(all(test, feature = "tables"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prewarm_sample_renders_on_both_backends() {
+        prewarm();
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                ..RenderOptions::default()
+            };
+            let html = render(PREWARM_SAMPLE, &options).unwrap().html;
+            assert!(html.contains("<table>"), "{backend:?}: {html}");
+
+            let options = prewarm_options(backend, FootnoteStyle::Gfm);
+            let html = render(PREWARM_SAMPLE, &options).unwrap().html;
+            for tag in ["<details>", "<kbd>", "<ruby>", "<figure", "data-footnotes"] {
+                assert!(html.contains(tag), "{backend:?}: {tag} in {html}");
+            }
+        }
+    }
+}
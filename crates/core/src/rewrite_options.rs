@@ -0,0 +1,538 @@
+//! Options for the streaming HTML rewriter, available with or without the `rewriter` feature so
+//! option structs and profiles stay the same in every build.
+
+use std::fmt;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::smart_punct::SmartPunctuation;
+use crate::urls::{self, DataUriPolicy};
+
+/// Configuration flags that control how the streaming rewriter manipulates HTML.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewriteOptions {
+    /// Called with the URL of every `<a href>` and `<img src>` before the other rules run;
+    /// `Some` replaces the URL. Not serialized.
+    #[serde(skip)]
+    pub rewrite_urls: Option<UrlRewriter>,
+    /// Strip active content from the HTML, including raw HTML and plugin output, for
+    /// user-generated content. Runs right after [`rewrite_urls`](Self::rewrite_urls).
+    pub sanitize: Option<SanitizePolicy>,
+    /// When enabled, missing `loading` attributes on `<img>` tags are defaulted to `lazy`.
+    pub enforce_img_loading_lazy: bool,
+    /// When enabled, missing `loading` attributes on `<iframe>` tags are defaulted to `lazy`.
+    pub enforce_iframe_loading_lazy: bool,
+    /// When enabled, missing `preload` attributes on `<video>` tags are defaulted to `none`, so
+    /// nothing is fetched until the reader presses play.
+    pub enforce_video_preload_none: bool,
+    /// `rel` tokens merged into links to other sites.
+    pub harden_links: LinkHardening,
+    /// `target` and `rel` given to links that leave the site.
+    pub external_links: Option<ExternalLinks>,
+    /// Which inline `data:` sources `<img>` tags keep, including those written as raw HTML;
+    /// rejected ones are emptied and reported by
+    /// [`StreamingRewriter::blocked_data_uris`](crate::StreamingRewriter::blocked_data_uris).
+    pub data_uri_images: DataUriPolicy,
+    /// When rewritten HTML is handed to the underlying writer.
+    pub chunk_policy: ChunkPolicy,
+    /// Remove every `<!-- comment -->` from the HTML, including comments written by plugins
+    /// or cached blocks.
+    pub strip_comments: bool,
+    /// Wrap `<img>` tags in `<picture>` with a `<source>` per alternate format, for assets a CDN
+    /// serves in several encodings.
+    pub picture: Option<PictureSources>,
+    /// `nonce` given to every `<script>` and `<style>` left in the output, replacing any it
+    /// had, so pages can be served under a `script-src 'nonce-…'` Content-Security-Policy. Use a
+    /// fresh random value per response.
+    pub csp_nonce: Option<String>,
+    /// Ids given to the `<h1>`–`<h6>` elements in document order, replacing any they have;
+    /// `None` entries and headings past the end of the list are left alone. Feed it the ids of
+    /// [`HeadingCollector`](crate::HeadingCollector), one entry per heading, to give HTML
+    /// rendered elsewhere from the same Markdown the ids [`render`](crate::render) would. The
+    /// collector does not see headings written as raw HTML, so documents with those need a
+    /// `None` in their place.
+    pub heading_ids: Vec<Option<String>>,
+    /// Record the same-page `#anchor` and relative `href` of every `<a>`, for checking against
+    /// the document's heading ids and the site's pages.
+    pub collect_internal_links: bool,
+    /// Site-specific rewrites, applied in order after the built-in rules above.
+    pub rules: Vec<RewriteRule>,
+}
+
+/// One entry of [`RewriteOptions::rules`]: `action` applied to every element `selector` matches.
+///
+/// An invalid selector, tag, or attribute name fails the first write to the rewriter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewriteRule {
+    /// CSS selector in the subset lol_html supports, e.g. `table` or `pre > code`.
+    pub selector: String,
+    /// What to do with each matching element.
+    pub action: RewriteAction,
+}
+
+/// Change made by a [`RewriteRule`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RewriteAction {
+    /// Sets attribute `name` to `value`, replacing any earlier value.
+    SetAttribute {
+        /// Attribute name.
+        name: String,
+        /// New value, escaped on output.
+        value: String,
+    },
+    /// Adds a class to the element's `class`, unless it is already there.
+    AddClass(String),
+    /// Removes the element and everything inside it.
+    Remove,
+    /// Rewrites the text inside the element, leaving `<pre>`, `<code>`, `<script>`, and
+    /// `<style>` content alone.
+    Text(TextRewrite),
+    /// Wraps the element in a new `tag` element with the given attributes, e.g. a
+    /// `<div class="table-wrapper">` around tables.
+    Wrap {
+        /// Tag name of the wrapper.
+        tag: String,
+        /// Attributes of the wrapper, in output order.
+        attributes: Vec<(String, String)>,
+    },
+}
+
+/// Link target recorded by [`RewriteOptions::collect_internal_links`], as written in the `href`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InternalLink {
+    /// Fragment of a same-page `#anchor` link, without the `#`.
+    Anchor(String),
+    /// Relative or root-relative URL, e.g. `../guide.md#setup` or `/docs/`.
+    Relative(String),
+}
+
+impl InternalLink {
+    /// The link target of `href`, or `None` for links to other sites and other schemes.
+    pub fn from_href(href: &str) -> Option<Self> {
+        let href = href.trim();
+        if let Some(anchor) = href.strip_prefix('#') {
+            return (!anchor.is_empty()).then(|| InternalLink::Anchor(anchor.to_string()));
+        }
+        urls::is_local(href).then(|| InternalLink::Relative(href.to_string()))
+    }
+}
+
+/// Shared URL mapping for [`RewriteOptions::rewrite_urls`], e.g. `.md` links to routes, locale
+/// prefixes, or cache-busting query parameters.
+///
+/// Clones share the function, and two rewriters are equal only when they share it.
+#[derive(Clone)]
+pub struct UrlRewriter(Arc<RewriteFn>);
+
+type RewriteFn = dyn Fn(&str) -> Option<String> + Send + Sync;
+
+impl UrlRewriter {
+    /// Wraps `rewrite`, which returns the new URL or `None` to keep the old one.
+    pub fn new(rewrite: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(rewrite))
+    }
+
+    /// The replacement for `url`, if any.
+    pub fn rewrite(&self, url: &str) -> Option<String> {
+        (self.0)(url)
+    }
+}
+
+impl fmt::Debug for UrlRewriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UrlRewriter(..)")
+    }
+}
+
+impl PartialEq for UrlRewriter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for UrlRewriter {}
+
+/// What [`RewriteOptions::sanitize`] keeps. Elements and attributes are allowlisted: anything
+/// the policy does not name is removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SanitizePolicy {
+    /// Elements removed together with their content.
+    pub remove_elements: Vec<String>,
+    /// Elements kept; any other element is replaced by its content, except raw-text and RCDATA
+    /// elements such as `title` and `textarea`, which are removed with their content.
+    pub allowed_elements: Vec<String>,
+    /// Attributes kept on allowed elements; any other attribute is removed. A trailing `*`
+    /// matches a prefix, as in `data-*`. Inline `style` is not allowed by default, so table
+    /// alignment needs [`HtmlOptions::table_colgroup`](crate::HtmlOptions::table_colgroup).
+    pub allowed_attributes: Vec<String>,
+    /// Remove `on*` event-handler attributes such as `onclick` and `onerror`, even when
+    /// [`allowed_attributes`](Self::allowed_attributes) matches them.
+    pub strip_event_handlers: bool,
+    /// Schemes URL attributes (`href`, `src`, `srcset`, `action`, ...) may use; attributes with
+    /// other schemes are removed. Relative URLs are always kept, and `data:` image sources are
+    /// left to [`RewriteOptions::data_uri_images`].
+    pub allowed_schemes: Vec<String>,
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> Self {
+        let strings = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        Self {
+            // SVG and MathML carry their own scriptable attributes (`<set to="javascript:…">`),
+            // and raw-text elements parse differently in browsers than here.
+            remove_elements: strings(&[
+                "script",
+                "style",
+                "iframe",
+                "frame",
+                "frameset",
+                "object",
+                "embed",
+                "applet",
+                "svg",
+                "math",
+                "set",
+                "animate",
+                "meta",
+                "base",
+                "link",
+                "form",
+                "textarea",
+                "select",
+                "button",
+                "noscript",
+                "noembed",
+                "noframes",
+                "xmp",
+                "plaintext",
+                "title",
+            ]),
+            allowed_elements: strings(&[
+                "a",
+                "abbr",
+                "aside",
+                "b",
+                "bdi",
+                "bdo",
+                "blockquote",
+                "br",
+                "caption",
+                "cite",
+                "code",
+                "col",
+                "colgroup",
+                "dd",
+                "del",
+                "details",
+                "dfn",
+                "div",
+                "dl",
+                "dt",
+                "em",
+                "figcaption",
+                "figure",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "hr",
+                "i",
+                "img",
+                "input",
+                "ins",
+                "kbd",
+                "label",
+                "li",
+                "mark",
+                "nav",
+                "ol",
+                "p",
+                "picture",
+                "pre",
+                "q",
+                "rp",
+                "rt",
+                "ruby",
+                "s",
+                "samp",
+                "section",
+                "small",
+                "source",
+                "span",
+                "strong",
+                "sub",
+                "summary",
+                "sup",
+                "table",
+                "tbody",
+                "td",
+                "template",
+                "tfoot",
+                "th",
+                "thead",
+                "time",
+                "tr",
+                "u",
+                "ul",
+                "var",
+            ]),
+            allowed_attributes: strings(&[
+                "alt", "aria-*", "checked", "cite", "class", "colspan", "data-*", "datetime",
+                "decoding", "dir", "disabled", "for", "headers", "height", "href", "id", "lang",
+                "loading", "media", "open", "rel", "reversed", "role", "rowspan", "scope", "sizes",
+                "span", "src", "srcset", "start", "target", "title", "type", "width",
+            ]),
+            strip_event_handlers: true,
+            allowed_schemes: strings(&["http", "https", "mailto", "tel"]),
+        }
+    }
+}
+
+impl SanitizePolicy {
+    /// Whether `url` is relative or uses one of the allowed schemes.
+    pub fn allows_url(&self, url: &str) -> bool {
+        urls::scheme(url).is_none_or(|scheme| {
+            self.allowed_schemes
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(&scheme))
+        })
+    }
+
+    /// Whether elements named `name` (lowercase) are kept.
+    pub fn allows_element(&self, name: &str) -> bool {
+        self.allowed_elements
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(name))
+    }
+
+    /// Whether attributes named `name` (lowercase) are kept, before their URLs are checked.
+    pub fn allows_attribute(&self, name: &str) -> bool {
+        if self.strip_event_handlers && name.starts_with("on") {
+            return false;
+        }
+        self.allowed_attributes
+            .iter()
+            .any(|allowed| match allowed.strip_suffix('*') {
+                Some(prefix) => name.starts_with(&prefix.to_ascii_lowercase()),
+                None => allowed.eq_ignore_ascii_case(name),
+            })
+    }
+}
+
+/// Text change made by [`RewriteAction::Text`]. Entities are decoded first, so `&quot;`
+/// counts as a straight quote.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextRewrite {
+    /// Smart punctuation for text the Markdown pass never sees, such as raw HTML and plugin
+    /// output.
+    SmartPunctuation(SmartPunctuation),
+    /// French spacing: the space before `;`, `!`, and `?` becomes a narrow no-break space, and
+    /// the spaces before `:` and `»` and after `«` become no-break spaces.
+    FrenchSpacing,
+    /// Replaces every occurrence of `from` with `to`.
+    Replace {
+        /// Text to look for.
+        from: String,
+        /// Its replacement.
+        to: String,
+    },
+}
+
+/// Off-site link policy for [`RewriteOptions::external_links`].
+///
+/// A link is off-site when its `href` is an absolute `http(s)://` or protocol-relative `//` URL
+/// whose host is not one of `internal_hosts` or a subdomain of one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalLinks {
+    /// Open off-site links in a new tab with `target="_blank"`, unless they name a target.
+    pub target_blank: bool,
+    /// `rel` tokens merged into off-site links.
+    pub rel: Vec<String>,
+    /// Hosts of the site itself, e.g. `example.com`.
+    pub internal_hosts: Vec<String>,
+}
+
+impl Default for ExternalLinks {
+    fn default() -> Self {
+        Self {
+            target_blank: true,
+            rel: vec!["noopener".to_string(), "noreferrer".to_string()],
+            internal_hosts: Vec::new(),
+        }
+    }
+}
+
+impl ExternalLinks {
+    /// Whether a link to `href` leaves the site.
+    pub fn is_external(&self, href: &str) -> bool {
+        let Some(host) = link_host(href) else {
+            return false;
+        };
+        !self.internal_hosts.iter().any(|internal| {
+            let internal = internal.trim().trim_end_matches('.');
+            host.eq_ignore_ascii_case(internal)
+                || host
+                    .to_ascii_lowercase()
+                    .strip_suffix(&internal.to_ascii_lowercase())
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+    }
+}
+
+/// Host of an absolute `http(s):` or protocol-relative `//` URL, without user info or port.
+///
+/// The URL is read the way browsers parse it: leading spaces and control characters and every
+/// tab or newline are dropped, `\` counts as `/`, and any number of slashes may follow, so
+/// `/\evil.com` and `https:\\evil.com` both lead to `evil.com`.
+pub(crate) fn link_host(href: &str) -> Option<String> {
+    let href: String = href
+        .trim_start_matches(|ch: char| ch <= ' ')
+        .chars()
+        .filter(|ch| !matches!(ch, '\t' | '\n' | '\r'))
+        .map(|ch| if ch == '\\' { '/' } else { ch })
+        .collect();
+    let lower = href.to_ascii_lowercase();
+    let rest = match ["https:", "http:"]
+        .into_iter()
+        .find(|scheme| lower.starts_with(scheme))
+    {
+        Some(scheme) => &href[scheme.len()..],
+        None if href.starts_with("//") => &href,
+        None => return None,
+    };
+    let rest = rest.trim_start_matches('/');
+    let authority = &rest[..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|byte| byte.is_ascii_digit()) => host,
+        _ => host,
+    };
+    Some(host.to_string())
+}
+
+/// Alternate image formats for [`RewriteOptions::picture`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PictureSources {
+    /// Formats to offer, most preferred first; each becomes `<source type="image/{format}">`.
+    pub formats: Vec<String>,
+    /// URL of each variant. `{src}` is the original source, `{stem}` its path without the file
+    /// extension, query, or fragment, and `{format}` the format, so `{stem}.{format}` turns
+    /// `/a/cat.png?v=2` into `/a/cat.avif` and `{src}?format={format}` suits image services.
+    pub url_template: String,
+}
+
+impl Default for PictureSources {
+    fn default() -> Self {
+        Self {
+            formats: vec!["avif".to_string(), "webp".to_string()],
+            url_template: "{stem}.{format}".to_string(),
+        }
+    }
+}
+
+impl PictureSources {
+    /// URL of the `format` variant of `src`.
+    pub fn variant_url(&self, src: &str, format: &str) -> String {
+        let path = &src[..src.find(['?', '#']).unwrap_or(src.len())];
+        let file_start = path.rfind('/').map_or(0, |slash| slash + 1);
+        let stem = match path[file_start..].rfind('.') {
+            Some(dot) if dot > 0 => &path[..file_start + dot],
+            _ => path,
+        };
+        self.url_template
+            .replace("{src}", src)
+            .replace("{stem}", stem)
+            .replace("{format}", format)
+    }
+}
+
+/// Chunking of the output written to the underlying writer.
+///
+/// The default forwards every chunk lol_html produces as soon as it is produced. Browser
+/// streaming usually wants `flush_on_block_end`, so each write carries whole blocks; file output
+/// wants a large `min_bytes` to cut per-write overhead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkPolicy {
+    /// Buffer output until at least this many bytes are pending.
+    pub min_bytes: usize,
+    /// Only cut chunks right after the end of a block element (`</p>`, `</pre>`, `<hr />`, ...).
+    pub flush_on_block_end: bool,
+    /// With `flush_on_block_end`, only cut after blocks outside any other element, so lists,
+    /// quotes, and tables arrive whole.
+    pub top_level_only: bool,
+}
+
+/// How much [`RewriteOptions::harden_links`] distrusts links to other sites.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkHardening {
+    /// Links are left alone.
+    #[default]
+    Off,
+    /// `rel="noopener noreferrer"`: the target page gets no `window.opener` or referrer.
+    Trusted,
+    /// Additionally `nofollow ugc`, so user-supplied links pass no search ranking.
+    UserContent,
+}
+
+impl Default for RewriteOptions {
+    fn default() -> Self {
+        RewriteOptions {
+            rewrite_urls: None,
+            sanitize: None,
+            enforce_img_loading_lazy: true,
+            enforce_iframe_loading_lazy: false,
+            enforce_video_preload_none: false,
+            harden_links: LinkHardening::Off,
+            external_links: None,
+            data_uri_images: DataUriPolicy::Allow,
+            chunk_policy: ChunkPolicy::default(),
+            strip_comments: false,
+            picture: None,
+            csp_nonce: None,
+            heading_ids: Vec::new(),
+            collect_internal_links: false,
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// Throughput counters collected by a [`StreamingRewriter`](crate::StreamingRewriter), for
+/// monitoring how much the rewrite pass grows the HTML.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RewriteThroughput {
+    /// Bytes written into the rewriter.
+    pub bytes_in: u64,
+    /// Bytes the rewriter emitted to the underlying writer.
+    pub bytes_out: u64,
+    /// Number of `write` calls received.
+    pub chunks_in: u64,
+    /// Number of non-empty chunks lol_html emitted.
+    pub chunks_out: u64,
+}
+
+/// Summary of a finished rewrite, from
+/// [`StreamingRewriter::into_parts`](crate::StreamingRewriter::into_parts), for telling why a
+/// rule did not fire and for performance dashboards.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RewriteStats {
+    /// Bytes written into the rewriter.
+    pub bytes_in: u64,
+    /// Bytes the rewriter emitted to the underlying writer.
+    pub bytes_out: u64,
+    /// Elements each of [`RewriteOptions::rules`] matched, in rule order.
+    pub elements_matched_per_rule: Vec<u64>,
+}
+
+impl RewriteThroughput {
+    /// `bytes_out / bytes_in`; `1.0` when nothing was written.
+    pub fn amplification(&self) -> f64 {
+        if self.bytes_in == 0 {
+            1.0
+        } else {
+            self.bytes_out as f64 / self.bytes_in as f64
+        }
+    }
+}
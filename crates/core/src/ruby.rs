@@ -0,0 +1,114 @@
+//! `{漢字|かんじ}` ruby annotations, rendered as `<ruby>漢字<rt>かんじ</rt></ruby>`.
+//!
+//! The syntax follows DenDenMarkdown: one reading annotates the whole base, and one reading per
+//! base character (`{漢字|かん|じ}`) annotates each character separately.
+
+use crate::event::{Event, Tag, TagEnd};
+use crate::text_runs::{Expansion, TextRuns};
+
+/// Turns `{base|reading}` in text outside code blocks and image descriptions into
+/// [`Tag::Ruby`] with a [`Tag::RubyText`] after each annotated run. Braces whose readings match
+/// neither the whole base nor its characters stay literal.
+pub(crate) fn ruby<I>(inner: I) -> impl Iterator<Item = Event<'static>>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    TextRuns::new(inner, expand)
+}
+
+fn expand(text: &str, out: &mut Expansion<'_>) -> bool {
+    if !(text.contains('{') && text.contains('|')) {
+        return false;
+    }
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        let Some((end, pairs)) = after
+            .find(['{', '}'])
+            .filter(|&end| after[end..].starts_with('}'))
+            .and_then(|end| annotate(&after[..end]).map(|pairs| (end, pairs)))
+        else {
+            out.push_text(&rest[..start + 1]);
+            rest = after;
+            continue;
+        };
+        out.push_text(&rest[..start]);
+        out.push(Event::Start(Tag::Ruby));
+        for (base, reading) in pairs {
+            out.push_text(base);
+            out.push(Event::Start(Tag::RubyText));
+            out.push_text(reading);
+            out.push(Event::End(TagEnd::RubyText));
+        }
+        out.push(Event::End(TagEnd::Ruby));
+        rest = &after[end + 1..];
+    }
+    out.push_text(rest);
+    true
+}
+
+/// Base runs paired with their readings for the inside of `{…}`, or `None` when it is not an
+/// annotation.
+fn annotate(inner: &str) -> Option<Vec<(&str, &str)>> {
+    let mut parts = inner.split('|');
+    let base = parts.next()?;
+    let readings: Vec<&str> = parts.collect();
+    if base.trim().is_empty()
+        || base.contains('\n')
+        || readings.is_empty()
+        || readings
+            .iter()
+            .any(|reading| reading.trim().is_empty() || reading.contains('\n'))
+    {
+        return None;
+    }
+    if let [reading] = readings[..] {
+        return Some(vec![(base, reading)]);
+    }
+    let chars: Vec<(usize, char)> = base.char_indices().collect();
+    if chars.len() != readings.len() {
+        return None;
+    }
+    Some(
+        chars
+            .iter()
+            .zip(readings)
+            .map(|(&(idx, ch), reading)| (&base[idx..idx + ch.len_utf8()], reading))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ParserBackend, RenderOptions, render};
+
+    #[test]
+    fn renders_ruby_annotations() {
+        let cases = [
+            (
+                "{漢字|かんじ}を{読|よ}む",
+                "<p><ruby>漢字<rt>かんじ</rt></ruby>を<ruby>読<rt>よ</rt></ruby>む</p>",
+            ),
+            (
+                "{東京|とう|きょう} *{日本|にほん}*",
+                "<p><ruby>東<rt>とう</rt>京<rt>きょう</rt></ruby> <em><ruby>日本<rt>にほん</rt></ruby></em></p>",
+            ),
+            (
+                "{a|b|c|d} {|x} {x|} {x} `{a|b}` {{a|b}",
+                "<p>{a|b|c|d} {|x} {x|} {x} <code>{a|b}</code> {<ruby>a<rt>b</rt></ruby></p>",
+            ),
+        ];
+
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            for (input, expected) in cases {
+                let options = RenderOptions {
+                    backend,
+                    ruby: true,
+                    ..RenderOptions::default()
+                };
+                let html = render(input, &options).unwrap().html;
+                assert_eq!(html.trim_end(), expected, "{backend:?}: {input:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Whole-document script composition gathered while text events stream past.
+//!
+//! Hosts use the ratios to pick fonts, line-height, and justification settings for CJK-heavy
+//! documents without re-scanning the rendered HTML in JavaScript.
+
+use crate::event::{Event, Tag, TagEnd};
+
+/// Unicode script buckets tracked for layout decisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Script {
+    /// CJK ideographs (kanji / hanzi / hanja).
+    Han,
+    /// Japanese hiragana.
+    Hiragana,
+    /// Japanese katakana, including half-width forms.
+    Katakana,
+    /// Korean hangul syllables and jamo.
+    Hangul,
+    /// Latin letters, including accented and full-width forms.
+    Latin,
+    /// Cyrillic letters.
+    Cyrillic,
+    /// Greek letters.
+    Greek,
+    /// Arabic letters.
+    Arabic,
+    /// Any other alphabetic character.
+    Other,
+}
+
+impl Script {
+    /// Every tracked script, in declaration order.
+    pub const ALL: [Script; 9] = [
+        Script::Han,
+        Script::Hiragana,
+        Script::Katakana,
+        Script::Hangul,
+        Script::Latin,
+        Script::Cyrillic,
+        Script::Greek,
+        Script::Arabic,
+        Script::Other,
+    ];
+
+    /// Stable lowercase name used by the bindings.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Script::Han => "han",
+            Script::Hiragana => "hiragana",
+            Script::Katakana => "katakana",
+            Script::Hangul => "hangul",
+            Script::Latin => "latin",
+            Script::Cyrillic => "cyrillic",
+            Script::Greek => "greek",
+            Script::Arabic => "arabic",
+            Script::Other => "other",
+        }
+    }
+
+    /// Returns `true` for scripts that use CJK layout rules.
+    pub fn is_cjk(self) -> bool {
+        matches!(
+            self,
+            Script::Han | Script::Hiragana | Script::Katakana | Script::Hangul
+        )
+    }
+
+    /// Classifies a character, returning `None` for digits, punctuation, whitespace, and symbols.
+    pub fn of(ch: char) -> Option<Script> {
+        let script = match ch as u32 {
+            0x3040..=0x309F => Script::Hiragana,
+            0x30A0..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Script::Katakana,
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x3134F => Script::Han,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+            0xFF21..=0xFF3A | 0xFF41..=0xFF5A => Script::Latin,
+            0x0370..=0x03FF | 0x1F00..=0x1FFF => Script::Greek,
+            0x0400..=0x052F => Script::Cyrillic,
+            0x0600..=0x06FF | 0x0750..=0x077F => Script::Arabic,
+            _ if ch.is_ascii_alphabetic() => Script::Latin,
+            0x00C0..=0x024F if ch.is_alphabetic() => Script::Latin,
+            _ if ch.is_alphabetic() => Script::Other,
+            _ => return None,
+        };
+        Some(script)
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Per-document character counts grouped by [`Script`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptStats {
+    counts: [usize; Script::ALL.len()],
+    total: usize,
+    in_code_block: bool,
+}
+
+impl ScriptStats {
+    /// Records the visible text of an event; markup, code, and math are ignored.
+    pub fn observe(&mut self, event: &Event<'_>) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => self.in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => self.in_code_block = false,
+            Event::Text(text) if !self.in_code_block => self.record(text.as_ref()),
+            _ => {}
+        }
+    }
+
+    /// Adds every classifiable character in `text` to the counts.
+    pub fn record(&mut self, text: &str) {
+        for script in text.chars().filter_map(Script::of) {
+            self.counts[script.index()] += 1;
+            self.total += 1;
+        }
+    }
+
+    /// Number of characters counted for `script`.
+    pub fn count(&self, script: Script) -> usize {
+        self.counts[script.index()]
+    }
+
+    /// Total number of classified characters.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Share of `script` in the range `0.0..=1.0`; `0.0` for documents without letters.
+    pub fn ratio(&self, script: Script) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.count(script) as f64 / self.total as f64
+        }
+    }
+
+    /// Combined share of Han, kana, and Hangul characters.
+    pub fn cjk_ratio(&self) -> f64 {
+        Script::ALL
+            .iter()
+            .filter(|script| script.is_cjk())
+            .map(|script| self.ratio(*script))
+            .sum()
+    }
+
+    /// The most frequent script, if any characters were classified. Ties resolve to the script
+    /// declared first in [`Script`], so CJK wins over Latin on an even split.
+    pub fn dominant(&self) -> Option<Script> {
+        self.iter()
+            .fold(
+                None,
+                |best: Option<(Script, usize)>, (script, count)| match best {
+                    Some((_, best_count)) if best_count >= count => best,
+                    _ => Some((script, count)),
+                },
+            )
+            .map(|(script, _)| script)
+    }
+
+    /// Iterates over scripts with a non-zero count, in declaration order.
+    pub fn iter(&self) -> impl Iterator<Item = (Script, usize)> + '_ {
+        Script::ALL
+            .iter()
+            .map(|script| (*script, self.count(*script)))
+            .filter(|(_, count)| *count > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn classifies_mixed_japanese_and_latin() {
+        let mut stats = ScriptStats::default();
+        stats.record("日本語のテキスト with Rust");
+
+        assert_eq!(stats.count(Script::Han), 3);
+        assert_eq!(stats.count(Script::Hiragana), 1);
+        assert_eq!(stats.count(Script::Katakana), 4);
+        assert_eq!(stats.count(Script::Latin), 8);
+        assert_eq!(stats.total(), 16);
+        assert_eq!(stats.dominant(), Some(Script::Latin));
+        assert!((stats.cjk_ratio() - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn ignores_code_punctuation_and_digits() {
+        let mut stats = ScriptStats::default();
+        stats.observe(&Event::Code(Cow::Borrowed("let x = 1;")));
+        stats.observe(&Event::Text(Cow::Borrowed("、。123 !?")));
+
+        assert_eq!(stats.total(), 0);
+        assert_eq!(stats.dominant(), None);
+        assert_eq!(stats.ratio(Script::Latin), 0.0);
+    }
+}
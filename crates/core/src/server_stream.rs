@@ -0,0 +1,155 @@
+//! Block-aligned HTML chunks for chunked transfer encoding and server-sent events.
+//!
+//! [`HtmlChunks`] renders an event stream lazily and hands out the HTML of each finished
+//! top-level block, so a response body can show markdown while it is still being generated. A
+//! chunk never ends inside an element, so every chunk can be inserted into the page on arrival.
+//! Async servers can wrap the iterator in their stream type (`futures::stream::iter`,
+//! `Body::wrap_stream`, …); rendering itself never waits.
+
+use crate::event::Event;
+use crate::html_renderer::{HtmlOptions, HtmlRenderer};
+
+/// [`HtmlChunks`] framed as server-sent events.
+pub type SseFrames<I> = std::iter::Map<HtmlChunks<I>, fn(Vec<u8>) -> Vec<u8>>;
+
+/// Iterator over the rendered HTML of an event stream, one or more top-level blocks per chunk.
+pub struct HtmlChunks<I> {
+    events: I,
+    renderer: HtmlRenderer<Vec<u8>>,
+    /// Open tags of the block being rendered.
+    depth: usize,
+    min_bytes: usize,
+    done: bool,
+}
+
+impl<'a, I> HtmlChunks<I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    /// Chunks of `events` rendered with `options`, one per top-level block.
+    pub fn new(events: I, options: &HtmlOptions) -> Self {
+        Self {
+            events,
+            renderer: HtmlRenderer::with_options(Vec::new(), options.clone()),
+            depth: 0,
+            min_bytes: 0,
+            done: false,
+        }
+    }
+
+    /// Holds blocks back until a chunk has at least `min_bytes` of HTML, to save per-chunk
+    /// overhead on documents with many short blocks. The last chunk may be smaller.
+    pub fn min_bytes(mut self, min_bytes: usize) -> Self {
+        self.min_bytes = min_bytes;
+        self
+    }
+
+    /// Frames each chunk as a server-sent event (see [`sse_frame`]).
+    pub fn sse(self) -> SseFrames<I> {
+        self.map(|chunk| sse_frame(&chunk))
+    }
+
+    fn take(&mut self) -> Option<Vec<u8>> {
+        let chunk = std::mem::take(self.renderer.writer_mut());
+        (!chunk.is_empty()).then_some(chunk)
+    }
+}
+
+impl<'a, I> Iterator for HtmlChunks<I>
+where
+    I: Iterator<Item = Event<'a>>,
+{
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        // Rendering into a `Vec` cannot fail, so the results below carry no errors.
+        while let Some(event) = self.events.next() {
+            match &event {
+                Event::Start(_) => self.depth += 1,
+                Event::End(_) => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
+            let _ = self.renderer.render_events([event]);
+            if self.depth == 0
+                && self.renderer.writer_mut().len() >= self.min_bytes
+                && let Some(chunk) = self.take()
+            {
+                return Some(chunk);
+            }
+        }
+        self.done = true;
+        let _ = self.renderer.close_sections(0);
+        self.take()
+    }
+}
+
+/// `chunk` as one server-sent event: a `data:` field per line, ended by a blank line. Line
+/// breaks inside the HTML survive, since clients join the fields of an event with `\n`.
+pub fn sse_frame(chunk: &[u8]) -> Vec<u8> {
+    let chunk = chunk.strip_suffix(b"\n").unwrap_or(chunk);
+    let mut frame = Vec::with_capacity(chunk.len() + 16);
+    for line in chunk.split(|&byte| byte == b'\n') {
+        frame.extend_from_slice(b"data: ");
+        frame.extend_from_slice(line);
+        frame.push(b'\n');
+    }
+    frame.push(b'\n');
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_event_iterator;
+
+    fn chunks(input: &str, min_bytes: usize) -> Vec<String> {
+        let events = get_event_iterator(input).unwrap();
+        HtmlChunks::new(events, &HtmlOptions::default())
+            .min_bytes(min_bytes)
+            .map(|chunk| String::from_utf8(chunk).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn yields_block_aligned_chunks() {
+        let input = "# Title\n\n- a\n- b\n\n> quote\n> more\n\n---\n";
+        assert_eq!(
+            chunks(input, 0),
+            [
+                "<h1 id=\"title\">Title</h1>\n",
+                "<ul><li>a</li><li>b</li></ul>\n",
+                "<blockquote><p>quote\nmore</p>\n</blockquote>\n",
+                "<hr />\n",
+            ]
+        );
+        assert_eq!(
+            chunks(input, 40),
+            [
+                "<h1 id=\"title\">Title</h1>\n<ul><li>a</li><li>b</li></ul>\n",
+                "<blockquote><p>quote\nmore</p>\n</blockquote>\n",
+                "<hr />\n",
+            ]
+        );
+        assert_eq!(chunks(input, 0).concat(), crate::parse(input).unwrap());
+        assert!(chunks("", 0).is_empty());
+    }
+
+    #[test]
+    fn frames_server_sent_events() {
+        let events = get_event_iterator("Hello\n\n> a\n> b").unwrap();
+        let frames: Vec<String> = HtmlChunks::new(events, &HtmlOptions::default())
+            .sse()
+            .map(|frame| String::from_utf8(frame).unwrap())
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                "data: <p>Hello</p>\n\n",
+                "data: <blockquote><p>a\ndata: b</p>\ndata: </blockquote>\n\n",
+            ]
+        );
+    }
+}
@@ -0,0 +1,398 @@
+//! Passthrough for static-site-generator template tags (Hugo shortcodes, Liquid tags).
+//!
+//! Tags are swapped for private-use placeholders before parsing, so the Markdown parser cannot
+//! escape, link, or emphasize their contents, and restored verbatim in the event stream.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::event::{Event, Tag, TagEnd};
+use crate::span::SourceSpan;
+
+/// Opening and closing delimiters of recognized tags, longest opener first.
+const DELIMITERS: [(&str, &str); 3] = [("{{<", ">}}"), ("{{%", "%}}"), ("{%", "%}")];
+
+const PLACEHOLDER_START: char = '\u{E000}';
+const PLACEHOLDER_END: char = '\u{E001}';
+
+/// Replaces every template tag in `input` with a placeholder, returning the rewritten source and
+/// the original tags by placeholder index. Tags may span lines but not blank lines; openers
+/// without a closer before the next blank line are left alone.
+pub(crate) fn protect(input: &str) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(input.len());
+    let mut tags = Vec::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let tag_len = DELIMITERS.iter().find_map(|(open, close)| {
+            let body = rest.strip_prefix(open)?;
+            current_block(body)
+                .find(close)
+                .map(|end| open.len() + end + close.len())
+        });
+        match tag_len {
+            Some(len) => {
+                output.push(PLACEHOLDER_START);
+                output.push_str(&tags.len().to_string());
+                output.push(PLACEHOLDER_END);
+                tags.push(rest[..len].to_string());
+                rest = &rest[len..];
+            }
+            None => {
+                output.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    (output, tags)
+}
+
+/// `body` up to its first blank line, so a stray opener cannot swallow the blocks after it.
+fn current_block(body: &str) -> &str {
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        // The first line is the rest of the opener's own line.
+        if offset > 0 && line.trim().is_empty() {
+            return &body[..offset];
+        }
+        offset += line.len();
+    }
+    body
+}
+
+/// Maps `span` of the protected `text` back onto the input [`protect`] was given.
+pub(crate) fn original_span(text: &str, tags: &[String], span: SourceSpan) -> SourceSpan {
+    let (start, lines_before_start) = original_offset(text, tags, span.start);
+    let (end, lines_before_end) = original_offset(text, tags, span.end);
+    SourceSpan {
+        start,
+        end,
+        start_line: span.start_line + lines_before_start,
+        end_line: span.end_line + lines_before_end,
+    }
+}
+
+/// The input offset of `offset` in the protected `text`, and the line breaks that restoring
+/// the tags before it adds.
+fn original_offset(text: &str, tags: &[String], offset: usize) -> (usize, usize) {
+    let mut shift = 0isize;
+    let mut lines = 0;
+    let mut rest = 0;
+    while let Some(found) = text[rest..].find(PLACEHOLDER_START) {
+        let start = rest + found;
+        let after = start + PLACEHOLDER_START.len_utf8();
+        let Some(len) = text[after..].find(PLACEHOLDER_END) else {
+            break;
+        };
+        let end = after + len + PLACEHOLDER_END.len_utf8();
+        let Some(tag) = text[after..after + len]
+            .parse::<usize>()
+            .ok()
+            .and_then(|idx| tags.get(idx))
+        else {
+            break;
+        };
+        if start >= offset {
+            break;
+        }
+        if end > offset {
+            // Inside a placeholder: point at its tag's start.
+            return (start.saturating_add_signed(shift), lines);
+        }
+        shift += tag.len() as isize - (end - start) as isize;
+        lines += tag.matches('\n').count();
+        rest = end;
+    }
+    (offset.saturating_add_signed(shift), lines)
+}
+
+/// Splits `text` into literal runs and restored tags, in order.
+fn split<'t>(text: &'t str, tags: &'t [String]) -> Vec<Segment<'t>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(PLACEHOLDER_START) {
+        let after = &rest[start + PLACEHOLDER_START.len_utf8()..];
+        let Some(end) = after.find(PLACEHOLDER_END) else {
+            break;
+        };
+        let Some(tag) = after[..end].parse::<usize>().ok().and_then(|i| tags.get(i)) else {
+            break;
+        };
+        if start > 0 {
+            segments.push(Segment::Text(&rest[..start]));
+        }
+        segments.push(Segment::Tag(tag));
+        rest = &after[end + PLACEHOLDER_END.len_utf8()..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+    segments
+}
+
+enum Segment<'t> {
+    Text(&'t str),
+    Tag(&'t str),
+}
+
+/// Replaces placeholders with their original tags.
+fn restore_str(text: &str, tags: &[String]) -> String {
+    split(text, tags)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) | Segment::Tag(text) => text,
+        })
+        .collect()
+}
+
+fn restore_cow(text: Cow<'static, str>, tags: &[String]) -> Cow<'static, str> {
+    if text.contains(PLACEHOLDER_START) {
+        Cow::Owned(restore_str(&text, tags))
+    } else {
+        text
+    }
+}
+
+/// Restores the tags [`protect`] replaced.
+///
+/// In prose, tags reach the output byte-for-byte; a paragraph holding nothing but tags is emitted
+/// as a raw block without `<p>`. Inside code, images, and link destinations and titles
+/// (`[x]({{< relref "a.md" >}})`) the tag text is restored as ordinary text, so the renderer
+/// escapes it and `sanitize_urls` and `harden_links` check it like any other link.
+pub(crate) struct RestoreShortcodes<'t, I> {
+    inner: I,
+    tags: &'t [String],
+    pending: VecDeque<Event<'static>>,
+    /// Open code blocks and images, where tags must stay text.
+    literal_depth: usize,
+}
+
+impl<'t, I> RestoreShortcodes<'t, I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    pub(crate) fn new(inner: I, tags: &'t [String]) -> Self {
+        Self {
+            inner,
+            tags,
+            pending: VecDeque::new(),
+            literal_depth: 0,
+        }
+    }
+
+    /// Buffers a paragraph; one made only of tags and whitespace becomes a raw block.
+    fn paragraph(&mut self) {
+        let mut body = Vec::new();
+        let mut only_tags = true;
+        let mut closed = false;
+        for event in self.inner.by_ref() {
+            match &event {
+                Event::End(TagEnd::Paragraph) => {
+                    closed = true;
+                    break;
+                }
+                Event::Text(text) => {
+                    only_tags &= split(text, self.tags).iter().all(|segment| match segment {
+                        Segment::Text(text) => text.trim().is_empty(),
+                        Segment::Tag(_) => true,
+                    });
+                }
+                Event::SoftBreak => {}
+                _ => only_tags = false,
+            }
+            body.push(event);
+            if !only_tags {
+                break;
+            }
+        }
+
+        let has_tag = body
+            .iter()
+            .any(|event| matches!(event, Event::Text(text) if text.contains(PLACEHOLDER_START)));
+        if only_tags && closed && has_tag {
+            let mut raw = String::new();
+            for event in body {
+                match event {
+                    Event::Text(text) => raw.push_str(&restore_str(&text, self.tags)),
+                    _ => raw.push('\n'),
+                }
+            }
+            raw.push('\n');
+            self.pending.push_back(Event::Html(Cow::Owned(raw)));
+            return;
+        }
+
+        self.pending.push_back(Event::Start(Tag::Paragraph));
+        for event in body {
+            self.restore(event);
+        }
+        if closed {
+            self.pending.push_back(Event::End(TagEnd::Paragraph));
+        }
+    }
+
+    fn restore(&mut self, event: Event<'static>) {
+        let tags = self.tags;
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                self.literal_depth += 1;
+                self.pending.push_back(Event::Start(Tag::CodeBlock(kind)));
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => self.pending.push_back(Event::Start(Tag::Link {
+                link_type,
+                dest_url: restore_cow(dest_url, tags),
+                title: restore_cow(title, tags),
+                id,
+            })),
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+                width,
+                height,
+                attrs,
+            }) => {
+                self.literal_depth += 1;
+                self.pending.push_back(Event::Start(Tag::Image {
+                    link_type,
+                    dest_url: restore_cow(dest_url, tags),
+                    title: restore_cow(title, tags),
+                    id,
+                    width,
+                    height,
+                    attrs,
+                }));
+            }
+            Event::End(end @ (TagEnd::CodeBlock | TagEnd::Image)) => {
+                self.literal_depth = self.literal_depth.saturating_sub(1);
+                self.pending.push_back(Event::End(end));
+            }
+            Event::Text(text) if text.contains(PLACEHOLDER_START) => {
+                if self.literal_depth > 0 {
+                    self.pending
+                        .push_back(Event::Text(Cow::Owned(restore_str(&text, tags))));
+                    return;
+                }
+                for segment in split(&text, tags) {
+                    self.pending.push_back(match segment {
+                        Segment::Text(text) => Event::Text(Cow::Owned(text.to_string())),
+                        Segment::Tag(tag) => Event::InlineHtml(Cow::Owned(tag.to_string())),
+                    });
+                }
+            }
+            Event::Code(text) => self.pending.push_back(Event::Code(restore_cow(text, tags))),
+            Event::Html(html) => self.pending.push_back(Event::Html(restore_cow(html, tags))),
+            Event::InlineHtml(html) => self
+                .pending
+                .push_back(Event::InlineHtml(restore_cow(html, tags))),
+            other => self.pending.push_back(other),
+        }
+    }
+}
+
+impl<I> Iterator for RestoreShortcodes<'_, I>
+where
+    I: Iterator<Item = Event<'static>>,
+{
+    type Item = Event<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            match self.inner.next()? {
+                Event::Start(Tag::Paragraph) if self.literal_depth == 0 => self.paragraph(),
+                event => self.restore(event),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CjkPunctuation, Profile, RenderOptions, render};
+
+    #[test]
+    fn passes_template_tags_through() {
+        let cases = [
+            (
+                "{{< figure src=\"a.png\" title=\"*not* emphasis\" >}}",
+                "{{< figure src=\"a.png\" title=\"*not* emphasis\" >}}\n",
+            ),
+            (
+                "{% include note.html %}\n{{% notice %}}",
+                "{% include note.html %}\n{{% notice %}}\n",
+            ),
+            (
+                "See {{< ref \"docs/_index.md\" >}} for *more*.",
+                "<p>See {{< ref \"docs/_index.md\" >}} for <em>more</em>.</p>\n",
+            ),
+            (
+                "[link]({{< relref \"a.md\" >}}) `{% raw %}`",
+                "<p><a href=\"{{&lt; relref &quot;a.md&quot; &gt;}}\">link</a> <code>{% raw %}</code></p>\n",
+            ),
+            (
+                "```\n{{< x >}}\n```\n\n{ not a tag } {{< unterminated",
+                "<pre><code>{{&lt; x &gt;}}</code></pre>\n<p>{ not a tag } {{&lt; unterminated</p>\n",
+            ),
+            (
+                "{% a\n\n# heading\n\nb %}",
+                "<p>{% a</p>\n<h1 id=\"heading\">heading</h1>\n<p>b %}</p>\n",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let options = RenderOptions {
+                shortcodes: true,
+                ..RenderOptions::default()
+            };
+            assert_eq!(
+                render(input, &options).unwrap().html,
+                expected,
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn checks_link_destinations_with_tags() {
+        let options = RenderOptions {
+            shortcodes: true,
+            ..Profile::Untrusted.options()
+        };
+        let html = |input| render(input, &options).unwrap().html;
+        assert_eq!(
+            html("[x](javascript:{{<_x_>}})"),
+            "<p><a href=\"\">x</a></p>\n"
+        );
+        #[cfg(feature = "rewriter")]
+        assert_eq!(
+            html("[x](https://evil.example/{{<_x_\">}})"),
+            "<p><a href=\"https://evil.example/{{&lt;_x_&quot;&gt;}}\" \
+             rel=\"nofollow ugc noopener noreferrer\">x</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn tags_survive_smart_punctuation() {
+        let options = RenderOptions {
+            shortcodes: true,
+            cjk_punctuation: Some(CjkPunctuation::default()),
+            ..RenderOptions::default()
+        };
+        let html = render("日本語{{< note \"x\" >}}...", &options)
+            .unwrap()
+            .html;
+        assert_eq!(html, "<p>日本語{{< note \"x\" >}}……</p>\n");
+    }
+}
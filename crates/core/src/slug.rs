@@ -0,0 +1,361 @@
+//! Heading slugs and heading extraction shared by every parser backend.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+use crate::event::{Event, Tag, TagEnd};
+
+/// Lowercased alphanumerics joined by single dashes; `None` when nothing is left.
+///
+/// Non-ASCII letters and digits are kept, so CJK headings get readable slugs.
+pub fn slugify(text: &str) -> Option<String> {
+    let mut slug = String::new();
+    let mut last_dash = false;
+
+    for ch in text.chars() {
+        if ch.is_alphanumeric() {
+            for lower in ch.to_lowercase() {
+                slug.push(lower);
+            }
+            last_dash = false;
+        } else if (ch.is_whitespace() || matches!(ch, '-' | '_' | ':' | '.'))
+            && !slug.is_empty()
+            && !last_dash
+        {
+            slug.push('-');
+            last_dash = true;
+        }
+    }
+
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+
+    if slug.is_empty() { None } else { Some(slug) }
+}
+
+/// Slugify a heading the way GitHub does: lowercase, drop punctuation other than `-` and `_`, and
+/// turn each space into a dash without collapsing runs, so `A - B` becomes `a---b`. `None` when
+/// nothing is left.
+pub fn slugify_github(text: &str) -> Option<String> {
+    let mut slug = String::new();
+    for ch in text.trim().chars() {
+        if ch == ' ' {
+            slug.push('-');
+        } else if ch.is_alphanumeric() || matches!(ch, '-' | '_') {
+            slug.extend(ch.to_lowercase());
+        }
+    }
+    if slug.is_empty() { None } else { Some(slug) }
+}
+
+/// Slug algorithm used for generated heading ids.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SlugStyle {
+    /// [`slugify`]: runs of separators collapse into one dash.
+    #[default]
+    Markflow,
+    /// [`slugify_github`]: matches the anchors GitHub generates for READMEs.
+    GitHub,
+}
+
+impl SlugStyle {
+    /// Slugifies `text` with this algorithm.
+    pub fn slugify(self, text: &str) -> Option<String> {
+        match self {
+            SlugStyle::Markflow => slugify(text),
+            SlugStyle::GitHub => slugify_github(text),
+        }
+    }
+}
+
+/// `text` in Unicode Normalization Form C, borrowed when it already is.
+pub(crate) fn to_nfc(text: Cow<'_, str>) -> Cow<'_, str> {
+    if is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        text
+    } else {
+        Cow::Owned(text.nfc().collect())
+    }
+}
+
+/// Hands out document-unique slugs: repeats get `-1`, `-2`, ... suffixes, skipping suffixed
+/// forms that an earlier heading already claimed literally.
+#[derive(Debug, Clone, Default)]
+pub struct Slugger {
+    /// Every slug handed out, with the next suffix to try for it.
+    seen: HashMap<String, usize>,
+    style: SlugStyle,
+    /// Normalize heading text to NFC before slugifying.
+    nfc: bool,
+}
+
+impl Slugger {
+    /// Creates a slugger with no slugs taken.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a slugger that slugifies with `style`.
+    pub fn with_style(style: SlugStyle) -> Self {
+        Self {
+            style,
+            ..Self::default()
+        }
+    }
+
+    /// Normalizes heading text to Unicode NFC before slugifying when `nfc` is set, so text typed
+    /// in decomposed form (as macOS file names are) gets the same slug as precomposed text.
+    pub fn with_nfc(mut self, nfc: bool) -> Self {
+        self.nfc = nfc;
+        self
+    }
+
+    /// Slugifies `text` and makes the result unique within this slugger.
+    pub fn slug(&mut self, text: &str) -> Option<String> {
+        let text = if self.nfc {
+            to_nfc(Cow::Borrowed(text))
+        } else {
+            Cow::Borrowed(text)
+        };
+        self.style.slugify(&text).map(|slug| self.unique(slug))
+    }
+
+    /// Makes an already-computed slug (e.g. an explicit `{#id}`) unique within this slugger.
+    pub fn unique(&mut self, slug: String) -> String {
+        let Some(next) = self.seen.get(&slug).copied() else {
+            self.seen.insert(slug.clone(), 1);
+            return slug;
+        };
+
+        let mut suffix = next;
+        let candidate = loop {
+            let candidate = format!("{slug}-{suffix}");
+            suffix += 1;
+            if !self.seen.contains_key(&candidate) {
+                break candidate;
+            }
+        };
+        self.seen.insert(slug, suffix);
+        self.seen.insert(candidate.clone(), 1);
+        candidate
+    }
+
+    /// Forgets every slug, e.g. between documents.
+    pub fn reset(&mut self) {
+        self.seen.clear();
+    }
+}
+
+/// A heading found in the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// Heading level (1-6).
+    pub level: u8,
+    /// Plain text of the heading.
+    pub text: String,
+    /// The heading's `id` attribute, when it has one.
+    pub id: Option<String>,
+    /// Hierarchical number (`1.2`), when
+    /// [`RenderOptions::number_headings`](crate::RenderOptions::number_headings) is enabled.
+    pub number: Option<String>,
+}
+
+/// Heading attribute that carries the number from [`HeadingNumbers`].
+pub(crate) const NUMBER_ATTR: &str = "data-number";
+
+/// Hands out hierarchical heading numbers (`1`, `1.1`, `1.2.1`) in document order.
+///
+/// Headings nest the way [`toc_html`](crate::toc::toc_html) nests them: a skipped level (H2 →
+/// H4) adds a single step, and a heading shallower than its siblings but deeper than their
+/// parent continues the siblings' count.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HeadingNumbers {
+    /// Level and count of each open numbering step, outermost first.
+    open: Vec<(u8, u32)>,
+}
+
+impl HeadingNumbers {
+    /// Number of the next heading, at `level`.
+    pub(crate) fn next(&mut self, level: u8) -> String {
+        let mut closed = None;
+        while let Some(&(open, count)) = self.open.last()
+            && open > level
+        {
+            closed = Some(count);
+            self.open.pop();
+        }
+        match self.open.last_mut() {
+            Some((open, count)) if *open == level => *count += 1,
+            _ => self.open.push((level, closed.map_or(1, |count| count + 1))),
+        }
+        self.open
+            .iter()
+            .map(|(_, count)| count.to_string())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+/// Collects [`Heading`]s while events stream past.
+#[derive(Debug, Default)]
+pub struct HeadingCollector {
+    current: Option<Heading>,
+    headings: Vec<Heading>,
+}
+
+impl HeadingCollector {
+    /// Creates an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one event; call for every event of the document in order.
+    pub fn observe(&mut self, event: &Event<'_>) {
+        match event {
+            Event::Start(Tag::Heading {
+                level, id, attrs, ..
+            }) => {
+                self.current = Some(Heading {
+                    level: *level as u8,
+                    text: String::new(),
+                    id: id.as_ref().map(|id| id.to_string()),
+                    number: attrs
+                        .iter()
+                        .find(|(key, _)| key == NUMBER_ATTR)
+                        .and_then(|(_, value)| value.as_ref())
+                        .map(|number| number.to_string()),
+                });
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(mut heading) = self.current.take() {
+                    heading.text = heading.text.trim().to_string();
+                    self.headings.push(heading);
+                }
+            }
+            Event::Text(text) | Event::Code(text) | Event::InlineMath(text) => {
+                if let Some(heading) = self.current.as_mut() {
+                    heading.text.push_str(text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(heading) = self.current.as_mut() {
+                    heading.text.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Headings collected so far.
+    pub fn headings(&self) -> &[Heading] {
+        &self.headings
+    }
+
+    /// Consumes the collector, returning the headings in document order.
+    pub fn finish(self) -> Vec<Heading> {
+        self.headings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn slugifies_text() {
+        let cases = [
+            ("Hello World", Some("hello-world")),
+            ("  API: v2.0 -- Notes  ", Some("api-v2-0-notes")),
+            ("見出し テスト", Some("見出し-テスト")),
+            ("!!!", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(slugify(input).as_deref(), expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn dedupes_repeated_and_colliding_slugs() {
+        let mut slugger = Slugger::new();
+        let slugs: Vec<_> = ["Intro", "Intro", "Intro 1", "Intro", "Other"]
+            .iter()
+            .map(|text| slugger.slug(text).unwrap())
+            .collect();
+
+        assert_eq!(slugs, ["intro", "intro-1", "intro-1-1", "intro-2", "other"]);
+
+        slugger.reset();
+        assert_eq!(slugger.slug("Intro").unwrap(), "intro");
+    }
+
+    /// Heading text and explicit ids from a small alphabet, so collisions and `-1`-style
+    /// lookalikes are common, mixed with arbitrary strings.
+    fn headings() -> impl Strategy<Value = Vec<(bool, String)>> {
+        prop::collection::vec(
+            (
+                any::<bool>(),
+                prop_oneof!["[aB1 _:.-]{0,6}", any::<String>()],
+            ),
+            0..40,
+        )
+    }
+
+    /// Characters that would need escaping in a `#fragment` or break an `id` attribute.
+    fn is_valid_fragment(slug: &str) -> bool {
+        !slug.is_empty()
+            && !slug.chars().any(|ch| {
+                ch.is_whitespace()
+                    || ch.is_control()
+                    || matches!(ch, '#' | '%' | '"' | '\'' | '<' | '>' | '`' | '/' | '?')
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn slugger_never_repeats_an_id(headings in headings(), github in any::<bool>()) {
+            let style = if github { SlugStyle::GitHub } else { SlugStyle::Markflow };
+            let mut slugger = Slugger::with_style(style);
+            let mut ids = std::collections::HashSet::new();
+            for (explicit, text) in &headings {
+                let id = if *explicit {
+                    Some(slugger.unique(text.clone()))
+                } else {
+                    slugger.slug(text)
+                };
+                if let Some(id) = id {
+                    prop_assert!(ids.insert(id.clone()), "{id:?} handed out twice");
+                }
+            }
+        }
+
+        #[test]
+        fn slugs_are_valid_fragments(text in any::<String>()) {
+            for style in [SlugStyle::Markflow, SlugStyle::GitHub] {
+                if let Some(slug) = style.slugify(&text) {
+                    prop_assert!(is_valid_fragment(&slug), "{style:?}: {slug:?}");
+                }
+            }
+            if let Some(slug) = slugify(&text) {
+                prop_assert!(!slug.starts_with('-') && !slug.ends_with('-') && !slug.contains("--"));
+            }
+        }
+
+        #[test]
+        fn same_headings_get_the_same_ids(headings in headings()) {
+            let mut first = Slugger::new();
+            let ids: Vec<_> = headings.iter().map(|(_, text)| first.slug(text)).collect();
+            first.reset();
+            let again: Vec<_> = headings.iter().map(|(_, text)| first.slug(text)).collect();
+            prop_assert_eq!(&ids, &again);
+            let mut fresh = Slugger::new();
+            let fresh: Vec<_> = headings.iter().map(|(_, text)| fresh.slug(text)).collect();
+            prop_assert_eq!(ids, fresh);
+        }
+    }
+}
@@ -0,0 +1,500 @@
+//! Typographic punctuation passes applied to text events before rendering.
+//!
+//! The CJK pass only touches punctuation that sits next to CJK characters, so Western quotes in
+//! embedded English phrases are left for the regular smart punctuation rules, which
+//! [`SmartPunctuator`] applies afterwards.
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::{Event, Tag, TagEnd};
+use crate::script_stats::Script;
+
+/// Locale that decides which CJK quotation marks replace straight quotes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CjkLocale {
+    /// Japanese: `「」` and `『』`.
+    #[default]
+    Japanese,
+    /// Traditional Chinese: `「」` and `『』`.
+    ChineseTraditional,
+    /// Simplified Chinese: `“”` and `‘’`.
+    ChineseSimplified,
+    /// Korean: `“”` and `‘’`.
+    Korean,
+}
+
+impl CjkLocale {
+    fn double_quotes(self) -> (char, char) {
+        match self {
+            CjkLocale::Japanese | CjkLocale::ChineseTraditional => ('「', '」'),
+            CjkLocale::ChineseSimplified | CjkLocale::Korean => ('“', '”'),
+        }
+    }
+
+    fn single_quotes(self) -> (char, char) {
+        match self {
+            CjkLocale::Japanese | CjkLocale::ChineseTraditional => ('『', '』'),
+            CjkLocale::ChineseSimplified | CjkLocale::Korean => ('‘', '’'),
+        }
+    }
+}
+
+/// CJK-aware punctuation substitutions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CjkPunctuation {
+    /// Quote style to use.
+    pub locale: CjkLocale,
+    /// Replace ASCII `...` next to CJK text with `……`.
+    pub ellipsis: bool,
+    /// Replace straight quotes next to CJK text with the locale's brackets.
+    pub quotes: bool,
+}
+
+impl Default for CjkPunctuation {
+    fn default() -> Self {
+        CjkPunctuation {
+            locale: CjkLocale::default(),
+            ellipsis: true,
+            quotes: true,
+        }
+    }
+}
+
+/// Quotation marks that [`SmartPunctuation`] turns straight quotes into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuoteStyle {
+    /// `“double”` and `‘single’`.
+    #[default]
+    English,
+    /// `„double“` and `‚single‘`.
+    German,
+    /// `« double »` and `‹ single ›`, with no-break spaces inside.
+    French,
+}
+
+impl QuoteStyle {
+    fn double_quotes(self) -> (&'static str, &'static str) {
+        match self {
+            QuoteStyle::English => ("“", "”"),
+            QuoteStyle::German => ("„", "“"),
+            QuoteStyle::French => ("«\u{a0}", "\u{a0}»"),
+        }
+    }
+
+    fn single_quotes(self) -> (&'static str, &'static str) {
+        match self {
+            QuoteStyle::English => ("‘", "’"),
+            QuoteStyle::German => ("‚", "‘"),
+            QuoteStyle::French => ("‹\u{a0}", "\u{a0}›"),
+        }
+    }
+}
+
+/// SmartyPants-style typography for text outside code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmartPunctuation {
+    /// Style for curled quotes; `None` leaves straight quotes alone. A `'` inside a word or
+    /// before a year (`'90s`) is always the apostrophe `’`.
+    pub quotes: Option<QuoteStyle>,
+    /// Replace `--` with `–` and `---` with `—`.
+    pub dashes: bool,
+    /// Replace `...` with `…`.
+    pub ellipsis: bool,
+}
+
+impl Default for SmartPunctuation {
+    fn default() -> Self {
+        SmartPunctuation {
+            quotes: Some(QuoteStyle::English),
+            dashes: true,
+            ellipsis: true,
+        }
+    }
+}
+
+/// Stateful event transformer implementing [`CjkPunctuation`].
+///
+/// Quote pairing is tracked per block so an opening bracket in one text event can be closed in a
+/// later one (e.g. across emphasis).
+#[derive(Debug, Clone)]
+pub(crate) struct CjkPunctuator {
+    options: CjkPunctuation,
+    in_code_block: bool,
+    double_open: bool,
+    single_open: bool,
+    /// Whether the last emitted character counts as CJK context, carried across text events.
+    prev_cjk: bool,
+}
+
+impl CjkPunctuator {
+    pub(crate) fn new(options: CjkPunctuation) -> Self {
+        Self {
+            options,
+            in_code_block: false,
+            double_open: false,
+            single_open: false,
+            prev_cjk: false,
+        }
+    }
+
+    pub(crate) fn apply<'a>(&mut self, event: Event<'a>) -> Event<'a> {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.in_code_block = true;
+                event
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                self.in_code_block = false;
+                self.reset();
+                event
+            }
+            Event::Start(ref tag) if is_block(tag) => {
+                self.reset();
+                event
+            }
+            Event::End(ref end) if is_block_end(end) => {
+                self.reset();
+                event
+            }
+            Event::Code(ref code) => {
+                self.prev_cjk = is_cjk_context(code.chars().last());
+                event
+            }
+            Event::Text(text) if !self.in_code_block => Event::Text(self.rewrite(text)),
+            other => other,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.double_open = false;
+        self.single_open = false;
+        self.prev_cjk = false;
+    }
+
+    fn rewrite<'a>(&mut self, text: Cow<'a, str>) -> Cow<'a, str> {
+        if !text.contains(['"', '\'', '.']) {
+            if let Some(last) = text.chars().last() {
+                self.prev_cjk = is_cjk_context(Some(last));
+            }
+            return text;
+        }
+
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len());
+        let mut idx = 0;
+
+        while idx < chars.len() {
+            let ch = chars[idx];
+
+            if self.options.ellipsis && chars[idx..].starts_with(&['.', '.', '.']) {
+                let next = chars.get(idx + 3).copied();
+                if self.prev_cjk || is_cjk_context(next) {
+                    out.push_str("……");
+                    self.prev_cjk = true;
+                    idx += 3;
+                    continue;
+                }
+            }
+
+            let next = chars.get(idx + 1).copied();
+            if self.options.quotes
+                && matches!(ch, '"' | '\'')
+                && (self.prev_cjk || is_cjk_context(next))
+            {
+                let (open, close, state) = if ch == '"' {
+                    let (open, close) = self.options.locale.double_quotes();
+                    (open, close, &mut self.double_open)
+                } else {
+                    let (open, close) = self.options.locale.single_quotes();
+                    (open, close, &mut self.single_open)
+                };
+                out.push(if *state { close } else { open });
+                *state = !*state;
+                self.prev_cjk = true;
+            } else {
+                out.push(ch);
+                self.prev_cjk = is_cjk_context(Some(ch));
+            }
+            idx += 1;
+        }
+
+        Cow::Owned(out)
+    }
+}
+
+/// Stateful event transformer implementing [`SmartPunctuation`].
+///
+/// The character before a quote decides whether it opens or closes, so the last character of
+/// a text event carries over to the next one within a block.
+#[derive(Debug, Clone)]
+pub(crate) struct SmartPunctuator {
+    options: SmartPunctuation,
+    in_code_block: bool,
+    /// Last character written in the current block.
+    prev: Option<char>,
+}
+
+impl SmartPunctuator {
+    pub(crate) fn new(options: SmartPunctuation) -> Self {
+        Self {
+            options,
+            in_code_block: false,
+            prev: None,
+        }
+    }
+
+    pub(crate) fn apply<'a>(&mut self, event: Event<'a>) -> Event<'a> {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.in_code_block = true;
+                event
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                self.in_code_block = false;
+                self.prev = None;
+                event
+            }
+            Event::Start(ref tag) if is_block(tag) => {
+                self.prev = None;
+                event
+            }
+            Event::End(ref end) if is_block_end(end) => {
+                self.prev = None;
+                event
+            }
+            Event::Code(ref code) => {
+                self.prev = code.chars().last();
+                event
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                self.prev = Some('\n');
+                event
+            }
+            Event::Text(text) if !self.in_code_block => Event::Text(self.rewrite(text)),
+            other => other,
+        }
+    }
+
+    pub(crate) fn rewrite<'a>(&mut self, text: Cow<'a, str>) -> Cow<'a, str> {
+        if !text.contains(['"', '\'', '-', '.']) {
+            self.prev = text.chars().last().or(self.prev);
+            return text;
+        }
+
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len());
+        let mut idx = 0;
+
+        while idx < chars.len() {
+            let ch = chars[idx];
+            let next = chars.get(idx + 1).copied();
+            let (replacement, consumed) = match ch {
+                '-' if self.options.dashes && next == Some('-') => {
+                    if chars.get(idx + 2) == Some(&'-') {
+                        ("—", 3)
+                    } else {
+                        ("–", 2)
+                    }
+                }
+                '.' if self.options.ellipsis && chars[idx..].starts_with(&['.', '.', '.']) => {
+                    ("…", 3)
+                }
+                '"' | '\'' => match self.options.quotes {
+                    Some(style) => (self.quote(style, ch, next), 1),
+                    None => (if ch == '"' { "\"" } else { "'" }, 1),
+                },
+                _ => {
+                    out.push(ch);
+                    self.prev = Some(ch);
+                    idx += 1;
+                    continue;
+                }
+            };
+            out.push_str(replacement);
+            self.prev = replacement.chars().last();
+            idx += consumed;
+        }
+
+        Cow::Owned(out)
+    }
+
+    fn quote(&self, style: QuoteStyle, ch: char, next: Option<char>) -> &'static str {
+        let opens = self.prev.is_none_or(opens_quote);
+        let (open, close) = if ch == '"' {
+            style.double_quotes()
+        } else if (self.prev.is_some_and(char::is_alphanumeric)
+            && next.is_some_and(char::is_alphanumeric))
+            || (opens && next.is_some_and(|next| next.is_ascii_digit()))
+        {
+            // `don't`, `the '90s`: apostrophes, not quotes.
+            return "’";
+        } else {
+            style.single_quotes()
+        };
+        if opens { open } else { close }
+    }
+}
+
+/// Whether a quote after `prev` opens rather than closes.
+fn opens_quote(prev: char) -> bool {
+    prev.is_whitespace()
+        || matches!(
+            prev,
+            '(' | '[' | '{' | '–' | '—' | '“' | '‘' | '„' | '‚' | '«' | '‹'
+        )
+}
+
+fn is_block(tag: &Tag<'_>) -> bool {
+    matches!(
+        tag,
+        Tag::Paragraph
+            | Tag::Heading { .. }
+            | Tag::Item
+            | Tag::TableCell
+            | Tag::BlockQuote
+            | Tag::Callout(_)
+            | Tag::Summary
+            | Tag::FigureCaption
+    )
+}
+
+fn is_block_end(end: &TagEnd) -> bool {
+    matches!(
+        end,
+        TagEnd::Paragraph
+            | TagEnd::Heading(_)
+            | TagEnd::Item
+            | TagEnd::TableCell
+            | TagEnd::BlockQuote
+            | TagEnd::Callout
+            | TagEnd::Summary
+            | TagEnd::FigureCaption
+    )
+}
+
+/// CJK letters plus the CJK symbol and full-width punctuation blocks.
+fn is_cjk_context(ch: Option<char>) -> bool {
+    match ch {
+        Some(ch) => {
+            Script::of(ch).is_some_and(Script::is_cjk)
+                || matches!(ch as u32, 0x3000..=0x303F | 0xFF01..=0xFF60)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_smart_punctuation() {
+        let cases = [
+            (
+                QuoteStyle::English,
+                "\"Don't\" -- she said --- 'it's the '90s'...",
+                "“Don’t” – she said — ‘it’s the ’90s’…",
+            ),
+            (
+                QuoteStyle::German,
+                "Er sagte \"ja\" ('vielleicht').",
+                "Er sagte „ja“ (‚vielleicht‘).",
+            ),
+            (
+                QuoteStyle::French,
+                "Il a dit \"oui\".",
+                "Il a dit «\u{a0}oui\u{a0}».",
+            ),
+        ];
+
+        for (style, input, expected) in cases {
+            let mut punctuator = SmartPunctuator::new(SmartPunctuation {
+                quotes: Some(style),
+                ..SmartPunctuation::default()
+            });
+            assert_eq!(punctuator.apply(text(input)), text(expected), "{style:?}");
+        }
+    }
+
+    #[test]
+    fn pairs_smart_quotes_across_events() {
+        let mut punctuator = SmartPunctuator::new(SmartPunctuation::default());
+        let events = vec![
+            Event::Start(Tag::Paragraph),
+            text("a \""),
+            Event::Start(Tag::Emphasis),
+            text("b"),
+            Event::End(TagEnd::Emphasis),
+            text("\" "),
+            Event::Code(Cow::Borrowed("x--y")),
+            Event::End(TagEnd::Paragraph),
+        ];
+        let output: Vec<_> = events.into_iter().map(|e| punctuator.apply(e)).collect();
+        assert_eq!(output[1], text("a “"));
+        assert_eq!(output[5], text("” "));
+        assert_eq!(output[6], Event::Code(Cow::Borrowed("x--y")));
+    }
+
+    fn run(options: CjkPunctuation, events: Vec<Event<'static>>) -> Vec<Event<'static>> {
+        let mut punctuator = CjkPunctuator::new(options);
+        events.into_iter().map(|e| punctuator.apply(e)).collect()
+    }
+
+    fn text(value: &'static str) -> Event<'static> {
+        Event::Text(Cow::Borrowed(value))
+    }
+
+    #[test]
+    fn converts_quotes_and_ellipsis_by_locale() {
+        let cases = [
+            (
+                CjkLocale::Japanese,
+                "彼は\"はい\"と言った...",
+                "彼は「はい」と言った……",
+            ),
+            (CjkLocale::ChineseSimplified, "他说\"好\"...", "他说“好”……"),
+            (
+                CjkLocale::Japanese,
+                "これは'引用'です",
+                "これは『引用』です",
+            ),
+        ];
+
+        for (locale, input, expected) in cases {
+            let options = CjkPunctuation {
+                locale,
+                ..CjkPunctuation::default()
+            };
+            assert_eq!(run(options, vec![text(input)]), vec![text(expected)]);
+        }
+    }
+
+    #[test]
+    fn leaves_western_context_and_code_untouched() {
+        let events = vec![
+            text("He said \"hi\"... and left. 日本語"),
+            Event::Start(Tag::CodeBlock(crate::event::CodeBlockKind::Indented)),
+            text("\"引用\"..."),
+            Event::End(TagEnd::CodeBlock),
+        ];
+        assert_eq!(run(CjkPunctuation::default(), events.clone()), events);
+    }
+
+    #[test]
+    fn pairs_quotes_across_events_within_a_block() {
+        let events = vec![
+            Event::Start(Tag::Paragraph),
+            text("彼は\""),
+            Event::Start(Tag::Emphasis),
+            text("はい"),
+            Event::End(TagEnd::Emphasis),
+            text("\"と"),
+            Event::End(TagEnd::Paragraph),
+        ];
+        let output = run(CjkPunctuation::default(), events);
+        assert_eq!(output[1], text("彼は「"));
+        assert_eq!(output[5], text("」と"));
+    }
+}
@@ -0,0 +1,58 @@
+//! Source positions attached to extracted blocks and diagnostics.
+
+use std::ops::Range;
+
+use markdown::unist::Position;
+
+/// A region of the Markdown source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SourceSpan {
+    /// Byte offset of the first character.
+    pub start: usize,
+    /// Byte offset just past the last character.
+    pub end: usize,
+    /// 1-based line of the first character.
+    pub start_line: usize,
+    /// 1-based line of the last character.
+    pub end_line: usize,
+}
+
+impl SourceSpan {
+    /// Returns the spanned slice of `source`, or `None` if the span does not fit it.
+    pub fn slice<'s>(&self, source: &'s str) -> Option<&'s str> {
+        source.get(self.start..self.end)
+    }
+
+    /// Span of the bytes `range` of `source`, with the lines counted from the start.
+    pub(crate) fn from_range(source: &str, range: Range<usize>) -> Self {
+        let line_at = |offset: usize| {
+            source.as_bytes()[..offset.min(source.len())]
+                .iter()
+                .filter(|&&byte| byte == b'\n')
+                .count()
+                + 1
+        };
+        SourceSpan {
+            start: range.start,
+            end: range.end,
+            start_line: line_at(range.start),
+            end_line: line_at(range.end.saturating_sub(1).max(range.start)),
+        }
+    }
+
+    /// Whether `offset` falls inside this span.
+    pub fn contains(&self, offset: usize) -> bool {
+        (self.start..self.end).contains(&offset)
+    }
+}
+
+impl From<&Position> for SourceSpan {
+    fn from(position: &Position) -> Self {
+        SourceSpan {
+            start: position.start.offset,
+            end: position.end.offset,
+            start_line: position.start.line,
+            end_line: position.end.line,
+        }
+    }
+}
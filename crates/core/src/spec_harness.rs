@@ -0,0 +1,182 @@
+//! CommonMark spec conformance for both parser backends.
+//!
+//! Every example of `fixtures/commonmark/spec.json` (CommonMark 0.31.2) is parsed by each
+//! [`ParserBackend`] and rendered by [`HtmlRenderer`], then compared with the spec's HTML after
+//! normalizing differences the spec does not care about (whitespace between tags, self-closing
+//! slashes, generated heading ids). Examples that fail today are listed per backend in
+//! `known-failures.json`, so the test fails on regressions and on examples that start passing;
+//! set `MARKFLOW_BLESS=1` to rewrite that list after checking the change.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::MarkdownStream;
+use crate::backend::{ParserBackend, SyntaxOptions};
+use crate::html_renderer::HtmlOptions;
+
+/// One example of the spec.
+#[derive(Deserialize)]
+struct SpecCase {
+    example: u32,
+    markdown: String,
+    html: String,
+}
+
+/// Pass and fail counts of one backend.
+struct SpecReport {
+    passed: usize,
+    failed: BTreeSet<u32>,
+}
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../fixtures/commonmark")
+}
+
+/// Plain CommonMark: the extensions that change how spec examples parse are off.
+fn commonmark_syntax() -> SyntaxOptions {
+    SyntaxOptions {
+        autolinks: false,
+        math: false,
+        footnotes: false,
+        frontmatter: false,
+        ..SyntaxOptions::default()
+    }
+}
+
+fn run(backend: ParserBackend, cases: &[SpecCase]) -> SpecReport {
+    let syntax = commonmark_syntax();
+    let mut report = SpecReport {
+        passed: 0,
+        failed: BTreeSet::new(),
+    };
+    for case in cases {
+        let html = backend
+            .events(&case.markdown, &syntax)
+            .ok()
+            .and_then(|events| {
+                events
+                    .stream_to_writer_with_options(Vec::new(), &HtmlOptions::default())
+                    .ok()
+            })
+            .and_then(|html| String::from_utf8(html).ok());
+        if html.is_some_and(|html| normalize(&html) == normalize(&case.html)) {
+            report.passed += 1;
+        } else {
+            report.failed.insert(case.example);
+        }
+    }
+    report
+}
+
+/// `html` without the whitespace between tags, the `/` of self-closing tags, heading ids, and
+/// quotes escaped in text.
+fn normalize(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        push_text(&mut out, &rest[..start]);
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let mut tag = &rest[start..start + len + 1];
+        let stripped;
+        if let Some(inner) = tag.strip_suffix(" />") {
+            stripped = format!("{inner}>");
+            tag = &stripped;
+        }
+        if is_heading_tag(tag) {
+            out.push_str(&tag[..3]);
+            out.push('>');
+        } else {
+            out.push_str(tag);
+        }
+        rest = &rest[start + len + 1..];
+        // Newlines between block tags are formatting, not content.
+        if rest.starts_with('\n') && rest[1..].starts_with('<') && !out.ends_with("<code>") {
+            rest = &rest[1..];
+        }
+    }
+    push_text(&mut out, rest);
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// Text between tags, with `"` and `'` unescaped: escaping them is optional outside attributes.
+fn push_text(out: &mut String, text: &str) {
+    out.push_str(&text.replace("&quot;", "\"").replace("&#39;", "'"));
+}
+
+fn is_heading_tag(tag: &str) -> bool {
+    let bytes = tag.as_bytes();
+    bytes.len() > 3
+        && bytes[1] == b'h'
+        && (b'1'..=b'6').contains(&bytes[2])
+        && matches!(bytes[3], b' ' | b'>')
+}
+
+#[test]
+fn commonmark_spec_conformance() {
+    let dir = fixtures();
+    let cases: Vec<SpecCase> =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("spec.json")).unwrap()).unwrap();
+    assert!(!cases.is_empty(), "no examples in {}", dir.display());
+    let known_path = dir.join("known-failures.json");
+    let known: BTreeMap<String, BTreeSet<u32>> = std::fs::read_to_string(&known_path)
+        .map(|json| serde_json::from_str(&json).unwrap())
+        .unwrap_or_default();
+    let bless = std::env::var_os("MARKFLOW_BLESS").is_some();
+
+    let mut failures = BTreeMap::new();
+    for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+        let name = format!("{backend:?}");
+        let report = run(backend, &cases);
+        eprintln!(
+            "{name}: {} of {} CommonMark examples pass, {} fail",
+            report.passed,
+            cases.len(),
+            report.failed.len()
+        );
+        if !bless {
+            let expected = known.get(&name).cloned().unwrap_or_default();
+            let regressed: Vec<_> = report.failed.difference(&expected).collect();
+            let fixed: Vec<_> = expected.difference(&report.failed).collect();
+            assert!(
+                regressed.is_empty(),
+                "{name}: examples that used to pass now fail: {regressed:?}"
+            );
+            assert!(
+                fixed.is_empty(),
+                "{name}: examples now pass, rerun with MARKFLOW_BLESS=1: {fixed:?}"
+            );
+        }
+        failures.insert(name, report.failed);
+    }
+    if bless {
+        let json = serde_json::to_string_pretty(&failures).unwrap();
+        std::fs::write(known_path, json + "\n").unwrap();
+    }
+}
+
+#[test]
+fn normalizes_formatting_only_differences() {
+    let cases = [
+        (
+            "<h2 id=\"foo\">Foo</h2>\n<ul>\n<li>a<br /></li>\n</ul>\n",
+            "<h2>Foo</h2><ul><li>a<br></li></ul>",
+        ),
+        (
+            "<pre><code>\n<b>\n</code></pre>",
+            "<pre><code>\n<b></code></pre>",
+        ),
+        ("<p>&quot;it&#39;s&quot;</p>", "<p>\"it's\"</p>"),
+        (
+            "<a title=\"&quot;\">&amp;</a>",
+            "<a title=\"&quot;\">&amp;</a>",
+        ),
+    ];
+    for (html, expected) in cases {
+        assert_eq!(normalize(html), expected, "{html:?}");
+    }
+}
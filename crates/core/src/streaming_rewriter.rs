@@ -1,28 +1,316 @@
 //! Streaming HTML rewriter glue that feeds markdown HTML into lol_html without buffering.
 
-use lol_html::errors::RewritingError;
-use lol_html::{HtmlRewriter, OutputSink, Selector, Settings, element};
+use html_escape::{decode_html_entities, encode_double_quoted_attribute, encode_text};
+pub use lol_html::HandlerResult;
+use lol_html::errors::{AttributeNameError, RewritingError};
+pub use lol_html::html_content::Element;
+use lol_html::html_content::{Comment, ContentType, TextChunk, TextType};
+use lol_html::{
+    DocumentContentHandlers, ElementContentHandlers, HtmlRewriter, OutputSink, Selector, Settings,
+};
 use std::borrow::Cow;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::io::{self, Write};
+use std::marker::PhantomData;
 use std::rc::Rc;
+use std::sync::{Arc, LazyLock};
 
-/// Configuration flags that control how the streaming rewriter manipulates HTML.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct RewriteOptions {
-    /// When enabled, missing `loading` attributes on `<img>` tags are defaulted to `lazy`.
-    pub enforce_img_loading_lazy: bool,
+use crate::html_renderer::is_name;
+use crate::rewrite_options::link_host;
+pub use crate::rewrite_options::{
+    ChunkPolicy, ExternalLinks, InternalLink, LinkHardening, PictureSources, RewriteAction,
+    RewriteOptions, RewriteRule, RewriteStats, RewriteThroughput, SanitizePolicy, TextRewrite,
+    UrlRewriter,
+};
+use crate::smart_punct::SmartPunctuator;
+use crate::urls::{self, BlockedDataUri, DataUriPolicy};
+
+/// Selectors are parsed once per process rather than once per rewriter.
+static IMG_SELECTOR: LazyLock<Selector> = LazyLock::new(|| parse_selector("img"));
+static IFRAME_SELECTOR: LazyLock<Selector> = LazyLock::new(|| parse_selector("iframe"));
+static VIDEO_SELECTOR: LazyLock<Selector> = LazyLock::new(|| parse_selector("video"));
+static EXTERNAL_LINK_SELECTOR: LazyLock<Selector> = LazyLock::new(|| parse_selector("a[href]"));
+static HEADING_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| parse_selector("h1, h2, h3, h4, h5, h6"));
+static PICTURE_SELECTOR: LazyLock<Selector> = LazyLock::new(|| parse_selector("picture"));
+static URL_SELECTOR: LazyLock<Selector> = LazyLock::new(|| parse_selector("a[href], img[src]"));
+static ANY_SELECTOR: LazyLock<Selector> = LazyLock::new(|| parse_selector("*"));
+static CODE_SELECTOR: LazyLock<Selector> = LazyLock::new(|| parse_selector("pre, code"));
+static SCRIPT_STYLE_SELECTOR: LazyLock<Selector> =
+    LazyLock::new(|| parse_selector("script, style"));
+
+/// Raw-text and RCDATA elements. Their content is text here but markup once unwrapped, so
+/// sanitizing removes them with their content when the policy does not allow them.
+const RAW_CONTENT_ELEMENTS: [&str; 10] = [
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+    "script",
+    "style",
+    "textarea",
+    "title",
+    "xmp",
+];
+
+/// Attributes [`SanitizePolicy::allowed_schemes`] applies to; `srcset` is checked per candidate.
+const URL_ATTRIBUTES: [&str; 10] = [
+    "action",
+    "background",
+    "cite",
+    "data",
+    "formaction",
+    "href",
+    "poster",
+    "src",
+    "srcset",
+    "xlink:href",
+];
+
+fn parse_selector(selector: &str) -> Selector {
+    selector.parse().expect("built-in selector is valid")
+}
+
+/// Parses the built-in selectors ahead of the first rewrite.
+pub(crate) fn prewarm() {
+    LazyLock::force(&IMG_SELECTOR);
+    LazyLock::force(&IFRAME_SELECTOR);
+    LazyLock::force(&VIDEO_SELECTOR);
+    LazyLock::force(&EXTERNAL_LINK_SELECTOR);
+    LazyLock::force(&HEADING_SELECTOR);
+    LazyLock::force(&PICTURE_SELECTOR);
+    LazyLock::force(&URL_SELECTOR);
+    LazyLock::force(&ANY_SELECTOR);
+    LazyLock::force(&CODE_SELECTOR);
+    LazyLock::force(&SCRIPT_STYLE_SELECTOR);
+}
+
+/// Elements without an end tag.
+const VOID_ELEMENTS: [&[u8]; 13] = [
+    b"area", b"base", b"br", b"col", b"embed", b"hr", b"img", b"input", b"link", b"meta",
+    b"source", b"track", b"wbr",
+];
+
+/// Elements whose text may contain `<` that is not markup.
+const RAW_TEXT_ELEMENTS: [&[u8]; 4] = [b"script", b"style", b"textarea", b"title"];
+
+/// Elements whose end marks a block boundary for [`ChunkPolicy::flush_on_block_end`].
+const BLOCK_ELEMENTS: [&str; 23] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+impl ChunkPolicy {
+    fn is_passthrough(&self) -> bool {
+        *self == ChunkPolicy::default()
+    }
+
+    /// Length of the prefix of `pending` to write now, given that bytes from `scan_from` on
+    /// arrived with the latest chunk.
+    fn cut(&self, pending: &mut Pending, scan_from: usize) -> Option<usize> {
+        if !self.flush_on_block_end {
+            return (pending.bytes.len() >= self.min_bytes).then_some(pending.bytes.len());
+        }
+        if self.top_level_only {
+            pending.nesting.scan(&pending.bytes);
+            return pending
+                .nesting
+                .boundary
+                .filter(|&end| end >= self.min_bytes);
+        }
+        last_block_end(&pending.bytes, scan_from.saturating_sub(1))
+            .filter(|&end| end >= self.min_bytes)
+    }
+}
+
+/// Output held back by the chunk policy.
+#[derive(Debug, Default)]
+struct Pending {
+    bytes: Vec<u8>,
+    /// How deeply `bytes` nests, for [`ChunkPolicy::top_level_only`].
+    nesting: Nesting,
+}
+
+impl Pending {
+    /// Removes the first `len` bytes, which were written.
+    fn consume(&mut self, len: usize) {
+        self.bytes.drain(..len);
+        self.nesting.scanned = self.nesting.scanned.saturating_sub(len);
+        self.nesting.boundary = None;
+    }
+}
+
+/// Element nesting of held-back output, scanned tag by tag as it arrives.
+#[derive(Debug, Default)]
+struct Nesting {
+    /// Elements open at `scanned`.
+    depth: usize,
+    /// Bytes scanned so far; a tag that has not fully arrived is scanned with the next chunk.
+    scanned: usize,
+    /// End of the last `</block>\n` or `<hr />\n` outside any other element.
+    boundary: Option<usize>,
+}
+
+impl Nesting {
+    fn scan(&mut self, html: &[u8]) {
+        while let Some(offset) = html[self.scanned..].iter().position(|&byte| byte == b'<') {
+            let lt = self.scanned + offset;
+            let Some(next) = self.tag(html, lt) else {
+                self.scanned = lt;
+                return;
+            };
+            self.scanned = next;
+        }
+        self.scanned = html.len();
+    }
+
+    /// Scans the markup at `lt`, returning where scanning continues, or `None` when the markup
+    /// has not fully arrived.
+    fn tag(&mut self, html: &[u8], lt: usize) -> Option<usize> {
+        let rest = &html[lt + 1..];
+        if rest.starts_with(b"!--") {
+            let end = rest.windows(3).position(|window| window == b"-->")?;
+            return Some(lt + 1 + end + 3);
+        }
+        let (closing, body) = match rest.strip_prefix(b"/") {
+            Some(body) => (true, body),
+            None => (false, rest),
+        };
+        if !body.first().is_some_and(|byte| byte.is_ascii_alphabetic()) {
+            // `<!doctype>`, `<?…>`, or a stray `<` in text.
+            return match body.first() {
+                Some(b'!' | b'?') => Some(lt + 1 + rest.iter().position(|&byte| byte == b'>')? + 1),
+                Some(_) => Some(lt + 1),
+                None => None,
+            };
+        }
+        let gt = lt + 1 + tag_end(rest)?;
+        let name_len = body
+            .iter()
+            .position(|byte| !byte.is_ascii_alphanumeric())
+            .unwrap_or(body.len());
+        let name = body[..name_len].to_ascii_lowercase();
+        let is_block = BLOCK_ELEMENTS
+            .iter()
+            .any(|block| block.as_bytes() == name.as_slice());
+        let self_closing = html[gt - 1] == b'/' || VOID_ELEMENTS.contains(&name.as_slice());
+
+        if closing {
+            self.depth = self.depth.saturating_sub(1);
+        } else if !self_closing {
+            self.depth += 1;
+            if RAW_TEXT_ELEMENTS.contains(&name.as_slice()) {
+                // Skip to the end tag so `<` in scripts and styles is not taken for markup.
+                let mut end_tag = b"</".to_vec();
+                end_tag.extend_from_slice(&name);
+                let close = html[gt..]
+                    .windows(end_tag.len())
+                    .position(|window| window.eq_ignore_ascii_case(&end_tag));
+                return match close {
+                    Some(close) => Some(gt + close),
+                    None => {
+                        self.depth -= 1;
+                        None
+                    }
+                };
+            }
+        }
+        if self.depth == 0 && is_block && (closing || self_closing) {
+            match html.get(gt + 1) {
+                Some(b'\n') => self.boundary = Some(gt + 2),
+                Some(_) => {}
+                // The newline that ends the block has not arrived yet.
+                None => {
+                    if closing {
+                        self.depth += 1;
+                    }
+                    return None;
+                }
+            }
+        }
+        Some(gt + 1)
+    }
+}
+
+/// Index of the `>` ending the tag that starts `rest`, skipping quoted attribute values.
+fn tag_end(rest: &[u8]) -> Option<usize> {
+    let mut quote = None;
+    for (idx, &byte) in rest.iter().enumerate() {
+        match (quote, byte) {
+            (None, b'"' | b'\'') => quote = Some(byte),
+            (Some(open), _) if byte == open => quote = None,
+            (None, b'>') => return Some(idx),
+            _ => {}
+        }
+    }
+    None
 }
 
-impl Default for RewriteOptions {
-    fn default() -> Self {
-        RewriteOptions {
-            enforce_img_loading_lazy: true,
+/// End of the last `</block>\n` or `<hr />\n` in `html` whose `>` is at or after `from`.
+fn last_block_end(html: &[u8], from: usize) -> Option<usize> {
+    let mut search_end = html.len();
+    while search_end >= from + 2 {
+        let gt = from
+            + html[from..search_end]
+                .windows(2)
+                .rposition(|pair| pair == b">\n")?;
+        let lt = html[..gt].iter().rposition(|&byte| byte == b'<')?;
+        let tag = &html[lt + 1..gt];
+        let (closing, name) = match tag.strip_prefix(b"/") {
+            Some(name) => (true, name),
+            None => (tag.ends_with(b"/"), tag),
+        };
+        let name_len = name
+            .iter()
+            .position(|byte| !byte.is_ascii_alphanumeric())
+            .unwrap_or(name.len());
+        let name = &name[..name_len];
+        if closing
+            && BLOCK_ELEMENTS
+                .iter()
+                .any(|block| block.as_bytes().eq_ignore_ascii_case(name))
+        {
+            return Some(gt + 2);
         }
+        search_end = gt + 1;
     }
+    None
 }
 
-/// Implements [`Write`] so the streaming events API (via the `MarkdownStream` trait) can push raw HTML directly into lol_html.
+impl LinkHardening {
+    fn rel_tokens(self) -> &'static [&'static str] {
+        match self {
+            LinkHardening::Off => &[],
+            LinkHardening::Trusted => &["noopener", "noreferrer"],
+            LinkHardening::UserContent => &["nofollow", "ugc", "noopener", "noreferrer"],
+        }
+    }
+}
+
+/// Implements [`Write`] so the streaming events API (via the `MarkdownStream` trait) can push raw
+/// HTML directly into lol_html.
 ///
 /// Internally we share the destination writer through a single `Rc<RefCell<Option<W>>>`, which is
 /// the only heap allocation required to satisfy lol_html's `OutputSink` trait without buffering
@@ -31,44 +319,309 @@ pub struct StreamingRewriter<W: Write> {
     rewriter: Option<HtmlRewriter<'static, OutputProxy<W>>>,
     target: Rc<RefCell<Option<W>>>,
     sink_error: Rc<RefCell<Option<io::Error>>>,
+    throughput: Rc<Cell<RewriteThroughput>>,
+    /// Output held back by the chunk policy.
+    pending: Rc<RefCell<Pending>>,
+    /// Image sources emptied by [`RewriteOptions::data_uri_images`].
+    blocked_data_uris: Rc<RefCell<Vec<BlockedDataUri>>>,
+    /// Links recorded by [`RewriteOptions::collect_internal_links`].
+    internal_links: Rc<RefCell<Vec<InternalLink>>>,
+    /// Elements matched by each of [`RewriteOptions::rules`].
+    rule_matches: Rc<RefCell<Vec<u64>>>,
+}
+
+/// Low-quality stand-in shown while an image loads, from the hook passed to
+/// [`StreamingRewriter::with_image_placeholders`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImagePlaceholder {
+    /// A BlurHash string, written as `data-placeholder` for client script to decode.
+    Blurhash(String),
+    /// A tiny inline image (`data:image/webp;base64,…`), written as the `<img>`'s CSS
+    /// `background-image` so it shows without script.
+    DataUri(String),
+}
+
+/// Maps an image source to its placeholder, if it has one.
+type PlaceholderFn = Box<dyn FnMut(&str) -> Option<ImagePlaceholder>>;
+
+/// Configures a [`StreamingRewriter`] with custom element handlers, from
+/// [`StreamingRewriter::builder`].
+pub struct StreamingRewriterBuilder<W> {
+    options: RewriteOptions,
+    /// `options` compiled by a [`RewriterFactory`].
+    compiled: Option<Arc<CompiledSelectors>>,
+    placeholders: Option<PlaceholderFn>,
+    handlers: Vec<(Cow<'static, Selector>, ElementContentHandlers<'static>)>,
+    /// First selector passed to [`on`](Self::on) that did not parse.
+    error: Option<io::Error>,
+    writer: PhantomData<fn() -> W>,
+}
+
+impl<W: Write> StreamingRewriterBuilder<W> {
+    /// Rewrite options for the built-in rules; the default is [`RewriteOptions::default`].
+    pub fn options(mut self, options: RewriteOptions) -> Self {
+        self.options = options;
+        self.compiled = None;
+        self
+    }
+
+    /// Gives every `<img>` the placeholder `placeholders` returns for its `src`, as
+    /// [`StreamingRewriter::with_image_placeholders`] does.
+    pub fn image_placeholders<F>(mut self, placeholders: F) -> Self
+    where
+        F: FnMut(&str) -> Option<ImagePlaceholder> + 'static,
+    {
+        self.placeholders = Some(Box::new(placeholders));
+        self
+    }
+
+    /// Calls `handler` with every element `selector` matches, after the built-in rules and
+    /// [`RewriteOptions::rules`] ran. An invalid selector fails the first write.
+    pub fn on<F>(mut self, selector: &str, handler: F) -> Self
+    where
+        F: FnMut(&mut Element<'_, '_>) -> HandlerResult + 'static,
+    {
+        match selector.parse::<Selector>() {
+            Ok(parsed) => self.handlers.push((
+                Cow::Owned(parsed),
+                ElementContentHandlers::default().element(handler),
+            )),
+            Err(err) => {
+                self.error.get_or_insert_with(|| {
+                    invalid_rule(format!("invalid handler selector `{selector}`: {err}"))
+                });
+            }
+        }
+        self
+    }
+
+    /// Creates the rewriter, forwarding its output into `writer`.
+    pub fn build(self, writer: W) -> StreamingRewriter<W> {
+        StreamingRewriter::build(writer, self)
+    }
+}
+
+/// Compiles [`RewriteOptions`] once and stamps out a [`StreamingRewriter`] per document, for site
+/// generators rendering many files with the same options. Clones share the compiled selectors.
+#[derive(Debug, Clone)]
+pub struct RewriterFactory {
+    options: RewriteOptions,
+    compiled: Arc<CompiledSelectors>,
+}
+
+impl RewriterFactory {
+    /// Compiles `options`, failing on the first rule whose selector or names lol_html cannot use.
+    pub fn new(options: RewriteOptions) -> io::Result<Self> {
+        let compiled = Arc::new(options.compile()?);
+        Ok(Self { options, compiled })
+    }
+
+    /// The options the factory was compiled from.
+    pub fn options(&self) -> &RewriteOptions {
+        &self.options
+    }
+
+    /// A rewriter for one document, forwarding its output into `writer`.
+    pub fn rewriter<W: Write>(&self, writer: W) -> StreamingRewriter<W> {
+        self.builder().build(writer)
+    }
+
+    /// A builder with the compiled options, for adding placeholders or custom handlers.
+    pub fn builder<W: Write>(&self) -> StreamingRewriterBuilder<W> {
+        StreamingRewriterBuilder {
+            options: self.options.clone(),
+            compiled: Some(Arc::clone(&self.compiled)),
+            ..StreamingRewriter::builder()
+        }
+    }
 }
 
 impl<W: Write> StreamingRewriter<W> {
     /// Creates a new streaming rewriter that forwards lol_html output into `writer` while applying
     /// the supplied rewrite options.
     pub fn new(writer: W, options: RewriteOptions) -> Self {
+        Self::builder().options(options).build(writer)
+    }
+
+    /// Starts configuring a rewriter with custom element handlers.
+    pub fn builder() -> StreamingRewriterBuilder<W> {
+        StreamingRewriterBuilder {
+            options: RewriteOptions::default(),
+            compiled: None,
+            placeholders: None,
+            handlers: Vec::new(),
+            error: None,
+            writer: PhantomData,
+        }
+    }
+
+    /// Like [`new`](Self::new), also giving every `<img>` the placeholder `placeholders` returns
+    /// for its `src` (after the other image rules ran), so sites can plug in their BlurHash or
+    /// LQIP pipeline.
+    pub fn with_image_placeholders<F>(writer: W, options: RewriteOptions, placeholders: F) -> Self
+    where
+        F: FnMut(&str) -> Option<ImagePlaceholder> + 'static,
+    {
+        Self::builder()
+            .options(options)
+            .image_placeholders(placeholders)
+            .build(writer)
+    }
+
+    fn build(writer: W, builder: StreamingRewriterBuilder<W>) -> Self {
+        let StreamingRewriterBuilder {
+            options,
+            compiled,
+            placeholders,
+            handlers,
+            error,
+            ..
+        } = builder;
+        let blocked_data_uris = Rc::new(RefCell::new(Vec::new()));
+        let internal_links = Rc::new(RefCell::new(Vec::new()));
+        let rule_matches = Rc::new(RefCell::new(vec![0; options.rules.len()]));
+        // A rule or selector that does not compile leaves the input unrewritten and fails the
+        // first write.
+        let compiled = match (error, compiled) {
+            (Some(err), _) => Err(err),
+            (None, Some(compiled)) => Ok(compiled),
+            (None, None) => options.compile().map(Arc::new),
+        };
+        let (mut settings, setup_error) = match compiled {
+            Ok(compiled) => {
+                let mut settings = options.as_settings(
+                    &compiled,
+                    &blocked_data_uris,
+                    &internal_links,
+                    &rule_matches,
+                );
+                settings.element_content_handlers.extend(handlers);
+                (settings, None)
+            }
+            Err(err) => (Settings::default(), Some(err)),
+        };
         let target = Rc::new(RefCell::new(Some(writer)));
-        let sink_error = Rc::new(RefCell::new(None));
-        let output_sink = OutputProxy::new(Rc::clone(&target), Rc::clone(&sink_error));
-        let settings = options.as_settings();
+        let sink_error = Rc::new(RefCell::new(setup_error));
+        let throughput = Rc::new(Cell::new(RewriteThroughput::default()));
+        let pending = Rc::new(RefCell::new(Pending::default()));
+        let output_sink = OutputProxy {
+            target: Rc::clone(&target),
+            sink_error: Rc::clone(&sink_error),
+            throughput: Rc::clone(&throughput),
+            pending: Rc::clone(&pending),
+            policy: options.chunk_policy,
+        };
+        if let Some(placeholders) = placeholders {
+            settings
+                .element_content_handlers
+                .push(placeholder_handler(placeholders));
+        }
         let rewriter = HtmlRewriter::new(settings, output_sink);
 
         Self {
             rewriter: Some(rewriter),
             target,
             sink_error,
+            throughput,
+            pending,
+            blocked_data_uris,
+            internal_links,
+            rule_matches,
+        }
+    }
+
+    /// Image sources removed by [`RewriteOptions::data_uri_images`] so far, in document order.
+    /// Call [`flush`](Write::flush) first to include images at the very end of the input.
+    pub fn blocked_data_uris(&self) -> Vec<BlockedDataUri> {
+        self.blocked_data_uris.borrow().clone()
+    }
+
+    /// Same-page anchors and relative links seen so far, in document order, when
+    /// [`RewriteOptions::collect_internal_links`] is set. Call [`flush`](Write::flush) first to
+    /// include links at the very end of the input.
+    pub fn internal_links(&self) -> Vec<InternalLink> {
+        self.internal_links.borrow().clone()
+    }
+
+    /// Hands everything rewritten so far to the underlying writer, whatever the chunk policy
+    /// holds back, and flushes it without ending the document. For boundaries the policy cannot
+    /// see, such as the end of each region a server-rendered page streams; lol_html may still
+    /// hold back a tag that has not fully arrived.
+    pub fn flush_chunk(&mut self) -> io::Result<()> {
+        Self::take_sink_error(&self.sink_error)?;
+        self.write_pending()?;
+        match self.target.borrow_mut().as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
         }
     }
 
     /// Consumes the rewriter, ensures lol_html has flushed, and returns the underlying writer.
-    pub fn into_inner(mut self) -> io::Result<W> {
+    pub fn into_inner(self) -> io::Result<W> {
+        self.into_inner_with_stats().map(|(writer, _)| writer)
+    }
+
+    /// Same as [`into_inner`](Self::into_inner), also returning the throughput counters.
+    pub fn into_inner_with_stats(mut self) -> io::Result<(W, RewriteThroughput)> {
         self.finalize_if_needed()?;
+        let throughput = self.throughput.get();
 
         let cell =
             Rc::try_unwrap(self.target).map_err(|_| io::Error::other("rewriter still borrowed"))?;
 
-        cell.into_inner()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "writer missing"))
+        let writer = cell
+            .into_inner()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "writer missing"))?;
+        Ok((writer, throughput))
+    }
+
+    /// Same as [`into_inner`](Self::into_inner), also returning every link recorded by
+    /// [`RewriteOptions::collect_internal_links`], so callers can check anchors against the
+    /// document's heading ids and relative links against their pages.
+    pub fn into_inner_with_links(mut self) -> io::Result<(W, Vec<InternalLink>)> {
+        self.finalize_if_needed()?;
+        let links = self.internal_links.take();
+        Ok((self.into_inner()?, links))
+    }
+
+    /// Same as [`into_inner`](Self::into_inner), also returning the byte counts and how many
+    /// elements each rewrite rule matched.
+    pub fn into_parts(mut self) -> io::Result<(W, RewriteStats)> {
+        self.finalize_if_needed()?;
+        let elements_matched_per_rule = self.rule_matches.take();
+        let (writer, throughput) = self.into_inner_with_stats()?;
+        let stats = RewriteStats {
+            bytes_in: throughput.bytes_in,
+            bytes_out: throughput.bytes_out,
+            elements_matched_per_rule,
+        };
+        Ok((writer, stats))
     }
 
     fn finalize_if_needed(&mut self) -> io::Result<()> {
         if let Some(rewriter) = self.rewriter.take() {
             rewriter.end().map_err(rewriting_error_to_io)?;
+            Self::take_sink_error(&self.sink_error)?;
+            self.write_pending()?;
         }
 
         Self::take_sink_error(&self.sink_error)
     }
 
+    /// Writes everything the chunk policy holds back as one chunk.
+    fn write_pending(&mut self) -> io::Result<()> {
+        let mut pending = self.pending.borrow_mut();
+        let len = pending.bytes.len();
+        if len > 0
+            && let Some(writer) = self.target.borrow_mut().as_mut()
+        {
+            writer.write_all(&pending.bytes)?;
+            record_output(&self.throughput, len);
+        }
+        pending.consume(len);
+        Ok(())
+    }
+
     fn take_sink_error(cell: &Rc<RefCell<Option<io::Error>>>) -> io::Result<()> {
         if let Some(err) = cell.borrow_mut().take() {
             Err(err)
@@ -78,6 +631,18 @@ impl<W: Write> StreamingRewriter<W> {
     }
 }
 
+impl StreamingRewriter<Vec<u8>> {
+    /// Takes the output written so far, leaving the buffer empty for more.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn take_output(&mut self) -> Vec<u8> {
+        self.target
+            .borrow_mut()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
 impl<W: Write> Write for StreamingRewriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         let rewriter = self
@@ -87,6 +652,10 @@ impl<W: Write> Write for StreamingRewriter<W> {
 
         rewriter.write(buf).map_err(rewriting_error_to_io)?;
         Self::take_sink_error(&self.sink_error)?;
+        let mut throughput = self.throughput.get();
+        throughput.bytes_in += buf.len() as u64;
+        throughput.chunks_in += 1;
+        self.throughput.set(throughput);
         Ok(buf.len())
     }
 
@@ -95,45 +664,623 @@ impl<W: Write> Write for StreamingRewriter<W> {
     }
 }
 
+/// The selectors of [`RewriteOptions`] that come from user input, parsed and checked once.
+#[derive(Debug)]
+struct CompiledSelectors {
+    /// Elements [`SanitizePolicy::remove_elements`] removes.
+    sanitize: Option<Selector>,
+    /// One per entry of [`RewriteOptions::rules`].
+    rules: Vec<Selector>,
+}
+
 impl RewriteOptions {
-    fn as_settings(&self) -> Settings<'static, 'static> {
+    /// Parses the user-supplied selectors, rejecting the first rule lol_html cannot use.
+    fn compile(&self) -> io::Result<CompiledSelectors> {
+        let sanitize = match &self.sanitize {
+            Some(policy) if !policy.remove_elements.is_empty() => {
+                for name in &policy.remove_elements {
+                    check_name("element", name)?;
+                }
+                Some(parse_selector(&policy.remove_elements.join(", ")))
+            }
+            _ => None,
+        };
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| {
+                check_action(&rule.action)?;
+                rule.selector.parse().map_err(|err| {
+                    invalid_rule(format!(
+                        "invalid rewrite rule selector `{}`: {err}",
+                        rule.selector
+                    ))
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(CompiledSelectors { sanitize, rules })
+    }
+
+    fn as_settings(
+        &self,
+        compiled: &CompiledSelectors,
+        blocked_data_uris: &Rc<RefCell<Vec<BlockedDataUri>>>,
+        internal_links: &Rc<RefCell<Vec<InternalLink>>>,
+        rule_matches: &Rc<RefCell<Vec<u64>>>,
+    ) -> Settings<'static, 'static> {
         let mut settings = Settings::default();
         let mut handlers = Vec::new();
 
+        if let Some(rewriter) = &self.rewrite_urls {
+            handlers.push(rewrite_urls_handler(rewriter.clone()));
+        }
+        if let Some(policy) = &self.sanitize {
+            handlers.extend(sanitize_handlers(policy, compiled.sanitize.as_ref()));
+        }
+        if !self.heading_ids.is_empty() {
+            handlers.push(heading_ids_handler(self.heading_ids.clone()));
+        }
+        if self.collect_internal_links {
+            handlers.push(internal_links_handler(Rc::clone(internal_links)));
+        }
         if self.enforce_img_loading_lazy {
-            handlers.push(lazy_img_handler());
+            handlers.push(default_attribute_handler(&IMG_SELECTOR, "loading", "lazy"));
+        }
+        if self.enforce_iframe_loading_lazy {
+            handlers.push(default_attribute_handler(
+                &IFRAME_SELECTOR,
+                "loading",
+                "lazy",
+            ));
+        }
+        if self.enforce_video_preload_none {
+            handlers.push(default_attribute_handler(
+                &VIDEO_SELECTOR,
+                "preload",
+                "none",
+            ));
+        }
+        if self.data_uri_images != DataUriPolicy::Allow {
+            handlers.push(data_uri_img_handler(
+                self.data_uri_images,
+                Rc::clone(blocked_data_uris),
+            ));
+        }
+        if let Some(picture) = &self.picture {
+            handlers.extend(picture_handlers(picture.clone()));
+        }
+        if self.harden_links != LinkHardening::Off {
+            handlers.push(harden_links_handler(self.harden_links.rel_tokens()));
+        }
+        if let Some(external_links) = &self.external_links {
+            handlers.push(external_links_handler(external_links.clone()));
+        }
+        if let Some(nonce) = &self.csp_nonce {
+            handlers.push(csp_nonce_handler(nonce.clone()));
+        }
+        for (index, (rule, selector)) in self.rules.iter().zip(&compiled.rules).enumerate() {
+            handlers.extend(rule_handlers(rule, selector, index, rule_matches));
         }
 
         settings.element_content_handlers = handlers;
+        if self.strip_comments {
+            settings.document_content_handlers =
+                vec![
+                    DocumentContentHandlers::default().comments(|comment: &mut Comment<'_>| {
+                        comment.remove();
+                        Ok(())
+                    }),
+                ];
+        }
         settings
     }
 }
 
-fn lazy_img_handler() -> (
-    Cow<'static, Selector>,
-    lol_html::ElementContentHandlers<'static>,
+fn rewrite_urls_handler(
+    rewriter: UrlRewriter,
+) -> (Cow<'static, Selector>, ElementContentHandlers<'static>) {
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        let attribute = if el.tag_name() == "img" {
+            "src"
+        } else {
+            "href"
+        };
+        if let Some(url) = el.get_attribute(attribute)
+            && let Some(rewritten) = rewriter.rewrite(&url)
+        {
+            el.set_attribute(attribute, &rewritten)?;
+        }
+        Ok(())
+    });
+    (Cow::Borrowed(&*URL_SELECTOR), handlers)
+}
+
+/// Gives each heading the next of `ids`, in document order.
+fn heading_ids_handler(
+    ids: Vec<Option<String>>,
+) -> (Cow<'static, Selector>, ElementContentHandlers<'static>) {
+    let mut ids = ids.into_iter();
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        if let Some(Some(id)) = ids.next() {
+            el.set_attribute("id", &id)?;
+        }
+        Ok(())
+    });
+    (Cow::Borrowed(&*HEADING_SELECTOR), handlers)
+}
+
+/// Sets `name` to `value` on matching elements that lack it.
+fn default_attribute_handler(
+    selector: &'static Selector,
+    name: &'static str,
+    value: &'static str,
+) -> (Cow<'static, Selector>, ElementContentHandlers<'static>) {
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        if el.get_attribute(name).is_none() {
+            el.set_attribute(name, value)?;
+        }
+
+        Ok(())
+    });
+    (Cow::Borrowed(selector), handlers)
+}
+
+fn data_uri_img_handler(
+    policy: DataUriPolicy,
+    blocked: Rc<RefCell<Vec<BlockedDataUri>>>,
+) -> (Cow<'static, Selector>, ElementContentHandlers<'static>) {
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        let violation = el
+            .get_attribute("src")
+            .and_then(|src| policy.violation(&src));
+        if let Some(violation) = violation {
+            el.set_attribute("src", "")?;
+            blocked.borrow_mut().push(violation);
+        }
+        Ok(())
+    });
+    (Cow::Borrowed(&*IMG_SELECTOR), handlers)
+}
+
+/// Wraps `<img>` in `<picture>` with a `<source>` per format of `sources`. Images already inside
+/// a `<picture>`, without a source, with a `data:` source, or in SVG (which has no raster
+/// variants) are left alone.
+fn picture_handlers(
+    sources: PictureSources,
+) -> [(Cow<'static, Selector>, ElementContentHandlers<'static>); 2] {
+    let open_pictures = Rc::new(Cell::new(0usize));
+    let depth = Rc::clone(&open_pictures);
+    let pictures = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        if let Some(handlers) = el.end_tag_handlers() {
+            depth.set(depth.get() + 1);
+            let depth = Rc::clone(&depth);
+            handlers.push(Box::new(move |_| {
+                depth.set(depth.get().saturating_sub(1));
+                Ok(())
+            }));
+        }
+        Ok(())
+    });
+    let images = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        if open_pictures.get() > 0 {
+            return Ok(());
+        }
+        let Some(src) = el.get_attribute("src") else {
+            return Ok(());
+        };
+        let path = src.split(['?', '#']).next().unwrap_or_default();
+        if path.is_empty()
+            || src.trim_start().to_ascii_lowercase().starts_with("data:")
+            || path.to_ascii_lowercase().ends_with(".svg")
+        {
+            return Ok(());
+        }
+
+        let mut html = String::from("<picture>");
+        for format in &sources.formats {
+            html.push_str("<source type=\"image/");
+            html.push_str(&encode_double_quoted_attribute(format));
+            html.push_str("\" srcset=\"");
+            html.push_str(&encode_double_quoted_attribute(
+                &sources.variant_url(&src, format),
+            ));
+            html.push_str("\" />");
+        }
+        el.before(&html, ContentType::Html);
+        el.after("</picture>", ContentType::Html);
+        Ok(())
+    });
+    [
+        (Cow::Borrowed(&*PICTURE_SELECTOR), pictures),
+        (Cow::Borrowed(&*IMG_SELECTOR), images),
+    ]
+}
+
+fn placeholder_handler(
+    mut placeholders: PlaceholderFn,
+) -> (Cow<'static, Selector>, ElementContentHandlers<'static>) {
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        let Some(src) = el.get_attribute("src").filter(|src| !src.is_empty()) else {
+            return Ok(());
+        };
+        match placeholders(&src) {
+            Some(ImagePlaceholder::Blurhash(hash)) => {
+                el.set_attribute("data-placeholder", &hash)?
+            }
+            // Quotes, backslashes, and line breaks could end the CSS `url()` early.
+            Some(ImagePlaceholder::DataUri(uri))
+                if !uri.contains(['\'', '"', '\\', '\n', '\r']) =>
+            {
+                let mut style = el.get_attribute("style").unwrap_or_default();
+                let existing = style.trim_end();
+                if !existing.is_empty() && !existing.ends_with(';') {
+                    style.truncate(existing.len());
+                    style.push(';');
+                }
+                style.push_str(&format!(
+                    "background-image:url('{uri}');background-size:cover"
+                ));
+                el.set_attribute("style", &style)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    });
+    (Cow::Borrowed(&*IMG_SELECTOR), handlers)
+}
+
+fn internal_links_handler(
+    links: Rc<RefCell<Vec<InternalLink>>>,
+) -> (Cow<'static, Selector>, ElementContentHandlers<'static>) {
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        if let Some(link) = el
+            .get_attribute("href")
+            .and_then(|href| InternalLink::from_href(&href))
+        {
+            links.borrow_mut().push(link);
+        }
+        Ok(())
+    });
+    (Cow::Borrowed(&*EXTERNAL_LINK_SELECTOR), handlers)
+}
+
+fn harden_links_handler(
+    tokens: &'static [&'static str],
+) -> (Cow<'static, Selector>, ElementContentHandlers<'static>) {
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        let is_external = el
+            .get_attribute("href")
+            .is_some_and(|href| link_host(&href).is_some());
+        if is_external {
+            merge_rel(el, tokens.iter().copied())?;
+        }
+        Ok(())
+    });
+    (Cow::Borrowed(&*EXTERNAL_LINK_SELECTOR), handlers)
+}
+
+fn external_links_handler(
+    policy: ExternalLinks,
+) -> (Cow<'static, Selector>, ElementContentHandlers<'static>) {
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        let is_external = el
+            .get_attribute("href")
+            .is_some_and(|href| policy.is_external(&href));
+        if !is_external {
+            return Ok(());
+        }
+        if policy.target_blank && el.get_attribute("target").is_none() {
+            el.set_attribute("target", "_blank")?;
+        }
+        merge_rel(el, policy.rel.iter().map(String::as_str))?;
+        Ok(())
+    });
+    (Cow::Borrowed(&*EXTERNAL_LINK_SELECTOR), handlers)
+}
+
+/// Removes the policy's elements and disallowed raw-text elements, replaces other elements it
+/// does not allow by their content, and strips attributes it does not allow and URLs with
+/// disallowed schemes from the rest.
+fn sanitize_handlers(
+    policy: &SanitizePolicy,
+    remove_elements: Option<&Selector>,
+) -> Vec<(Cow<'static, Selector>, ElementContentHandlers<'static>)> {
+    let mut handlers = Vec::new();
+    if let Some(selector) = remove_elements {
+        let remove = ElementContentHandlers::default().element(|el: &mut Element<'_, '_>| {
+            el.remove();
+            Ok(())
+        });
+        handlers.push((Cow::Owned(selector.clone()), remove));
+    }
+
+    let policy = policy.clone();
+    let strip = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        if el.removed() {
+            return Ok(());
+        }
+        let tag_name = el.tag_name();
+        if !policy.allows_element(&tag_name) {
+            if RAW_CONTENT_ELEMENTS.contains(&tag_name.as_str()) {
+                el.remove();
+            } else {
+                el.remove_and_keep_content();
+            }
+            return Ok(());
+        }
+        let is_img = tag_name == "img";
+        let unsafe_attributes: Vec<String> = el
+            .attributes()
+            .iter()
+            .map(|attribute| (attribute.name(), attribute.value()))
+            .filter(|(name, value)| {
+                if !policy.allows_attribute(name) {
+                    return true;
+                }
+                if !URL_ATTRIBUTES.contains(&name.as_str()) {
+                    return false;
+                }
+                // Values come back as written; browsers decode `java&#9;script:` first.
+                let value = decode_html_entities(value);
+                if name == "srcset" {
+                    return value.split(',').any(|candidate| {
+                        let url = candidate.split_whitespace().next().unwrap_or_default();
+                        !policy.allows_url(url)
+                    });
+                }
+                let is_data_image = is_img
+                    && name == "src"
+                    && urls::scheme(&value).is_some_and(|scheme| scheme == "data");
+                !is_data_image && !policy.allows_url(&value)
+            })
+            .map(|(name, _)| name)
+            .collect();
+        for name in unsafe_attributes {
+            el.remove_attribute(&name);
+        }
+        Ok(())
+    });
+    handlers.push((Cow::Borrowed(&*ANY_SELECTOR), strip));
+    handlers
+}
+
+/// Handlers for the rule at `index` of [`RewriteOptions::rules`], counting its matches into
+/// `matches`. The rule was checked by [`RewriteOptions::compile`].
+fn rule_handlers(
+    rule: &RewriteRule,
+    selector: &Selector,
+    index: usize,
+    matches: &Rc<RefCell<Vec<u64>>>,
+) -> Vec<(Cow<'static, Selector>, ElementContentHandlers<'static>)> {
+    let matches = Rc::clone(matches);
+    let count = ElementContentHandlers::default().element(move |_: &mut Element<'_, '_>| {
+        matches.borrow_mut()[index] += 1;
+        Ok(())
+    });
+    let count = (Cow::Owned(selector.clone()), count);
+    let handlers = ElementContentHandlers::default();
+    let handlers = match rule.action.clone() {
+        RewriteAction::SetAttribute { name, value } => {
+            handlers.element(move |el: &mut Element<'_, '_>| {
+                el.set_attribute(&name, &value)?;
+                Ok(())
+            })
+        }
+        RewriteAction::AddClass(class) => handlers.element(move |el: &mut Element<'_, '_>| {
+            let existing = el.get_attribute("class").unwrap_or_default();
+            if !existing.split_ascii_whitespace().any(|name| name == class) {
+                let classes = if existing.trim().is_empty() {
+                    class.clone()
+                } else {
+                    format!("{} {class}", existing.trim_end())
+                };
+                el.set_attribute("class", &classes)?;
+            }
+            Ok(())
+        }),
+        RewriteAction::Remove => handlers.element(|el: &mut Element<'_, '_>| {
+            el.remove();
+            Ok(())
+        }),
+        RewriteAction::Text(rewrite) => {
+            let (code, open_code) = code_depth_handler();
+            let mut rewrite = text_rewriter(rewrite);
+            let mut node = String::new();
+            let text = handlers.text(move |chunk: &mut TextChunk<'_>| {
+                if open_code.get() > 0 || chunk.text_type() != TextType::Data {
+                    return Ok(());
+                }
+                // A text node can arrive in several chunks; hold them back until it is whole.
+                node.push_str(chunk.as_str());
+                if !chunk.last_in_text_node() {
+                    chunk.remove();
+                    return Ok(());
+                }
+                let raw = std::mem::take(&mut node);
+                let decoded = decode_html_entities(&raw);
+                let rewritten = rewrite(&decoded);
+                if rewritten == decoded {
+                    chunk.replace(&raw, ContentType::Html);
+                } else {
+                    chunk.replace(&encode_text(&rewritten), ContentType::Html);
+                }
+                Ok(())
+            });
+            return vec![count, code, (Cow::Owned(selector.clone()), text)];
+        }
+        RewriteAction::Wrap { tag, attributes } => {
+            let mut open = format!("<{tag}");
+            for (name, value) in &attributes {
+                open.push_str(&format!(
+                    " {name}=\"{}\"",
+                    encode_double_quoted_attribute(value)
+                ));
+            }
+            open.push('>');
+            let close = format!("</{tag}>");
+            handlers.element(move |el: &mut Element<'_, '_>| {
+                el.before(&open, ContentType::Html);
+                el.after(&close, ContentType::Html);
+                Ok(())
+            })
+        }
+    };
+    vec![count, (Cow::Owned(selector.clone()), handlers)]
+}
+
+/// Rejects class, tag, and attribute names in `action` that would break the markup.
+fn check_action(action: &RewriteAction) -> io::Result<()> {
+    match action {
+        RewriteAction::SetAttribute { name, .. } => check_name("attribute", name),
+        RewriteAction::AddClass(class) => {
+            if class.is_empty() || class.contains(|ch: char| ch.is_ascii_whitespace()) {
+                return Err(invalid_rule(format!("invalid class `{class}`")));
+            }
+            Ok(())
+        }
+        RewriteAction::Wrap { tag, attributes } => {
+            check_name("tag", tag)?;
+            for (name, _) in attributes {
+                check_name("attribute", name)?;
+            }
+            Ok(())
+        }
+        RewriteAction::Remove | RewriteAction::Text(_) => Ok(()),
+    }
+}
+
+/// Counts the open `<pre>` and `<code>` elements, whose text stays as written.
+fn code_depth_handler() -> (
+    (Cow<'static, Selector>, ElementContentHandlers<'static>),
+    Rc<Cell<usize>>,
 ) {
-    element!("img", |el| {
-        if el.get_attribute("loading").is_none() {
-            el.set_attribute("loading", "lazy")?;
+    let open_code = Rc::new(Cell::new(0usize));
+    let depth = Rc::clone(&open_code);
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        if let Some(handlers) = el.end_tag_handlers() {
+            depth.set(depth.get() + 1);
+            let depth = Rc::clone(&depth);
+            handlers.push(Box::new(move |_| {
+                depth.set(depth.get().saturating_sub(1));
+                Ok(())
+            }));
+        }
+        Ok(())
+    });
+    ((Cow::Borrowed(&*CODE_SELECTOR), handlers), open_code)
+}
+
+/// The function a [`RewriteAction::Text`] rule applies to each decoded text node.
+fn text_rewriter(rewrite: TextRewrite) -> Box<dyn FnMut(&str) -> String> {
+    match rewrite {
+        TextRewrite::SmartPunctuation(options) => {
+            // Quotes pair across text nodes, as in `"<em>quoted</em>"`.
+            let mut punctuator = SmartPunctuator::new(options);
+            Box::new(move |text| punctuator.rewrite(Cow::Borrowed(text)).into_owned())
         }
+        TextRewrite::FrenchSpacing => Box::new(french_spacing),
+        TextRewrite::Replace { from, to } => Box::new(move |text| {
+            if from.is_empty() {
+                text.to_string()
+            } else {
+                text.replace(&from, &to)
+            }
+        }),
+    }
+}
+
+fn french_spacing(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut prev = None;
+    while let Some(ch) = chars.next() {
+        let spaced = match (ch, chars.peek()) {
+            (' ', Some(';' | '!' | '?')) => '\u{202f}',
+            (' ', Some(':' | '»')) => '\u{a0}',
+            (' ', _) if prev == Some('«') => '\u{a0}',
+            _ => ch,
+        };
+        out.push(spaced);
+        prev = Some(ch);
+    }
+    out
+}
+
+/// Accepts tag and attribute names made of ASCII letters, digits, `-`, and `_`, starting with a
+/// letter.
+fn check_name(kind: &str, name: &str) -> io::Result<()> {
+    if is_name(name) {
+        Ok(())
+    } else {
+        Err(invalid_rule(format!("invalid {kind} name `{name}`")))
+    }
+}
+
+fn invalid_rule(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
 
+fn csp_nonce_handler(nonce: String) -> (Cow<'static, Selector>, ElementContentHandlers<'static>) {
+    let handlers = ElementContentHandlers::default().element(move |el: &mut Element<'_, '_>| {
+        el.set_attribute("nonce", &nonce)?;
         Ok(())
-    })
+    });
+    (Cow::Borrowed(&*SCRIPT_STYLE_SELECTOR), handlers)
+}
+
+/// Adds the `tokens` an element's `rel` lacks, keeping the existing ones and their order.
+fn merge_rel<'t>(
+    el: &mut Element<'_, '_>,
+    tokens: impl Iterator<Item = &'t str>,
+) -> Result<(), AttributeNameError> {
+    let existing = el.get_attribute("rel").unwrap_or_default();
+    let mut rel = existing.clone();
+    for token in tokens {
+        if !rel
+            .split_ascii_whitespace()
+            .any(|present| present.eq_ignore_ascii_case(token))
+        {
+            if !rel.is_empty() {
+                rel.push(' ');
+            }
+            rel.push_str(token);
+        }
+    }
+    if rel != existing {
+        el.set_attribute("rel", &rel)?;
+    }
+    Ok(())
 }
 
 fn rewriting_error_to_io(err: RewritingError) -> io::Error {
     io::Error::other(err)
 }
 
+fn record_output(throughput: &Cell<RewriteThroughput>, len: usize) {
+    let mut stats = throughput.get();
+    stats.bytes_out += len as u64;
+    stats.chunks_out += 1;
+    throughput.set(stats);
+}
+
 struct OutputProxy<W: Write> {
     target: Rc<RefCell<Option<W>>>,
     sink_error: Rc<RefCell<Option<io::Error>>>,
+    throughput: Rc<Cell<RewriteThroughput>>,
+    pending: Rc<RefCell<Pending>>,
+    policy: ChunkPolicy,
 }
 
 impl<W: Write> OutputProxy<W> {
-    fn new(target: Rc<RefCell<Option<W>>>, sink_error: Rc<RefCell<Option<io::Error>>>) -> Self {
-        OutputProxy { target, sink_error }
+    fn emit(&self, chunk: &[u8]) {
+        let mut borrow = self.target.borrow_mut();
+
+        if let Some(writer) = borrow.as_mut() {
+            match writer.write_all(chunk) {
+                Ok(()) => record_output(&self.throughput, chunk.len()),
+                Err(err) => *self.sink_error.borrow_mut() = Some(err),
+            }
+        }
     }
 }
 
@@ -147,12 +1294,17 @@ impl<W: Write> OutputSink for OutputProxy<W> {
             return;
         }
 
-        let mut borrow = self.target.borrow_mut();
+        if self.policy.is_passthrough() {
+            self.emit(chunk);
+            return;
+        }
 
-        if let Some(writer) = borrow.as_mut()
-            && let Err(err) = writer.write_all(chunk)
-        {
-            *self.sink_error.borrow_mut() = Some(err);
+        let mut pending = self.pending.borrow_mut();
+        let scan_from = pending.bytes.len();
+        pending.bytes.extend_from_slice(chunk);
+        if let Some(cut) = self.policy.cut(&mut pending, scan_from) {
+            self.emit(&pending.bytes[..cut]);
+            pending.consume(cut);
         }
     }
 }
@@ -160,6 +1312,7 @@ impl<W: Write> OutputSink for OutputProxy<W> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::SmartPunctuation;
     use std::io::Write;
 
     #[test]
@@ -183,4 +1336,753 @@ mod tests {
 
         assert!(output.contains("loading=\"eager\""));
     }
+
+    #[test]
+    fn defers_iframes_and_videos_per_element_type() {
+        let input = r#"<iframe src="/a"></iframe><iframe src="/b" loading="eager"></iframe><video src="/c.mp4"></video><video src="/d.mp4" preload="auto"></video><img src="/e.png">"#;
+        let cases = [
+            (
+                (false, false),
+                r#"<iframe src="/a"></iframe><iframe src="/b" loading="eager"></iframe><video src="/c.mp4"></video><video src="/d.mp4" preload="auto"></video><img src="/e.png" loading="lazy">"#,
+            ),
+            (
+                (true, true),
+                r#"<iframe src="/a" loading="lazy"></iframe><iframe src="/b" loading="eager"></iframe><video src="/c.mp4" preload="none"></video><video src="/d.mp4" preload="auto"></video><img src="/e.png" loading="lazy">"#,
+            ),
+        ];
+
+        for ((iframes, videos), expected) in cases {
+            let options = RewriteOptions {
+                enforce_iframe_loading_lazy: iframes,
+                enforce_video_preload_none: videos,
+                ..RewriteOptions::default()
+            };
+            let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+            rewriter.write_all(input.as_bytes()).unwrap();
+            let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+            assert_eq!(output, expected, "{iframes} {videos}");
+        }
+    }
+
+    #[test]
+    fn adds_image_placeholders() {
+        let options = RewriteOptions {
+            enforce_img_loading_lazy: false,
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::with_image_placeholders(
+            Vec::new(),
+            options,
+            |src: &str| match src {
+                "/a.png" => Some(ImagePlaceholder::Blurhash("LEHV6nWB2yk8".to_string())),
+                "/b.png" => Some(ImagePlaceholder::DataUri(
+                    "data:image/webp;base64,UklG".to_string(),
+                )),
+                "/c.png" => Some(ImagePlaceholder::DataUri("x');color:red".to_string())),
+                _ => None,
+            },
+        );
+        rewriter
+            .write_all(
+                br#"<img src="/a.png"><img src="/b.png" style="width:50%"><img src="/c.png"><img src="/d.png">"#,
+            )
+            .unwrap();
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+
+        assert_eq!(
+            output,
+            r#"<img src="/a.png" data-placeholder="LEHV6nWB2yk8"><img src="/b.png" style="width:50%;background-image:url('data:image/webp;base64,UklG');background-size:cover"><img src="/c.png"><img src="/d.png">"#
+        );
+    }
+
+    #[test]
+    fn wraps_images_in_pictures() {
+        let options = RewriteOptions {
+            enforce_img_loading_lazy: false,
+            picture: Some(PictureSources::default()),
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+        rewriter
+            .write_all(
+                br#"<p><img src="/a/cat.png?v=2" alt="Cat"><img src="/logo.svg"><img src="data:image/png;base64,AA=="></p><picture><img src="/b.jpg"></picture>"#,
+            )
+            .unwrap();
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+
+        assert_eq!(
+            output,
+            r#"<p><picture><source type="image/avif" srcset="/a/cat.avif" /><source type="image/webp" srcset="/a/cat.webp" /><img src="/a/cat.png?v=2" alt="Cat"></picture><img src="/logo.svg"><img src="data:image/png;base64,AA=="></p><picture><img src="/b.jpg"></picture>"#
+        );
+
+        let sources = PictureSources {
+            formats: vec!["webp".to_string()],
+            url_template: "https://cdn.test/img?url={src}&fm={format}".to_string(),
+        };
+        assert_eq!(
+            sources.variant_url("/x.v1/photo", "webp"),
+            "https://cdn.test/img?url=/x.v1/photo&fm=webp"
+        );
+        assert_eq!(
+            PictureSources::default().variant_url("/x.v1/photo", "webp"),
+            "/x.v1/photo.webp"
+        );
+    }
+
+    #[test]
+    fn hardens_external_links_only() {
+        let input = r#"<a href="https://x.test">a</a><a href="//x.test" rel="me NoFollow">b</a><a href="/local">c</a>"#;
+        let cases = [
+            (
+                LinkHardening::UserContent,
+                r#"<a href="https://x.test" rel="nofollow ugc noopener noreferrer">a</a><a href="//x.test" rel="me NoFollow ugc noopener noreferrer">b</a><a href="/local">c</a>"#,
+            ),
+            (
+                LinkHardening::Trusted,
+                r#"<a href="https://x.test" rel="noopener noreferrer">a</a><a href="//x.test" rel="me NoFollow noopener noreferrer">b</a><a href="/local">c</a>"#,
+            ),
+            (LinkHardening::Off, input),
+        ];
+
+        for (harden_links, expected) in cases {
+            let options = RewriteOptions {
+                harden_links,
+                ..RewriteOptions::default()
+            };
+            let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+            rewriter.write_all(input.as_bytes()).unwrap();
+            let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+            assert_eq!(output, expected, "{harden_links:?}");
+        }
+    }
+
+    #[test]
+    fn finds_hosts_the_way_browsers_do() {
+        let external = [
+            r"/\evil.test",
+            r"\\evil.test",
+            r"https:\\evil.test",
+            r"https:evil.test",
+            "///evil.test",
+            "\t//evil.test",
+            "/\n/evil.test",
+            "ht\ttps://evil.test",
+            "\u{1}https://evil.test",
+        ];
+        for href in external {
+            let options = RewriteOptions {
+                harden_links: LinkHardening::Trusted,
+                external_links: Some(ExternalLinks {
+                    internal_hosts: vec!["example.com".to_string()],
+                    ..ExternalLinks::default()
+                }),
+                ..RewriteOptions::default()
+            };
+            let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+            write!(rewriter, "<a href=\"{href}\">x</a>").unwrap();
+            let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+            assert!(
+                output.contains(r#" rel="noopener noreferrer" target="_blank">"#),
+                "{href:?}: {output}"
+            );
+        }
+
+        let policy = ExternalLinks {
+            internal_hosts: vec!["example.com".to_string()],
+            ..ExternalLinks::default()
+        };
+        for href in [
+            r"https:\\docs.example.com\x",
+            "/local",
+            r"x\y",
+            "/%5Cevil.test",
+        ] {
+            assert!(!policy.is_external(href), "{href:?}");
+        }
+    }
+
+    #[test]
+    fn rewrites_urls_before_other_rules() {
+        let options = RewriteOptions {
+            rewrite_urls: Some(UrlRewriter::new(|url| {
+                if let Some(page) = url.strip_suffix(".md") {
+                    Some(format!("/en/{page}/"))
+                } else if url.starts_with("/img/") {
+                    Some(format!("https://cdn.test{url}?v=3"))
+                } else {
+                    None
+                }
+            })),
+            harden_links: LinkHardening::Trusted,
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options.clone());
+        rewriter
+            .write_all(br#"<a href="guide.md">g</a><a href="/x">x</a><img src="/img/a.png"><a name="top">t</a>"#)
+            .unwrap();
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+
+        assert_eq!(
+            output,
+            r#"<a href="/en/guide/">g</a><a href="/x">x</a><img src="https://cdn.test/img/a.png?v=3" loading="lazy"><a name="top">t</a>"#
+        );
+        assert_eq!(options, options.clone());
+        assert_ne!(
+            options,
+            RewriteOptions {
+                rewrite_urls: Some(UrlRewriter::new(|_| None)),
+                ..options.clone()
+            }
+        );
+    }
+
+    #[test]
+    fn applies_external_link_policy() {
+        let input = r#"<a href="https://other.test/x">a</a><a href="HTTPS://Docs.Example.com:8443/y">b</a><a href="//example.com">c</a><a href="http://user@evil-example.com" target="_self" rel="me">d</a><a href="/local">e</a><a href="mailto:a@b.test">f</a>"#;
+        let options = RewriteOptions {
+            external_links: Some(ExternalLinks {
+                internal_hosts: vec!["example.com".to_string()],
+                ..ExternalLinks::default()
+            }),
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+        rewriter.write_all(input.as_bytes()).unwrap();
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+
+        assert_eq!(
+            output,
+            r#"<a href="https://other.test/x" target="_blank" rel="noopener noreferrer">a</a><a href="HTTPS://Docs.Example.com:8443/y">b</a><a href="//example.com">c</a><a href="http://user@evil-example.com" target="_self" rel="me noopener noreferrer">d</a><a href="/local">e</a><a href="mailto:a@b.test">f</a>"#
+        );
+    }
+
+    #[test]
+    fn assigns_heading_ids_in_order() {
+        let markdown = "# Intro\n\ntext\n\n## Intro\n\n### Ünïcode *em*";
+        let mut collector = crate::HeadingCollector::new();
+        for event in crate::get_event_iterator(markdown).unwrap() {
+            collector.observe(&event);
+        }
+        let mut ids: Vec<Option<String>> = collector
+            .finish()
+            .into_iter()
+            .map(|heading| heading.id)
+            .collect();
+        // A heading written as raw HTML, which the collector does not see.
+        ids.insert(1, None);
+
+        let input = r#"<h1>Intro</h1><h5 id="raw">raw</h5><p>text</p><h2 id="old">Intro</h2><h3>Ünïcode <em>em</em></h3><h4>extra</h4>"#;
+        let options = RewriteOptions {
+            heading_ids: ids,
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+        rewriter.write_all(input.as_bytes()).unwrap();
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+        let rendered = crate::render(markdown, &crate::RenderOptions::default())
+            .unwrap()
+            .html;
+
+        assert_eq!(
+            output,
+            r#"<h1 id="intro">Intro</h1><h5 id="raw">raw</h5><p>text</p><h2 id="intro-1">Intro</h2><h3 id="ünïcode-em">Ünïcode <em>em</em></h3><h4>extra</h4>"#
+        );
+        for id in ["intro", "intro-1", "ünïcode-em"] {
+            assert!(rendered.contains(&format!("id=\"{id}\"")), "{rendered}");
+        }
+    }
+
+    #[test]
+    fn collects_internal_links() {
+        let input = r##"<a href="#setup">a</a><a href="#">b</a><a href="../guide.md#install">c</a><a href="https://example.com/#x">d</a><a href="mailto:a@b.test">e</a><p><a href=" /docs/ ">f</a></p>"##;
+        let options = RewriteOptions {
+            rewrite_urls: Some(UrlRewriter::new(|url| {
+                url.strip_suffix(".md#install")
+                    .map(|stem| format!("{stem}.html#install"))
+            })),
+            collect_internal_links: true,
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+        rewriter.write_all(input.as_bytes()).unwrap();
+        let (output, links) = rewriter.into_inner_with_links().unwrap();
+
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("../guide.html#install")
+        );
+        assert_eq!(
+            links,
+            [
+                InternalLink::Anchor("setup".to_string()),
+                InternalLink::Relative("../guide.html#install".to_string()),
+                InternalLink::Relative("/docs/".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn adds_csp_nonces() {
+        let input = r#"<script nonce="old">a()</script><style>p{}</style><p>x</p><script src="/s.js"></script>"#;
+        let options = RewriteOptions {
+            csp_nonce: Some("r4nd\"0m".to_string()),
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+        rewriter.write_all(input.as_bytes()).unwrap();
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+
+        assert_eq!(
+            output,
+            r#"<script nonce="r4nd&quot;0m">a()</script><style nonce="r4nd&quot;0m">p{}</style><p>x</p><script src="/s.js" nonce="r4nd&quot;0m"></script>"#
+        );
+    }
+
+    #[test]
+    fn sanitizes_active_content() {
+        let input = r#"<p onclick="x()">a<script>alert(1)</script><style>p{}</style></p><iframe src="https://e.test"></iframe><a href="java&#9;script:x()" OnMouseOver="y()">b</a><a href="/ok" title="on">c</a><img src="data:image/png;base64,AAAA" onerror="z()" srcset="/a.png 1x, javascript:x 2x" /><img src="vbscript:x" />"#;
+        let options = RewriteOptions {
+            sanitize: Some(SanitizePolicy::default()),
+            enforce_img_loading_lazy: false,
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options.clone());
+        rewriter.write_all(input.as_bytes()).unwrap();
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+
+        assert_eq!(
+            output,
+            r#"<p>a</p><a>b</a><a href="/ok" title="on">c</a><img src="data:image/png;base64,AAAA" /><img/>"#
+        );
+
+        let cases = [
+            (
+                r#"<svg><a><set attributeName="href" to="javascript:alert(1)"/><text>x</text></a></svg>"#,
+                "",
+            ),
+            (
+                r#"<svg><a><animate attributeName="href" values="javascript:alert(1)"/></a></svg>ok"#,
+                "ok",
+            ),
+            (
+                r#"<set to="javascript:x"></set><animate values="javascript:x"></animate>"#,
+                "",
+            ),
+            (r#"<math><mi xlink:href="javascript:x">m</mi></math>"#, ""),
+            (
+                r#"<meta http-equiv="refresh" content="0;url=https://evil.test"><p>a</p>"#,
+                "<p>a</p>",
+            ),
+            (
+                r#"<base href="https://evil.test/"><link rel="stylesheet" href="/x.css">b"#,
+                "b",
+            ),
+            (
+                r#"<form action="https://evil.test"><input name="q"></form>c"#,
+                "c",
+            ),
+            (
+                r#"<p><title><img src=x onerror=alert(1)></title>g</p>"#,
+                "<p>g</p>",
+            ),
+            // Parsed with scripting on, as browsers do: the `<img>` is real and loses its handler.
+            (
+                r#"<noscript><p title="</noscript><img src=x onerror=alert(1)>"></noscript>d"#,
+                r#"<img src=x>"></noscript>d"#,
+            ),
+            (
+                r#"<custom-el data-x="1"><b style="color:red" formaction="https://e.test">e</b></custom-el>"#,
+                "<b>e</b>",
+            ),
+            (
+                r#"<p class="c" data-id="1" aria-label="l" role="note" name="n">f</p>"#,
+                r#"<p class="c" data-id="1" aria-label="l" role="note">f</p>"#,
+            ),
+        ];
+        for (input, expected) in cases {
+            let mut rewriter = StreamingRewriter::new(Vec::new(), options.clone());
+            rewriter.write_all(input.as_bytes()).unwrap();
+            let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+            assert_eq!(output, expected, "{input}");
+        }
+
+        let unlisted = RewriteOptions {
+            sanitize: Some(SanitizePolicy {
+                remove_elements: Vec::new(),
+                ..SanitizePolicy::default()
+            }),
+            ..options
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), unlisted);
+        rewriter
+            .write_all(b"<p><title><img src=x></title><textarea><b>t</b></textarea><u>h</u></p>")
+            .unwrap();
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+        assert_eq!(output, "<p><u>h</u></p>");
+    }
+
+    #[test]
+    fn applies_rewrite_rules() {
+        let input = r#"<table><tr><td>1</td></tr></table><pre><code class="x">a</code></pre><script>b</script><img src="a.png" />"#;
+        let rule = |selector: &str, action| RewriteRule {
+            selector: selector.to_string(),
+            action,
+        };
+        let options = RewriteOptions {
+            rules: vec![
+                rule(
+                    "table",
+                    RewriteAction::Wrap {
+                        tag: "div".to_string(),
+                        attributes: vec![("class".to_string(), "scroll \"x\"".to_string())],
+                    },
+                ),
+                rule("pre > code", RewriteAction::AddClass("hl".to_string())),
+                rule("pre > code", RewriteAction::AddClass("x".to_string())),
+                rule("script", RewriteAction::Remove),
+                rule(
+                    "img",
+                    RewriteAction::SetAttribute {
+                        name: "loading".to_string(),
+                        value: "eager".to_string(),
+                    },
+                ),
+                rule("video", RewriteAction::Remove),
+            ],
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+        rewriter.write_all(input.as_bytes()).unwrap();
+        let (output, stats) = rewriter.into_parts().unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(stats.elements_matched_per_rule, [1, 1, 1, 1, 1, 0]);
+        assert_eq!(stats.bytes_in, input.len() as u64);
+        assert_eq!(stats.bytes_out, output.len() as u64);
+        assert_eq!(
+            output,
+            r#"<div class="scroll &quot;x&quot;"><table><tr><td>1</td></tr></table></div><pre><code class="x hl">a</code></pre><img src="a.png" loading="eager" />"#
+        );
+
+        for action in [
+            RewriteAction::Remove,
+            RewriteAction::AddClass("a b".to_string()),
+            RewriteAction::Wrap {
+                tag: "div><script".to_string(),
+                attributes: Vec::new(),
+            },
+        ] {
+            let selector = match action {
+                RewriteAction::Remove => "p[",
+                _ => "p",
+            };
+            let options = RewriteOptions {
+                rules: vec![rule(selector, action)],
+                ..RewriteOptions::default()
+            };
+            let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+            let err = rewriter.write_all(b"<p>x</p>").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{err}");
+        }
+    }
+
+    #[test]
+    fn rewrites_text_outside_code() {
+        let rule = |selector: &str, rewrite| RewriteRule {
+            selector: selector.to_string(),
+            action: RewriteAction::Text(rewrite),
+        };
+        let cases = [
+            (
+                rule(
+                    "p",
+                    TextRewrite::SmartPunctuation(SmartPunctuation::default()),
+                ),
+                r#"<p>&quot;Don't&quot; -- <em>"wait"</em> <code>"x"</code></p><pre>"y"</pre><div>"z"</div>"#,
+                r#"<p>“Don’t” – <em>“wait”</em> <code>"x"</code></p><pre>"y"</pre><div>"z"</div>"#,
+            ),
+            (
+                rule("*", TextRewrite::FrenchSpacing),
+                "<p>Quoi ? « Oui » : non ; <b>a</b> !</p><pre>a ?</pre>",
+                "<p>Quoi\u{202f}? «\u{a0}Oui\u{a0}»\u{a0}: non\u{202f}; <b>a</b>\u{202f}!</p><pre>a ?</pre>",
+            ),
+            (
+                rule(
+                    "p",
+                    TextRewrite::Replace {
+                        from: "<3".to_string(),
+                        to: "♥ & more".to_string(),
+                    },
+                ),
+                "<p>I &lt;3 it</p><script>a <3</script>",
+                "<p>I ♥ &amp; more it</p><script>a <3</script>",
+            ),
+        ];
+
+        for (rule, input, expected) in cases {
+            let options = RewriteOptions {
+                rules: vec![rule],
+                ..RewriteOptions::default()
+            };
+            let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+            for chunk in input.as_bytes().chunks(5) {
+                rewriter.write_all(chunk).unwrap();
+            }
+            let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn reuses_compiled_options() {
+        fn shareable<T: Send + Sync>(_: &T) {}
+
+        let options = RewriteOptions {
+            sanitize: Some(SanitizePolicy::default()),
+            rules: vec![RewriteRule {
+                selector: "table".to_string(),
+                action: RewriteAction::AddClass("wide".to_string()),
+            }],
+            ..RewriteOptions::default()
+        };
+        let factory = RewriterFactory::new(options.clone()).unwrap();
+        shareable(&factory);
+
+        for input in [
+            "<table></table><script>x</script>",
+            "<p><img src=\"a.png\"></p>",
+        ] {
+            let mut expected = StreamingRewriter::new(Vec::new(), options.clone());
+            expected.write_all(input.as_bytes()).unwrap();
+            let mut rewriter = factory.rewriter(Vec::new());
+            rewriter.write_all(input.as_bytes()).unwrap();
+            assert_eq!(
+                rewriter.into_inner().unwrap(),
+                expected.into_inner().unwrap()
+            );
+        }
+
+        let invalid = RewriteOptions {
+            rules: vec![RewriteRule {
+                selector: "table".to_string(),
+                action: RewriteAction::AddClass(String::new()),
+            }],
+            ..RewriteOptions::default()
+        };
+        let err = RewriterFactory::new(invalid).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{err}");
+    }
+
+    #[test]
+    fn runs_custom_element_handlers() {
+        let options = RewriteOptions {
+            rules: vec![RewriteRule {
+                selector: "pre > code".to_string(),
+                action: RewriteAction::AddClass("hl".to_string()),
+            }],
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::builder()
+            .options(options)
+            .on("pre > code", |el| {
+                let class = el.get_attribute("class").unwrap_or_default();
+                el.set_attribute("data-class", &class)?;
+                Ok(())
+            })
+            .on("img", |el| {
+                el.remove_attribute("loading");
+                Ok(())
+            })
+            .build(Vec::new());
+        rewriter
+            .write_all(br#"<pre><code>ls</code></pre><img src="a.png" />"#)
+            .unwrap();
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+
+        assert_eq!(
+            output,
+            r#"<pre><code class="hl" data-class="hl">ls</code></pre><img src="a.png" />"#
+        );
+
+        let mut rewriter = StreamingRewriter::builder()
+            .on("p[", |_| Ok(()))
+            .build(Vec::new());
+        let err = rewriter.write_all(b"<p>x</p>").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{err}");
+    }
+
+    #[test]
+    fn empties_rejected_data_uri_images() {
+        let input = r#"<img src="data:image/png;base64,AAAAAAAA"><img src="data:image/gif;base64,R0"><img src="/a.png">"#;
+        let options = RewriteOptions {
+            enforce_img_loading_lazy: false,
+            data_uri_images: DataUriPolicy::MaxBytes(24),
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+        rewriter.write_all(input.as_bytes()).unwrap();
+        rewriter.flush().unwrap();
+
+        assert_eq!(
+            rewriter.blocked_data_uris(),
+            [BlockedDataUri {
+                media_type: "image/png".into(),
+                bytes: 30,
+            }]
+        );
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            output,
+            r#"<img src=""><img src="data:image/gif;base64,R0"><img src="/a.png">"#
+        );
+    }
+
+    #[test]
+    fn strips_comments() {
+        let options = RewriteOptions {
+            strip_comments: true,
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Vec::new(), options);
+        rewriter.write_all(b"<p>a<!-- one").unwrap();
+        rewriter
+            .write_all(b" --></p><!--two--><pre>b</pre>")
+            .unwrap();
+
+        let output = String::from_utf8(rewriter.into_inner().unwrap()).unwrap();
+        assert_eq!(output, "<p>a</p><pre>b</pre>");
+    }
+
+    #[test]
+    fn counts_rewrite_throughput() {
+        let mut rewriter = StreamingRewriter::new(Vec::new(), RewriteOptions::default());
+        rewriter.write_all(b"<p>hi</p>").unwrap();
+        rewriter.write_all(br#"<img src="/a.png">"#).unwrap();
+        let (output, stats) = rewriter.into_inner_with_stats().unwrap();
+
+        assert_eq!(stats.bytes_in, 27);
+        assert_eq!(stats.chunks_in, 2);
+        assert_eq!(stats.bytes_out, output.len() as u64);
+        assert_eq!(stats.bytes_out, 27 + " loading=\"lazy\"".len() as u64);
+        assert!(stats.chunks_out >= 1);
+        assert!(stats.amplification() > 1.0);
+    }
+
+    /// Records each chunk written to it.
+    #[derive(Default)]
+    struct Chunks(Vec<String>);
+
+    impl Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(String::from_utf8_lossy(buf).into_owned());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn chunks_output_by_policy() {
+        let pieces = [
+            "<h1>T</h1>\n",
+            "<p>a <b>b</b>\n",
+            "c</p>\n",
+            "<hr />\n",
+            "<ul>\n<li>x</li>\n</ul>\n",
+            "<p>tail",
+        ];
+        let cases = [
+            (
+                ChunkPolicy {
+                    min_bytes: 0,
+                    flush_on_block_end: true,
+                    top_level_only: false,
+                },
+                vec![
+                    "<h1>T</h1>\n",
+                    "<p>a <b>b</b>\nc</p>\n",
+                    "<hr />\n",
+                    "<ul>\n<li>x</li>\n</ul>\n",
+                    "<p>tail",
+                ],
+            ),
+            (
+                ChunkPolicy {
+                    min_bytes: 20,
+                    flush_on_block_end: true,
+                    top_level_only: false,
+                },
+                vec![
+                    "<h1>T</h1>\n<p>a <b>b</b>\nc</p>\n",
+                    "<hr />\n<ul>\n<li>x</li>\n</ul>\n",
+                    "<p>tail",
+                ],
+            ),
+            (
+                ChunkPolicy {
+                    min_bytes: 1024,
+                    flush_on_block_end: false,
+                    top_level_only: false,
+                },
+                vec!["<h1>T</h1>\n<p>a <b>b</b>\nc</p>\n<hr />\n<ul>\n<li>x</li>\n</ul>\n<p>tail"],
+            ),
+        ];
+
+        for (chunk_policy, expected) in cases {
+            let options = RewriteOptions {
+                chunk_policy,
+                ..RewriteOptions::default()
+            };
+            let mut rewriter = StreamingRewriter::new(Chunks::default(), options);
+            for piece in pieces {
+                rewriter.write_all(piece.as_bytes()).unwrap();
+            }
+            let (chunks, stats) = rewriter.into_inner_with_stats().unwrap();
+            assert_eq!(chunks.0, expected, "{chunk_policy:?}");
+            assert_eq!(stats.chunks_out, expected.len() as u64);
+        }
+    }
+
+    #[test]
+    fn chunks_output_at_top_level_blocks() {
+        let pieces = [
+            "<h1 title=\"a>b\">T</h1>",
+            "\n<blockquote>\n<p>a</p>\n<",
+            "p>b</p>\n</blockquote>\n",
+            "<!-- </div> --><script>if (a<b) {}</script>\n<hr />\n<ul>\n<li>\n<p>x</p>\n</li>\n",
+            "</ul>\n<p>tail",
+        ];
+        let options = RewriteOptions {
+            chunk_policy: ChunkPolicy {
+                min_bytes: 0,
+                flush_on_block_end: true,
+                top_level_only: true,
+            },
+            enforce_img_loading_lazy: false,
+            ..RewriteOptions::default()
+        };
+        let mut rewriter = StreamingRewriter::new(Chunks::default(), options);
+        for piece in pieces {
+            rewriter.write_all(piece.as_bytes()).unwrap();
+        }
+        rewriter.write_all(b" end").unwrap();
+        rewriter.flush_chunk().unwrap();
+        rewriter.write_all(b"</p>\n").unwrap();
+        let chunks = rewriter.into_inner().unwrap();
+
+        assert_eq!(
+            chunks.0,
+            [
+                "<h1 title=\"a>b\">T</h1>\n",
+                "<blockquote>\n<p>a</p>\n<p>b</p>\n</blockquote>\n",
+                "<!-- </div> --><script>if (a<b) {}</script>\n<hr />\n",
+                "<ul>\n<li>\n<p>x</p>\n</li>\n</ul>\n",
+                "<p>tail end",
+                "</p>\n",
+            ]
+        );
+    }
 }
@@ -0,0 +1,168 @@
+use crate::event::{Event, Tag, TagEnd};
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// Separator owed before the next piece of text, strongest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Gap {
+    None,
+    Space,
+    Line,
+    Paragraph,
+}
+
+impl Gap {
+    fn as_str(self) -> &'static str {
+        match self {
+            Gap::None => "",
+            Gap::Space => " ",
+            Gap::Line => "\n",
+            Gap::Paragraph => "\n\n",
+        }
+    }
+}
+
+/// Renders events as plain text: markup and raw HTML are dropped, blocks are separated by blank
+/// lines, and list items and table rows by single newlines.
+pub struct TextRenderer<W: Write> {
+    writer: W,
+    gap: Gap,
+    wrote_any: bool,
+    /// Code blocks keep their line structure; elsewhere soft line breaks become spaces.
+    in_code_block: bool,
+}
+
+impl<W: Write> TextRenderer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            gap: Gap::None,
+            wrote_any: false,
+            in_code_block: false,
+        }
+    }
+
+    pub fn render<'a, I>(mut self, iter: I) -> io::Result<W>
+    where
+        I: IntoIterator<Item = Event<'a>>,
+    {
+        for event in iter {
+            match event {
+                Event::Start(tag) => self.start_tag(&tag),
+                Event::End(tag) => self.end_tag(&tag),
+                Event::Text(text)
+                | Event::Code(text)
+                | Event::InlineMath(text)
+                | Event::DisplayMath(text) => self.write_text(&text)?,
+                Event::SoftBreak => self.separate(Gap::Space),
+                Event::HardBreak => self.separate(Gap::Line),
+                Event::Rule => self.separate(Gap::Paragraph),
+                Event::Html(_)
+                | Event::InlineHtml(_)
+                | Event::MdxExpression(_)
+                | Event::FootnoteReference(_)
+                | Event::TaskListMarker(_) => {}
+            }
+        }
+        Ok(self.writer)
+    }
+
+    fn start_tag(&mut self, tag: &Tag<'_>) {
+        match tag {
+            Tag::CodeBlock(_) => self.in_code_block = true,
+            Tag::TableCell => self.separate(Gap::Space),
+            Tag::FigureCaption => self.separate(Gap::Line),
+            Tag::Sidenote { .. } => self.separate(Gap::Space),
+            _ => {}
+        }
+    }
+
+    fn end_tag(&mut self, tag: &TagEnd) {
+        match tag {
+            TagEnd::Item | TagEnd::TableRow => self.separate(Gap::Line),
+            TagEnd::HeadingNumber | TagEnd::Sidenote => self.separate(Gap::Space),
+            TagEnd::CodeBlock => {
+                self.in_code_block = false;
+                self.separate(Gap::Paragraph);
+            }
+            TagEnd::Paragraph
+            | TagEnd::Heading(_)
+            | TagEnd::BlockQuote
+            | TagEnd::Details
+            | TagEnd::Callout
+            | TagEnd::Summary
+            | TagEnd::Figure
+            | TagEnd::List(_)
+            | TagEnd::Table
+            | TagEnd::FootnoteDefinition
+            | TagEnd::Endnotes
+            | TagEnd::FootnoteList
+            | TagEnd::FootnoteItem => self.separate(Gap::Paragraph),
+            _ => {}
+        }
+    }
+
+    fn separate(&mut self, gap: Gap) {
+        self.gap = self.gap.max(gap);
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        let text = if self.in_code_block {
+            Cow::Borrowed(text.trim_end_matches('\n'))
+        } else if text.contains('\n') {
+            Cow::Owned(text.replace('\n', " "))
+        } else {
+            Cow::Borrowed(text)
+        };
+        if text.is_empty() {
+            return Ok(());
+        }
+        // Leading separators are dropped so the output starts with content.
+        if self.wrote_any {
+            self.writer.write_all(self.gap.as_str().as_bytes())?;
+        }
+        self.gap = Gap::None;
+        self.wrote_any = true;
+        self.writer.write_all(text.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MarkdownStream;
+
+    fn to_text(input: &str) -> String {
+        let events = crate::get_event_iterator(input).unwrap();
+        String::from_utf8(events.stream_to_text(Vec::new()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn strips_markup_and_separates_blocks() {
+        let cases = [
+            (
+                "# Title\n\nSome *em* and **strong**",
+                "Title\n\nSome em and strong",
+            ),
+            ("- [x] one\n- two\n\nafter", "one\ntwo\n\nafter"),
+            ("line\nsoft\\\nhard", "line soft\nhard"),
+            (
+                "<div>raw</div>\n\n![alt text](i.png) <b>x</b>",
+                "alt text x",
+            ),
+            (
+                "```rust\nfn main() {}\n```\n\n---\n\nend",
+                "fn main() {}\n\nend",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(to_text(input), expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn separates_table_cells_and_rows() {
+        assert_eq!(to_text("| a | b |\n|---|---|\n| 1 | 2 |"), "a b\n1 2");
+    }
+}
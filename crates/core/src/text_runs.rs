@@ -0,0 +1,129 @@
+//! Shared driver for inline syntax neither parser knows, such as `[[wiki links]]`, `[[keys]]`,
+//! and `{ruby|annotations}`.
+//!
+//! The markers reach the event stream as text, sometimes split across several text events, so
+//! [`TextRuns`] joins adjacent text before handing it to the syntax's `expand` callback.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+use crate::event::{Event, Tag, TagEnd};
+
+/// Events an `expand` callback queues in place of a run of text.
+pub(crate) struct Expansion<'q> {
+    pending: &'q mut VecDeque<Event<'static>>,
+}
+
+impl Expansion<'_> {
+    /// Queues `event`.
+    pub(crate) fn push(&mut self, event: Event<'static>) {
+        self.pending.push_back(event);
+    }
+
+    /// Queues `text` as a text event, unless it is empty.
+    pub(crate) fn push_text(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.push(Event::Text(Cow::Owned(text.to_string())));
+        }
+    }
+}
+
+/// Joins adjacent text events outside code blocks and image descriptions and passes each run to
+/// `expand`. The callback queues the run's replacement and returns `true`, or returns `false`
+/// without queueing anything to keep the original events.
+pub(crate) struct TextRuns<I, F> {
+    inner: I,
+    expand: F,
+    pending: VecDeque<Event<'static>>,
+    /// Open containers whose text must stay as written.
+    literal_depth: usize,
+    /// Keep link text as written too.
+    skip_links: bool,
+}
+
+impl<I, F> TextRuns<I, F>
+where
+    I: Iterator<Item = Event<'static>>,
+    F: FnMut(&str, &mut Expansion<'_>) -> bool,
+{
+    pub(crate) fn new(inner: I, expand: F) -> Self {
+        Self {
+            inner,
+            expand,
+            pending: VecDeque::new(),
+            literal_depth: 0,
+            skip_links: false,
+        }
+    }
+
+    /// Leaves the text of links alone as well.
+    pub(crate) fn skip_links(mut self) -> Self {
+        self.skip_links = true;
+        self
+    }
+
+    /// Joins `first` with the text events right after it and queues the result through
+    /// `expand`.
+    fn text(&mut self, first: Cow<'static, str>) {
+        let mut runs = vec![first];
+        let mut next = None;
+        for event in self.inner.by_ref() {
+            match event {
+                Event::Text(text) => runs.push(text),
+                other => {
+                    next = Some(other);
+                    break;
+                }
+            }
+        }
+
+        let joined: String = runs.concat();
+        let mut expansion = Expansion {
+            pending: &mut self.pending,
+        };
+        if !(self.expand)(&joined, &mut expansion) {
+            self.pending.extend(runs.into_iter().map(Event::Text));
+        }
+        if let Some(event) = next {
+            self.track(&event);
+            self.pending.push_back(event);
+        }
+    }
+
+    fn track(&mut self, event: &Event<'_>) {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::Image { .. }) => self.literal_depth += 1,
+            Event::Start(Tag::Link { .. }) if self.skip_links => self.literal_depth += 1,
+            Event::End(TagEnd::CodeBlock | TagEnd::Image) => {
+                self.literal_depth = self.literal_depth.saturating_sub(1);
+            }
+            Event::End(TagEnd::Link) if self.skip_links => {
+                self.literal_depth = self.literal_depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<I, F> Iterator for TextRuns<I, F>
+where
+    I: Iterator<Item = Event<'static>>,
+    F: FnMut(&str, &mut Expansion<'_>) -> bool,
+{
+    type Item = Event<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+            match self.inner.next()? {
+                Event::Text(text) if self.literal_depth == 0 => self.text(text),
+                event => {
+                    self.track(&event);
+                    return Some(event);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Table of contents rendering from collected headings.
+
+use std::borrow::Cow;
+
+use html_escape::{encode_double_quoted_attribute, encode_text};
+
+use crate::event::{Event, EventStream, LinkType, Tag, TagEnd};
+use crate::slug::{Heading, HeadingCollector};
+
+/// Paragraph texts replaced by [`insert_toc`], compared case-insensitively.
+const MARKERS: [&str; 2] = ["[[toc]]", "[toc]"];
+
+/// Renders `headings` as nested `<ul>` lists linking to each heading's id.
+///
+/// The shallowest level present becomes the top list, and skipped levels (H2 → H4) nest only
+/// one step. Headings without an id are listed as plain text, and numbered headings keep their
+/// number in front. Returns an empty string when there are no headings.
+pub fn toc_html(headings: &[Heading]) -> String {
+    let mut out = String::new();
+    walk(headings, |step| match step {
+        Step::OpenList => out.push_str("<ul>"),
+        Step::CloseList => out.push_str("</ul>"),
+        Step::OpenItem(heading) => {
+            out.push_str("<li>");
+            let mut label = String::new();
+            if let Some(number) = &heading.number {
+                label.push_str("<span class=\"heading-number\">");
+                label.push_str(&encode_text(number));
+                label.push_str("</span> ");
+            }
+            label.push_str(&encode_text(&heading.text));
+            match &heading.id {
+                Some(id) => {
+                    out.push_str("<a href=\"#");
+                    out.push_str(&encode_double_quoted_attribute(id));
+                    out.push_str("\">");
+                    out.push_str(&label);
+                    out.push_str("</a>");
+                }
+                None => out.push_str(&label),
+            }
+        }
+        Step::CloseItem => out.push_str("</li>"),
+    });
+    out
+}
+
+/// The same nested lists as [`toc_html`], as events for a renderer to write.
+fn toc_events(headings: &[Heading]) -> Vec<Event<'static>> {
+    let mut out = Vec::new();
+    walk(headings, |step| match step {
+        Step::OpenList => out.push(Event::Start(Tag::List(None))),
+        Step::CloseList => out.push(Event::End(TagEnd::List(false))),
+        Step::OpenItem(heading) => {
+            out.push(Event::Start(Tag::Item));
+            if let Some(id) = &heading.id {
+                out.push(Event::Start(Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: Cow::Owned(format!("#{id}")),
+                    title: Cow::Borrowed(""),
+                    id: Cow::Borrowed(""),
+                }));
+            }
+            if let Some(number) = &heading.number {
+                out.push(Event::Start(Tag::HeadingNumber));
+                out.push(Event::Text(Cow::Owned(number.clone())));
+                out.push(Event::End(TagEnd::HeadingNumber));
+            }
+            out.push(Event::Text(Cow::Owned(heading.text.clone())));
+            if heading.id.is_some() {
+                out.push(Event::End(TagEnd::Link));
+            }
+        }
+        Step::CloseItem => out.push(Event::End(TagEnd::Item)),
+    });
+    out
+}
+
+/// One step of the nested list structure built by [`walk`].
+enum Step<'h> {
+    OpenList,
+    CloseList,
+    OpenItem(&'h Heading),
+    CloseItem,
+}
+
+/// Feeds `visit` the nested list structure for `headings`, or nothing when there are none.
+fn walk<'h>(headings: &'h [Heading], mut visit: impl FnMut(Step<'h>)) {
+    let Some(top) = headings.iter().map(|heading| heading.level).min() else {
+        return;
+    };
+
+    visit(Step::OpenList);
+    // Level of each open list, outermost first.
+    let mut levels = vec![top];
+    let mut item_open = false;
+
+    for heading in headings {
+        // Close nested lists until the heading fits under the enclosing list's item; a heading
+        // shallower than its siblings but deeper than their parent joins the sibling list.
+        while levels.len() > 1 && heading.level <= levels[levels.len() - 2] {
+            visit(Step::CloseItem);
+            visit(Step::CloseList);
+            levels.pop();
+        }
+        if item_open && heading.level > levels[levels.len() - 1] {
+            visit(Step::OpenList);
+            levels.push(heading.level);
+        } else if item_open {
+            visit(Step::CloseItem);
+        }
+        visit(Step::OpenItem(heading));
+        item_open = true;
+    }
+
+    visit(Step::CloseItem);
+    for _ in 1..levels.len() {
+        visit(Step::CloseList);
+        visit(Step::CloseItem);
+    }
+    visit(Step::CloseList);
+}
+
+/// Conservative pre-scan: `false` only when `input` cannot contain a TOC marker, so documents
+/// without one skip buffering.
+pub(crate) fn may_contain_marker(input: &str) -> bool {
+    let lower = input.to_ascii_lowercase();
+    MARKERS.iter().any(|marker| lower.contains(marker))
+}
+
+/// Replaces every paragraph consisting of just `[[toc]]` or `[TOC]` with the list events of the
+/// document's table of contents. Their links go through the same transforms as other same-page
+/// links, such as the id prefix.
+///
+/// The headings are only known at the end of the stream, so this buffers the whole document.
+pub(crate) fn insert_toc<'a>(events: EventStream<'a>) -> EventStream<'a> {
+    let events: Vec<Event<'static>> = events.collect();
+    let mut collector = HeadingCollector::new();
+    for event in &events {
+        collector.observe(event);
+    }
+    let toc = toc_events(&collector.finish());
+
+    let mut out = Vec::with_capacity(events.len());
+    let mut rest = &events[..];
+    while let Some((event, tail)) = rest.split_first() {
+        match marker_len(rest) {
+            Some(len) => {
+                out.extend(toc.iter().cloned());
+                rest = &rest[len..];
+            }
+            None => {
+                out.push(event.clone());
+                rest = tail;
+            }
+        }
+    }
+    Box::new(out.into_iter())
+}
+
+/// Number of events in the marker paragraph at the start of `events`, if there is one. Parsers
+/// may split the brackets into separate text events.
+fn marker_len(events: &[Event<'_>]) -> Option<usize> {
+    let [Event::Start(Tag::Paragraph), rest @ ..] = events else {
+        return None;
+    };
+    let mut text = String::new();
+    for (idx, event) in rest.iter().enumerate() {
+        match event {
+            Event::Text(run) => text.push_str(run),
+            Event::End(TagEnd::Paragraph) => {
+                let text = text.trim().to_ascii_lowercase();
+                return MARKERS.contains(&text.as_str()).then_some(idx + 2);
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heading(level: u8, text: &str) -> Heading {
+        Heading {
+            level,
+            text: text.to_string(),
+            id: Some(text.to_lowercase()),
+            number: None,
+        }
+    }
+
+    #[test]
+    fn nests_by_level() {
+        let cases = [
+            (vec![], ""),
+            (
+                vec![heading(2, "A"), heading(3, "B"), heading(2, "C")],
+                "<ul><li><a href=\"#a\">A</a><ul><li><a href=\"#b\">B</a></li></ul></li><li><a href=\"#c\">C</a></li></ul>",
+            ),
+            (
+                vec![
+                    heading(1, "A"),
+                    heading(3, "B"),
+                    heading(4, "C"),
+                    heading(2, "D"),
+                ],
+                "<ul><li><a href=\"#a\">A</a><ul><li><a href=\"#b\">B</a><ul><li><a href=\"#c\">C</a></li></ul></li><li><a href=\"#d\">D</a></li></ul></li></ul>",
+            ),
+            (
+                vec![
+                    heading(3, "A"),
+                    Heading {
+                        level: 2,
+                        text: "<B>".into(),
+                        id: None,
+                        number: Some("2".into()),
+                    },
+                ],
+                "<ul><li><a href=\"#a\">A</a></li><li><span class=\"heading-number\">2</span> &lt;B&gt;</li></ul>",
+            ),
+        ];
+
+        for (headings, expected) in cases {
+            assert_eq!(toc_html(&headings), expected);
+        }
+    }
+
+    #[test]
+    fn replaces_markers() {
+        use crate::{ParserBackend, RenderOptions, WikiLinkOptions, render};
+
+        let input = "# Guide\n\n[[toc]]\n\n## Install\n\n[TOC]\n\nSee [TOC] and `[[toc]]`.";
+        let toc = "<ul><li><a href=\"#doc-guide\">Guide</a><ul><li><a href=\"#doc-install\">Install</a></li></ul>\n</li></ul>\n";
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            let options = RenderOptions {
+                backend,
+                toc_marker: true,
+                keyboard_keys: true,
+                wiki_links: Some(WikiLinkOptions::default()),
+                id_prefix: "doc-".into(),
+                ..RenderOptions::default()
+            };
+            let html = render(input, &options).unwrap().html;
+            assert_eq!(
+                html,
+                format!(
+                    "<h1 id=\"doc-guide\">Guide</h1>\n{toc}<h2 id=\"doc-install\">Install</h2>\n{toc}\
+                     <p>See [TOC] and <code>[[toc]]</code>.</p>\n"
+                ),
+                "{backend:?}"
+            );
+        }
+
+        let html = render("[[toc]]", &RenderOptions::default()).unwrap().html;
+        assert_eq!(html, "<p>[[toc]]</p>\n");
+    }
+
+    #[test]
+    fn marker_toc_survives_escaped_raw_html() {
+        use crate::{HtmlOptions, RenderOptions, render};
+
+        let options = RenderOptions {
+            toc_marker: true,
+            number_headings: true,
+            html: HtmlOptions {
+                escape_raw_html: true,
+                ..HtmlOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let html = render("[[toc]]\n\n# A <b>", &options).unwrap().html;
+        assert!(
+            html.starts_with(
+                "<ul><li><a href=\"#a\"><span class=\"heading-number\">1</span> A</a></li></ul>\n\
+                 <h1 id=\"a\" data-number=\"1\"><span class=\"heading-number\">1</span> A &lt;b&gt;</h1>"
+            ),
+            "{html}"
+        );
+    }
+}
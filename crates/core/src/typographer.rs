@@ -0,0 +1,174 @@
+//! markdown-it style typographic replacements: arrows, common fractions, and `(c)`-style
+//! symbols.
+//!
+//! [`Typographer`] works both as an [`EventFilter`] for hand-built streams and as a
+//! [`MarkflowPlugin`] for a [`Pipeline`](crate::Pipeline). As a plugin it runs after the
+//! built-in transforms, so with [`RenderOptions::smart_punctuation`](crate::RenderOptions)
+//! dashes have already been replaced and `-->` arrives as `–>`.
+
+use std::borrow::Cow;
+
+use smallvec::smallvec;
+
+use crate::adapter::{EventFilter, FilterOutput};
+use crate::event::{Event, EventStream, Tag, TagEnd};
+use crate::plugin::MarkflowPlugin;
+
+/// Replacements tried at every position, longer patterns before their prefixes.
+const REPLACEMENTS: [(&str, &str); 14] = [
+    ("<-->", "↔"),
+    ("<==>", "⇔"),
+    ("-->", "→"),
+    ("<--", "←"),
+    ("==>", "⇒"),
+    ("<==", "⇐"),
+    ("(tm)", "™"),
+    ("(TM)", "™"),
+    ("(c)", "©"),
+    ("(C)", "©"),
+    ("(r)", "®"),
+    ("(R)", "®"),
+    ("(p)", "§"),
+    ("+-", "±"),
+];
+
+/// Fractions with a Unicode glyph, replaced only when not part of a longer number or date.
+const FRACTIONS: [(&str, &str); 5] = [
+    ("1/2", "½"),
+    ("1/3", "⅓"),
+    ("2/3", "⅔"),
+    ("1/4", "¼"),
+    ("3/4", "¾"),
+];
+
+/// Replaces `-->`, `<--`, `==>`, `1/2`, `(c)`, `(tm)`, `+-` and similar sequences in text
+/// outside code.
+#[derive(Debug, Clone, Default)]
+pub struct Typographer {
+    in_code_block: bool,
+}
+
+impl Typographer {
+    /// Creates a typographer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn apply<'a>(&mut self, event: Event<'a>) -> Event<'a> {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.in_code_block = true;
+                event
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                self.in_code_block = false;
+                event
+            }
+            Event::Text(text) if !self.in_code_block => match replace(&text) {
+                Some(replaced) => Event::Text(Cow::Owned(replaced)),
+                None => Event::Text(text),
+            },
+            other => other,
+        }
+    }
+}
+
+impl<'a> EventFilter<'a> for Typographer {
+    fn map(&mut self, event: Event<'a>) -> FilterOutput<'a> {
+        smallvec![self.apply(event)]
+    }
+}
+
+impl MarkflowPlugin for Typographer {
+    fn transform<'a>(&'a mut self, events: EventStream<'a>) -> EventStream<'a> {
+        Box::new(events.map(|event| self.apply(event)))
+    }
+}
+
+/// `text` with every replacement applied, or `None` when nothing matched.
+fn replace(text: &str) -> Option<String> {
+    if !text.contains(['-', '=', '(', '+', '/']) {
+        return None;
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut changed = false;
+    let mut idx = 0;
+    'scan: while let Some(ch) = text[idx..].chars().next() {
+        let rest = &text[idx..];
+        for (pattern, replacement) in REPLACEMENTS {
+            if rest.starts_with(pattern) {
+                out.push_str(replacement);
+                idx += pattern.len();
+                changed = true;
+                continue 'scan;
+            }
+        }
+        for (pattern, replacement) in FRACTIONS {
+            if rest.starts_with(pattern)
+                && !text[..idx].ends_with(is_number_part)
+                && !continues_number(&rest[pattern.len()..])
+            {
+                out.push_str(replacement);
+                idx += pattern.len();
+                changed = true;
+                continue 'scan;
+            }
+        }
+        out.push(ch);
+        idx += ch.len_utf8();
+    }
+    changed.then_some(out)
+}
+
+fn is_number_part(ch: char) -> bool {
+    ch.is_ascii_digit() || matches!(ch, '/' | '.' | ',')
+}
+
+/// Whether `rest` carries on the number before it, as in `1/25` or `1/2.5`.
+fn continues_number(rest: &str) -> bool {
+    let mut chars = rest.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_digit() || ch == '/' => true,
+        Some('.' | ',') => chars.next().is_some_and(|ch| ch.is_ascii_digit()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Pipeline, RenderOptions};
+
+    #[test]
+    fn replaces_typographic_sequences() {
+        let cases = [
+            ("a --> b <-- c <--> d", "a → b ← c ↔ d"),
+            ("x ==> y <== z <==> w", "x ⇒ y ⇐ z ⇔ w"),
+            ("(c) 2024 (R) Acme(tm) (p)1", "© 2024 ® Acme™ §1"),
+            ("1/2 cup, 3/4 mile, 2/3.", "½ cup, ¾ mile, ⅔."),
+            ("11/2 1/25 12/31/2024 1/2.5", "11/2 1/25 12/31/2024 1/2.5"),
+            ("5 +- 0.1", "5 ± 0.1"),
+            ("nothing to do", "nothing to do"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                replace(input).as_deref().unwrap_or(input),
+                expected,
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn runs_as_plugin_outside_code() {
+        let mut pipeline = Pipeline::new(RenderOptions::default()).with_plugin(Typographer::new());
+        let html = pipeline
+            .render("Next --> `a --> b` (c)\n\n```\n1/2 --> (c)\n```")
+            .unwrap()
+            .html;
+        assert_eq!(
+            html,
+            "<p>Next → <code>a --&gt; b</code> ©</p>\n<pre><code>1/2 --&gt; (c)</code></pre>\n"
+        );
+    }
+}
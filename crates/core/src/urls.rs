@@ -0,0 +1,171 @@
+//! URL safety checks for links and images taken from untrusted Markdown.
+
+use serde::{Deserialize, Serialize};
+
+/// Schemes links may use; relative URLs and fragments are always allowed.
+const SAFE_LINK_SCHEMES: [&str; 4] = ["http", "https", "mailto", "tel"];
+
+/// Raster image types allowed in `data:` image sources. SVG is excluded because it can carry
+/// scripts.
+const SAFE_DATA_IMAGE_TYPES: [&str; 5] = ["png", "gif", "jpeg", "jpg", "webp"];
+
+/// `true` when `url` is relative or uses one of the allowed link schemes.
+pub fn is_safe_link(url: &str) -> bool {
+    match scheme(url) {
+        None => true,
+        Some(scheme) => SAFE_LINK_SCHEMES.contains(&scheme.as_str()),
+    }
+}
+
+/// Like [`is_safe_link`], additionally allowing base64 `data:` URLs of raster images.
+pub fn is_safe_image(url: &str) -> bool {
+    is_safe_link(url) || is_raster_data_url(url)
+}
+
+/// `true` when `url` points into the same site as the document: no scheme, no `//host`, and
+/// more than a bare `#fragment`.
+pub(crate) fn is_local(url: &str) -> bool {
+    let url = url.trim();
+    !url.is_empty() && !url.starts_with('#') && !url.starts_with("//") && scheme(url).is_none()
+}
+
+/// Lowercased scheme of `url`, ignoring the whitespace and control characters browsers strip
+/// (`java\tscript:` is still `javascript:`); `None` for relative URLs.
+pub(crate) fn scheme(url: &str) -> Option<String> {
+    let mut scheme = String::new();
+    for ch in url.chars() {
+        match ch {
+            ':' => return (!scheme.is_empty()).then_some(scheme),
+            '/' | '?' | '#' => return None,
+            ch if ch.is_ascii_whitespace() || ch.is_ascii_control() => {}
+            ch => scheme.push(ch.to_ascii_lowercase()),
+        }
+    }
+    None
+}
+
+/// What happens to images whose source is an inline `data:` URL.
+///
+/// Pasted screenshots arrive as base64 data URLs of several hundred kilobytes; capping them keeps
+/// a single image from dominating the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DataUriPolicy {
+    /// Data URLs are written like any other source.
+    #[default]
+    Allow,
+    /// Every data URL source is replaced by an empty string.
+    Block,
+    /// Data URLs longer than this many bytes are replaced by an empty string.
+    MaxBytes(usize),
+}
+
+impl DataUriPolicy {
+    /// `Some` when this policy removes the image source `url`. Sources that are not data URLs
+    /// always pass.
+    pub fn violation(self, url: &str) -> Option<BlockedDataUri> {
+        if self == DataUriPolicy::Allow || scheme(url).as_deref() != Some("data") {
+            return None;
+        }
+        let url = url.trim();
+        match self {
+            DataUriPolicy::MaxBytes(max) if url.len() <= max => None,
+            _ => Some(BlockedDataUri::new(url)),
+        }
+    }
+}
+
+/// An image source removed by a [`DataUriPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockedDataUri {
+    /// Declared media type, e.g. `image/png`; empty when the URL has none.
+    pub media_type: String,
+    /// Length of the removed URL in bytes.
+    pub bytes: usize,
+}
+
+impl BlockedDataUri {
+    fn new(url: &str) -> Self {
+        let header = url
+            .split_once(',')
+            .map_or(url, |(header, _)| header)
+            .split_once(':')
+            .map_or("", |(_, header)| header);
+        let media_type = header.split(';').next().unwrap_or_default().trim();
+        Self {
+            media_type: media_type.to_ascii_lowercase(),
+            bytes: url.len(),
+        }
+    }
+}
+
+fn is_raster_data_url(url: &str) -> bool {
+    const PREFIX: &str = "data:image/";
+    let url = url.trim_start();
+    if !url
+        .get(..PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(PREFIX))
+    {
+        return false;
+    }
+    let Some((kind, _)) = url[PREFIX.len()..].split_once(";base64,") else {
+        return false;
+    };
+    SAFE_DATA_IMAGE_TYPES
+        .iter()
+        .any(|safe| kind.eq_ignore_ascii_case(safe))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_urls() {
+        let cases = [
+            ("https://example.com", true, true),
+            ("/docs/page?x=1#top", true, true),
+            ("#section", true, true),
+            ("mailto:a@example.com", true, true),
+            ("rel:ative/path", false, false),
+            ("javascript:alert(1)", false, false),
+            (" JaVa\tScRiPt:alert(1)", false, false),
+            ("vbscript:msgbox", false, false),
+            ("data:text/html;base64,PHNjcmlwdD4=", false, false),
+            ("data:image/png;base64,iVBORw0KGgo=", false, true),
+            ("data:image/svg+xml;base64,PHN2Zz4=", false, false),
+        ];
+
+        for (url, link, image) in cases {
+            assert_eq!(is_safe_link(url), link, "link: {url:?}");
+            assert_eq!(is_safe_image(url), image, "image: {url:?}");
+        }
+    }
+
+    #[test]
+    fn applies_data_uri_policies() {
+        let png = "data:image/png;base64,iVBORw0KGgo=";
+        let blocked = Some(BlockedDataUri {
+            media_type: "image/png".into(),
+            bytes: png.len(),
+        });
+        let cases = [
+            (DataUriPolicy::Allow, png, None),
+            (DataUriPolicy::Block, png, blocked.clone()),
+            (DataUriPolicy::MaxBytes(png.len()), png, None),
+            (DataUriPolicy::MaxBytes(16), png, blocked),
+            (DataUriPolicy::Block, "/img/cat.png", None),
+            (
+                DataUriPolicy::Block,
+                " DATA:,x",
+                Some(BlockedDataUri {
+                    media_type: String::new(),
+                    bytes: 7,
+                }),
+            ),
+        ];
+
+        for (policy, url, expected) in cases {
+            assert_eq!(policy.violation(url), expected, "{policy:?}: {url:?}");
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Writer adapter that only forwards output on UTF-8 character boundaries.
+//!
+//! Renderers and lol_html emit bytes, and a chunk may end in the middle of a multibyte
+//! character. Sinks that decode every chunk as a string (JS callbacks, SSE frames) would reject
+//! it, so [`Utf8ChunkWriter`] holds back the incomplete tail until the rest of the character
+//! arrives.
+
+use std::io::{self, Write};
+
+/// Forwards writes to `W`, each ending on a UTF-8 character boundary.
+///
+/// At most three bytes are held back between writes. Bytes that are not valid UTF-8 for other
+/// reasons pass through unchanged; only a character split across writes is delayed.
+pub struct Utf8ChunkWriter<W: Write> {
+    inner: W,
+    /// Leading bytes of a character whose remaining bytes have not been written yet.
+    carry: Vec<u8>,
+}
+
+impl<W: Write> Utf8ChunkWriter<W> {
+    /// Wraps `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            carry: Vec::with_capacity(4),
+        }
+    }
+
+    /// Returns the wrapped writer, failing with [`io::ErrorKind::InvalidData`] when the output
+    /// ended inside a character.
+    pub fn into_inner(self) -> io::Result<W> {
+        if !self.carry.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "output ended inside a UTF-8 character",
+            ));
+        }
+        Ok(self.inner)
+    }
+
+    /// Completes the carried character from the front of `buf`, returning the bytes used.
+    fn complete_carry(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let width = char_width(self.carry[0]);
+        let mut used = 0;
+        while self.carry.len() < width && used < buf.len() && is_continuation(buf[used]) {
+            self.carry.push(buf[used]);
+            used += 1;
+        }
+        // Either complete, or broken by a non-continuation byte: pass it on as-is.
+        if self.carry.len() == width || used < buf.len() {
+            self.inner.write_all(&self.carry)?;
+            self.carry.clear();
+        }
+        Ok(used)
+    }
+}
+
+impl<W: Write> Write for Utf8ChunkWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        if !self.carry.is_empty() {
+            let used = self.complete_carry(rest)?;
+            rest = &rest[used..];
+        }
+        let split = boundary(rest);
+        if split > 0 {
+            self.inner.write_all(&rest[..split])?;
+        }
+        self.carry.extend_from_slice(&rest[split..]);
+        Ok(buf.len())
+    }
+
+    /// Flushes the wrapped writer; a held-back partial character stays held back.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Length of the longest prefix of `buf` that does not end inside a character.
+fn boundary(buf: &[u8]) -> usize {
+    // A character is at most four bytes, so its lead byte is within the last three.
+    for back in 1..=buf.len().min(3) {
+        let idx = buf.len() - back;
+        let byte = buf[idx];
+        if is_continuation(byte) {
+            continue;
+        }
+        return if char_width(byte) > back {
+            idx
+        } else {
+            buf.len()
+        };
+    }
+    buf.len()
+}
+
+/// Encoded length announced by a lead byte; `1` for ASCII and stray bytes.
+fn char_width(lead: u8) -> usize {
+    match lead {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    }
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records each chunk the adapter forwards.
+    #[derive(Default)]
+    struct Chunks(Vec<Vec<u8>>);
+
+    impl Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn forwards_only_whole_characters() {
+        let text = "日本語のテキスト, emoji 🎉 and ü.".as_bytes();
+        for size in 1..=5 {
+            let mut chunks = Chunks::default();
+            let mut writer = Utf8ChunkWriter::new(&mut chunks);
+            for piece in text.chunks(size) {
+                writer.write_all(piece).unwrap();
+            }
+            writer.into_inner().unwrap();
+
+            for chunk in &chunks.0 {
+                assert!(std::str::from_utf8(chunk).is_ok(), "size {size}: {chunk:?}");
+            }
+            assert_eq!(chunks.0.concat(), text, "size {size}");
+        }
+    }
+
+    #[test]
+    fn reports_truncated_output_and_passes_invalid_bytes() {
+        let mut writer = Utf8ChunkWriter::new(Vec::new());
+        writer.write_all(&"語".as_bytes()[..2]).unwrap();
+        let err = writer.into_inner().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut writer = Utf8ChunkWriter::new(Vec::new());
+        writer.write_all(&[b'a', 0xE8]).unwrap();
+        writer.write_all(&[b'b', 0x80, b'c']).unwrap();
+        assert_eq!(writer.into_inner().unwrap(), [b'a', 0xE8, b'b', 0x80, b'c']);
+    }
+}
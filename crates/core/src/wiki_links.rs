@@ -0,0 +1,163 @@
+//! Obsidian-style `[[Target]]` and `[[Target|Label]]` links for knowledge-base content.
+//!
+//! Neither parser knows the syntax, so the brackets reach the event stream as text, sometimes
+//! split across several text events. [`wiki_links`] joins adjacent text and replaces each
+//! bracketed target with an ordinary link.
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::anchors::encode_fragment;
+use crate::event::{Event, LinkType, Tag, TagEnd};
+use crate::slug::slugify;
+use crate::text_runs::{Expansion, TextRuns};
+
+/// How wiki link targets become URLs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WikiLinkOptions {
+    /// URL for a page. `{slug}` is replaced by the page name slugified per `/`-separated
+    /// segment, and `{target}` by the page name as written, percent-encoded per segment.
+    /// Defaults to `{slug}`, so `[[Page Name]]` links to the sibling page `page-name`.
+    pub url_template: String,
+}
+
+impl Default for WikiLinkOptions {
+    fn default() -> Self {
+        Self {
+            url_template: "{slug}".to_string(),
+        }
+    }
+}
+
+impl WikiLinkOptions {
+    /// URL for `target`. A `#Heading` suffix becomes a fragment with the heading's slug, and a
+    /// target that is only a fragment links within the current page.
+    pub fn url(&self, target: &str) -> String {
+        let (page, heading) = match target.split_once('#') {
+            Some((page, heading)) => (page.trim(), Some(heading.trim())),
+            None => (target.trim(), None),
+        };
+        let mut url = if page.is_empty() {
+            String::new()
+        } else {
+            let encoded = page
+                .split('/')
+                .map(encode_fragment)
+                .collect::<Vec<_>>()
+                .join("/");
+            let slug = page
+                .split('/')
+                .filter_map(slugify)
+                .collect::<Vec<_>>()
+                .join("/");
+            self.url_template
+                .replace("{slug}", &slug)
+                .replace("{target}", &encoded)
+        };
+        if let Some(slug) = heading.and_then(slugify) {
+            url.push('#');
+            url.push_str(&encode_fragment(&slug));
+        }
+        url
+    }
+}
+
+/// Rewrites wiki links in text events outside code, links, and images.
+pub(crate) fn wiki_links<'o, I>(
+    inner: I,
+    options: &'o WikiLinkOptions,
+) -> impl Iterator<Item = Event<'static>> + 'o
+where
+    I: Iterator<Item = Event<'static>> + 'o,
+{
+    TextRuns::new(inner, move |text: &str, out: &mut Expansion<'_>| {
+        expand(text, options, out)
+    })
+    .skip_links()
+}
+
+fn expand(text: &str, options: &WikiLinkOptions, out: &mut Expansion<'_>) -> bool {
+    if !text.contains("[[") {
+        return false;
+    }
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
+        };
+        let inner = &after[..end];
+        if inner.trim().is_empty() || inner.contains(['[', ']', '\n']) {
+            out.push_text(&rest[..start + 2]);
+            rest = after;
+            continue;
+        }
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, label.trim()),
+            None => (inner, inner.trim()),
+        };
+        out.push_text(&rest[..start]);
+        out.push(Event::Start(Tag::Link {
+            link_type: LinkType::Inline,
+            dest_url: Cow::Owned(options.url(target)),
+            title: Cow::Borrowed(""),
+            id: Cow::Borrowed(""),
+        }));
+        out.push_text(label);
+        out.push(Event::End(TagEnd::Link));
+        rest = &after[end + 2..];
+    }
+    out.push_text(rest);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParserBackend, RenderOptions, render};
+
+    #[test]
+    fn renders_wiki_links_on_both_backends() {
+        let cases = [
+            (
+                "See [[Page Name]] and [[Other Page|the other one]].",
+                "<p>See <a href=\"/wiki/page-name\">Page Name</a> and <a href=\"/wiki/other-page\">the other one</a>.</p>",
+            ),
+            (
+                "[[Guide#Install Steps]] [[#Local]]",
+                "<p><a href=\"/wiki/guide#install-steps\">Guide#Install Steps</a> <a href=\"#local\">#Local</a></p>",
+            ),
+            (
+                "`[[code]]` [[]] [[a [b]] [x](y) [[open",
+                "<p><code>[[code]]</code> [[]] [[a [b]] <a href=\"y\">x</a> [[open</p>",
+            ),
+        ];
+
+        for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+            for (input, expected) in cases {
+                let options = RenderOptions {
+                    backend,
+                    wiki_links: Some(WikiLinkOptions {
+                        url_template: "/wiki/{slug}".into(),
+                    }),
+                    ..RenderOptions::default()
+                };
+                let html = render(input, &options).unwrap().html;
+                assert_eq!(html.trim_end(), expected, "{backend:?}: {input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn fills_url_templates() {
+        let options = WikiLinkOptions {
+            url_template: "https://kb.example/{target}?s={slug}".into(),
+        };
+        assert_eq!(
+            options.url("Notes/Tōkyō trip"),
+            "https://kb.example/Notes/T%C5%8Dky%C5%8D%20trip?s=notes/tōkyō-trip"
+        );
+        assert_eq!(WikiLinkOptions::default().url("A B#C"), "a-b#c");
+    }
+}
@@ -1,7 +1,10 @@
 #![deny(missing_docs)]
 //! Node.js bindings that surface Markflow's Rust implementation.
 
-use markflow_core::{MarkdownStream, MarkflowError, RewriteOptions, StreamingRewriter};
+use markflow_core::{
+    AnchorOptions, DataUriPolicy, MarkdownOptions, MarkdownStream, MarkflowError, OptionsOverride,
+    Profile, RenderOptions, RewriteOptions, ScriptStats, StreamingRewriter,
+};
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 
@@ -11,12 +14,19 @@ use napi_derive::napi;
 pub struct RewriteConfig {
     /// Enable lazy loading for images (default: true)
     pub enforce_img_loading_lazy: bool,
+    /// Empty `data:` image sources longer than this many bytes; `0` empties all of them
+    /// (default: no limit)
+    pub max_data_uri_bytes: Option<u32>,
+    /// Remove HTML comments from the output (default: false)
+    pub strip_comments: Option<bool>,
 }
 
 impl Default for RewriteConfig {
     fn default() -> Self {
         Self {
             enforce_img_loading_lazy: true,
+            max_data_uri_bytes: None,
+            strip_comments: None,
         }
     }
 }
@@ -25,6 +35,13 @@ impl From<RewriteConfig> for RewriteOptions {
     fn from(config: RewriteConfig) -> Self {
         RewriteOptions {
             enforce_img_loading_lazy: config.enforce_img_loading_lazy,
+            data_uri_images: match config.max_data_uri_bytes {
+                None => DataUriPolicy::Allow,
+                Some(0) => DataUriPolicy::Block,
+                Some(max) => DataUriPolicy::MaxBytes(max as usize),
+            },
+            strip_comments: config.strip_comments.unwrap_or(false),
+            ..RewriteOptions::default()
         }
     }
 }
@@ -37,17 +54,46 @@ pub struct ParseResult {
     pub html: String,
     /// Processing time in milliseconds
     pub processing_time_ms: f64,
+    /// Script composition of the document's prose, most frequent first
+    pub script_stats: Vec<ScriptShare>,
+    /// Text of the first H1, if any
+    pub title: Option<String>,
+}
+
+/// Share of a single Unicode script in the document text
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct ScriptShare {
+    /// Script name (`han`, `hiragana`, `katakana`, `hangul`, `latin`, ...)
+    pub script: String,
+    /// Number of characters classified as this script
+    pub count: u32,
+    /// Fraction of all classified characters (0.0 - 1.0)
+    pub ratio: f64,
+}
+
+fn script_shares(stats: &ScriptStats) -> Vec<ScriptShare> {
+    let mut shares: Vec<ScriptShare> = stats
+        .iter()
+        .map(|(script, count)| ScriptShare {
+            script: script.as_str().to_string(),
+            count: count as u32,
+            ratio: stats.ratio(script),
+        })
+        .collect();
+    shares.sort_by_key(|share| std::cmp::Reverse(share.count));
+    shares
 }
 
 /// Parses markdown string to HTML with default options
 #[napi]
-pub fn parse(input: String) -> napi::Result<String> {
+pub fn parse(input: String) -> napi::Result<String, ErrorCode> {
     markflow_core::parse(&input).map_err(convert_error)
 }
 
 /// Parses markdown string to HTML with custom rewrite options
 #[napi]
-pub fn parse_with_options(input: String, config: RewriteConfig) -> napi::Result<String> {
+pub fn parse_with_options(input: String, config: RewriteConfig) -> napi::Result<String, ErrorCode> {
     let events = markflow_core::get_event_iterator(&input).map_err(convert_error)?;
     let options: RewriteOptions = config.into();
     let rewriter = StreamingRewriter::new(Vec::new(), options);
@@ -57,33 +103,268 @@ pub fn parse_with_options(input: String, config: RewriteConfig) -> napi::Result<
     String::from_utf8(output).map_err(convert_error)
 }
 
+/// Parses markdown string to HTML with a named option preset (`default`, `untrusted`, `github`,
+/// or `docs`)
+#[napi]
+pub fn parse_with_profile(input: String, profile: String) -> napi::Result<String, ErrorCode> {
+    let profile = Profile::from_name(&profile)
+        .ok_or_else(|| Error::new("InvalidArg", format!("Unknown profile: {profile}")))?;
+    markflow_core::render(&input, &profile.into())
+        .map(|result| result.html)
+        .map_err(convert_error)
+}
+
+/// Parses markdown string to HTML with options layered over a named profile (`default` when
+/// omitted). Each layer is a partial options object in snake_case, applied in order; a field set
+/// in a layer wins even when it turns an option off, and `null` clears an optional option set
+#[napi]
+pub fn parse_with_layers(
+    input: String,
+    profile: Option<String>,
+    layers: Vec<serde_json::Value>,
+) -> napi::Result<String, ErrorCode> {
+    let profile = profile.as_deref().unwrap_or("default");
+    let mut options: RenderOptions = Profile::from_name(profile)
+        .ok_or_else(|| Error::new("InvalidArg", format!("Unknown profile: {profile}")))?
+        .into();
+    for layer in layers {
+        let layer: OptionsOverride = serde_json::from_value(layer)
+            .map_err(|err| Error::new("InvalidArg", format!("Invalid options layer: {err}")))?;
+        options = options.apply(&layer);
+    }
+    markflow_core::render(&input, &options)
+        .map(|result| result.html)
+        .map_err(convert_error)
+}
+
+/// Renders markdown as plain text with all markup removed, for search indexes and previews
+#[napi]
+pub fn parse_text(input: String) -> napi::Result<String, ErrorCode> {
+    markflow_core::render_text(&input, &RenderOptions::default()).map_err(convert_error)
+}
+
 /// Parses markdown and returns both HTML output and processing statistics
 #[napi]
-pub fn parse_with_stats(input: String) -> napi::Result<ParseResult> {
+pub fn parse_with_stats(input: String) -> napi::Result<ParseResult, ErrorCode> {
     use std::time::Instant;
 
+    let options = RenderOptions {
+        script_stats: true,
+        extract_title: true,
+        ..RenderOptions::default()
+    };
+
     let start = Instant::now();
-    let html = parse(input)?;
+    let result = markflow_core::render(&input, &options).map_err(convert_error)?;
     let elapsed = start.elapsed();
 
     Ok(ParseResult {
-        html,
+        html: result.html,
         processing_time_ms: elapsed.as_secs_f64() * 1000.0,
+        script_stats: result
+            .script_stats
+            .as_ref()
+            .map(script_shares)
+            .unwrap_or_default(),
+        title: result.title,
+    })
+}
+
+/// Parses markdown into a JSON document tree (`{ type, attrs?, value?, children? }` nodes)
+#[napi]
+pub fn parse_ast(input: String) -> napi::Result<serde_json::Value, ErrorCode> {
+    let tree =
+        markflow_core::render_ast(&input, &RenderOptions::default()).map_err(convert_error)?;
+    Ok(tree.to_json())
+}
+
+/// Heading found in the document
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct HeadingInfo {
+    /// Heading level (1-6)
+    pub level: u32,
+    /// Plain text of the heading
+    pub text: String,
+    /// Slug used as the heading id
+    pub id: Option<String>,
+}
+
+/// HTML output together with the document's headings
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct ParseWithHeadingsResult {
+    /// The parsed HTML output
+    pub html: String,
+    /// Headings in document order
+    pub headings: Vec<HeadingInfo>,
+}
+
+/// Parses markdown and returns the HTML plus every heading, e.g. for building a table of contents
+#[napi]
+pub fn parse_with_headings(input: String) -> napi::Result<ParseWithHeadingsResult, ErrorCode> {
+    let options = RenderOptions {
+        headings: true,
+        ..RenderOptions::default()
+    };
+    let result = markflow_core::render(&input, &options).map_err(convert_error)?;
+    Ok(ParseWithHeadingsResult {
+        html: result.html,
+        headings: result
+            .headings
+            .into_iter()
+            .map(|heading| HeadingInfo {
+                level: heading.level as u32,
+                text: heading.text,
+                id: heading.id,
+            })
+            .collect(),
     })
 }
 
-/// Improved error converter that matches on enum variants
-fn convert_error<E: Into<MarkflowError>>(err: E) -> Error {
+/// Heading with its fully-qualified anchor URL
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct HeadingAnchorInfo {
+    /// Heading level (1-6)
+    pub level: u32,
+    /// Plain text of the heading
+    pub text: String,
+    /// Slug used as the heading id
+    pub id: String,
+    /// `baseUrl/path#id`
+    pub url: String,
+}
+
+/// Lists every heading with an absolute anchor URL, e.g. for sitemaps and structured data
+#[napi]
+pub fn heading_anchors(
+    input: String,
+    base_url: String,
+    path: String,
+) -> napi::Result<Vec<HeadingAnchorInfo>, ErrorCode> {
+    let options = RenderOptions {
+        anchors: Some(AnchorOptions { base_url, path }),
+        ..RenderOptions::default()
+    };
+    let result = markflow_core::render(&input, &options).map_err(convert_error)?;
+    Ok(result
+        .anchors
+        .into_iter()
+        .map(|anchor| HeadingAnchorInfo {
+            level: anchor.level as u32,
+            text: anchor.text,
+            id: anchor.id,
+            url: anchor.url,
+        })
+        .collect())
+}
+
+/// Pays one-time initialization costs now (selector parsing, first-render warm-up) so the first
+/// request of a serverless function is not slower than the rest.
+#[napi]
+pub fn prewarm() {
+    markflow_core::prewarm();
+}
+
+/// Re-serializes markdown as normalized CommonMark (`-` bullets, backtick fences, aligned tables)
+#[napi]
+pub fn format_markdown(input: String) -> napi::Result<String, ErrorCode> {
+    markflow_core::format(&input, &MarkdownOptions::default()).map_err(convert_error)
+}
+
+/// Parses markdown into an mdast-compatible tree for unified/remark tooling
+#[napi]
+pub fn parse_mdast(input: String) -> napi::Result<serde_json::Value, ErrorCode> {
+    markflow_core::render_mdast(&input, &RenderOptions::default()).map_err(convert_error)
+}
+
+/// Fenced code block with its location in the source
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct CodeBlockInfo {
+    /// Language from the info string
+    pub lang: Option<String>,
+    /// Remainder of the info string after the language
+    pub meta: Option<String>,
+    /// Code content without the fences
+    pub code: String,
+    /// Byte offset where the block starts
+    pub start: u32,
+    /// Byte offset just past the block
+    pub end: u32,
+    /// 1-based line of the opening fence
+    pub start_line: u32,
+    /// 1-based line of the closing fence
+    pub end_line: u32,
+}
+
+/// Returns every fenced code block, e.g. for running documentation snippets as tests
+#[napi]
+pub fn extract_code_blocks(input: String) -> napi::Result<Vec<CodeBlockInfo>, ErrorCode> {
+    let blocks = markflow_core::extract_code_blocks(&input).map_err(convert_error)?;
+    Ok(blocks
+        .into_iter()
+        .map(|block| CodeBlockInfo {
+            lang: block.lang,
+            meta: block.meta,
+            code: block.code,
+            start: block.span.start as u32,
+            end: block.span.end as u32,
+            start_line: block.span.start_line as u32,
+            end_line: block.span.end_line as u32,
+        })
+        .collect())
+}
+
+/// Heading that encloses a source position
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct BreadcrumbInfo {
+    /// Heading level (1-6)
+    pub level: u32,
+    /// Plain text of the heading
+    pub text: String,
+    /// The heading's `id` in the rendered HTML
+    pub id: Option<String>,
+    /// Byte offset where the heading starts
+    pub start: u32,
+    /// Byte offset just past the heading
+    pub end: u32,
+    /// 1-based line of the heading
+    pub start_line: u32,
+}
+
+/// Returns the headings enclosing a byte offset, outermost first, e.g. for editor breadcrumbs
+#[napi]
+pub fn breadcrumbs_for_offset(
+    input: String,
+    offset: u32,
+) -> napi::Result<Vec<BreadcrumbInfo>, ErrorCode> {
+    let crumbs =
+        markflow_core::breadcrumbs_for_offset(&input, offset as usize).map_err(convert_error)?;
+    Ok(crumbs
+        .into_iter()
+        .map(|crumb| BreadcrumbInfo {
+            level: u32::from(crumb.level),
+            text: crumb.text,
+            id: crumb.id,
+            start: crumb.span.start as u32,
+            end: crumb.span.end as u32,
+            start_line: crumb.span.start_line as u32,
+        })
+        .collect())
+}
+
+/// Value of the thrown JS error's `code` property, e.g. `ERR_MARKFLOW_LIMIT`
+type ErrorCode = &'static str;
+
+/// Maps a core error to a JS error, keeping its stable code and source line
+fn convert_error<E: Into<MarkflowError>>(err: E) -> Error<ErrorCode> {
     let err = err.into();
-    match err {
-        // Map specific errors to specific NAPI statuses
-        MarkflowError::EncodingError(e) => {
-            Error::new(Status::InvalidArg, format!("Encoding error: {}", e))
-        }
-        // IO errors and Adapter errors usually imply a runtime failure
-        MarkflowError::IoError(e) => Error::from_reason(format!("IO error: {}", e)),
-        MarkflowError::MarkdownAdapter(msg) => {
-            Error::from_reason(format!("Markdown parser error: {}", msg))
-        }
-    }
+    let reason = match err.span() {
+        Some(span) => format!("{err} (line {})", span.start_line),
+        None => err.to_string(),
+    };
+    Error::new(err.code(), reason)
 }
@@ -0,0 +1,21 @@
+import test from 'ava';
+import { breadcrumbsForOffset } from '../index.js';
+
+test('breadcrumbsForOffset() returns the enclosing headings outermost first', (t) => {
+  const input = '# Guide\n\n## Install\n\ntext\n\n## Usage\n';
+  const crumbs = breadcrumbsForOffset(input, input.indexOf('text'));
+
+  t.deepEqual(
+    crumbs.map((crumb) => [crumb.level, crumb.text, crumb.id]),
+    [
+      [1, 'Guide', 'guide'],
+      [2, 'Install', 'install'],
+    ],
+  );
+  t.is(crumbs[1].startLine, 3);
+  t.is(input.slice(crumbs[1].start, crumbs[1].end), '## Install');
+});
+
+test('breadcrumbsForOffset() is empty before the first heading', (t) => {
+  t.deepEqual(breadcrumbsForOffset('intro\n\n# Title\n', 0), []);
+});
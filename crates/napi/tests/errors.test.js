@@ -0,0 +1,14 @@
+import test from 'ava';
+import { parseWithProfile } from '../index.js';
+
+test('limit failures carry ERR_MARKFLOW_LIMIT as the error code', (t) => {
+  const deep = '> '.repeat(40) + 'deep';
+  t.throws(() => parseWithProfile(deep, 'untrusted'), {
+    code: 'ERR_MARKFLOW_LIMIT',
+    message: /nesting depth limit of 32 exceeded/,
+  });
+});
+
+test('invalid arguments keep the InvalidArg code', (t) => {
+  t.throws(() => parseWithProfile('# x', 'bogus'), { code: 'InvalidArg' });
+});
@@ -0,0 +1,13 @@
+import test from 'ava';
+import { extractCodeBlocks } from '../index.js';
+
+test('extractCodeBlocks() returns fenced blocks with meta and lines', (t) => {
+  const input = '# Guide\n\n```bash no-test\necho hi\n```\n';
+  const [block] = extractCodeBlocks(input);
+
+  t.is(block.lang, 'bash');
+  t.is(block.meta, 'no-test');
+  t.is(block.code, 'echo hi');
+  t.is(block.startLine, 3);
+  t.is(input.slice(block.start, block.end), '```bash no-test\necho hi\n```');
+});
@@ -0,0 +1,8 @@
+import test from 'ava';
+import { formatMarkdown } from '../index.js';
+
+test('formatMarkdown() normalizes bullets and fences', (t) => {
+  const output = formatMarkdown('* one\n* two\n\n~~~js\nlet a;\n~~~');
+
+  t.is(output, '- one\n- two\n\n```js\nlet a;\n```\n');
+});
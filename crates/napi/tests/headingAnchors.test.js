@@ -0,0 +1,13 @@
+import test from 'ava';
+import { headingAnchors } from '../index.js';
+
+test('headingAnchors() joins base url, path and slug', (t) => {
+  const anchors = headingAnchors('# Intro\n\n## Next Steps', 'https://example.com/', '/guide');
+
+  t.deepEqual(
+    anchors.map((anchor) => anchor.url),
+    ['https://example.com/guide#intro', 'https://example.com/guide#next-steps'],
+  );
+  t.is(anchors[1].level, 2);
+  t.is(anchors[1].text, 'Next Steps');
+});
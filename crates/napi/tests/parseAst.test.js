@@ -0,0 +1,19 @@
+import test from 'ava';
+import { parseAst } from '../index.js';
+
+test('parseAst() returns a document tree', (t) => {
+  const tree = parseAst('# Title\n\nSome *text*');
+
+  t.is(tree.type, 'document');
+  t.is(tree.children[0].type, 'heading');
+  t.is(tree.children[0].attrs.level, 1);
+  t.is(tree.children[1].children[1].type, 'emphasis');
+});
+
+test('parseAst() keeps code block source as value', (t) => {
+  const tree = parseAst('```js\nconst a = 1;\n```');
+
+  t.is(tree.children[0].type, 'codeBlock');
+  t.is(tree.children[0].attrs.lang, 'js');
+  t.is(tree.children[0].value, 'const a = 1;');
+});
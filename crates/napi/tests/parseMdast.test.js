@@ -0,0 +1,14 @@
+import test from 'ava';
+import { parseMdast } from '../index.js';
+
+test('parseMdast() returns an mdast root', (t) => {
+  const tree = parseMdast('# Title\n\n- item\n\n`code`');
+
+  t.is(tree.type, 'root');
+  t.is(tree.children[0].type, 'heading');
+  t.is(tree.children[0].depth, 1);
+  t.is(tree.children[1].type, 'list');
+  t.is(tree.children[1].children[0].type, 'listItem');
+  t.is(tree.children[1].children[0].children[0].type, 'paragraph');
+  t.is(tree.children[2].children[0].type, 'inlineCode');
+});
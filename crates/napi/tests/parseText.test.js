@@ -0,0 +1,6 @@
+import test from 'ava';
+import { parseText } from '../index.js';
+
+test('parseText() strips markup', (t) => {
+  t.is(parseText('# Title\n\nSome **bold** <b>html</b>\n\n- a\n- b'), 'Title\n\nSome bold html\n\na\nb');
+});
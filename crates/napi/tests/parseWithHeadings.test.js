@@ -0,0 +1,12 @@
+import test from 'ava';
+import { parseWithHeadings } from '../index.js';
+
+test('parseWithHeadings() returns html and headings', (t) => {
+  const result = parseWithHeadings('# Guide\n\n## Install `cargo`');
+
+  t.true(result.html.includes('<h1 id="guide">Guide</h1>'));
+  t.deepEqual(result.headings, [
+    { level: 1, text: 'Guide', id: 'guide' },
+    { level: 2, text: 'Install cargo', id: 'install-cargo' },
+  ]);
+});
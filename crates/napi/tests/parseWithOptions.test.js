@@ -38,3 +38,23 @@ test('parseWithOptions() returns a string', (t) => {
   const output = parseWithOptions('# Test', { enforceImgLoadingLazy: false });
   t.is(typeof output, 'string');
 });
+
+test('parseWithOptions() empties data URI images over maxDataUriBytes', (t) => {
+  const small = 'data:image/png;base64,AAAA';
+  const large = `data:image/png;base64,${'A'.repeat(64)}`;
+  const input = `![a](${small}) ![b](${large})`;
+  const output = parseWithOptions(input, { enforceImgLoadingLazy: false, maxDataUriBytes: 32 });
+
+  t.true(output.includes(`src="${small}"`));
+  t.false(output.includes(large));
+  t.false(parseWithOptions(input, { enforceImgLoadingLazy: false, maxDataUriBytes: 0 }).includes('data:'));
+});
+
+test('parseWithOptions() strips HTML comments when stripComments is set', (t) => {
+  const input = 'Text <!-- draft --> here.\n\n<!-- note -->';
+
+  t.true(parseWithOptions(input, { enforceImgLoadingLazy: true }).includes('<!-- draft -->'));
+  const output = parseWithOptions(input, { enforceImgLoadingLazy: true, stripComments: true });
+  t.false(output.includes('<!--'));
+  t.true(output.includes('<p>Text  here.</p>'));
+});
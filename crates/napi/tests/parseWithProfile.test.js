@@ -0,0 +1,25 @@
+import test from 'ava';
+import { parseWithProfile } from '../index.js';
+
+test('parseWithProfile() escapes raw HTML and drops unsafe URLs for untrusted input', (t) => {
+  const html = parseWithProfile('<script>x</script>\n\n[a](javascript:alert(1)) https://example.com', 'untrusted');
+  t.true(html.includes('&lt;script&gt;'));
+  t.true(html.includes('<a href="">a</a>'));
+  t.true(html.includes('rel="nofollow ugc noopener noreferrer"'));
+});
+
+test('parseWithProfile() rejects unknown profiles', (t) => {
+  t.throws(() => parseWithProfile('# x', 'bogus'), { message: /Unknown profile/ });
+});
+
+test('parseWithProfile() renders callouts and heading self-links for docs', (t) => {
+  const html = parseWithProfile('# Guide\n\n> [!NOTE]\n> Read this.', 'docs');
+  t.true(html.includes('<a class="heading-anchor" href="#guide" aria-hidden="true">#</a>'));
+  t.true(html.includes('<div class="markdown-alert markdown-alert-note">'));
+});
+
+test('parseWithProfile() uses GitHub heading anchors for github', (t) => {
+  const html = parseWithProfile('## Install & Setup\n\n## A - B', 'github');
+  t.true(html.includes('<h2 id="user-content-install--setup">'));
+  t.true(html.includes('<h2 id="user-content-a---b">'));
+});
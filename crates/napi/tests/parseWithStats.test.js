@@ -53,3 +53,18 @@ test('parseWithStats() works with empty input', (t) => {
   t.is(typeof result.html, 'string');
   t.true(result.processingTimeMs >= 0);
 });
+
+test('parseWithStats() reports script composition', (t) => {
+  const result = parseWithStats('# 見出し\n\n日本語の文章と漢字 with Rust');
+
+  t.true(Array.isArray(result.scriptStats));
+  t.is(result.scriptStats[0].script, 'han');
+  t.true(result.scriptStats.some((share) => share.script === 'latin'));
+  const total = result.scriptStats.reduce((sum, share) => sum + share.ratio, 0);
+  t.true(Math.abs(total - 1) < 1e-9);
+});
+
+test('parseWithStats() reports the first H1 as title', (t) => {
+  t.is(parseWithStats('Intro\n\n# Guide *one*\n\n# Other').title, 'Guide one');
+  t.is(parseWithStats('No heading').title, undefined);
+});
@@ -0,0 +1,9 @@
+import test from 'ava';
+import { parse, prewarm } from '../index.js';
+
+test('prewarm() can run repeatedly and leaves rendering unchanged', (t) => {
+  const before = parse('# Title\n\nSee https://example.com');
+  prewarm();
+  prewarm();
+  t.is(parse('# Title\n\nSee https://example.com'), before);
+});
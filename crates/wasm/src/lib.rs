@@ -1,8 +1,118 @@
+#[cfg(feature = "rewriter")]
+use std::io::{self, Write};
+
+use js_sys::{Object, Reflect};
+#[cfg(feature = "rewriter")]
+use markflow_core::{
+    ChunkPolicy, MarkdownStream, RewriteOptions, StreamingRewriter, Utf8ChunkWriter,
+    get_event_iterator,
+};
+use markflow_core::{MarkflowError, OptionsOverride, Profile, RenderOptions};
 use wasm_bindgen::prelude::*;
 
 /// Parses markdown string to HTML.
 /// Returns a Result explicitly to handle errors in JS as exceptions.
 #[wasm_bindgen]
-pub fn parse(input: &str) -> Result<String, JsError> {
-    markflow_core::parse(input).map_err(|e| JsError::new(&e.to_string()))
+pub fn parse(input: &str) -> Result<String, JsValue> {
+    markflow_core::parse(input).map_err(to_js_error)
+}
+
+/// Parses markdown string to HTML with options layered over a named profile (`default` when
+/// omitted). `layers` is an array of partial options objects in snake_case, applied in order; a
+/// field set in a layer wins even when it turns an option off, and `null` clears an optional
+/// option set.
+#[wasm_bindgen(js_name = parseWithLayers)]
+pub fn parse_with_layers(
+    input: &str,
+    profile: Option<String>,
+    layers: &JsValue,
+) -> Result<String, JsValue> {
+    let profile = profile.as_deref().unwrap_or("default");
+    let mut options: RenderOptions = Profile::from_name(profile)
+        .ok_or_else(|| js_sys::Error::new(&format!("Unknown profile: {profile}")))?
+        .into();
+    let json = String::from(js_sys::JSON::stringify(layers)?);
+    let layers: Vec<OptionsOverride> = serde_json::from_str(&json)
+        .map_err(|err| js_sys::Error::new(&format!("Invalid options layers: {err}")))?;
+    for layer in &layers {
+        options = options.apply(layer);
+    }
+    markflow_core::render(input, &options)
+        .map(|result| result.html)
+        .map_err(to_js_error)
+}
+
+/// Pays one-time initialization costs now so the first request of an edge function renders at
+/// steady-state speed.
+#[wasm_bindgen]
+pub fn prewarm() {
+    markflow_core::prewarm();
+}
+
+/// Parses markdown and hands the HTML to `on_chunk` as strings while it is produced.
+/// Chunks always end after a block element and on a character boundary, so multibyte text never
+/// splits across calls. Requires the `rewriter` feature.
+#[cfg(feature = "rewriter")]
+#[wasm_bindgen(js_name = parseStreaming)]
+pub fn parse_streaming(input: &str, on_chunk: &js_sys::Function) -> Result<(), JsValue> {
+    let events = get_event_iterator(input).map_err(to_js_error)?;
+    let sink = Utf8ChunkWriter::new(JsChunkWriter { callback: on_chunk });
+    let options = RewriteOptions {
+        chunk_policy: ChunkPolicy {
+            min_bytes: 0,
+            flush_on_block_end: true,
+            top_level_only: false,
+        },
+        ..RewriteOptions::default()
+    };
+    let rewriter = StreamingRewriter::new(sink, options);
+    events
+        .stream_to_writer(rewriter)
+        .and_then(StreamingRewriter::into_inner)
+        .and_then(Utf8ChunkWriter::into_inner)
+        .map(drop)
+        .map_err(|e| to_js_error(e.into()))
+}
+
+/// Converts a core error into a JS `Error` carrying `code` and, when known,
+/// `span: { start, end, startLine, endLine }`.
+fn to_js_error(err: MarkflowError) -> JsValue {
+    let error = js_sys::Error::new(&err.to_string());
+    set(&error, "code", &err.code().into());
+    if let Some(span) = err.span() {
+        let object = Object::new();
+        set(&object, "start", &(span.start as u32).into());
+        set(&object, "end", &(span.end as u32).into());
+        set(&object, "startLine", &(span.start_line as u32).into());
+        set(&object, "endLine", &(span.end_line as u32).into());
+        set(&error, "span", &object);
+    }
+    error.into()
+}
+
+fn set(target: &Object, key: &str, value: &JsValue) {
+    // Defining a property on a fresh, non-frozen object cannot fail.
+    let _ = Reflect::set(target, &key.into(), value);
+}
+
+/// Passes each written chunk to a JS callback as a string.
+#[cfg(feature = "rewriter")]
+struct JsChunkWriter<'f> {
+    callback: &'f js_sys::Function,
+}
+
+#[cfg(feature = "rewriter")]
+impl Write for JsChunkWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let chunk =
+            std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.callback
+            .call1(&JsValue::NULL, &JsValue::from_str(chunk))
+            .map_err(|_| io::Error::other(MarkflowError::callback("chunk callback threw")))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
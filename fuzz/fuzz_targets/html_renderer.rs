@@ -0,0 +1,27 @@
+//! The HTML renderer behind both backends: output is UTF-8 and its elements nest properly.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use markflow_core::{HtmlOptions, MarkdownStream, ParserBackend};
+use markflow_fuzz::{assert_tags_balanced, split_input, syntax};
+
+fuzz_target!(|data: &[u8]| {
+    let (flags, input) = split_input(data);
+    let syntax = syntax(flags);
+    // Raw HTML passes through as written, so only escaped output has to balance.
+    let options = HtmlOptions {
+        escape_raw_html: true,
+        ..HtmlOptions::default()
+    };
+    for backend in [ParserBackend::MarkdownRs, ParserBackend::PulldownCmark] {
+        let Ok(events) = backend.events(&input, &syntax) else {
+            continue;
+        };
+        let html = events
+            .stream_to_writer_with_options(Vec::new(), &options)
+            .expect("rendering into a Vec cannot fail");
+        let html = String::from_utf8(html).expect("renderer wrote invalid UTF-8");
+        assert_tags_balanced(&html);
+    }
+});
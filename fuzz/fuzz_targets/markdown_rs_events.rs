@@ -0,0 +1,15 @@
+//! `MarkdownRsEventIter` over arbitrary input: no panics, and every `Start` has its `End`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use markflow_core::MarkdownEventStream;
+use markflow_fuzz::{assert_events_balanced, split_input, syntax};
+
+fuzz_target!(|data: &[u8]| {
+    let (flags, input) = split_input(data);
+    // Only MDX input can fail to parse.
+    if let Ok(events) = MarkdownEventStream::with_syntax(&input, &syntax(flags)) {
+        assert_events_balanced(events);
+    }
+});
@@ -0,0 +1,32 @@
+//! `StreamingRewriter::write` over arbitrary bytes split at an arbitrary point: no panics, the
+//! output does not depend on where the input was split, and UTF-8 input stays UTF-8.
+
+#![no_main]
+
+use std::io::Write;
+
+use libfuzzer_sys::fuzz_target;
+use markflow_core::{RewriteOptions, StreamingRewriter};
+
+fn rewrite(chunks: &[&[u8]]) -> Option<Vec<u8>> {
+    let mut rewriter = StreamingRewriter::new(Vec::new(), RewriteOptions::default());
+    for chunk in chunks {
+        rewriter.write_all(chunk).ok()?;
+    }
+    rewriter.into_inner().ok()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&split, html)) = data.split_first() else {
+        return;
+    };
+    let at = usize::from(split).min(html.len());
+    let whole = rewrite(&[html]);
+    let split = rewrite(&[&html[..at], &html[at..]]);
+    assert_eq!(whole, split, "output depends on the write boundary at {at}");
+    if let Some(output) = whole
+        && std::str::from_utf8(html).is_ok()
+    {
+        String::from_utf8(output).expect("rewriter turned UTF-8 input into invalid UTF-8");
+    }
+});
@@ -0,0 +1,103 @@
+//! Input decoding and output invariants shared by the fuzz targets.
+
+use markflow_core::SyntaxOptions;
+use markflow_core::event::Event;
+
+/// Elements without a closing tag.
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Splits fuzzer bytes into a flag byte and the Markdown input. Invalid UTF-8 is replaced rather
+/// than rejected, so every input reaches the parser.
+pub fn split_input(data: &[u8]) -> (u8, String) {
+    match data.split_first() {
+        Some((&flags, rest)) => (flags, String::from_utf8_lossy(rest).into_owned()),
+        None => (0, String::new()),
+    }
+}
+
+/// Syntax extensions picked by the bits of `flags`, so one corpus covers their combinations.
+pub fn syntax(flags: u8) -> SyntaxOptions {
+    let bit = |n: u8| flags & (1 << n) != 0;
+    SyntaxOptions {
+        autolinks: bit(0),
+        math: bit(1),
+        footnotes: bit(2),
+        frontmatter: bit(3),
+        heading_attributes: bit(4),
+        highlight: bit(5),
+        superscript_subscript: bit(6),
+        mdx: bit(7),
+        ..SyntaxOptions::default()
+    }
+}
+
+/// Panics unless every `Start` event is closed by an `End` event, in order.
+pub fn assert_events_balanced<'a>(events: impl IntoIterator<Item = Event<'a>>) {
+    let mut depth = 0usize;
+    for event in events {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => {
+                depth = depth
+                    .checked_sub(1)
+                    .expect("End event without a matching Start");
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(depth, 0, "{depth} Start events are never closed");
+}
+
+/// Panics unless the elements of `html` nest properly. Only meaningful for HTML rendered with
+/// raw HTML escaped, since raw HTML passes through as written.
+pub fn assert_tags_balanced(html: &str) {
+    let mut open: Vec<&str> = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").expect("unterminated comment");
+            rest = &comment[end + 3..];
+            continue;
+        }
+        let end = tag_end(rest).unwrap_or_else(|| panic!("unterminated tag in {html:?}"));
+        let tag = &rest[..end];
+        rest = &rest[end..];
+        let (closing, body) = match tag.strip_prefix("</") {
+            Some(body) => (true, body),
+            None => (false, &tag[1..]),
+        };
+        let len = body
+            .find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '-')
+            .unwrap_or(body.len());
+        let name = &body[..len];
+        assert!(!name.is_empty(), "stray `<` in {html:?}");
+        if closing {
+            assert_eq!(
+                open.pop(),
+                Some(name),
+                "`</{name}>` closes the wrong element in {html:?}"
+            );
+        } else if !tag.ends_with("/>") && !VOID_ELEMENTS.contains(&name) {
+            open.push(name);
+        }
+    }
+    assert!(open.is_empty(), "unclosed {open:?} in {html:?}");
+}
+
+/// Length of the tag at the start of `html`, skipping `>` inside quoted attribute values.
+fn tag_end(html: &str) -> Option<usize> {
+    let mut quote = None;
+    for (at, ch) in html.char_indices() {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, '>') => return Some(at + 1),
+            _ => {}
+        }
+    }
+    None
+}